@@ -0,0 +1,51 @@
+//! Node bindings of the library API of protots (see `protots::api`).
+
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use napi_derive::napi;
+
+fn error(err: protots::errors::PtError) -> Error {
+    Error::from_reason(err.to_string())
+}
+
+/// Issue of a JSON payload, e.g. a value not matching the field type.
+#[napi(object)]
+pub struct Issue {
+    /// location of the value, e.g. `$.items[0].name`
+    pub path: String,
+    pub message: String,
+}
+
+/// Generate the typescript (or the output given via `emit`) of the proto
+/// source, options are passed as JSON object.
+#[napi]
+pub fn generate(source: String, options: Option<String>) -> Result<Buffer> {
+    let output = protots::api::generate(&source, options.as_deref()).map_err(error)?;
+    Ok(output.into())
+}
+
+/// Outline of the proto source as JSON text, including its syntax errors.
+#[napi]
+pub fn parse(source: String, options: Option<String>) -> Result<String> {
+    let outline = protots::api::parse(&source, options.as_deref()).map_err(error)?;
+    Ok(outline.to_string())
+}
+
+/// Issues of the JSON payload of the message.
+#[napi]
+pub fn validate_json(
+    source: String,
+    message: String,
+    payload: String,
+    options: Option<String>,
+) -> Result<Vec<Issue>> {
+    let issues = protots::api::validate_json(&source, options.as_deref(), &message, &payload)
+        .map_err(error)?;
+    Ok(issues
+        .into_iter()
+        .map(|issue| Issue {
+            path: issue.path,
+            message: issue.message,
+        })
+        .collect())
+}
@@ -0,0 +1,26 @@
+"""Python bindings of protots: generate zod schemas of protobuf files."""
+
+import json
+
+from . import _protots
+
+
+def _options(file=None, **options):
+    if file is not None:
+        options["file"] = file
+    return json.dumps({key.replace("_", "-"): value for key, value in options.items()})
+
+
+def generate(source, file=None, **options):
+    """Typescript generated from the proto source, e.g. `generate(src, clients=True)`."""
+    return _protots.generate(source, _options(file, **options)).decode()
+
+
+def parse(source, file=None, **options):
+    """Outline of the proto source: syntax, package, types and syntax errors."""
+    return json.loads(_protots.parse(source, _options(file, **options)))
+
+
+def validate_json(source, message, payload, file=None, **options):
+    """Issues of the JSON payload of the message as (path, message) tuples."""
+    return _protots.validate_json(source, message, payload, _options(file, **options))
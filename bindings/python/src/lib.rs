@@ -0,0 +1,53 @@
+//! Python bindings of the library API of protots (see `protots::api`).
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+fn error(err: protots::errors::PtError) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+/// Generate the typescript (or the output given via `emit`) of the proto
+/// source, options are passed as JSON object.
+#[pyfunction]
+#[pyo3(signature = (source, options = None))]
+fn generate<'py>(
+    py: Python<'py>,
+    source: &str,
+    options: Option<&str>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let output = protots::api::generate(source, options).map_err(error)?;
+    Ok(PyBytes::new_bound(py, &output))
+}
+
+/// Outline of the proto source as JSON text, including its syntax errors.
+#[pyfunction]
+#[pyo3(signature = (source, options = None))]
+fn parse(source: &str, options: Option<&str>) -> PyResult<String> {
+    Ok(protots::api::parse(source, options).map_err(error)?.to_string())
+}
+
+/// Issues of the JSON payload of the message as (path, message) tuples.
+#[pyfunction]
+#[pyo3(signature = (source, message, payload, options = None))]
+fn validate_json(
+    source: &str,
+    message: &str,
+    payload: &str,
+    options: Option<&str>,
+) -> PyResult<Vec<(String, String)>> {
+    let issues = protots::api::validate_json(source, options, message, payload).map_err(error)?;
+    Ok(issues
+        .into_iter()
+        .map(|issue| (issue.path, issue.message))
+        .collect())
+}
+
+#[pymodule]
+fn _protots(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(generate, module)?)?;
+    module.add_function(wrap_pyfunction!(parse, module)?)?;
+    module.add_function(wrap_pyfunction!(validate_json, module)?)?;
+    Ok(())
+}
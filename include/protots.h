@@ -0,0 +1,47 @@
+/*
+ * C API of protots, see `src/ffi.rs`.
+ *
+ * Link against the shared library built via
+ *
+ *     cargo build --release --features ffi
+ */
+
+#ifndef PROTOTS_H
+#define PROTOTS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define PROTOTS_OK 0
+#define PROTOTS_ERROR 1
+#define PROTOTS_INVALID_ARGUMENT 2
+
+/* Buffer owned by protots, to be released via `protots_buf_free`. */
+typedef struct protots_buf {
+    uint8_t *data;
+    size_t len;
+} protots_buf;
+
+/*
+ * Generate the output of the given proto source. The options are given as
+ * JSON object of the command line options without their leading dashes,
+ * e.g. `{"file": "foo/v1/user.proto", "clients": true, "I": ["protos"]}`,
+ * and may be NULL.
+ *
+ * Returns PROTOTS_OK with the generated code in `out`, otherwise the error
+ * message is found in `out`.
+ */
+int protots_generate(const char *input, const char *options_json, protots_buf *out);
+
+/* Release a buffer returned by `protots_generate`. */
+void protots_buf_free(protots_buf *buf);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
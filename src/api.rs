@@ -0,0 +1,242 @@
+//! Library API for embedding protots, which the C API (see `ffi`) and the
+//! Python and Node bindings (see `bindings/`) are based on. Everything is
+//! passed as strings, so the bindings stay thin.
+//!
+//! Options are given as JSON object of the command line options without
+//! their leading dashes, e.g. `{"clients": true, "I": ["protos"]}`, along
+//! with the name of the proto file under `file` (default: `input.proto`).
+//! Unlike the command line, no `.prototsrc` is looked up in the current
+//! directory, its content may be given under `config` instead.
+
+use crate::config::Config;
+use crate::errors::PtError;
+use crate::json::{self, Json};
+#[cfg(feature = "json-schema")]
+use crate::json_schema;
+#[cfg(feature = "openapi")]
+use crate::openapi;
+use crate::payload::{self, Issue};
+use crate::registry::{Kind, Registry};
+use crate::{ast, descriptor, imports, parser, provider, typescript, Emit, Opts};
+
+/// Generate the output of the given proto source, typescript unless another
+/// output is requested via the `emit` option.
+pub fn generate(input: &str, options: Option<&str>) -> Result<Vec<u8>, PtError> {
+    let opts = opts(options)?;
+    if opts.emit == Emit::Runtime {
+        return Ok(typescript::runtime(&opts).into_bytes());
+    }
+
+    if opts.emit == Emit::Ast {
+        let proto = parser::parse(&opts, &opts.file, input)?;
+        return Ok(format!(
+            "{:#}",
+            ast::to_json_version(&proto, opts.ast_version.unwrap_or(ast::VERSION))
+        )
+        .into_bytes());
+    }
+
+    let (proto, deps) = load(&opts, input)?;
+    match opts.emit {
+        Emit::Descriptor => descriptor::to_descriptor_set(&proto, &deps),
+        // the documents by file name
+        #[cfg(feature = "json-schema")]
+        Emit::JsonSchema => {
+            let documents = json_schema::documents(&proto, &deps)?;
+            let documents = documents
+                .iter()
+                .map(|(name, document)| (name.as_str(), document.clone()));
+            Ok(Json::object(documents).to_string().into_bytes())
+        }
+        #[cfg(feature = "openapi")]
+        Emit::OpenApi => Ok(openapi::document(&proto, &deps)?.to_string().into_bytes()),
+        _ => Ok(typescript::to_schema(&opts, &proto, &deps)?.into_bytes()),
+    }
+}
+
+/// Outline of the given proto source as JSON: its syntax, package and the
+/// fully-qualified names of its types, along with the syntax errors of the
+/// statements that were skipped and the warnings (see
+/// `parser::parse_partial`).
+pub fn parse(input: &str, options: Option<&str>) -> Result<Json, PtError> {
+    let opts = opts(options)?;
+    let parser::Partial {
+        proto,
+        diagnostics,
+        warnings,
+    } = parser::parse_partial(&opts, &opts.file, input)?;
+
+    let mut types = Registry::new([&proto])
+        .types()
+        .map(|ptype| {
+            let kind = match ptype.kind {
+                Kind::Message => "message",
+                Kind::Enum => "enum",
+            };
+            (ptype.full_name.clone(), kind)
+        })
+        .collect::<Vec<_>>();
+    types.sort();
+
+    Ok(Json::object([
+        ("file", Json::str(&proto.file)),
+        ("syntax", Json::str(&proto.syntax)),
+        ("package", proto.package().map_or(Json::Null, Json::str)),
+        (
+            "types",
+            Json::Array(
+                types
+                    .iter()
+                    .map(|(name, kind)| {
+                        Json::object([("name", Json::str(name)), ("kind", Json::str(kind))])
+                    })
+                    .collect(),
+            ),
+        ),
+        (
+            "diagnostics",
+            Json::Array(
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| {
+                        Json::object([
+                            ("line", Json::num(diagnostic.line as f64)),
+                            ("column", Json::num(diagnostic.column as f64)),
+                            ("message", Json::str(&diagnostic.message)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+        (
+            "warnings",
+            Json::Array(warnings.iter().map(|warning| Json::str(warning)).collect()),
+        ),
+    ]))
+}
+
+/// Check the JSON payload against the definition of the fully-qualified
+/// message type of the given proto source (see `payload::validate`).
+pub fn validate_json(
+    input: &str,
+    options: Option<&str>,
+    message: &str,
+    payload: &str,
+) -> Result<Vec<Issue>, PtError> {
+    let opts = opts(options)?;
+    let (proto, mut protos) = load(&opts, input)?;
+    protos.push(proto);
+
+    let json = json::parse(payload).map_err(|err| PtError::InvalidJson(err.to_string()))?;
+    payload::validate(&protos, message, &json)
+}
+
+/// Parse the proto source and all of its imports.
+fn load(opts: &Opts, input: &str) -> Result<(parser::Proto, Vec<parser::Proto>), PtError> {
+    let proto = parser::parse(opts, &opts.file, input)?;
+    let deps = imports::resolve(opts, &proto)?;
+    Ok((proto, deps))
+}
+
+/// Options given as JSON object, translated into command line arguments like
+/// the parameter of the `protoc` plugin (see `plugin`).
+fn opts(options: Option<&str>) -> Result<Opts, PtError> {
+    let invalid = |message: String| PtError::InvalidArgument(message);
+    let fields = match options.map(json::parse).transpose()? {
+        Some(Json::Object(fields)) => fields,
+        Some(_) => return Err(invalid("options are no JSON object".to_string())),
+        None => Vec::new(),
+    };
+
+    let mut args = vec!["protots".to_string()];
+    let mut file = "input.proto".to_string();
+    let mut config = Config::default();
+    for (key, value) in fields {
+        let flag = match key.len() {
+            1 => format!("-{}", key),
+            _ => format!("--{}", key),
+        };
+        let values = match value {
+            Json::Str(value) if key == "file" => {
+                file = value;
+                continue;
+            }
+            Json::Str(value) if key == "config" => {
+                config = Config::parse(&value)?;
+                continue;
+            }
+            Json::Bool(true) => {
+                args.push(flag);
+                continue;
+            }
+            Json::Bool(false) | Json::Null => continue,
+            Json::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let (Json::Str(value) | Json::Num(value)) = value else {
+                return Err(invalid(format!("invalid value of option {}", key)));
+            };
+            args.push(flag.clone());
+            args.push(value);
+        }
+    }
+    // the input file comes last, so unknown options are taken as input
+    args.push(file.clone());
+
+    let opts = crate::opts(args, &provider::Files::default(), &config)?
+        .ok_or_else(|| invalid("invalid options".to_string()))?;
+    if opts.file != file {
+        return Err(invalid(format!("unknown option: {}", opts.file)));
+    }
+
+    // archives among the include paths are read as if they were directories
+    let files = provider::mount(&opts.files, &opts.include_paths)?;
+    Ok(Opts { files, ..opts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, parse, validate_json};
+
+    const INPUT: &str = r#"syntax = "proto3";
+package foo.v1;
+message User {
+  string name = 1;
+  int32 age = 2;
+  string email = ;
+}"#;
+
+    #[test]
+    fn api_parse_outline() {
+        let outline = parse(INPUT, Some(r#"{"file": "foo/v1/user.proto"}"#)).unwrap();
+        assert_eq!(
+            outline.to_string(),
+            r#"{"file":"foo/v1/user.proto","syntax":"proto3","package":"foo.v1","types":[{"name":"foo.v1.User","kind":"message"}],"diagnostics":[{"line":6,"column":18,"message":"expected field number, found `;`"}],"warnings":[]}"#
+        );
+    }
+
+    #[test]
+    fn api_validate_json() {
+        let input = INPUT.replace("  string email = ;\n", "");
+        let issues = validate_json(&input, None, "foo.v1.User", r#"{"name": 1}"#).unwrap();
+        let issues = issues
+            .iter()
+            .map(|issue| format!("{}: {}", issue.path, issue.message))
+            .collect::<Vec<_>>();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].starts_with("$.name: "));
+    }
+
+    #[test]
+    fn api_generate_with_config() {
+        let input = INPUT.replace("  string email = ;\n", "");
+        let output = generate(&input, Some(r#"{"config": "banner = Copyright ACME"}"#)).unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("// Copyright ACME\n"));
+
+        let output = String::from_utf8(generate(&input, None).unwrap()).unwrap();
+        assert!(!output.contains("ACME"));
+    }
+}
@@ -0,0 +1,500 @@
+//! JSON representation of the AST of proto files (see `--emit ast`), e.g.
+//! for tools inspecting proto files and the grammar cases of the parser.
+//!
+//! Every node is an object tagged with its `kind` along with its `span`:
+//! the byte offsets of its start and end and the line and column it starts
+//! at. Keys of absent values (no doc comment, no options, no label) are left
+//! out, so the representation stays readable.
+//!
+//! The shape is versioned (see `VERSION`), external consumers pin the version
+//! they understand via `--ast-version` and get the AST converted back to it:
+//!
+//! - 1: the line and column of nodes as `line` and `column` keys
+//! - 2: the position of nodes as `span` object, including the byte offsets
+
+use crate::json::Json;
+use crate::parser::{
+    Doc, Elem, Enum, EnumValue, Field, Flag, ImportModifier, Msg, Number, Option, OptionValue,
+    Proto, ReservedField, Rpc, ServiceNode, Span,
+};
+
+/// Version of the shape of the AST, bumped on changes that may break
+/// consumers, e.g. renamed or restructured keys.
+pub const VERSION: u32 = 2;
+
+/// AST of the given proto file in the latest version.
+pub fn to_json(proto: &Proto) -> Json {
+    let mut node = vec![("version", Json::num(VERSION as f64))];
+    node.push(("file", Json::str(&proto.file)));
+    node.push(("syntax", Json::str(&proto.syntax)));
+    if let Some(edition) = &proto.edition {
+        node.push(("edition", Json::str(edition)));
+    }
+    node.push(("elems", Json::Array(proto.elems.iter().map(elem).collect())));
+    Json::object(node)
+}
+
+/// AST of the given proto file in the given version, which has to be one of
+/// `1..=VERSION`.
+pub fn to_json_version(proto: &Proto, version: u32) -> Json {
+    let mut ast = to_json(proto);
+    if version < 2 {
+        ast = flatten_spans(ast);
+    }
+    if let Json::Object(fields) = &mut ast {
+        fields[0].1 = Json::num(version as f64);
+    }
+    ast
+}
+
+/// Version 1 of the given AST: the `span` objects of all nodes replaced by
+/// the `line` and `column` keys.
+fn flatten_spans(ast: Json) -> Json {
+    match ast {
+        Json::Array(values) => Json::Array(values.into_iter().map(flatten_spans).collect()),
+        Json::Object(fields) => {
+            let mut flattened = Vec::with_capacity(fields.len() + 1);
+            let mut position = Vec::new();
+            for (key, value) in fields {
+                match (key.as_str(), value) {
+                    ("span", Json::Object(span)) => position.extend(
+                        span.into_iter()
+                            .filter(|(key, _)| key == "line" || key == "column"),
+                    ),
+                    (_, value) => flattened.push((key, flatten_spans(value))),
+                }
+            }
+            flattened.extend(position);
+            Json::Object(flattened)
+        }
+        value => value,
+    }
+}
+
+/// Node of the given kind: the tag first and the position last.
+fn node(kind: &str, mut fields: Vec<(&str, Json)>, span: &Span) -> Json {
+    fields.insert(0, ("kind", Json::str(kind)));
+    fields.push((
+        "span",
+        Json::object([
+            ("start", Json::num(span.start as f64)),
+            ("end", Json::num(span.end as f64)),
+            ("line", Json::num(span.line as f64)),
+            ("column", Json::num(span.column as f64)),
+        ]),
+    ));
+    Json::object(fields)
+}
+
+fn push_doc(fields: &mut Vec<(&str, Json)>, doc: &Doc) {
+    if let Some(doc) = doc {
+        fields.push(("doc", Json::str(doc)));
+    }
+}
+
+fn push_deprecated(fields: &mut Vec<(&str, Json)>, deprecated: bool) {
+    if deprecated {
+        fields.push(("deprecated", Json::Bool(true)));
+    }
+}
+
+fn push_options(fields: &mut Vec<(&str, Json)>, options: &[Option]) {
+    if !options.is_empty() {
+        fields.push(("options", Json::Array(options.iter().map(option).collect())));
+    }
+}
+
+fn elem(elem: &Elem) -> Json {
+    match elem {
+        Elem::Message(msg) => message(msg),
+        Elem::Enum(e) => enumeration(e),
+        Elem::Option(opt) => option(opt),
+        Elem::Import {
+            name,
+            modifier,
+            span,
+        } => {
+            let mut fields = vec![("name", Json::str(name))];
+            match modifier {
+                ImportModifier::None => (),
+                ImportModifier::Public => fields.push(("modifier", Json::str("public"))),
+                ImportModifier::Weak => fields.push(("modifier", Json::str("weak"))),
+            }
+            node("import", fields, span)
+        }
+        Elem::Package { name, span } => node("package", vec![("name", Json::str(name))], span),
+        Elem::Extend { name, fields, span } => extend(name, fields, span),
+        Elem::Service { name, nodes, span } => {
+            let nodes = nodes
+                .iter()
+                .map(|service_node| match service_node {
+                    ServiceNode::Rpc(method) => rpc(method),
+                    ServiceNode::Option(opt) => option(opt),
+                })
+                .collect();
+            node(
+                "service",
+                vec![("name", Json::str(name)), ("nodes", Json::Array(nodes))],
+                span,
+            )
+        }
+    }
+}
+
+fn message(msg: &Msg) -> Json {
+    let mut fields = vec![("name", Json::str(&msg.name))];
+    push_deprecated(&mut fields, msg.deprecated);
+    push_doc(&mut fields, &msg.doc);
+    fields.push((
+        "fields",
+        Json::Array(msg.fields.iter().map(field).collect()),
+    ));
+    node("message", fields, &msg.span)
+}
+
+fn extend(name: &str, fields: &[Field], span: &Span) -> Json {
+    node(
+        "extend",
+        vec![
+            ("name", Json::str(name)),
+            ("fields", Json::Array(fields.iter().map(field).collect())),
+        ],
+        span,
+    )
+}
+
+fn field(member: &Field) -> Json {
+    match member {
+        Field::Single {
+            name,
+            field_type,
+            idx,
+            flag,
+            options,
+            deprecated,
+            json_name,
+            doc,
+            span,
+        } => {
+            let mut fields = vec![
+                ("name", Json::str(name)),
+                ("type", Json::str(field_type)),
+                ("number", Json::num(*idx as f64)),
+            ];
+            let label = match flag {
+                Flag::None => None,
+                Flag::Optional => Some("optional"),
+                Flag::Repeated => Some("repeated"),
+                Flag::Required => Some("required"),
+            };
+            if let Some(label) = label {
+                fields.push(("label", Json::str(label)));
+            }
+            if let Some(json_name) = json_name {
+                fields.push(("json_name", Json::str(json_name)));
+            }
+            push_deprecated(&mut fields, *deprecated);
+            push_options(&mut fields, options);
+            push_doc(&mut fields, doc);
+            node("field", fields, span)
+        }
+        Field::Map {
+            name,
+            key_type,
+            value_type,
+            idx,
+            options,
+            deprecated,
+            doc,
+            span,
+        } => {
+            let mut fields = vec![
+                ("name", Json::str(name)),
+                ("key_type", Json::str(key_type)),
+                ("value_type", Json::str(value_type)),
+                ("number", Json::num(*idx as f64)),
+            ];
+            push_deprecated(&mut fields, *deprecated);
+            push_options(&mut fields, options);
+            push_doc(&mut fields, doc);
+            node("map", fields, span)
+        }
+        Field::OneOf {
+            name,
+            fields: members,
+            options,
+            span,
+        } => {
+            let mut fields = vec![("name", Json::str(name))];
+            push_options(&mut fields, options);
+            fields.push(("fields", Json::Array(members.iter().map(field).collect())));
+            node("oneof", fields, span)
+        }
+        Field::SubMessage(msg) => message(msg),
+        Field::SubEnum(e) => enumeration(e),
+        Field::Reserved(reserved) => {
+            let numbers = |numbers: Vec<Json>| ("numbers", Json::Array(numbers));
+            let fields = match reserved {
+                ReservedField::Idx { idx } => {
+                    numbers(idx.iter().map(|idx| Json::num(*idx as f64)).collect())
+                }
+                ReservedField::Range { ranges } => numbers(ranges.iter().map(range).collect()),
+                ReservedField::Name { name } => (
+                    "names",
+                    Json::Array(name.iter().map(|name| Json::str(name)).collect()),
+                ),
+            };
+            Json::object([("kind", Json::str("reserved")), fields])
+        }
+        Field::Extensions {
+            ranges,
+            options,
+            span,
+        } => {
+            let mut fields = vec![("ranges", Json::Array(ranges.iter().map(range).collect()))];
+            push_options(&mut fields, options);
+            node("extensions", fields, span)
+        }
+        Field::Extend { name, fields, span } => extend(name, fields, span),
+        Field::Option(opt) => option(opt),
+    }
+}
+
+/// Range of field numbers with inclusive ends as `[from, to]`.
+fn range(&(from, to): &(i32, i32)) -> Json {
+    Json::Array(vec![Json::num(from as f64), Json::num(to as f64)])
+}
+
+fn enumeration(e: &Enum) -> Json {
+    let values = e
+        .values
+        .iter()
+        .map(|value| match value {
+            EnumValue::Single {
+                name,
+                idx,
+                options,
+                deprecated,
+                doc,
+                span,
+            } => {
+                let mut fields = vec![
+                    ("name", Json::str(name)),
+                    ("number", Json::num(*idx as f64)),
+                ];
+                push_deprecated(&mut fields, *deprecated);
+                push_options(&mut fields, options);
+                push_doc(&mut fields, doc);
+                node("value", fields, span)
+            }
+            EnumValue::Reserved { idx } => Json::object([
+                ("kind", Json::str("reserved")),
+                ("number", Json::num(*idx as f64)),
+            ]),
+        })
+        .collect();
+
+    let mut fields = vec![("name", Json::str(&e.name))];
+    push_deprecated(&mut fields, e.deprecated);
+    push_options(&mut fields, &e.options);
+    fields.push(("values", Json::Array(values)));
+    node("enum", fields, &e.span)
+}
+
+fn rpc(method: &Rpc) -> Json {
+    let mut fields = vec![
+        ("name", Json::str(&method.name)),
+        ("request", Json::str(&method.request)),
+        ("response", Json::str(&method.response)),
+    ];
+    if method.stream_request {
+        fields.push(("client_streaming", Json::Bool(true)));
+    }
+    if method.stream_response {
+        fields.push(("server_streaming", Json::Bool(true)));
+    }
+    push_deprecated(&mut fields, method.deprecated);
+    push_options(&mut fields, &method.options);
+    push_doc(&mut fields, &method.doc);
+    node("rpc", fields, &method.span)
+}
+
+fn option(opt: &Option) -> Json {
+    node(
+        "option",
+        vec![
+            ("name", Json::Str(opt.name.to_string())),
+            ("value", option_value(&opt.value)),
+        ],
+        &opt.span,
+    )
+}
+
+/// Value of an option: strings, booleans and (finite) numbers as the
+/// corresponding JSON values, identifiers as `{"constant": "NAME"}`, `inf`
+/// and `nan` as `{"float": "inf"}` and aggregate values as their fields in
+/// order, as `{"message": [{"name": "a", "value": 1}]}`.
+fn option_value(value: &OptionValue) -> Json {
+    match value {
+        OptionValue::Str { value } => Json::str(value),
+        OptionValue::Bool { value } => Json::Bool(*value),
+        OptionValue::Num {
+            value: value @ Number::Float(float),
+        } if !float.is_finite() => Json::object([("float", Json::Str(value.to_string()))]),
+        OptionValue::Num { value } => Json::Num(value.to_string()),
+        OptionValue::Constant { value } => Json::object([("constant", Json::str(value))]),
+        OptionValue::Array(values) => Json::Array(values.iter().map(option_value).collect()),
+        OptionValue::Msg(fields) => {
+            let fields = fields
+                .iter()
+                .map(|(name, value)| {
+                    Json::object([("name", Json::str(name)), ("value", option_value(value))])
+                })
+                .collect();
+            Json::object([("message", Json::Array(fields))])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! Grammar cases of the parser: every `tests/grammar/<case>.proto` is
+    //! parsed and compared against the AST in `<case>.json`, along with the
+    //! syntax errors of the statements skipped (or the error the file fails
+    //! with). Run with `PROTOTS_BLESS=1` to write the expected files of new
+    //! (or changed) cases.
+    //!
+    //! With `PROTOTS_CONFORMANCE=1` the cases are compiled with `protoc` as
+    //! well, if found on the PATH, and the resolved models compared (see
+    //! `conformance`). Cases protoc rejects are skipped.
+
+    use std::path::Path;
+
+    use super::{to_json, to_json_version};
+    use crate::json::{self, Json};
+    #[cfg(feature = "plugin")]
+    use crate::{conformance, imports};
+    use crate::{parser, Opts};
+
+    fn case(input: &str) -> Json {
+        match parser::parse_partial(&Opts::default(), "case.proto", input) {
+            Ok(parser::Partial {
+                proto, diagnostics, ..
+            }) => {
+                let Json::Object(mut fields) = to_json(&proto) else {
+                    unreachable!()
+                };
+                fields.retain(|(key, _)| key != "version" && key != "file");
+                if !diagnostics.is_empty() {
+                    let diagnostics = diagnostics
+                        .iter()
+                        .map(|diagnostic| {
+                            Json::Str(format!(
+                                "{}:{}: {}",
+                                diagnostic.line, diagnostic.column, diagnostic.message
+                            ))
+                        })
+                        .collect();
+                    fields.push(("diagnostics".to_string(), Json::Array(diagnostics)));
+                }
+                Json::Object(fields)
+            }
+            Err(err) => Json::object([("error", Json::Str(err.to_string()))]),
+        }
+    }
+
+    /// Divergences of the model of the given case from the one of protoc, or
+    /// why the case was skipped.
+    #[cfg(feature = "plugin")]
+    fn conformance_case(dir: &Path, path: &Path) -> Result<Vec<String>, String> {
+        let opts = Opts {
+            file: path.display().to_string(),
+            include_paths: vec![dir.display().to_string()],
+            ..Opts::default()
+        };
+        let input = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let file = imports::import_name(&opts);
+        let proto = parser::parse(&opts, &file, &input).map_err(|err| err.to_string())?;
+        let deps = imports::resolve(&opts, &proto).map_err(|err| err.to_string())?;
+
+        let divergences = conformance::check(&opts.include_paths, &file, &proto, &deps)
+            .map_err(|err| err.to_string())?;
+        Ok(divergences.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn grammar_cases() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/grammar");
+        let bless = std::env::var_os("PROTOTS_BLESS").is_some();
+        #[cfg(feature = "plugin")]
+        let conformance =
+            std::env::var_os("PROTOTS_CONFORMANCE").is_some() && conformance::protoc_available();
+
+        let mut cases = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "proto"))
+            .collect::<Vec<_>>();
+        cases.sort();
+        assert!(!cases.is_empty());
+
+        let mut failures = Vec::new();
+        for path in cases {
+            let actual = case(&std::fs::read_to_string(&path).unwrap());
+            let expected_path = path.with_extension("json");
+            if bless {
+                std::fs::write(&expected_path, format!("{:#}\n", actual)).unwrap();
+                continue;
+            }
+
+            let expected = std::fs::read_to_string(&expected_path)
+                .map_err(|err| err.to_string())
+                .and_then(|expected| json::parse(&expected).map_err(|err| err.to_string()));
+            match expected {
+                Ok(expected) if expected == actual => (),
+                Ok(expected) => failures.push(format!(
+                    "{}:\nexpected: {:#}\nactual: {:#}",
+                    path.display(),
+                    expected,
+                    actual
+                )),
+                Err(err) => failures.push(format!("{}: {}", expected_path.display(), err)),
+            }
+
+            #[cfg(feature = "plugin")]
+            if conformance {
+                match conformance_case(&dir, &path) {
+                    Ok(divergences) if divergences.is_empty() => (),
+                    Ok(divergences) => failures.push(format!(
+                        "{} diverges from protoc:\n{}",
+                        path.display(),
+                        divergences.join("\n")
+                    )),
+                    Err(err) => eprintln!("skipped conformance of {}: {}", path.display(), err),
+                }
+            }
+        }
+
+        assert!(
+            failures.is_empty(),
+            "{} grammar case(s) failed (run with PROTOTS_BLESS=1 to update):\n\n{}",
+            failures.len(),
+            failures.join("\n\n")
+        );
+    }
+
+    #[test]
+    fn ast_versions() {
+        let input = "syntax = \"proto3\";\nmessage Foo { string a = 1 [(my.span) = { line: 1 }]; }";
+        let proto = parser::parse(&Opts::default(), "foo.proto", input).unwrap();
+
+        assert_eq!(
+            to_json_version(&proto, 2).to_string(),
+            to_json(&proto).to_string()
+        );
+        assert_eq!(
+            to_json_version(&proto, 1).to_string(),
+            r#"{"version":1,"file":"foo.proto","syntax":"proto3","elems":[{"kind":"message","name":"Foo","fields":[{"kind":"field","name":"a","type":"string","number":1,"options":[{"kind":"option","name":"(my.span)","value":{"message":[{"name":"line","value":1}]},"line":2,"column":29}],"line":2,"column":15}],"line":2,"column":1}]}"#
+        );
+    }
+}
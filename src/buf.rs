@@ -0,0 +1,457 @@
+//! Workspaces of the `buf` tool (see the `generate` command): the modules of
+//! a `buf.work.yaml` or `buf.yaml` (v1beta1, v1 and v2) are read so that the
+//! very same proto files are generated as `buf` builds, without repeating
+//! the roots and excludes in the configuration of protots.
+//!
+//! Only the subset of YAML used by these files is understood: nested
+//! mappings, block and flow lists and plain or quoted scalars.
+
+use std::path::{Path, PathBuf};
+
+use crate::errors::PtError;
+use crate::imports;
+use crate::Opts;
+
+pub const WORK_FILE: &str = "buf.work.yaml";
+pub const MODULE_FILE: &str = "buf.yaml";
+
+#[derive(Debug, PartialEq)]
+pub struct Workspace {
+    pub modules: Vec<Module>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Module {
+    /// directory the import names of the module's files are relative to
+    pub root: PathBuf,
+    /// directories or files below the root that are not part of the module
+    pub excludes: Vec<PathBuf>,
+    /// the `lint` section of the module as given, key by key, so lint rules
+    /// can honor the configuration of `buf`
+    pub lint: Vec<(String, Vec<String>)>,
+}
+
+impl Workspace {
+    /// Load the workspace of the given directory from its `buf.work.yaml`,
+    /// or its `buf.yaml` if there is none.
+    pub fn load(opts: &Opts, dir: &Path) -> Result<Workspace, PtError> {
+        let work = dir.join(WORK_FILE);
+        if opts.files.exists(&work) {
+            let yaml = load_yaml(opts, &work)?;
+            let mut modules = Vec::new();
+            for directory in yaml.get("directories").map_or(Vec::new(), Yaml::strings) {
+                let root = dir.join(directory);
+                let config = root.join(MODULE_FILE);
+                match opts.files.exists(&config) {
+                    true => modules.extend(modules_v1(&root, &load_yaml(opts, &config)?)),
+                    false => modules.push(Module::new(root)),
+                }
+            }
+            return Ok(Workspace { modules });
+        }
+
+        let config = dir.join(MODULE_FILE);
+        if !opts.files.exists(&config) {
+            return Err(PtError::InvalidArgument(format!(
+                "no {} or {} in {}",
+                WORK_FILE,
+                MODULE_FILE,
+                dir.display()
+            )));
+        }
+        let yaml = load_yaml(opts, &config)?;
+        let modules = match yaml.get("version").and_then(Yaml::str) {
+            Some("v2") => modules_v2(dir, &yaml),
+            Some("v1" | "v1beta1") | None => modules_v1(dir, &yaml),
+            Some(other) => {
+                return Err(PtError::InvalidArgument(format!(
+                    "{}: unsupported version {}",
+                    config.display(),
+                    other
+                )))
+            }
+        };
+        Ok(Workspace { modules })
+    }
+
+    /// Directories the files of the workspace import each other from.
+    pub fn roots(&self) -> impl Iterator<Item = &Path> {
+        self.modules.iter().map(|module| module.root.as_path())
+    }
+
+    /// The proto files of all modules that are not excluded, sorted by path
+    /// within each module.
+    pub fn files(&self, opts: &Opts) -> Result<Vec<PathBuf>, PtError> {
+        let mut files = Vec::new();
+        for module in &self.modules {
+            let mut paths = Vec::new();
+            imports::proto_files(opts, &module.root, &mut paths)?;
+            files.extend(paths.into_iter().filter(|path| {
+                !module
+                    .excludes
+                    .iter()
+                    .any(|exclude| path.starts_with(module.root.join(exclude)))
+            }));
+        }
+        Ok(files)
+    }
+}
+
+impl Module {
+    fn new(root: PathBuf) -> Module {
+        Module {
+            root,
+            excludes: Vec::new(),
+            lint: Vec::new(),
+        }
+    }
+}
+
+/// Modules of a `buf.yaml` of version v1 (the directory of the file) or
+/// v1beta1 (the `build.roots` below it).
+fn modules_v1(dir: &Path, yaml: &Yaml) -> Vec<Module> {
+    let build = yaml.get("build");
+    let roots = build
+        .and_then(|build| build.get("roots"))
+        .map(Yaml::strings);
+    let excludes = build
+        .and_then(|build| build.get("excludes"))
+        .map_or(Vec::new(), Yaml::strings);
+
+    let roots = match roots {
+        Some(roots) if !roots.is_empty() => roots,
+        _ => vec![".".to_string()],
+    };
+    roots
+        .iter()
+        .map(|root| Module {
+            // excludes are relative to the module, which may be prefixed
+            // with the root in v1beta1
+            excludes: excludes
+                .iter()
+                .map(|exclude| relative(exclude, root))
+                .collect(),
+            lint: lint(yaml),
+            root: dir.join(root),
+        })
+        .collect()
+}
+
+/// Modules of a `buf.yaml` of version v2, the directory of the file unless
+/// listed in `modules`. Their excludes are relative to the workspace.
+fn modules_v2(dir: &Path, yaml: &Yaml) -> Vec<Module> {
+    let Some(Yaml::List(modules)) = yaml.get("modules") else {
+        return vec![Module {
+            lint: lint(yaml),
+            ..Module::new(dir.to_path_buf())
+        }];
+    };
+
+    modules
+        .iter()
+        .map(|module| {
+            let root = module.get("path").and_then(Yaml::str).unwrap_or(".");
+            let excludes = module.get("excludes").map_or(Vec::new(), Yaml::strings);
+            Module {
+                root: dir.join(root),
+                excludes: excludes
+                    .iter()
+                    .map(|exclude| relative(exclude, root))
+                    .collect(),
+                // the module's own section replaces the workspace's one
+                lint: match module.get("lint") {
+                    Some(_) => lint(module),
+                    None => lint(yaml),
+                },
+            }
+        })
+        .collect()
+}
+
+/// The path relative to the root, if it is below it.
+fn relative(path: &str, root: &str) -> PathBuf {
+    let path = Path::new(path);
+    path.strip_prefix(root).unwrap_or(path).to_path_buf()
+}
+
+fn lint(yaml: &Yaml) -> Vec<(String, Vec<String>)> {
+    match yaml.get("lint") {
+        Some(Yaml::Map(entries)) => entries
+            .iter()
+            .map(|(key, value)| (key.clone(), value.strings()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn load_yaml(opts: &Opts, path: &Path) -> Result<Yaml, PtError> {
+    let content = opts.files.read_to_string(path)?;
+    parse_yaml(&content)
+        .map_err(|err| PtError::InvalidArgument(format!("{}: {}", path.display(), err)))
+}
+
+#[derive(Debug, PartialEq)]
+enum Yaml {
+    Str(String),
+    List(Vec<Yaml>),
+    Map(Vec<(String, Yaml)>),
+}
+
+impl Yaml {
+    fn get(&self, key: &str) -> Option<&Yaml> {
+        match self {
+            Yaml::Map(entries) => entries
+                .iter()
+                .find_map(|(name, value)| (name == key).then_some(value)),
+            _ => None,
+        }
+    }
+
+    fn str(&self) -> Option<&str> {
+        match self {
+            Yaml::Str(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Scalars of a list, or the scalar itself.
+    fn strings(&self) -> Vec<String> {
+        match self {
+            Yaml::Str(value) if value.is_empty() => Vec::new(),
+            Yaml::Str(value) => vec![value.clone()],
+            Yaml::List(values) => values
+                .iter()
+                .filter_map(|value| value.str().map(str::to_string))
+                .collect(),
+            Yaml::Map(_) => Vec::new(),
+        }
+    }
+}
+
+fn parse_yaml(input: &str) -> Result<Yaml, String> {
+    let mut lines = input
+        .lines()
+        .map(strip_comment)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let text = line.trim_start();
+            (line.len() - text.len(), text.trim_end().to_string())
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return Ok(Yaml::Map(Vec::new()));
+    }
+
+    let mut idx = 0;
+    let indent = lines[0].0;
+    let yaml = block(&mut lines, &mut idx, indent)?;
+    match lines.get(idx) {
+        Some((_, text)) => Err(format!("unexpected `{}`", text)),
+        None => Ok(yaml),
+    }
+}
+
+/// Mapping or list of the lines at the given indentation.
+fn block(lines: &mut [(usize, String)], idx: &mut usize, indent: usize) -> Result<Yaml, String> {
+    if is_item(&lines[*idx].1) {
+        let mut items = Vec::new();
+        while *idx < lines.len() && lines[*idx].0 == indent && is_item(&lines[*idx].1) {
+            let rest = lines[*idx].1[1..].trim_start().to_string();
+            if rest.is_empty() {
+                *idx += 1;
+                items.push(nested(lines, idx, indent, false)?);
+            } else if rest.contains(": ") || rest.ends_with(':') {
+                // mapping item, whose first entry is on the line of the dash
+                let offset = lines[*idx].1.len() - rest.len();
+                lines[*idx] = (indent + offset, rest);
+                items.push(block(lines, idx, indent + offset)?);
+            } else {
+                *idx += 1;
+                items.push(scalar(&rest));
+            }
+        }
+        return Ok(Yaml::List(items));
+    }
+
+    let mut entries = Vec::new();
+    while *idx < lines.len() && lines[*idx].0 == indent && !is_item(&lines[*idx].1) {
+        let text = lines[*idx].1.clone();
+        let (key, value) = match text.split_once(": ") {
+            Some((key, value)) => (key, value.trim()),
+            None => (
+                text.strip_suffix(':')
+                    .ok_or_else(|| format!("expected `key: value`, found `{}`", text))?,
+                "",
+            ),
+        };
+        *idx += 1;
+        let value = match value {
+            "" => nested(lines, idx, indent, true)?,
+            value => scalar(value),
+        };
+        entries.push((unquote(key).to_string(), value));
+    }
+    Ok(Yaml::Map(entries))
+}
+
+/// Block below a key or a dash, where lists of a key may be indented as far
+/// as the key itself.
+fn nested(
+    lines: &mut [(usize, String)],
+    idx: &mut usize,
+    indent: usize,
+    key: bool,
+) -> Result<Yaml, String> {
+    match lines.get(*idx) {
+        Some((next, _)) if *next > indent => {
+            let next = *next;
+            block(lines, idx, next)
+        }
+        Some((next, text)) if key && *next == indent && is_item(text) => block(lines, idx, indent),
+        _ => Ok(Yaml::Str(String::new())),
+    }
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+fn scalar(value: &str) -> Yaml {
+    match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(values) => Yaml::List(
+            values
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(scalar)
+                .collect(),
+        ),
+        None => Yaml::Str(unquote(value).to_string()),
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value| value.strip_suffix(*quote))
+        })
+        .unwrap_or(value)
+}
+
+/// The line without its comment, if any, which starts at a `#` at the start
+/// of the line or after whitespace outside of quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (idx, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return &line[..idx],
+            None => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    use super::{parse_yaml, Workspace, Yaml};
+    use crate::provider::{Files, Memory};
+    use crate::Opts;
+
+    #[test]
+    fn parse_buf_yaml() {
+        let yaml = parse_yaml(
+            "version: v2 # latest\nmodules:\n  - path: proto\n    excludes:\n    - proto/vendor\n\
+             lint:\n  use: [DEFAULT, \"COMMENTS\"]\n",
+        )
+        .unwrap();
+        assert_eq!(yaml.get("version"), Some(&Yaml::Str("v2".to_string())));
+        let Some(Yaml::List(modules)) = yaml.get("modules") else {
+            panic!("no modules: {:?}", yaml);
+        };
+        assert_eq!(
+            modules[0].get("excludes").unwrap().strings(),
+            ["proto/vendor"]
+        );
+        assert_eq!(
+            yaml.get("lint")
+                .and_then(|lint| lint.get("use"))
+                .unwrap()
+                .strings(),
+            ["DEFAULT", "COMMENTS"]
+        );
+    }
+
+    #[test]
+    fn workspace_files() {
+        let opts = |files: Vec<(&str, &str)>| Opts {
+            files: Files(Rc::new(Memory::new(files))),
+            ..Opts::default()
+        };
+
+        // v2 with the excludes relative to the workspace
+        let v2 = opts(vec![
+            (
+                "buf.yaml",
+                "version: v2\nmodules:\n  - path: proto\n    excludes: [proto/vendor]\n\
+                 lint:\n  except:\n    - PACKAGE_VERSION_SUFFIX\n",
+            ),
+            ("proto/foo/v1/foo.proto", ""),
+            ("proto/vendor/bar.proto", ""),
+            ("other/baz.proto", ""),
+        ]);
+        let workspace = Workspace::load(&v2, Path::new("")).unwrap();
+        assert_eq!(workspace.modules[0].excludes, [PathBuf::from("vendor")]);
+        assert_eq!(
+            workspace.modules[0].lint,
+            [(
+                "except".to_string(),
+                vec!["PACKAGE_VERSION_SUFFIX".to_string()]
+            )]
+        );
+        assert_eq!(
+            workspace.files(&v2).unwrap(),
+            [PathBuf::from("proto/foo/v1/foo.proto")]
+        );
+
+        // work file listing v1 modules, with or without configuration
+        let v1 = opts(vec![
+            (
+                "buf.work.yaml",
+                "version: v1\ndirectories:\n  - api\n  - vendor\n",
+            ),
+            (
+                "api/buf.yaml",
+                "version: v1\nbuild:\n  excludes:\n    - internal\n",
+            ),
+            ("api/user.proto", ""),
+            ("api/internal/debug.proto", ""),
+            ("vendor/google/type/date.proto", ""),
+        ]);
+        let workspace = Workspace::load(&v1, Path::new("")).unwrap();
+        assert_eq!(
+            workspace.roots().collect::<Vec<_>>(),
+            [Path::new("api"), Path::new("vendor")]
+        );
+        assert_eq!(
+            workspace.files(&v1).unwrap(),
+            [
+                PathBuf::from("api/user.proto"),
+                PathBuf::from("vendor/google/type/date.proto")
+            ]
+        );
+
+        assert!(Workspace::load(&opts(Vec::new()), Path::new("")).is_err());
+    }
+}
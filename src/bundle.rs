@@ -0,0 +1,89 @@
+//! Proto files embedded in the binary, so they can be imported without
+//! passing include paths. Files found on the include paths take precedence.
+//!
+//! - the well-known types below `google/protobuf` (feature
+//!   `well-known-types`, enabled by default)
+//! - the common protos of googleapis below `google/api`, `google/rpc` and
+//!   `google/type`, e.g. for HTTP annotations (feature `common-protos`)
+
+macro_rules! bundled {
+    ($($name:literal),* $(,)?) => {
+        &[$(($name, include_str!(concat!("../assets/bundle/", $name)))),*]
+    };
+}
+
+#[cfg(feature = "well-known-types")]
+const WELL_KNOWN_TYPES: &[(&str, &str)] = bundled![
+    "google/protobuf/any.proto",
+    "google/protobuf/api.proto",
+    "google/protobuf/descriptor.proto",
+    "google/protobuf/duration.proto",
+    "google/protobuf/empty.proto",
+    "google/protobuf/field_mask.proto",
+    "google/protobuf/source_context.proto",
+    "google/protobuf/struct.proto",
+    "google/protobuf/timestamp.proto",
+    "google/protobuf/type.proto",
+    "google/protobuf/wrappers.proto",
+];
+
+#[cfg(not(feature = "well-known-types"))]
+const WELL_KNOWN_TYPES: &[(&str, &str)] = bundled![];
+
+#[cfg(feature = "common-protos")]
+const COMMON_PROTOS: &[(&str, &str)] = bundled![
+    "google/api/annotations.proto",
+    "google/api/client.proto",
+    "google/api/field_behavior.proto",
+    "google/api/http.proto",
+    "google/api/httpbody.proto",
+    "google/api/launch_stage.proto",
+    "google/api/resource.proto",
+    "google/rpc/code.proto",
+    "google/rpc/error_details.proto",
+    "google/rpc/status.proto",
+    "google/type/calendar_period.proto",
+    "google/type/color.proto",
+    "google/type/date.proto",
+    "google/type/datetime.proto",
+    "google/type/dayofweek.proto",
+    "google/type/decimal.proto",
+    "google/type/expr.proto",
+    "google/type/fraction.proto",
+    "google/type/interval.proto",
+    "google/type/latlng.proto",
+    "google/type/localized_text.proto",
+    "google/type/money.proto",
+    "google/type/month.proto",
+    "google/type/phone_number.proto",
+    "google/type/postal_address.proto",
+    "google/type/quaternion.proto",
+    "google/type/timeofday.proto",
+];
+
+#[cfg(not(feature = "common-protos"))]
+const COMMON_PROTOS: &[(&str, &str)] = bundled![];
+
+/// Content of the bundled proto file of the given import name.
+pub fn source(import: &str) -> Option<&'static str> {
+    WELL_KNOWN_TYPES
+        .iter()
+        .chain(COMMON_PROTOS)
+        .find(|(name, _)| *name == import)
+        .map(|(_, content)| *content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{COMMON_PROTOS, WELL_KNOWN_TYPES};
+    use crate::parser::parse;
+    use crate::Opts;
+
+    #[test]
+    fn parse_bundled_files() {
+        for (name, content) in WELL_KNOWN_TYPES.iter().chain(COMMON_PROTOS) {
+            let proto = parse(&Opts::default(), name, content);
+            assert!(proto.is_ok(), "{}: {:?}", name, proto.err());
+        }
+    }
+}
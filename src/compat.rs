@@ -0,0 +1,133 @@
+//! Versioned behavior of the code generator.
+//!
+//! Every release that changes the generated code adds an entry to
+//! `VERSIONS`, so the output of an older release can be pinned via `--compat
+//! <VERSION>`. That way the binary may be upgraded for parser fixes without
+//! churning all generated files at once. Generated files are stamped with
+//! the version their emission style corresponds to.
+//!
+//! The naming of the types and the representation of oneofs are the same in
+//! all releases so far; `tests/compat` holds the output of each release for
+//! a fixture, which the generation with the pinned version has to reproduce.
+
+use crate::errors::PtError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Compat {
+    /// `<major>.<minor>` of the release
+    pub version: &'static str,
+    /// the `Source:` header names the input file by its import name
+    /// (relative to the include paths) instead of the path it was given as
+    pub source_import_name: bool,
+    /// the header states the version of the emission style
+    pub version_stamp: bool,
+    /// enums without a zero value fall back to their first value, instead
+    /// of rejecting unknown values
+    pub catch_first_value: bool,
+    /// enums declared further down the file are referenced via `z.lazy`
+    pub lazy_enum_refs: bool,
+    /// messages declared further down the file (or the message itself) are
+    /// referenced via `z.lazy`
+    pub lazy_message_refs: bool,
+    /// map keys are validated as the strings they are in JSON, instead of
+    /// by the schema of their scalar type
+    pub string_map_keys: bool,
+    /// fields are keyed by their `json_name` option, if given
+    pub json_name_keys: bool,
+    /// bytes fields are validated as base64
+    pub bytes_base64: bool,
+    /// deprecated messages, enums and fields are marked in jsdoc
+    pub deprecated_jsdoc: bool,
+}
+
+/// Behavior of all releases changing the generated code, oldest first.
+pub const VERSIONS: [Compat; 2] = [
+    Compat {
+        version: "0.1",
+        source_import_name: false,
+        version_stamp: false,
+        catch_first_value: false,
+        lazy_enum_refs: false,
+        lazy_message_refs: false,
+        string_map_keys: false,
+        json_name_keys: false,
+        bytes_base64: false,
+        deprecated_jsdoc: false,
+    },
+    Compat {
+        version: "0.2",
+        source_import_name: true,
+        version_stamp: true,
+        catch_first_value: true,
+        lazy_enum_refs: true,
+        lazy_message_refs: true,
+        string_map_keys: true,
+        json_name_keys: true,
+        bytes_base64: true,
+        deprecated_jsdoc: true,
+    },
+];
+
+impl Default for Compat {
+    fn default() -> Compat {
+        VERSIONS[VERSIONS.len() - 1]
+    }
+}
+
+impl Compat {
+    /// Behavior of the given release, e.g. `0.1`, `0.1.3` or `v0.1`.
+    pub fn parse(version: &str) -> Result<Compat, PtError> {
+        let version = version.trim_start_matches('v');
+        let minor = version.splitn(3, '.').take(2).collect::<Vec<_>>().join(".");
+
+        VERSIONS
+            .iter()
+            .find(|compat| compat.version == minor)
+            .copied()
+            .ok_or_else(|| {
+                PtError::InvalidArgument(format!(
+                    "unknown --compat version: {} (available: {})",
+                    version,
+                    VERSIONS.map(|compat| compat.version).join(", ")
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compat, VERSIONS};
+
+    #[test]
+    fn parse_compat_versions() {
+        assert_eq!(Compat::parse("0.1").unwrap(), VERSIONS[0]);
+        assert_eq!(Compat::parse("v0.1.4").unwrap(), VERSIONS[0]);
+        assert_eq!(Compat::parse("0.2.0").unwrap(), Compat::default());
+        assert!(Compat::parse("1.0").is_err());
+    }
+
+    /// The fixture generated by the releases, as their binaries print it
+    /// (followed by a line break).
+    #[cfg(feature = "zod")]
+    const RELEASES: [(&str, &str); 2] = [
+        ("0.1", include_str!("../tests/compat/golden.v0.1.ts")),
+        ("0.2", include_str!("../tests/compat/golden.v0.2.ts")),
+    ];
+
+    #[test]
+    #[cfg(feature = "zod")]
+    fn reproduce_release_output() {
+        let source = include_str!("../tests/compat/golden.proto");
+
+        for (version, expected) in RELEASES {
+            let opts = crate::Opts {
+                file: "golden.proto".to_string(),
+                compat: Compat::parse(version).unwrap(),
+                ..crate::Opts::default()
+            };
+            let proto = crate::parser::parse(&opts, "golden.proto", source).unwrap();
+            let schema = crate::typescript::to_schema(&opts, &proto, &[]).unwrap();
+            assert_eq!(format!("{}\n", schema), expected, "--compat {}", version);
+        }
+    }
+}
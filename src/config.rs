@@ -0,0 +1,158 @@
+//! Project configuration read from a `.prototsrc` file in the current
+//! directory or any of its parents.
+//!
+//! The file consists of `key = value` lines, empty lines and `#` comments.
+//! Keys may be given multiple times, e.g. to list several options.
+//!
+//! Besides the conventions of new files (see `scaffold`) the banner of the
+//! generated files is configured here:
+//!
+//! - `banner`: comment line(s) at the top of generated files
+//! - `banner_file`: file containing the banner, relative to the configuration
+//!
+//! Both may contain the placeholders `{year}` and `{file}` (see
+//! `typescript::banner`).
+
+use std::path::{Path, PathBuf};
+
+use crate::errors::PtError;
+use crate::provider::FileProvider;
+
+pub const FILE_NAME: &str = ".prototsrc";
+
+#[derive(Clone, Default, Debug)]
+pub struct Config {
+    entries: Vec<(String, String)>,
+    /// directory of the configuration file, if loaded from one
+    dir: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn parse(input: &str) -> Result<Config, PtError> {
+        let mut entries = Vec::new();
+
+        for (idx, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                PtError::InvalidArgument(format!(
+                    "{}:{}: expected `key = value`",
+                    FILE_NAME,
+                    idx + 1
+                ))
+            })?;
+            entries.push((key.trim().to_string(), value.trim().to_string()));
+        }
+
+        Ok(Config { entries, dir: None })
+    }
+
+    /// Load the nearest configuration file starting at `dir`, an empty
+    /// configuration if there is none.
+    pub fn load(files: &dyn FileProvider, dir: &Path) -> Result<Config, PtError> {
+        match find(files, dir) {
+            Some(path) => Ok(Config {
+                dir: path.parent().map(Path::to_path_buf),
+                ..Config::parse(&files.read_to_string(&path)?)?
+            }),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Banner template of the generated files, read from `banner_file` or
+    /// given line by line via `banner`.
+    pub fn banner(&self, files: &dyn FileProvider) -> Result<Option<String>, PtError> {
+        if let Some(file) = self.get("banner_file") {
+            let path = match &self.dir {
+                Some(dir) => dir.join(file),
+                None => PathBuf::from(file),
+            };
+            return Ok(Some(files.read_to_string(&path)?));
+        }
+
+        let lines = self.get_all("banner").collect::<Vec<_>>();
+        Ok((!lines.is_empty()).then(|| lines.join("\n")))
+    }
+
+    /// Last value of the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// All values of the given key in order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn find(files: &dyn FileProvider, dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| files.exists(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Config;
+    use crate::provider::{Fs, Memory};
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(
+            "# comment\nsyntax = proto3\n\noption = java_multiple_files = true\noption = optimize_for = SPEED\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.get("syntax"), Some("proto3"));
+        assert_eq!(
+            config.get_all("option").collect::<Vec<_>>(),
+            vec!["java_multiple_files = true", "optimize_for = SPEED"]
+        );
+        assert_eq!(config.get("missing"), None);
+        assert!(Config::parse("no value").is_err());
+        assert_eq!(config.banner(&Fs).unwrap(), None);
+
+        let config =
+            Config::parse("banner = Copyright {year} ACME\nbanner =\nbanner = {file}").unwrap();
+        assert_eq!(
+            config.banner(&Fs).unwrap().as_deref(),
+            Some("Copyright {year} ACME\n\n{file}")
+        );
+    }
+
+    #[test]
+    fn load_config_of_parent_directory() {
+        let files = Memory::new([
+            (
+                "project/.prototsrc",
+                "banner_file = LICENSE\nsyntax = proto2\n",
+            ),
+            ("project/LICENSE", "Copyright ACME"),
+        ]);
+
+        let config = Config::load(&files, Path::new("project/protos/foo")).unwrap();
+        assert_eq!(config.get("syntax"), Some("proto2"));
+        assert_eq!(
+            config.banner(&files).unwrap().as_deref(),
+            Some("Copyright ACME")
+        );
+        assert_eq!(
+            Config::load(&files, Path::new("other"))
+                .unwrap()
+                .get("syntax"),
+            None
+        );
+    }
+}
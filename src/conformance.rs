@@ -0,0 +1,327 @@
+//! Conformance check against `protoc` (see `--conformance`): the descriptor
+//! protots encodes from the parsed source is compared with the one `protoc`
+//! compiles from the same file. Both are decoded the same way and reduced to
+//! an outline of the resolved model, i.e. the fully-qualified names of all
+//! definitions along with their field numbers, labels and (resolved) types.
+//!
+//! Options and comments are not compared, protots keeps the standard options
+//! only.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use crate::descriptor;
+use crate::errors::PtError;
+use crate::parser::{Elem, EnumValue, Field, Flag, Proto, ServiceNode};
+use crate::registry;
+use crate::wire::{Decoder, Value};
+
+/// Definitions by their fully-qualified name, each along with a summary of
+/// its kind and shape, e.g. `field 1 optional string`.
+type Outline = BTreeMap<String, String>;
+
+/// Difference between the models of protots and `protoc`.
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    pub name: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+/// Compile the given proto file (relative to one of the include paths) with
+/// the `protoc` found on the PATH and return where its model diverges from
+/// the parsed proto.
+pub fn check(
+    include_paths: &[String],
+    file: &str,
+    proto: &Proto,
+    deps: &[Proto],
+) -> Result<Vec<Divergence>, PtError> {
+    let out = std::env::temp_dir().join(format!(
+        "protots-conformance-{}-{}.desc",
+        std::process::id(),
+        file.replace(['/', '\\'], "_")
+    ));
+
+    let mut protoc = Command::new("protoc");
+    for include_path in include_paths {
+        protoc.arg(format!("-I{}", include_path));
+    }
+    let output = protoc
+        .arg(format!("--descriptor_set_out={}", out.display()))
+        .arg(file)
+        .output()
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => PtError::Protoc("not found on PATH".to_string()),
+            _ => PtError::Protoc(err.to_string()),
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(PtError::Protoc(stderr.trim().to_string()));
+    }
+
+    let compiled = std::fs::read(&out);
+    let _ = std::fs::remove_file(&out);
+    compare(&descriptor::to_descriptor_set(proto, deps)?, &compiled?)
+}
+
+/// Whether `protoc` can be run, i.e. is found on the PATH.
+pub fn protoc_available() -> bool {
+    Command::new("protoc").arg("--version").output().is_ok()
+}
+
+/// Divergences between the last files of the given descriptor sets, which
+/// is the input file: protots lists the dependencies first and `protoc`
+/// includes none without `--include_imports`.
+pub fn compare(protots: &[u8], protoc: &[u8]) -> Result<Vec<Divergence>, PtError> {
+    let ours = outline(&last_file(protots)?);
+    let theirs = outline(&last_file(protoc)?);
+
+    let divergence = |name: &str, message: String| Divergence {
+        name: name.to_string(),
+        message,
+    };
+    let mut divergences = Vec::new();
+    for (name, shape) in &ours {
+        match theirs.get(name) {
+            None => divergences.push(divergence(name, format!("{} missing in protoc", shape))),
+            Some(other) if other != shape => divergences.push(divergence(
+                name,
+                format!("`{}` in protots but `{}` in protoc", shape, other),
+            )),
+            Some(_) => (),
+        }
+    }
+    for (name, shape) in theirs.iter().filter(|(name, _)| !ours.contains_key(*name)) {
+        divergences.push(divergence(name, format!("{} missing in protots", shape)));
+    }
+
+    Ok(divergences)
+}
+
+fn last_file(set: &[u8]) -> Result<Proto, PtError> {
+    let mut last = None;
+    for entry in Decoder::new(set) {
+        if let (1, Value::Bytes(bytes)) = entry? {
+            last = Some(bytes);
+        }
+    }
+    let bytes =
+        last.ok_or_else(|| PtError::InvalidDescriptor("empty descriptor set".to_string()))?;
+    descriptor::from_file_descriptor(bytes)
+}
+
+fn outline(proto: &Proto) -> Outline {
+    let mut outline = Outline::new();
+    let package = proto.package().unwrap_or_default();
+
+    for elem in &proto.elems {
+        match elem {
+            Elem::Message(msg) => {
+                let name = registry::qualify(package, &msg.name);
+                fields(&mut outline, &name, &msg.fields);
+                outline.insert(name, "message".to_string());
+            }
+            Elem::Enum(e) => {
+                let name = registry::qualify(package, &e.name);
+                values(&mut outline, &name, &e.values);
+                outline.insert(name, "enum".to_string());
+            }
+            Elem::Extend { name, fields, .. } => extensions(&mut outline, package, name, fields),
+            Elem::Service { name, nodes, .. } => {
+                let service = registry::qualify(package, name);
+                for node in nodes {
+                    if let ServiceNode::Rpc(rpc) = node {
+                        let stream = |stream: bool| if stream { "stream " } else { "" };
+                        outline.insert(
+                            format!("{}.{}", service, rpc.name),
+                            format!(
+                                "rpc ({}{}) returns ({}{})",
+                                stream(rpc.stream_request),
+                                rpc.request,
+                                stream(rpc.stream_response),
+                                rpc.response
+                            ),
+                        );
+                    }
+                }
+                outline.insert(service, "service".to_string());
+            }
+            _ => (),
+        }
+    }
+
+    outline
+}
+
+fn fields(outline: &mut Outline, scope: &str, fields: &[Field]) {
+    for field in fields {
+        match field {
+            Field::Single { name, .. } => {
+                outline.insert(format!("{}.{}", scope, name), single(field, None));
+            }
+            Field::Map {
+                name,
+                key_type,
+                value_type,
+                idx,
+                ..
+            } => {
+                outline.insert(
+                    format!("{}.{}", scope, name),
+                    format!("field {} map<{}, {}>", idx, key_type, value_type),
+                );
+            }
+            Field::OneOf {
+                name,
+                fields: members,
+                ..
+            } => {
+                for member in members {
+                    if let Field::Single {
+                        name: member_name, ..
+                    } = member
+                    {
+                        let shape = single(member, Some(name));
+                        outline.insert(format!("{}.{}", scope, member_name), shape);
+                    }
+                }
+                outline.insert(format!("{}.{}", scope, name), "oneof".to_string());
+            }
+            Field::SubMessage(msg) => {
+                let name = format!("{}.{}", scope, msg.name);
+                self::fields(outline, &name, &msg.fields);
+                outline.insert(name, "message".to_string());
+            }
+            Field::SubEnum(e) => {
+                let name = format!("{}.{}", scope, e.name);
+                values(outline, &name, &e.values);
+                outline.insert(name, "enum".to_string());
+            }
+            Field::Extend { name, fields, .. } => extensions(outline, scope, name, fields),
+            Field::Extensions { ranges, .. } => {
+                let ranges = ranges
+                    .iter()
+                    .map(|(from, to)| format!("{} to {}", from, to))
+                    .collect::<Vec<_>>();
+                let key = format!("{} (extensions)", scope);
+                let entry = outline.entry(key).or_default();
+                if !entry.is_empty() {
+                    entry.push_str(", ");
+                }
+                entry.push_str(&ranges.join(", "));
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Shape of a singular, optional or repeated field, e.g. `field 1 optional
+/// string` or `field 2 string (oneof contact)`.
+fn single(field: &Field, oneof: Option<&str>) -> String {
+    let Field::Single {
+        field_type,
+        idx,
+        flag,
+        ..
+    } = field
+    else {
+        return String::new();
+    };
+    let label = match flag {
+        Flag::None => "",
+        Flag::Optional => "optional ",
+        Flag::Repeated => "repeated ",
+        Flag::Required => "required ",
+    };
+    let oneof = oneof.map_or(String::new(), |oneof| format!(" (oneof {})", oneof));
+    format!("field {} {}{}{}", idx, label, field_type, oneof)
+}
+
+/// Extension fields are named by their scope, like protoc does.
+fn extensions(outline: &mut Outline, scope: &str, extendee: &str, fields: &[Field]) {
+    for field in fields {
+        if let Field::Single { name, .. } = field {
+            outline.insert(
+                registry::qualify(scope, name),
+                format!("extension of {} {}", extendee, single(field, None)),
+            );
+        }
+    }
+}
+
+fn values(outline: &mut Outline, scope: &str, values: &[EnumValue]) {
+    for value in values {
+        if let EnumValue::Single { name, idx, .. } = value {
+            // enum values are scoped like siblings of their enum
+            let parent = scope.rsplit_once('.').map_or("", |(parent, _)| parent);
+            outline.insert(
+                registry::qualify(parent, name),
+                format!("value {} of {}", idx, scope),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare;
+    use crate::descriptor::to_descriptor_set;
+    use crate::Opts;
+
+    fn descriptor_set(input: &str) -> Vec<u8> {
+        let proto = crate::parser::parse(&Opts::default(), "foo/v1/user.proto", input).unwrap();
+        to_descriptor_set(&proto, &[]).unwrap()
+    }
+
+    #[test]
+    fn compare_models() {
+        let protots = descriptor_set(
+            r#"syntax = "proto3";
+package foo.v1;
+message User {
+  optional string name = 1;
+  oneof contact { string email = 2; }
+  Kind kind = 3;
+  enum Kind { KIND_UNSPECIFIED = 0; }
+}
+service Users { rpc Get(User) returns (stream User); }"#,
+        );
+        let protoc = descriptor_set(
+            r#"syntax = "proto3";
+package foo.v1;
+message User {
+  string name = 1;
+  string email = 2;
+  Kind kind = 3;
+  enum Kind { KIND_UNSPECIFIED = 0; }
+  int32 age = 4;
+}
+service Users { rpc Get(User) returns (stream User); }"#,
+        );
+
+        assert_eq!(compare(&protots, &protots).unwrap(), vec![]);
+
+        let divergences = compare(&protots, &protoc)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            divergences,
+            [
+                "foo.v1.User.contact: oneof missing in protoc",
+                "foo.v1.User.email: `field 2 string (oneof contact)` in protots but `field 2 \
+                 string` in protoc",
+                "foo.v1.User.name: `field 1 optional string` in protots but `field 1 string` in \
+                 protoc",
+                "foo.v1.User.age: field 4 int32 missing in protots",
+            ]
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Coverage of message definitions by JSON fixtures: fields, oneof cases and
+//! enum values that none of the fixtures makes use of.
+//!
+//! The message types of the fixtures and all types reachable from them are
+//! taken into account, well-known types are not.
+
+use std::collections::HashSet;
+
+use crate::errors::PtError;
+use crate::json::Json;
+use crate::parser::{EnumValue, Proto};
+use crate::payload::{self, Exercised, Issue, Target};
+use crate::registry::{self, declared, Registry};
+
+/// Part of a message definition not exercised by any fixture.
+#[derive(Debug, PartialEq)]
+pub enum Gap {
+    Field {
+        message: String,
+        field: String,
+    },
+    OneofCase {
+        message: String,
+        oneof: String,
+        field: String,
+    },
+    EnumValue {
+        name: String,
+        value: String,
+    },
+}
+
+impl std::fmt::Display for Gap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Gap::Field { message, field } => write!(f, "{}.{}: field never set", message, field),
+            Gap::OneofCase {
+                message,
+                oneof,
+                field,
+            } => write!(f, "{}.{}: oneof case {} never set", message, oneof, field),
+            Gap::EnumValue { name, value } => {
+                write!(f, "{}.{}: enum value never used", name, value)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub gaps: Vec<Gap>,
+    /// number of fields, oneof cases and enum values taken into account
+    pub total: usize,
+    /// problems of the individual fixtures, by fixture index
+    pub issues: Vec<(usize, Issue)>,
+}
+
+impl Report {
+    pub fn covered(&self) -> usize {
+        self.total - self.gaps.len()
+    }
+}
+
+/// Coverage of the given fixtures, pairs of fully-qualified message name and
+/// payload, in order of the type definitions.
+pub fn coverage(protos: &[Proto], fixtures: &[(String, Json)]) -> Result<Report, PtError> {
+    let mut exercised = Exercised::default();
+    let mut issues = Vec::new();
+    for (idx, (message, json)) in fixtures.iter().enumerate() {
+        if registry::find_message(protos, message).is_none() {
+            return Err(Registry::new(protos).unresolved(message, ""));
+        }
+        for issue in payload::traverse(protos, message, json, &mut exercised) {
+            issues.push((idx, issue));
+        }
+    }
+
+    let registry = Registry::new(protos);
+    let mut types = Vec::new();
+    for (message, _) in fixtures {
+        reachable(protos, &registry, message, &mut types);
+    }
+
+    let mut gaps = Vec::new();
+    let mut total = 0;
+    let unused = |set: &HashSet<(String, String)>, name: &str, part: &str| {
+        !set.contains(&(name.to_string(), part.to_string()))
+    };
+    for target in &types {
+        match target {
+            Target::Message(message) => {
+                let Some(msg) = registry::find_message(protos, message) else {
+                    continue;
+                };
+                let mut decls = Vec::new();
+                declared(&msg.fields, None, &mut decls);
+                for decl in decls {
+                    total += 1;
+                    if !unused(&exercised.fields, message, decl.name) {
+                        continue;
+                    }
+                    gaps.push(match decl.oneof {
+                        Some(oneof) => Gap::OneofCase {
+                            message: message.clone(),
+                            oneof: oneof.to_string(),
+                            field: decl.name.to_string(),
+                        },
+                        None => Gap::Field {
+                            message: message.clone(),
+                            field: decl.name.to_string(),
+                        },
+                    });
+                }
+            }
+            Target::Enum(name) => {
+                let Some(e) = registry::find_enum(protos, name) else {
+                    continue;
+                };
+                for value in &e.values {
+                    let EnumValue::Single { name: value, .. } = value else {
+                        continue;
+                    };
+                    total += 1;
+                    if unused(&exercised.enum_values, name, value) {
+                        gaps.push(Gap::EnumValue {
+                            name: name.clone(),
+                            value: value.clone(),
+                        });
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(Report {
+        gaps,
+        total,
+        issues,
+    })
+}
+
+/// Collect the message type and all message and enum types reachable via
+/// its fields, depth first.
+fn reachable(protos: &[Proto], registry: &Registry, message: &str, into: &mut Vec<Target>) {
+    if into
+        .iter()
+        .any(|target| matches!(target, Target::Message(name) if name == message))
+    {
+        return;
+    }
+    into.push(Target::Message(message.to_string()));
+
+    let Some(msg) = registry::find_message(protos, message) else {
+        return;
+    };
+    let mut decls = Vec::new();
+    declared(&msg.fields, None, &mut decls);
+    for decl in decls {
+        match payload::target(registry, decl.field_type, message) {
+            Some(Target::Message(name)) => reachable(protos, registry, &name, into),
+            Some(Target::Enum(name))
+                if !into
+                    .iter()
+                    .any(|target| matches!(target, Target::Enum(other) if *other == name)) =>
+            {
+                into.push(Target::Enum(name))
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{coverage, Gap};
+    use crate::json;
+    use crate::parser::parse;
+    use crate::Opts;
+
+    #[test]
+    fn fixture_coverage() {
+        let proto = parse(
+            &Opts::default(),
+            "user.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+enum Role { ROLE_UNSPECIFIED = 0; ROLE_ADMIN = 1; ROLE_USER = 2; }
+message Address { string city = 1; string zip = 2; }
+message User {
+  string name = 1;
+  repeated Role roles = 2;
+  Address address = 3;
+  repeated string tags = 4;
+  oneof contact {
+    string email = 5;
+    string phone = 6;
+  }
+}"#,
+        )
+        .unwrap();
+
+        let fixtures = [
+            r#"{"name": "a", "roles": ["ROLE_ADMIN"], "email": "a@b.c", "tags": []}"#,
+            r#"{"address": {"city": "x"}, "roles": [2], "unknown": 1}"#,
+        ]
+        .iter()
+        .map(|json| ("foo.v1.User".to_string(), json::parse(json).unwrap()))
+        .collect::<Vec<_>>();
+
+        let report = coverage(&[proto], &fixtures).unwrap();
+        assert_eq!(
+            report
+                .gaps
+                .iter()
+                .map(|gap| gap.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "foo.v1.User.tags: field never set",
+                "foo.v1.User.contact: oneof case phone never set",
+                "foo.v1.Role.ROLE_UNSPECIFIED: enum value never used",
+                "foo.v1.Address.zip: field never set",
+            ]
+        );
+        assert_eq!((report.covered(), report.total), (7, 11));
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].0, 1);
+        assert!(matches!(&report.gaps[0], Gap::Field { field, .. } if field == "tags"));
+    }
+}
@@ -0,0 +1,1805 @@
+//! Serialization of parsed protos into a binary `google.protobuf.FileDescriptorSet`
+//! (see `google/protobuf/descriptor.proto`).
+//!
+//! Only the standard options that are known by name are encoded, custom
+//! (extension) options are skipped as their field numbers are not known
+//! without the extension's declaration. The HTTP annotations of methods
+//! (`google.api.http`) are the exception, their declaration is well known.
+
+use crate::errors::{Location, PtError};
+use crate::parser::{
+    self, Elem, Enum, EnumValue, Field, Flag, ImportModifier, Msg, Option, OptionName,
+    OptionNamePart, OptionValue, Proto, ReservedField, Rpc, ServiceNode, Span,
+};
+use crate::registry::{self, Kind, Registry};
+use crate::wire::{Decoder, Encoder};
+
+/// Exclusive upper bound of valid field numbers, the end of ranges up to `max`.
+const MAX_FIELD_NUMBER: i32 = 536_870_912;
+
+/// Encode the given proto including all its dependencies into a
+/// `FileDescriptorSet`. Dependencies come first, just like
+/// `protoc --include_imports` does.
+pub fn to_descriptor_set(proto: &Proto, deps: &[Proto]) -> Result<Vec<u8>, PtError> {
+    let registry = Registry::new(deps.iter().chain([proto]));
+    let mut enc = Encoder::default();
+
+    for file in deps.iter().chain([proto]) {
+        enc.message(1, |e| file_descriptor(&registry, file, e))?;
+    }
+
+    Ok(enc.buf)
+}
+
+fn file_descriptor(registry: &Registry, proto: &Proto, enc: &mut Encoder) -> Result<(), PtError> {
+    let package = proto.package().unwrap_or("");
+    let syntax = Syntax::of(proto);
+
+    enc.string(1, &proto.file);
+    if !package.is_empty() {
+        enc.string(2, package);
+    }
+
+    let imports = proto
+        .elems
+        .iter()
+        .filter_map(|elem| match elem {
+            Elem::Import { name, modifier, .. } => Some((name, *modifier)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for (name, _) in &imports {
+        enc.string(3, name);
+    }
+    for (modifier, number) in [(ImportModifier::Public, 10), (ImportModifier::Weak, 11)] {
+        for (idx, _) in imports.iter().enumerate().filter(|(_, i)| i.1 == modifier) {
+            enc.int32(number, idx as i32);
+        }
+    }
+
+    for elem in &proto.elems {
+        if let Elem::Message(msg) = elem {
+            enc.message(4, |e| message_descriptor(registry, msg, package, syntax, e))?;
+        }
+    }
+
+    for elem in &proto.elems {
+        if let Elem::Enum(value) = elem {
+            enc.message(5, |e| enum_descriptor(value, e))?;
+        }
+    }
+
+    for elem in &proto.elems {
+        if let Elem::Service { name, nodes, .. } = elem {
+            enc.message(6, |e| service_descriptor(registry, name, nodes, package, e))?;
+        }
+    }
+
+    for elem in &proto.elems {
+        if let Elem::Extend { name, fields, .. } = elem {
+            extensions(registry, name, fields, package, syntax, 7, enc)?;
+        }
+    }
+
+    let options = proto
+        .elems
+        .iter()
+        .filter_map(|elem| match elem {
+            Elem::Option(opt) => Some(opt),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !options.is_empty() {
+        enc.message(8, |e| {
+            options.iter().for_each(|opt| file_option(opt, e));
+            Ok(())
+        })?;
+    }
+
+    if proto.syntax != "proto2" {
+        enc.string(12, &proto.syntax);
+    }
+    if let Some(edition) = &proto.edition {
+        enc.int32(14, edition_number(edition)?);
+    }
+
+    Ok(())
+}
+
+fn message_descriptor(
+    registry: &Registry,
+    msg: &Msg,
+    scope: &str,
+    syntax: Syntax,
+    enc: &mut Encoder,
+) -> Result<(), PtError> {
+    let full_name = registry::qualify(scope, &msg.name);
+
+    enc.string(1, &msg.name);
+
+    // synthetic oneofs of proto3 optional fields are always put after the
+    // real oneofs of the message
+    let mut oneofs = msg
+        .fields
+        .iter()
+        .filter_map(|field| match field {
+            Field::OneOf { name, .. } => Some(name.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let mut oneof_idx = 0;
+
+    for field in &msg.fields {
+        match field {
+            Field::Single {
+                name,
+                field_type,
+                idx,
+                flag,
+                deprecated,
+                json_name,
+                span,
+                ..
+            } => {
+                let synthetic = syntax == Syntax::Proto3 && matches!(flag, Flag::Optional);
+                let oneof = if synthetic {
+                    oneofs.push(format!("_{}", name));
+                    Some(oneofs.len() as i32 - 1)
+                } else {
+                    None
+                };
+
+                enc.message(2, |e| {
+                    let field = FieldDescriptor {
+                        name,
+                        json_name: json_name.as_deref(),
+                        number: *idx,
+                        label: label(syntax, flag),
+                        field_type,
+                        oneof,
+                        proto3_optional: synthetic,
+                        presence: syntax.field_presence(flag),
+                        deprecated: *deprecated,
+                    };
+                    field.encode(registry, &full_name, e)
+                })
+                .map_err(|error| {
+                    error.at(Location::new("message", &full_name).member("field", name, span.line))
+                })?;
+            }
+            Field::Map {
+                name,
+                idx,
+                deprecated,
+                ..
+            } => {
+                let entry = format!("{}.{}", full_name, map_entry_name(name));
+                enc.message(2, |e| {
+                    e.string(1, name);
+                    e.int32(3, *idx);
+                    e.int32(4, LABEL_REPEATED);
+                    e.int32(5, TYPE_MESSAGE);
+                    e.string(6, &format!(".{}", entry));
+                    if *deprecated {
+                        e.message(8, |e| {
+                            e.bool(3, true);
+                            Ok(())
+                        })?;
+                    }
+                    e.string(10, &json_name(name));
+                    Ok(())
+                })?;
+            }
+            Field::OneOf {
+                name: oneof,
+                fields,
+                ..
+            } => {
+                for field in fields {
+                    if let Field::Single {
+                        name,
+                        field_type,
+                        idx,
+                        deprecated,
+                        json_name,
+                        span,
+                        ..
+                    } = field
+                    {
+                        enc.message(2, |e| {
+                            let field = FieldDescriptor {
+                                name,
+                                json_name: json_name.as_deref(),
+                                number: *idx,
+                                label: LABEL_OPTIONAL,
+                                field_type,
+                                oneof: Some(oneof_idx),
+                                proto3_optional: false,
+                                presence: None,
+                                deprecated: *deprecated,
+                            };
+                            field.encode(registry, &full_name, e)
+                        })
+                        .map_err(|error| {
+                            let case = format!("{}.{}", oneof, name);
+                            error.at(Location::new("message", &full_name)
+                                .member("field", case, span.line))
+                        })?;
+                    }
+                }
+                oneof_idx += 1;
+            }
+            _ => (),
+        }
+    }
+
+    for field in &msg.fields {
+        match field {
+            Field::SubMessage(sub) => enc.message(3, |e| {
+                message_descriptor(registry, sub, &full_name, syntax, e)
+            })?,
+            Field::Map {
+                name,
+                key_type,
+                value_type,
+                span,
+                ..
+            } => enc
+                .message(3, |e| {
+                    e.string(1, &map_entry_name(name));
+                    for (number, (name, field_type)) in [("key", key_type), ("value", value_type)]
+                        .iter()
+                        .enumerate()
+                    {
+                        e.message(2, |e| {
+                            let field = FieldDescriptor {
+                                name,
+                                json_name: None,
+                                number: number as i32 + 1,
+                                label: LABEL_OPTIONAL,
+                                field_type,
+                                oneof: None,
+                                proto3_optional: false,
+                                presence: None,
+                                deprecated: false,
+                            };
+                            field.encode(registry, &full_name, e)
+                        })?;
+                    }
+                    // map_entry = true
+                    e.message(7, |e| {
+                        e.bool(7, true);
+                        Ok(())
+                    })
+                })
+                .map_err(|error| {
+                    error.at(Location::new("message", &full_name).member("field", name, span.line))
+                })?,
+            _ => (),
+        }
+    }
+
+    for field in &msg.fields {
+        if let Field::SubEnum(value) = field {
+            enc.message(4, |e| enum_descriptor(value, e))?;
+        }
+    }
+
+    for field in &msg.fields {
+        if let Field::Extend { name, fields, .. } = field {
+            extensions(registry, name, fields, &full_name, syntax, 6, enc)?;
+        }
+    }
+
+    for field in &msg.fields {
+        if let Field::Extensions { ranges, .. } = field {
+            for (start, end) in ranges {
+                enc.message(5, |e| {
+                    e.int32(1, *start);
+                    e.int32(2, end.saturating_add(1).min(MAX_FIELD_NUMBER));
+                    Ok(())
+                })?;
+            }
+        }
+    }
+
+    let options = msg
+        .options()
+        .filter(|opt| opt.name == "deprecated")
+        .collect::<Vec<_>>();
+    if !options.is_empty() {
+        enc.message(7, |e| {
+            options.iter().for_each(|opt| bool_option(opt, 3, e));
+            Ok(())
+        })?;
+    }
+
+    for name in &oneofs {
+        enc.message(8, |e| {
+            e.string(1, name);
+            Ok(())
+        })?;
+    }
+
+    for field in &msg.fields {
+        match field {
+            Field::Reserved(ReservedField::Idx { idx }) => {
+                for idx in idx {
+                    enc.message(9, |e| {
+                        e.int32(1, *idx);
+                        e.int32(2, idx + 1);
+                        Ok(())
+                    })?;
+                }
+            }
+            Field::Reserved(ReservedField::Range { ranges }) => {
+                for (start, end) in ranges {
+                    enc.message(9, |e| {
+                        e.int32(1, *start);
+                        e.int32(2, end.saturating_add(1).min(MAX_FIELD_NUMBER));
+                        Ok(())
+                    })?;
+                }
+            }
+            Field::Reserved(ReservedField::Name { name }) => {
+                for name in name {
+                    enc.string(10, name);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+fn enum_descriptor(value: &Enum, enc: &mut Encoder) -> Result<(), PtError> {
+    enc.string(1, &value.name);
+
+    for value in &value.values {
+        if let EnumValue::Single {
+            name, idx, options, ..
+        } = value
+        {
+            enc.message(2, |e| {
+                e.string(1, name);
+                e.int32(2, *idx);
+                if parser::is_deprecated(options) {
+                    e.message(3, |e| {
+                        e.bool(1, true);
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    let options = value
+        .options
+        .iter()
+        .filter_map(|opt| {
+            if opt.name == "allow_alias" {
+                Some((2, opt))
+            } else if opt.name == "deprecated" {
+                Some((3, opt))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    if !options.is_empty() {
+        enc.message(3, |e| {
+            options
+                .iter()
+                .for_each(|(number, opt)| bool_option(opt, *number, e));
+            Ok(())
+        })?;
+    }
+
+    for value in &value.values {
+        if let EnumValue::Reserved { idx } = value {
+            // enum reserved ranges are inclusive
+            enc.message(4, |e| {
+                e.int32(1, *idx);
+                e.int32(2, *idx);
+                Ok(())
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+fn service_descriptor(
+    registry: &Registry,
+    name: &str,
+    nodes: &[ServiceNode],
+    scope: &str,
+    enc: &mut Encoder,
+) -> Result<(), PtError> {
+    enc.string(1, name);
+
+    for node in nodes {
+        if let ServiceNode::Rpc(rpc) = node {
+            enc.message(2, |e| {
+                e.string(1, &rpc.name);
+                e.string(2, &message_type(registry, &rpc.request, scope)?);
+                e.string(3, &message_type(registry, &rpc.response, scope)?);
+                let idempotency = rpc.options.iter().find_map(|opt| match &opt.value {
+                    OptionValue::Constant { value } if opt.name == "idempotency_level" => {
+                        IDEMPOTENCY_LEVELS.iter().position(|level| level == value)
+                    }
+                    _ => None,
+                });
+                let http = rpc
+                    .options
+                    .iter()
+                    .find(|opt| opt.name.extension() == Some("google.api.http"));
+                if rpc.deprecated || idempotency.is_some() || http.is_some() {
+                    e.message(4, |e| {
+                        if rpc.deprecated {
+                            e.bool(33, true);
+                        }
+                        if let Some(level) = idempotency {
+                            e.int32(34, level as i32);
+                        }
+                        if let Some(http) = http {
+                            e.message(HTTP_EXTENSION, |e| http_rule(&http.value, e))?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                if rpc.stream_request {
+                    e.bool(5, true);
+                }
+                if rpc.stream_response {
+                    e.bool(6, true);
+                }
+                Ok(())
+            })
+            .map_err(|error| {
+                let service = registry::qualify(scope, name);
+                error.at(Location::new("service", service).member(
+                    "method",
+                    &rpc.name,
+                    rpc.span.line,
+                ))
+            })?;
+        }
+    }
+
+    let options = nodes
+        .iter()
+        .filter_map(|node| match node {
+            ServiceNode::Option(opt) if opt.name == "deprecated" => Some(opt),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !options.is_empty() {
+        enc.message(3, |e| {
+            options.iter().for_each(|opt| bool_option(opt, 33, e));
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+fn extensions(
+    registry: &Registry,
+    extendee: &str,
+    fields: &[Field],
+    scope: &str,
+    syntax: Syntax,
+    number: u32,
+    enc: &mut Encoder,
+) -> Result<(), PtError> {
+    let extendee = message_type(registry, extendee, scope)?;
+
+    for field in fields {
+        if let Field::Single {
+            name,
+            field_type,
+            idx,
+            flag,
+            deprecated,
+            json_name,
+            ..
+        } = field
+        {
+            enc.message(number, |e| {
+                let field = FieldDescriptor {
+                    name,
+                    json_name: json_name.as_deref(),
+                    number: *idx,
+                    label: label(syntax, flag),
+                    field_type,
+                    oneof: None,
+                    proto3_optional: syntax == Syntax::Proto3 && matches!(flag, Flag::Optional),
+                    presence: syntax.field_presence(flag),
+                    deprecated: *deprecated,
+                };
+                e.string(2, &extendee);
+                field.encode(registry, scope, e)
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Syntax {
+    Proto2,
+    Proto3,
+    /// editions with the file default of the `field_presence` feature
+    Editions {
+        presence: i32,
+    },
+}
+
+impl Syntax {
+    fn of(proto: &Proto) -> Syntax {
+        match proto.syntax.as_str() {
+            "proto3" => Syntax::Proto3,
+            "editions" => Syntax::Editions {
+                presence: proto
+                    .elems
+                    .iter()
+                    .rev()
+                    .find_map(|elem| match elem {
+                        Elem::Option(opt) => presence_option(opt),
+                        _ => None,
+                    })
+                    .unwrap_or(PRESENCE_EXPLICIT),
+            },
+            _ => Syntax::Proto2,
+        }
+    }
+
+    /// Field presence of a field with the given flag if it differs from the
+    /// file default.
+    fn field_presence(self, flag: &Flag) -> std::option::Option<i32> {
+        let Syntax::Editions { presence } = self else {
+            return None;
+        };
+        let field_presence = match flag {
+            Flag::Repeated => return None,
+            Flag::None => PRESENCE_IMPLICIT,
+            Flag::Optional => PRESENCE_EXPLICIT,
+            Flag::Required => PRESENCE_LEGACY_REQUIRED,
+        };
+        Some(field_presence).filter(|value| *value != presence)
+    }
+}
+
+const PRESENCE_EXPLICIT: i32 = 1;
+const PRESENCE_IMPLICIT: i32 = 2;
+const PRESENCE_LEGACY_REQUIRED: i32 = 3;
+
+fn presence_option(opt: &Option) -> std::option::Option<i32> {
+    match &opt.value {
+        OptionValue::Constant { value } if opt.name == "features.field_presence" => {
+            match value.as_str() {
+                "EXPLICIT" => Some(PRESENCE_EXPLICIT),
+                "IMPLICIT" => Some(PRESENCE_IMPLICIT),
+                "LEGACY_REQUIRED" => Some(PRESENCE_LEGACY_REQUIRED),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn presence_name(presence: i32) -> &'static str {
+    match presence {
+        PRESENCE_IMPLICIT => "IMPLICIT",
+        PRESENCE_LEGACY_REQUIRED => "LEGACY_REQUIRED",
+        _ => "EXPLICIT",
+    }
+}
+
+fn edition_number(edition: &str) -> Result<i32, PtError> {
+    match edition {
+        "2023" => Ok(1000),
+        "2024" => Ok(1001),
+        other => Err(PtError::InvalidDescriptor(format!(
+            "unsupported edition: {}",
+            other
+        ))),
+    }
+}
+
+fn edition_name(number: i32) -> Result<String, PtError> {
+    match number {
+        1000 => Ok("2023".to_string()),
+        1001 => Ok("2024".to_string()),
+        other => Err(PtError::InvalidDescriptor(format!(
+            "unsupported edition: {}",
+            other
+        ))),
+    }
+}
+
+const LABEL_OPTIONAL: i32 = 1;
+const LABEL_REQUIRED: i32 = 2;
+const LABEL_REPEATED: i32 = 3;
+
+const TYPE_MESSAGE: i32 = 11;
+const TYPE_ENUM: i32 = 14;
+
+struct FieldDescriptor<'a> {
+    name: &'a str,
+    /// custom JSON name, derived from the name otherwise
+    json_name: std::option::Option<&'a str>,
+    number: i32,
+    label: i32,
+    field_type: &'a str,
+    oneof: std::option::Option<i32>,
+    proto3_optional: bool,
+    /// `field_presence` feature of fields of editions files that differs
+    /// from the file default
+    presence: std::option::Option<i32>,
+    deprecated: bool,
+}
+
+impl FieldDescriptor<'_> {
+    fn encode(&self, registry: &Registry, scope: &str, enc: &mut Encoder) -> Result<(), PtError> {
+        enc.string(1, self.name);
+        enc.int32(3, self.number);
+        enc.int32(4, self.label);
+
+        let mut message = false;
+        match scalar_type(self.field_type) {
+            Some(scalar) => enc.int32(5, scalar),
+            None => {
+                let ptype = registry
+                    .resolve(self.field_type, scope)
+                    .ok_or_else(|| registry.unresolved(self.field_type, scope))?;
+                let field_type = match ptype.kind {
+                    Kind::Message => TYPE_MESSAGE,
+                    Kind::Enum => TYPE_ENUM,
+                };
+                enc.int32(5, field_type);
+                enc.string(6, &format!(".{}", ptype.full_name));
+                message = ptype.kind == Kind::Message;
+            }
+        }
+
+        if let Some(oneof) = self.oneof {
+            enc.int32(9, oneof);
+        }
+        match self.json_name {
+            Some(custom) => enc.string(10, custom),
+            None => enc.string(10, &json_name(self.name)),
+        }
+        // message fields always have explicit presence
+        let presence = self.presence.filter(|_| !message && self.oneof.is_none());
+        if presence.is_some() || self.deprecated {
+            enc.message(8, |e| {
+                if self.deprecated {
+                    e.bool(3, true);
+                }
+                if let Some(presence) = presence {
+                    // features.field_presence
+                    e.message(21, |e| {
+                        e.int32(1, presence);
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+        if self.proto3_optional {
+            enc.bool(17, true);
+        }
+
+        Ok(())
+    }
+}
+
+fn label(syntax: Syntax, flag: &Flag) -> i32 {
+    match flag {
+        Flag::None | Flag::Optional => LABEL_OPTIONAL,
+        // editions express required fields via the field presence feature
+        Flag::Required if matches!(syntax, Syntax::Editions { .. }) => LABEL_OPTIONAL,
+        Flag::Required => LABEL_REQUIRED,
+        Flag::Repeated => LABEL_REPEATED,
+    }
+}
+
+fn scalar_type(name: &str) -> std::option::Option<i32> {
+    let scalar = match name {
+        "double" => 1,
+        "float" => 2,
+        "int64" => 3,
+        "uint64" => 4,
+        "int32" => 5,
+        "fixed64" => 6,
+        "fixed32" => 7,
+        "bool" => 8,
+        "string" => 9,
+        "bytes" => 12,
+        "uint32" => 13,
+        "sfixed32" => 15,
+        "sfixed64" => 16,
+        "sint32" => 17,
+        "sint64" => 18,
+        _ => return None,
+    };
+    Some(scalar)
+}
+
+fn message_type(registry: &Registry, name: &str, scope: &str) -> Result<String, PtError> {
+    registry
+        .resolve(name, scope)
+        .map(|ptype| format!(".{}", ptype.full_name))
+        .ok_or_else(|| registry.unresolved(name, scope))
+}
+
+/// Name of the synthesized nested message type of a map field, e.g.
+/// `user_labels` becomes `UserLabelsEntry`.
+fn map_entry_name(field: &str) -> String {
+    let mut name = json_name(field);
+    if let Some(first) = name.get(..1) {
+        name.replace_range(..1, &first.to_ascii_uppercase());
+    }
+    name.push_str("Entry");
+    name
+}
+
+/// JSON name of a field as protoc derives it: underscores are dropped and the
+/// following character is upper-cased.
+pub(crate) fn json_name(field: &str) -> String {
+    let mut name = String::with_capacity(field.len());
+    let mut upper = false;
+    for chr in field.chars() {
+        if chr == '_' {
+            upper = true;
+        } else if upper {
+            name.push(chr.to_ascii_uppercase());
+            upper = false;
+        } else {
+            name.push(chr);
+        }
+    }
+    name
+}
+
+/// Values of `google.protobuf.MethodOptions.IdempotencyLevel` in order.
+const IDEMPOTENCY_LEVELS: [&str; 3] = ["IDEMPOTENCY_UNKNOWN", "NO_SIDE_EFFECTS", "IDEMPOTENT"];
+
+/// Field number of the `google.api.http` extension of `MethodOptions`.
+const HTTP_EXTENSION: u32 = 72_295_728;
+
+/// Fields of `google.api.HttpRule` along with their numbers, the pattern of
+/// `custom` (`google.api.CustomHttpPattern`) and `additional_bindings` are
+/// messages of their own.
+const HTTP_RULE_FIELDS: [(&str, u32); 9] = [
+    ("selector", 1),
+    ("get", 2),
+    ("put", 3),
+    ("post", 4),
+    ("delete", 5),
+    ("patch", 6),
+    ("body", 7),
+    ("custom", 8),
+    ("response_body", 12),
+];
+
+fn http_rule(value: &OptionValue, enc: &mut Encoder) -> Result<(), PtError> {
+    let OptionValue::Msg(fields) = value else {
+        return Ok(());
+    };
+    for (name, value) in fields {
+        let number = HTTP_RULE_FIELDS
+            .iter()
+            .find_map(|(field, number)| (field == name).then_some(*number));
+        match (number, value) {
+            (Some(8), custom) => enc.message(8, |e| {
+                for (field, number) in [("kind", 1), ("path", 2)] {
+                    if let Some(OptionValue::Str { value }) = custom.get(field) {
+                        e.string(number, value);
+                    }
+                }
+                Ok(())
+            })?,
+            (Some(number), OptionValue::Str { value }) => enc.string(number, value),
+            _ if name == "additional_bindings" => {
+                let bindings = match value {
+                    OptionValue::Array(values) => values.iter().collect(),
+                    value => vec![value],
+                };
+                for binding in bindings {
+                    enc.message(11, |e| http_rule(binding, e))?;
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn decode_http_rule(bytes: &[u8]) -> Result<OptionValue, PtError> {
+    let mut fields = Vec::new();
+    for entry in Decoder::new(bytes) {
+        let (number, value) = entry?;
+        let name = HTTP_RULE_FIELDS
+            .iter()
+            .find_map(|(field, field_number)| (*field_number == number).then_some(*field));
+        match (number, name) {
+            (8, _) => {
+                let mut pattern = Vec::new();
+                for entry in Decoder::new(value.bytes()?) {
+                    let (number, value) = entry?;
+                    let field = match number {
+                        1 => "kind",
+                        2 => "path",
+                        _ => continue,
+                    };
+                    let value = value.string()?;
+                    pattern.push((field.to_string(), OptionValue::Str { value }));
+                }
+                fields.push(("custom".to_string(), OptionValue::Msg(pattern)));
+            }
+            (11, _) => fields.push((
+                "additional_bindings".to_string(),
+                decode_http_rule(value.bytes()?)?,
+            )),
+            (_, Some(name)) => {
+                let value = value.string()?;
+                fields.push((name.to_string(), OptionValue::Str { value }));
+            }
+            _ => (),
+        }
+    }
+    Ok(OptionValue::Msg(fields))
+}
+
+fn bool_option(opt: &Option, number: u32, enc: &mut Encoder) {
+    if let OptionValue::Bool { value } = opt.value {
+        enc.bool(number, value);
+    }
+}
+
+fn file_option(opt: &Option, enc: &mut Encoder) {
+    let name = opt.name.path();
+    let string_option = match name.as_str() {
+        "java_package" => Some(1),
+        "java_outer_classname" => Some(8),
+        "go_package" => Some(11),
+        "objc_class_prefix" => Some(36),
+        "csharp_namespace" => Some(37),
+        "swift_prefix" => Some(39),
+        "php_class_prefix" => Some(40),
+        "php_namespace" => Some(41),
+        "php_metadata_namespace" => Some(44),
+        "ruby_package" => Some(45),
+        _ => None,
+    };
+    let bool_option = match name.as_str() {
+        "java_multiple_files" => Some(10),
+        "cc_generic_services" => Some(16),
+        "java_generic_services" => Some(17),
+        "py_generic_services" => Some(18),
+        "java_generate_equals_and_hash" => Some(20),
+        "deprecated" => Some(23),
+        "java_string_check_utf8" => Some(27),
+        "cc_enable_arenas" => Some(31),
+        _ => None,
+    };
+
+    match (&opt.value, string_option, bool_option) {
+        (OptionValue::Str { value }, Some(number), _) => enc.string(number, value),
+        (OptionValue::Bool { value }, _, Some(number)) => enc.bool(number, *value),
+        (OptionValue::Constant { .. }, _, _) if opt.name == "features.field_presence" => {
+            if let Some(presence) = presence_option(opt) {
+                let mut features = Encoder::default();
+                features.int32(1, presence);
+                enc.bytes(50, &features.buf);
+            }
+        }
+        (OptionValue::Constant { value }, _, _) if opt.name == "optimize_for" => {
+            let mode = match value.as_str() {
+                "SPEED" => 1,
+                "CODE_SIZE" => 2,
+                "LITE_RUNTIME" => 3,
+                _ => return,
+            };
+            enc.int32(9, mode);
+        }
+        _ => (),
+    }
+}
+
+/// Decode a single binary `FileDescriptorProto` into the parser's AST.
+pub fn from_file_descriptor(bytes: &[u8]) -> Result<Proto, PtError> {
+    let mut file = String::new();
+    let mut syntax = String::new();
+    let mut edition = None;
+    let mut presence = None;
+    let mut elems = Vec::new();
+    let mut raw_extensions = Vec::new();
+    let mut raw_messages = Vec::new();
+    let mut modifiers = Vec::new();
+
+    for entry in Decoder::new(bytes) {
+        let (number, value) = entry?;
+        match number {
+            1 => file = value.string()?,
+            2 => elems.push(Elem::Package {
+                name: value.string()?,
+                span: Span::default(),
+            }),
+            3 => elems.push(Elem::Import {
+                name: value.string()?,
+                modifier: ImportModifier::None,
+                span: Span::default(),
+            }),
+            4 => raw_messages.push(value.bytes()?),
+            5 => elems.push(Elem::Enum(decode_enum(value.bytes()?)?)),
+            6 => elems.push(decode_service(value.bytes()?)?),
+            7 => raw_extensions.push(RawField::decode(value.bytes()?)?),
+            8 => presence = decode_features(value.bytes()?, 50)?.or(presence),
+            10 => modifiers.push((value.int32()?, ImportModifier::Public)),
+            11 => modifiers.push((value.int32()?, ImportModifier::Weak)),
+            12 => syntax = value.string()?,
+            14 => edition = Some(edition_name(value.int32()?)?),
+            _ => (),
+        }
+    }
+
+    // public and weak dependencies are given as indices into the imports
+    for (idx, elem) in elems
+        .iter_mut()
+        .filter(|elem| matches!(elem, Elem::Import { .. }))
+        .enumerate()
+    {
+        if let Elem::Import { modifier, .. } = elem {
+            if let Some((_, m)) = modifiers.iter().find(|(i, _)| *i as usize == idx) {
+                *modifier = *m;
+            }
+        }
+    }
+
+    if syntax.is_empty() {
+        syntax = "proto2".to_string();
+    }
+
+    let file_syntax = match syntax.as_str() {
+        "proto3" => Syntax::Proto3,
+        "editions" => Syntax::Editions {
+            presence: presence.unwrap_or(PRESENCE_EXPLICIT),
+        },
+        _ => Syntax::Proto2,
+    };
+
+    if let Some(presence) = presence {
+        elems.push(Elem::Option(Option {
+            name: "features.field_presence".into(),
+            value: OptionValue::Constant {
+                value: presence_name(presence).to_string(),
+            },
+            span: Span::default(),
+        }));
+    }
+
+    for raw in raw_messages {
+        elems.push(Elem::Message(decode_message(raw, file_syntax)?.0));
+    }
+
+    elems.extend(
+        extend_blocks(raw_extensions, file_syntax)
+            .into_iter()
+            .map(|(name, fields)| Elem::Extend {
+                name,
+                fields,
+                span: Span::default(),
+            }),
+    );
+
+    Ok(Proto {
+        file,
+        syntax,
+        edition,
+        elems,
+        span: Span::default(),
+    })
+}
+
+/// Value of the `field_presence` feature in the `features` (at the given
+/// field number) of the encoded options, if set.
+fn decode_features(options: &[u8], number: u32) -> Result<std::option::Option<i32>, PtError> {
+    let mut presence = None;
+    for entry in Decoder::new(options) {
+        let (field, value) = entry?;
+        if field == number {
+            for entry in Decoder::new(value.bytes()?) {
+                if let (1, value) = entry? {
+                    presence = Some(value.int32()?);
+                }
+            }
+        }
+    }
+    Ok(presence)
+}
+
+/// Value of the `deprecated` option (at the given field number) of the
+/// encoded options.
+fn decode_deprecated(options: &[u8], number: u32) -> Result<bool, PtError> {
+    let mut deprecated = false;
+    for entry in Decoder::new(options) {
+        let (field, value) = entry?;
+        if field == number {
+            deprecated = value.bool()?;
+        }
+    }
+    Ok(deprecated)
+}
+
+/// Reserved ranges of enums larger than this are not expanded into single
+/// numbers.
+const MAX_EXPANDED_RANGE: i32 = 100;
+
+#[derive(Default)]
+struct RawField {
+    name: String,
+    json_name: String,
+    extendee: String,
+    number: i32,
+    label: i32,
+    field_type: i32,
+    type_name: String,
+    oneof: std::option::Option<i32>,
+    proto3_optional: bool,
+    presence: std::option::Option<i32>,
+    deprecated: bool,
+}
+
+impl RawField {
+    fn decode(bytes: &[u8]) -> Result<RawField, PtError> {
+        let mut field = RawField::default();
+        for entry in Decoder::new(bytes) {
+            let (number, value) = entry?;
+            match number {
+                1 => field.name = value.string()?,
+                2 => field.extendee = strip_dot(value.string()?),
+                3 => field.number = value.int32()?,
+                4 => field.label = value.int32()?,
+                5 => field.field_type = value.int32()?,
+                6 => field.type_name = strip_dot(value.string()?),
+                8 => {
+                    let options = value.bytes()?;
+                    field.presence = decode_features(options, 21)?;
+                    field.deprecated = decode_deprecated(options, 3)?;
+                }
+                9 => field.oneof = Some(value.int32()?),
+                10 => field.json_name = value.string()?,
+                17 => field.proto3_optional = value.bool()?,
+                _ => (),
+            }
+        }
+        Ok(field)
+    }
+
+    fn type_name(&self) -> String {
+        match scalar_name(self.field_type) {
+            Some(scalar) => scalar.to_string(),
+            None => self.type_name.clone(),
+        }
+    }
+
+    fn into_field(self, syntax: Syntax) -> Field {
+        let flag = match (self.label, syntax) {
+            (LABEL_REPEATED, _) => Flag::Repeated,
+            (LABEL_REQUIRED, _) => Flag::Required,
+            (_, Syntax::Editions { presence }) => match self.presence.unwrap_or(presence) {
+                PRESENCE_IMPLICIT => Flag::None,
+                PRESENCE_LEGACY_REQUIRED => Flag::Required,
+                _ => Flag::Optional,
+            },
+            (_, Syntax::Proto2) => Flag::Optional,
+            _ if self.proto3_optional => Flag::Optional,
+            _ => Flag::None,
+        };
+        // protoc sets the JSON name of all fields, custom ones are kept only
+        let custom = !self.json_name.is_empty() && self.json_name != json_name(&self.name);
+        Field::Single {
+            field_type: self.type_name(),
+            json_name: custom.then_some(self.json_name),
+            name: self.name,
+            idx: self.number,
+            flag,
+            options: match self.deprecated {
+                true => vec![option_deprecated()],
+                false => Vec::new(),
+            },
+            deprecated: self.deprecated,
+            doc: None,
+            span: Span::default(),
+        }
+    }
+}
+
+/// Extension fields grouped by the message they extend, in order of the
+/// first extension of each message.
+fn extend_blocks(raw_extensions: Vec<RawField>, syntax: Syntax) -> Vec<(String, Vec<Field>)> {
+    let mut blocks: Vec<(String, Vec<Field>)> = Vec::new();
+    for raw in raw_extensions {
+        let extendee = raw.extendee.clone();
+        let field = raw.into_field(match syntax {
+            Syntax::Editions { .. } => syntax,
+            _ => Syntax::Proto2,
+        });
+        match blocks.iter_mut().find(|(name, _)| *name == extendee) {
+            Some((_, fields)) => fields.push(field),
+            None => blocks.push((extendee, vec![field])),
+        }
+    }
+    blocks
+}
+
+/// Decode a `DescriptorProto`, returning the message and whether it is the
+/// synthesized entry type of a map field.
+fn decode_message(bytes: &[u8], syntax: Syntax) -> Result<(Msg, bool), PtError> {
+    let mut syntax = syntax;
+    let mut name = String::new();
+    let mut raw_fields = Vec::new();
+    let mut raw_nested = Vec::new();
+    let mut enums = Vec::new();
+    let mut others = Vec::new();
+    let mut oneofs = Vec::new();
+    let mut raw_extensions = Vec::new();
+    let mut map_entry = false;
+    let mut deprecated = false;
+
+    for entry in Decoder::new(bytes) {
+        let (number, value) = entry?;
+        match number {
+            1 => name = value.string()?,
+            2 => raw_fields.push(RawField::decode(value.bytes()?)?),
+            3 => raw_nested.push(value.bytes()?),
+            4 => enums.push(Field::SubEnum(decode_enum(value.bytes()?)?)),
+            5 => {
+                let (start, end) = decode_range(value.bytes()?)?;
+                others.push(Field::Extensions {
+                    ranges: vec![(start, end - 1)],
+                    options: Vec::new(),
+                    span: Span::default(),
+                });
+            }
+            6 => raw_extensions.push(RawField::decode(value.bytes()?)?),
+            7 => {
+                for entry in Decoder::new(value.bytes()?) {
+                    let (number, value) = entry?;
+                    match number {
+                        3 if value.bool()? => {
+                            deprecated = true;
+                            others.push(deprecated_option());
+                        }
+                        7 => map_entry = value.bool()?,
+                        _ => (),
+                    }
+                }
+                if let (Syntax::Editions { .. }, Some(presence)) =
+                    (syntax, decode_features(value.bytes()?, 12)?)
+                {
+                    syntax = Syntax::Editions { presence };
+                }
+            }
+            8 => {
+                let mut oneof = String::new();
+                for entry in Decoder::new(value.bytes()?) {
+                    if let (1, value) = entry? {
+                        oneof = value.string()?;
+                    }
+                }
+                oneofs.push(oneof);
+            }
+            9 => {
+                let (start, end) = decode_range(value.bytes()?)?;
+                others.push(Field::Reserved(if end - start == 1 {
+                    ReservedField::Idx { idx: vec![start] }
+                } else {
+                    ReservedField::Range {
+                        ranges: vec![(start, end - 1)],
+                    }
+                }));
+            }
+            10 => others.push(Field::Reserved(ReservedField::Name {
+                name: vec![value.string()?],
+            })),
+            _ => (),
+        }
+    }
+
+    let nested = raw_nested
+        .into_iter()
+        .map(|raw| decode_message(raw, syntax))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut fields = Vec::new();
+    let mut oneof_fields: Vec<Vec<Field>> = oneofs.iter().map(|_| Vec::new()).collect();
+    let mut oneof_order = Vec::new();
+
+    for raw in raw_fields {
+        let entry = nested.iter().find(|(nested, is_entry)| {
+            *is_entry && raw.label == LABEL_REPEATED && raw.type_name.ends_with(&nested.name)
+        });
+
+        if let Some((entry, _)) = entry {
+            let field_type = |field_name: &str| {
+                entry.fields.iter().find_map(|field| match field {
+                    Field::Single {
+                        name, field_type, ..
+                    } if name == field_name => Some(field_type.clone()),
+                    _ => None,
+                })
+            };
+            fields.push(Field::Map {
+                name: raw.name.clone(),
+                key_type: field_type("key").unwrap_or_default(),
+                value_type: field_type("value").unwrap_or_default(),
+                idx: raw.number,
+                options: match raw.deprecated {
+                    true => vec![option_deprecated()],
+                    false => Vec::new(),
+                },
+                deprecated: raw.deprecated,
+                doc: None,
+                span: Span::default(),
+            });
+            continue;
+        }
+
+        match raw.oneof {
+            Some(idx) if !raw.proto3_optional && (idx as usize) < oneofs.len() => {
+                if !oneof_order.contains(&idx) {
+                    oneof_order.push(idx);
+                    // placeholder keeping the declaration order
+                    fields.push(Field::OneOf {
+                        name: oneofs[idx as usize].clone(),
+                        fields: Vec::new(),
+                        options: Vec::new(),
+                        span: Span::default(),
+                    });
+                }
+                oneof_fields[idx as usize].push(raw.into_field(syntax));
+            }
+            _ => fields.push(raw.into_field(syntax)),
+        }
+    }
+
+    for field in fields.iter_mut() {
+        if let Field::OneOf { name, fields, .. } = field {
+            if let Some(idx) = oneofs.iter().position(|oneof| oneof == name) {
+                *fields = std::mem::take(&mut oneof_fields[idx]);
+            }
+        }
+    }
+
+    fields.extend(
+        nested
+            .into_iter()
+            .filter(|(_, is_entry)| !is_entry)
+            .map(|(msg, _)| Field::SubMessage(msg)),
+    );
+    fields.extend(enums);
+    fields.extend(
+        extend_blocks(raw_extensions, syntax)
+            .into_iter()
+            .map(|(name, fields)| Field::Extend {
+                name,
+                fields,
+                span: Span::default(),
+            }),
+    );
+    fields.extend(others);
+
+    Ok((
+        Msg {
+            name,
+            fields,
+            deprecated,
+            doc: None,
+            span: Span::default(),
+        },
+        map_entry,
+    ))
+}
+
+fn decode_enum(bytes: &[u8]) -> Result<Enum, PtError> {
+    let mut name = String::new();
+    let mut values = Vec::new();
+    let mut options = Vec::new();
+
+    for entry in Decoder::new(bytes) {
+        let (number, value) = entry?;
+        match number {
+            1 => name = value.string()?,
+            2 => {
+                let mut value_name = String::new();
+                let mut idx = 0;
+                let mut options = Vec::new();
+                for entry in Decoder::new(value.bytes()?) {
+                    let (number, value) = entry?;
+                    match number {
+                        1 => value_name = value.string()?,
+                        2 => idx = value.int32()?,
+                        3 => {
+                            for entry in Decoder::new(value.bytes()?) {
+                                if let (1, value) = entry? {
+                                    if value.bool()? {
+                                        options.push(option_deprecated());
+                                    }
+                                }
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                values.push(EnumValue::Single {
+                    name: value_name,
+                    idx,
+                    deprecated: parser::is_deprecated(&options),
+                    options,
+                    doc: None,
+                    span: Span::default(),
+                });
+            }
+            3 => {
+                for entry in Decoder::new(value.bytes()?) {
+                    let (number, value) = entry?;
+                    let name = match number {
+                        2 => "allow_alias",
+                        3 => "deprecated",
+                        _ => continue,
+                    };
+                    options.push(Option {
+                        name: name.into(),
+                        value: OptionValue::Bool {
+                            value: value.bool()?,
+                        },
+                        span: Span::default(),
+                    });
+                }
+            }
+            4 => {
+                // enum reserved ranges are inclusive
+                let (start, end) = decode_range(value.bytes()?)?;
+                if end - start < MAX_EXPANDED_RANGE {
+                    values.extend((start..=end).map(|idx| EnumValue::Reserved { idx }));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(Enum {
+        name,
+        values,
+        deprecated: parser::is_deprecated(&options),
+        options,
+        span: Span::default(),
+    })
+}
+
+fn decode_service(bytes: &[u8]) -> Result<Elem, PtError> {
+    let mut name = String::new();
+    let mut nodes = Vec::new();
+
+    for entry in Decoder::new(bytes) {
+        let (number, value) = entry?;
+        match number {
+            1 => name = value.string()?,
+            2 => {
+                let mut rpc = Rpc {
+                    name: String::new(),
+                    request: String::new(),
+                    stream_request: false,
+                    response: String::new(),
+                    stream_response: false,
+                    deprecated: false,
+                    options: Vec::new(),
+                    doc: None,
+                    span: Span::default(),
+                };
+                for entry in Decoder::new(value.bytes()?) {
+                    let (number, value) = entry?;
+                    match number {
+                        1 => rpc.name = value.string()?,
+                        2 => rpc.request = strip_dot(value.string()?),
+                        3 => rpc.response = strip_dot(value.string()?),
+                        4 => {
+                            for entry in Decoder::new(value.bytes()?) {
+                                match entry? {
+                                    (33, value) => rpc.deprecated = value.bool()?,
+                                    (34, value) => {
+                                        let level = value.int32()?;
+                                        let level = IDEMPOTENCY_LEVELS
+                                            .get(level as usize)
+                                            .filter(|_| level > 0);
+                                        if let Some(level) = level {
+                                            rpc.options.push(Option {
+                                                name: "idempotency_level".into(),
+                                                value: OptionValue::Constant {
+                                                    value: level.to_string(),
+                                                },
+                                                span: Span::default(),
+                                            });
+                                        }
+                                    }
+                                    (HTTP_EXTENSION, value) => rpc.options.push(Option {
+                                        name: OptionName {
+                                            parts: vec![OptionNamePart::Extension(
+                                                "google.api.http".to_string(),
+                                            )],
+                                        },
+                                        value: decode_http_rule(value.bytes()?)?,
+                                        span: Span::default(),
+                                    }),
+                                    _ => (),
+                                }
+                            }
+                        }
+                        5 => rpc.stream_request = value.bool()?,
+                        6 => rpc.stream_response = value.bool()?,
+                        _ => (),
+                    }
+                }
+                if rpc.deprecated {
+                    rpc.options.push(option_deprecated());
+                }
+                nodes.push(ServiceNode::Rpc(rpc));
+            }
+            3 => {
+                for entry in Decoder::new(value.bytes()?) {
+                    if let (33, value) = entry? {
+                        if value.bool()? {
+                            nodes.push(ServiceNode::Option(option_deprecated()));
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(Elem::Service {
+        name,
+        nodes,
+        span: Span::default(),
+    })
+}
+
+fn decode_range(bytes: &[u8]) -> Result<(i32, i32), PtError> {
+    let mut range = (0, 0);
+    for entry in Decoder::new(bytes) {
+        let (number, value) = entry?;
+        match number {
+            1 => range.0 = value.int32()?,
+            2 => range.1 = value.int32()?,
+            _ => (),
+        }
+    }
+    Ok(range)
+}
+
+fn option_deprecated() -> Option {
+    Option {
+        name: "deprecated".into(),
+        value: OptionValue::Bool { value: true },
+        span: Span::default(),
+    }
+}
+
+fn deprecated_option() -> Field {
+    Field::Option(option_deprecated())
+}
+
+fn strip_dot(name: String) -> String {
+    match name.strip_prefix('.') {
+        Some(stripped) => stripped.to_string(),
+        None => name,
+    }
+}
+
+fn scalar_name(field_type: i32) -> std::option::Option<&'static str> {
+    let name = match field_type {
+        1 => "double",
+        2 => "float",
+        3 => "int64",
+        4 => "uint64",
+        5 => "int32",
+        6 => "fixed64",
+        7 => "fixed32",
+        8 => "bool",
+        9 => "string",
+        12 => "bytes",
+        13 => "uint32",
+        15 => "sfixed32",
+        16 => "sfixed64",
+        17 => "sint32",
+        18 => "sint64",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{Elem, Field, Flag, Msg, OptionValue, Proto, ServiceNode, Span};
+
+    use super::{from_file_descriptor, json_name, map_entry_name, to_descriptor_set};
+    use crate::wire::{Decoder, Value};
+    use crate::Opts;
+
+    #[test]
+    fn descriptor_set_single_message() {
+        let p = Proto {
+            syntax: "proto3".to_string(),
+            edition: None,
+            file: "a.proto".to_string(),
+            elems: vec![
+                Elem::Package {
+                    name: "p".to_string(),
+                    span: Span::default(),
+                },
+                Elem::Message(Msg {
+                    name: "M".to_string(),
+                    fields: vec![Field::Single {
+                        name: "s".to_string(),
+                        field_type: "string".to_string(),
+                        idx: 1,
+                        flag: Flag::None,
+                        options: vec![],
+                        json_name: None,
+                        deprecated: false,
+                        doc: None,
+                        span: Span::default(),
+                    }],
+                    deprecated: false,
+                    doc: None,
+                    span: Span::default(),
+                }),
+            ],
+            span: Span::default(),
+        };
+
+        let bytes = to_descriptor_set(&p, &[]).unwrap();
+
+        #[rustfmt::skip]
+        let expected = [
+            // FileDescriptorSet.file
+            0x0a, 0x27,
+            // name
+            0x0a, 0x07, b'a', b'.', b'p', b'r', b'o', b't', b'o',
+            // package
+            0x12, 0x01, b'p',
+            // message_type
+            0x22, 0x11,
+            0x0a, 0x01, b'M',
+            // field: name, number, label, type, json_name
+            0x12, 0x0c, 0x0a, 0x01, b's', 0x18, 0x01, 0x20, 0x01, 0x28, 0x09, 0x52, 0x01, b's',
+            // syntax
+            0x62, 0x06, b'p', b'r', b'o', b't', b'o', b'3',
+        ];
+
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn descriptor_set_unknown_type() {
+        let p = Proto {
+            syntax: "proto3".to_string(),
+            edition: None,
+            file: "a.proto".to_string(),
+            elems: vec![Elem::Message(Msg {
+                name: "M".to_string(),
+                fields: vec![Field::Single {
+                    name: "s".to_string(),
+                    field_type: "Unknown".to_string(),
+                    idx: 1,
+                    flag: Flag::None,
+                    options: vec![],
+                    json_name: None,
+                    deprecated: false,
+                    doc: None,
+                    span: Span::default(),
+                }],
+                deprecated: false,
+                doc: None,
+                span: Span::default(),
+            })],
+            span: Span::default(),
+        };
+
+        let err = to_descriptor_set(&p, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "could not find type named: Unknown in message M, field s"
+        );
+    }
+
+    #[test]
+    fn descriptor_names() {
+        assert_eq!(json_name("foo_bar_baz"), "fooBarBaz");
+        assert_eq!(map_entry_name("user_labels"), "UserLabelsEntry");
+    }
+
+    #[test]
+    fn descriptor_roundtrip() {
+        let opts = Opts::default();
+        let input = r#"syntax = "proto3";
+package foo.v1;
+import "foo/v1/a.proto";
+import public "foo/v1/b.proto";
+import weak "foo/v1/c.proto";
+message User {
+  optional string name = 1;
+  map<string, int32> labels = 2 [deprecated = true];
+  oneof contact {
+    string email = 3 [json_name = "mail", deprecated = true];
+    string phone = 4;
+  }
+  repeated Kind kinds = 5;
+  enum Kind {
+    option allow_alias = true;
+    NONE = 0;
+    ADMIN = 1;
+    ROOT = 1 [deprecated = true];
+  }
+  reserved 6, 8 to 10, 1000 to max;
+}
+service Users {
+  rpc Get(User) returns (User);
+}"#;
+        let proto = crate::parser::parse(&opts, "foo/v1/user.proto", input).unwrap();
+        let set = to_descriptor_set(&proto, &[]).unwrap();
+
+        let file = Decoder::new(&set)
+            .find_map(|entry| match entry.unwrap() {
+                (1, Value::Bytes(bytes)) => Some(bytes),
+                _ => None,
+            })
+            .unwrap();
+        let decoded = from_file_descriptor(file).unwrap();
+
+        let Some(Elem::Message(msg)) = decoded.elems.get(5) else {
+            panic!("expected message, got {:?}", decoded.elems);
+        };
+        assert!(matches!(
+            msg.fields[1],
+            Field::Map {
+                deprecated: true,
+                ..
+            }
+        ));
+        assert_eq!(to_descriptor_set(&decoded, &[]).unwrap(), set);
+    }
+
+    #[test]
+    fn descriptor_roundtrip_method_options() {
+        let opts = Opts::default();
+        let input = r#"syntax = "proto3";
+package foo.v1;
+message User {}
+service Users {
+  rpc Get(User) returns (User) {
+    option idempotency_level = NO_SIDE_EFFECTS;
+    option (google.api.http) = {
+      get: "/v1/users/{id}"
+      additional_bindings { post: "/v1/users:get" body: "*" }
+    };
+  }
+}"#;
+        let proto = crate::parser::parse(&opts, "foo/v1/user.proto", input).unwrap();
+        let set = to_descriptor_set(&proto, &[]).unwrap();
+
+        let file = Decoder::new(&set)
+            .find_map(|entry| match entry.unwrap() {
+                (1, Value::Bytes(bytes)) => Some(bytes),
+                _ => None,
+            })
+            .unwrap();
+        let decoded = from_file_descriptor(file).unwrap();
+
+        let Some(nodes) = decoded.elems.iter().find_map(|elem| match elem {
+            Elem::Service { nodes, .. } => Some(nodes),
+            _ => None,
+        }) else {
+            panic!("expected service, got {:?}", decoded.elems);
+        };
+        let ServiceNode::Rpc(rpc) = &nodes[0] else {
+            panic!("expected rpc, got {:?}", nodes[0]);
+        };
+        let names = rpc
+            .options
+            .iter()
+            .map(|opt| opt.name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["idempotency_level", "(google.api.http)"]);
+        let http = &rpc.options[1].value;
+        assert!(
+            matches!(http.get("get"), Some(OptionValue::Str { value }) if value == "/v1/users/{id}")
+        );
+        let binding = http.get("additional_bindings").unwrap();
+        assert!(matches!(binding.get("body"), Some(OptionValue::Str { value }) if value == "*"));
+        assert_eq!(to_descriptor_set(&decoded, &[]).unwrap(), set);
+    }
+
+    #[test]
+    fn descriptor_roundtrip_nested_extend() {
+        let opts = Opts::default();
+        let input = r#"syntax = "proto2";
+package foo.v1;
+message Base {
+  extensions 100 to 199;
+}
+message User {
+  extend Base {
+    optional User user = 100;
+    repeated string tags = 101;
+  }
+  optional string name = 1;
+}"#;
+        let proto = crate::parser::parse(&opts, "foo/v1/user.proto", input).unwrap();
+        let set = to_descriptor_set(&proto, &[]).unwrap();
+
+        let file = Decoder::new(&set)
+            .find_map(|entry| match entry.unwrap() {
+                (1, Value::Bytes(bytes)) => Some(bytes),
+                _ => None,
+            })
+            .unwrap();
+        let decoded = from_file_descriptor(file).unwrap();
+
+        let Some(Elem::Message(msg)) = decoded.elems.get(2) else {
+            panic!("expected message, got {:?}", decoded.elems);
+        };
+        assert!(matches!(&msg.fields[1],
+            Field::Extend { name, fields, .. } if name == "foo.v1.Base" && fields.len() == 2));
+        assert_eq!(to_descriptor_set(&decoded, &[]).unwrap(), set);
+    }
+
+    #[test]
+    fn descriptor_roundtrip_editions() {
+        let opts = Opts::default();
+        let input = r#"edition = "2023";
+package foo.v1;
+option features.field_presence = IMPLICIT;
+message User {
+  string name = 1;
+  string nickname = 2 [features.field_presence = EXPLICIT];
+  int32 id = 3 [features.field_presence = LEGACY_REQUIRED];
+  User parent = 4;
+}"#;
+        let proto = crate::parser::parse(&opts, "foo/v1/user.proto", input).unwrap();
+        let set = to_descriptor_set(&proto, &[]).unwrap();
+
+        let file = Decoder::new(&set)
+            .find_map(|entry| match entry.unwrap() {
+                (1, Value::Bytes(bytes)) => Some(bytes),
+                _ => None,
+            })
+            .unwrap();
+        let decoded = from_file_descriptor(file).unwrap();
+
+        assert_eq!(decoded.edition.as_deref(), Some("2023"));
+        let Some(Elem::Message(msg)) = decoded.elems.iter().find(|e| matches!(e, Elem::Message(_)))
+        else {
+            panic!("expected message, got {:?}", decoded.elems);
+        };
+        let flags = msg
+            .fields
+            .iter()
+            .filter_map(|field| match field {
+                Field::Single { flag, .. } => Some(format!("{:?}", flag)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(flags, ["None", "Optional", "Required", "None"]);
+        assert_eq!(to_descriptor_set(&decoded, &[]).unwrap(), set);
+    }
+}
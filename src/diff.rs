@@ -0,0 +1,188 @@
+//! Line based unified diff of generated files, used to preview the changes of
+//! a regeneration before the output file is overwritten.
+
+/// Number of unchanged lines shown around every change.
+const CONTEXT: usize = 3;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Unified diff of `old` and `new` content of the file `path`, `None` if
+/// both are the same. The file is treated as new if there is no old content.
+pub fn unified(path: &str, old: Option<&str>, new: &str, color: bool) -> Option<String> {
+    if old == Some(new) {
+        return None;
+    }
+
+    let old_lines = old
+        .map(|old| old.lines().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let new_lines = new.lines().collect::<Vec<_>>();
+    let ops = diff(&old_lines, &new_lines);
+
+    let paint = |code: &str, line: String| {
+        if color {
+            format!("{}{}{}\n", code, line, RESET)
+        } else {
+            format!("{}\n", line)
+        }
+    };
+
+    let mut str = String::new();
+    let old_name = if old.is_some() {
+        format!("a/{}", path)
+    } else {
+        "/dev/null".to_string()
+    };
+    str.push_str(&paint(BOLD, format!("--- {}", old_name)));
+    str.push_str(&paint(BOLD, format!("+++ b/{}", path)));
+
+    for hunk in hunks(&ops) {
+        let ops = &ops[hunk.0..hunk.1];
+        let (old_start, new_start) = start(&ops[0]);
+        let old_len = ops.iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+        let new_len = ops.iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+
+        str.push_str(&paint(
+            CYAN,
+            format!(
+                "@@ -{} +{} @@",
+                range(old_start, old_len),
+                range(new_start, new_len)
+            ),
+        ));
+        for op in ops {
+            match *op {
+                Op::Equal(idx, _) => str.push_str(&format!(" {}\n", old_lines[idx])),
+                Op::Delete(idx) => str.push_str(&paint(RED, format!("-{}", old_lines[idx]))),
+                Op::Insert(idx) => str.push_str(&paint(GREEN, format!("+{}", new_lines[idx]))),
+            }
+        }
+    }
+
+    Some(str)
+}
+
+/// Line numbers (0-based) of the old and new file the operation starts at.
+fn start(op: &Op) -> (usize, usize) {
+    match *op {
+        Op::Equal(old, new) => (old, new),
+        Op::Delete(old) => (old, 0),
+        Op::Insert(new) => (0, new),
+    }
+}
+
+fn range(start: usize, len: usize) -> String {
+    match len {
+        // empty ranges refer to the line before
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Ranges of operations that are reported together, i.e. changes with their
+/// surrounding context lines.
+fn hunks(ops: &[Op]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+
+    for (idx, op) in ops.iter().enumerate() {
+        if matches!(op, Op::Equal(..)) {
+            continue;
+        }
+        let from = idx.saturating_sub(CONTEXT);
+        let to = (idx + CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if last.1 >= from => last.1 = to,
+            _ => hunks.push((from, to)),
+        }
+    }
+
+    hunks
+}
+
+/// Edit script of two sequences of lines based on their longest common
+/// subsequence. Common leading and trailing lines are skipped beforehand, so
+/// the quadratic part is limited to the actually changed region.
+fn diff(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j]: length of the longest common subsequence of old_mid[i..]
+    // and new_mid[j..]
+    let width = new_mid.len() + 1;
+    let mut lcs = vec![0u32; (old_mid.len() + 1) * width];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = (0..prefix)
+        .map(|idx| Op::Equal(idx, idx))
+        .collect::<Vec<_>>();
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            ops.push(Op::Equal(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < old_mid.len()
+            && (j == new_mid.len() || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            ops.push(Op::Delete(prefix + i));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(prefix + j));
+            j += 1;
+        }
+    }
+    ops.extend(
+        (0..suffix).map(|idx| Op::Equal(old.len() - suffix + idx, new.len() - suffix + idx)),
+    );
+
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unified;
+
+    #[test]
+    fn unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+
+        assert_eq!(unified("out.ts", Some(old), old, false), None);
+        assert_eq!(
+            unified("out.ts", Some(old), new, false).unwrap(),
+            "--- a/out.ts\n+++ b/out.ts\n@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n@@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+        assert_eq!(
+            unified("out.ts", None, "a\n", true).unwrap(),
+            "\x1b[1m--- /dev/null\x1b[0m\n\x1b[1m+++ b/out.ts\x1b[0m\n\x1b[36m@@ -0,0 +1 @@\x1b[0m\n\x1b[32m+a\x1b[0m\n"
+        );
+    }
+}
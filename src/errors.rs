@@ -1,3 +1,6 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 #[derive(thiserror::Error, Debug)]
 pub enum PtError {
     #[error("input file does not exist: {0}")]
@@ -8,6 +11,164 @@ pub enum PtError {
     ParsingError(String),
     #[error("proto parsing was incomplete")]
     IncompleteParsing,
+    #[error("{}", syntax_errors(.file, .diagnostics))]
+    Syntax {
+        file: String,
+        diagnostics: Vec<Diagnostic>,
+    },
     #[error("could not find type named: {0}")]
     ProtobufTypeNotFound(String),
+    #[error("could not find type named: {name}, did you mean `{suggestion}`?")]
+    UnknownType { name: String, suggestion: String },
+    #[error("invalid descriptor: {0}")]
+    InvalidDescriptor(String),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("circular import detected: {0}")]
+    CircularImport(String),
+    #[error("failed to fetch remote source: {0}")]
+    RemoteFetch(String),
+    #[error("invalid text format message: {0}")]
+    InvalidTextproto(String),
+    #[error("invalid JSON payload: {0}")]
+    InvalidJson(String),
+    #[error("invalid archive: {0}")]
+    InvalidArchive(String),
+    #[error("generation targets disagree in {0} place(s)")]
+    TargetMismatch(usize),
+    #[error("protoc failed: {0}")]
+    Protoc(String),
+    #[error("protots and protoc disagree in {0} place(s)")]
+    ConformanceMismatch(usize),
+    #[error("lint found {0} problem(s)")]
+    LintFindings(usize),
+    #[error("{error} {location}")]
+    Located {
+        location: Location,
+        error: Box<PtError>,
+    },
+    #[error("{}", generation_errors(.0))]
+    Generation(Vec<PtError>),
+}
+
+/// Breadcrumb of the definition an error occurred in, e.g. `in message
+/// foo.v1.User, field address (line 42)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// kind of the enclosing definition, e.g. `message` or `service`
+    pub kind: &'static str,
+    /// fully-qualified name of the enclosing definition
+    pub name: String,
+    /// kind and name of the member, e.g. `field` and `contact.email`
+    pub member: Option<(&'static str, String)>,
+    /// line of the definition, 0 if unknown
+    pub line: usize,
+}
+
+/// Syntax error found while parsing a proto file, the parser continues with
+/// the next statement after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// 1-based line the error occurred at
+    pub line: usize,
+    /// 1-based (byte) column the error occurred at
+    pub column: usize,
+    pub message: String,
+}
+
+impl PtError {
+    /// The error annotated with the location it occurred at, unless it
+    /// carries a more specific one already.
+    pub fn at(self, location: Location) -> PtError {
+        match self {
+            PtError::Located { .. } => self,
+            error => PtError::Located {
+                location,
+                error: Box::new(error),
+            },
+        }
+    }
+}
+
+impl Location {
+    /// Location of the definition of the given kind and fully-qualified name.
+    pub fn new(kind: &'static str, name: impl Into<String>) -> Location {
+        Location {
+            kind,
+            name: name.into(),
+            member: None,
+            line: 0,
+        }
+    }
+
+    /// Location of a member of the definition, e.g. a field of a message.
+    pub fn member(self, kind: &'static str, name: impl Into<String>, line: usize) -> Location {
+        Location {
+            member: Some((kind, name.into())),
+            line,
+            ..self
+        }
+    }
+
+    /// Dotted path of the definition, e.g. `foo.v1.User.address`.
+    pub fn path(&self) -> String {
+        match &self.member {
+            Some((_, member)) => format!("{}.{}", self.name, member),
+            None => self.name.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "in {} {}", self.kind, self.name)?;
+        if let Some((kind, member)) = &self.member {
+            write!(f, ", {} {}", kind, member)?;
+        }
+        if self.line > 0 {
+            write!(f, " (line {})", self.line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lines for the user besides the output, e.g. warnings and the progress
+/// reported with `-v`, collected while running a command so the caller writes
+/// them to stderr (see `Io`). Clones share the same lines.
+#[derive(Clone, Debug, Default)]
+pub struct Messages(Rc<RefCell<Vec<String>>>);
+
+impl Messages {
+    pub fn push(&self, message: impl Into<String>) {
+        self.0.borrow_mut().push(message.into());
+    }
+
+    /// The lines collected so far, leaving none behind.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.borrow_mut())
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Listing of the syntax errors of a proto file.
+fn syntax_errors(file: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut str = format!("proto parsing failed with {} error(s):", diagnostics.len());
+    for diagnostic in diagnostics {
+        str.push_str(&format!("\n  {}:{}", file, diagnostic));
+    }
+    str
+}
+
+/// Listing of the errors of the generation phase.
+fn generation_errors(errors: &[PtError]) -> String {
+    let mut str = format!("code generation failed with {} error(s):", errors.len());
+    for error in errors {
+        str.push_str(&format!("\n  {}", error));
+    }
+    str
 }
@@ -0,0 +1,157 @@
+//! C ABI for embedding protots in non-Rust tooling, e.g. native addons of
+//! Node, build hooks of Python or editor plugins linking the `cdylib` of the
+//! crate. The declarations are found in `include/protots.h` (see `HEADER`).
+//!
+//! Options are passed as JSON object like to the functions of `api`.
+
+use std::ffi::{c_char, c_int, CStr};
+use std::panic;
+
+use crate::api;
+
+/// C header declaring the functions of this module.
+pub const HEADER: &str = include_str!("../include/protots.h");
+
+pub const PROTOTS_OK: c_int = 0;
+pub const PROTOTS_ERROR: c_int = 1;
+pub const PROTOTS_INVALID_ARGUMENT: c_int = 2;
+
+/// Buffer owned by protots, to be released via `protots_buf_free`.
+#[repr(C)]
+pub struct PrototsBuf {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl PrototsBuf {
+    fn new(bytes: Vec<u8>) -> PrototsBuf {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        PrototsBuf { data, len }
+    }
+}
+
+/// Generate the output of the given proto source, typescript unless another
+/// output is requested via the `emit` option. On success `PROTOTS_OK` is
+/// returned and `out` holds the output, otherwise `out` holds the error
+/// message. Panics do not unwind into the caller but are reported as errors.
+///
+/// # Safety
+///
+/// `input` and `options_json` (which may be null) have to be valid
+/// nul-terminated strings and `out` a valid pointer to write the buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn protots_generate(
+    input: *const c_char,
+    options_json: *const c_char,
+    out: *mut PrototsBuf,
+) -> c_int {
+    if out.is_null() {
+        return PROTOTS_INVALID_ARGUMENT;
+    }
+    let fail = |status, message: &str| {
+        *out = PrototsBuf::new(message.as_bytes().to_vec());
+        status
+    };
+
+    let input = match (!input.is_null()).then(|| CStr::from_ptr(input).to_str()) {
+        Some(Ok(input)) => input,
+        _ => return fail(PROTOTS_INVALID_ARGUMENT, "input is no valid utf-8 string"),
+    };
+    let options = match options_json.is_null() {
+        true => None,
+        false => match CStr::from_ptr(options_json).to_str() {
+            Ok(options) => Some(options),
+            Err(_) => {
+                return fail(
+                    PROTOTS_INVALID_ARGUMENT,
+                    "options are no valid utf-8 string",
+                )
+            }
+        },
+    };
+
+    match panic::catch_unwind(|| api::generate(input, options)) {
+        Ok(Ok(output)) => {
+            *out = PrototsBuf::new(output);
+            PROTOTS_OK
+        }
+        Ok(Err(err)) => fail(PROTOTS_ERROR, &err.to_string()),
+        Err(_) => fail(PROTOTS_ERROR, "internal error: generation panicked"),
+    }
+}
+
+/// Release a buffer returned by `protots_generate`.
+///
+/// # Safety
+///
+/// `buf` has to be null or point to a buffer returned by `protots_generate`
+/// that was not released before.
+#[no_mangle]
+pub unsafe extern "C" fn protots_buf_free(buf: *mut PrototsBuf) {
+    if buf.is_null() || (*buf).data.is_null() {
+        return;
+    }
+    let slice = std::ptr::slice_from_raw_parts_mut((*buf).data, (*buf).len);
+    drop(Box::from_raw(slice));
+    (*buf).data = std::ptr::null_mut();
+    (*buf).len = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_char, CString};
+
+    use super::{
+        protots_buf_free, protots_generate, PrototsBuf, HEADER, PROTOTS_ERROR, PROTOTS_OK,
+    };
+
+    fn generate(input: &str, options: Option<&str>) -> (i32, String) {
+        let input = CString::new(input).unwrap();
+        let options = options.map(|options| CString::new(options).unwrap());
+        let mut out = PrototsBuf {
+            data: std::ptr::null_mut(),
+            len: 0,
+        };
+
+        unsafe {
+            let status = protots_generate(
+                input.as_ptr(),
+                options.as_ref().map_or(std::ptr::null(), |options| {
+                    options.as_ptr() as *const c_char
+                }),
+                &mut out,
+            );
+            let output = std::slice::from_raw_parts(out.data, out.len);
+            let output = String::from_utf8(output.to_vec()).unwrap();
+            protots_buf_free(&mut out);
+            assert!(out.data.is_null());
+            (status, output)
+        }
+    }
+
+    #[test]
+    fn ffi_generate() {
+        let input = "syntax = \"proto3\";\nmessage User { string name = 1; }";
+        let (status, output) = generate(input, Some(r#"{"file": "user.proto", "lenient": true}"#));
+        assert_eq!(status, PROTOTS_OK);
+        assert!(output.contains("// Source: user.proto"));
+        assert!(output.contains("export const UserSchema = z.object({"));
+
+        let (status, output) = generate(input, Some(r#"{"no-such-option": true}"#));
+        assert_eq!(status, PROTOTS_ERROR);
+        assert_eq!(output, "invalid argument: unknown option: --no-such-option");
+
+        let (status, output) = generate("message {", None);
+        assert_eq!(status, PROTOTS_ERROR);
+        assert!(output.starts_with("proto parsing failed with"));
+    }
+
+    #[test]
+    fn ffi_header_declares_functions() {
+        assert!(HEADER.contains(
+            "int protots_generate(const char *input, const char *options_json, protots_buf *out);"
+        ));
+        assert!(HEADER.contains("void protots_buf_free(protots_buf *buf);"));
+    }
+}
@@ -0,0 +1,196 @@
+//! Structural fingerprints of message and enum types, e.g. to detect whether
+//! the shape of a type changed between versions of a schema registry without
+//! diffing the definitions.
+//!
+//! Only what makes up the shape of a type is taken into account: names,
+//! numbers, labels and types of fields, oneofs and nested types. Comments,
+//! options, reserved statements, nested `extend` blocks and the order of
+//! declarations are not. Field types are taken as written, so `User` and
+//! `foo.v1.User` differ.
+//!
+//! The fingerprints are stable across protots versions and platforms.
+
+use crate::parser::{Elem, Enum, EnumValue, Field, Flag, Msg, Proto};
+use crate::registry;
+
+/// Fingerprint of the message including its nested types, independent of
+/// the message's own name.
+pub fn fingerprint(msg: &Msg) -> u64 {
+    hash(&message_parts(msg))
+}
+
+/// Fingerprint of the enum values, independent of the enum's own name.
+pub fn enum_fingerprint(e: &Enum) -> u64 {
+    let parts = e
+        .values
+        .iter()
+        .filter_map(|value| match value {
+            EnumValue::Single { name, idx, .. } => Some(format!("value {} {}", idx, name)),
+            EnumValue::Reserved { .. } => None,
+        })
+        .collect::<Vec<_>>();
+    hash(&parts)
+}
+
+/// Fingerprints of all (nested) messages and enums of the proto file along
+/// with their fully-qualified names, in order of the type definitions.
+pub fn fingerprints(proto: &Proto) -> Vec<(String, u64)> {
+    let scope = proto.package().unwrap_or("");
+    let mut into = Vec::new();
+    for elem in &proto.elems {
+        match elem {
+            Elem::Message(msg) => message_fingerprints(msg, scope, &mut into),
+            Elem::Enum(e) => into.push((registry::qualify(scope, &e.name), enum_fingerprint(e))),
+            _ => (),
+        }
+    }
+    into
+}
+
+fn message_fingerprints(msg: &Msg, scope: &str, into: &mut Vec<(String, u64)>) {
+    let full_name = registry::qualify(scope, &msg.name);
+    into.push((full_name.clone(), fingerprint(msg)));
+    for field in &msg.fields {
+        match field {
+            Field::SubMessage(sub_msg) => message_fingerprints(sub_msg, &full_name, into),
+            Field::SubEnum(e) => {
+                into.push((registry::qualify(&full_name, &e.name), enum_fingerprint(e)))
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Canonical description of the message, one part per field or nested type.
+fn message_parts(msg: &Msg) -> Vec<String> {
+    let mut parts = Vec::new();
+    field_parts(&msg.fields, "", &mut parts);
+    parts
+}
+
+fn field_parts(fields: &[Field], prefix: &str, into: &mut Vec<String>) {
+    for field in fields {
+        match field {
+            Field::Single {
+                name,
+                field_type,
+                idx,
+                flag,
+                ..
+            } => into.push(format!(
+                "{}field {} {} {} {}",
+                prefix,
+                idx,
+                name,
+                label(flag),
+                field_type
+            )),
+            Field::Map {
+                name,
+                key_type,
+                value_type,
+                idx,
+                ..
+            } => into.push(format!(
+                "{}map {} {} {} {}",
+                prefix, idx, name, key_type, value_type
+            )),
+            Field::OneOf { name, fields, .. } => {
+                field_parts(fields, &format!("{}oneof {} ", prefix, name), into)
+            }
+            Field::SubMessage(msg) => {
+                into.push(format!("message {} {:016x}", msg.name, fingerprint(msg)))
+            }
+            Field::SubEnum(e) => into.push(format!("enum {} {:016x}", e.name, enum_fingerprint(e))),
+            Field::Reserved(_)
+            | Field::Extensions { .. }
+            | Field::Extend { .. }
+            | Field::Option(_) => (),
+        }
+    }
+}
+
+fn label(flag: &Flag) -> &'static str {
+    match flag {
+        Flag::None => "none",
+        Flag::Optional => "optional",
+        Flag::Repeated => "repeated",
+        Flag::Required => "required",
+    }
+}
+
+/// FNV-1a of the sorted parts, so the order of declarations does not matter.
+fn hash(parts: &[String]) -> u64 {
+    let mut parts = parts.iter().collect::<Vec<_>>();
+    parts.sort();
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        for byte in part.bytes().chain([b'\n']) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fingerprints;
+    use crate::parser::parse;
+    use crate::Opts;
+
+    fn parse_fingerprints(input: &str) -> Vec<(String, u64)> {
+        let input = format!("syntax = \"proto3\";\npackage foo.v1;\n{}", input);
+        fingerprints(&parse(&Opts::default(), "user.proto", &input).unwrap())
+    }
+
+    #[test]
+    fn structural_fingerprints() {
+        let original = parse_fingerprints(
+            r#"message User {
+  string name = 1;
+  oneof contact { string email = 2; string phone = 3; }
+  map<string, Role> roles = 4;
+  enum Role { ROLE_UNSPECIFIED = 0; ROLE_ADMIN = 1; }
+}"#,
+        );
+        let names = original
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["foo.v1.User", "foo.v1.User.Role"]);
+        // guard against accidental changes of the fingerprints
+        assert_eq!(format!("{:016x}", original[1].1), "c4c2811cd219b930");
+
+        // comments, options, reserved statements and order do not matter
+        let reordered = parse_fingerprints(
+            r#"// users of the system
+message User {
+  reserved 10;
+  enum Role { ROLE_ADMIN = 1; ROLE_UNSPECIFIED = 0; }
+  map<string, Role> roles = 4 [deprecated = true];
+  oneof contact { string phone = 3; string email = 2; }
+  string name = 1;
+}"#,
+        );
+        assert_eq!(original, reordered);
+
+        for changed in [
+            "optional string name = 1;",
+            "string full_name = 1;",
+            "bytes name = 1;",
+            "string name = 5;",
+        ] {
+            let input = format!(
+                "message User {{ {} oneof contact {{ string email = 2; string phone = 3; }} \
+                 map<string, Role> roles = 4; \
+                 enum Role {{ ROLE_UNSPECIFIED = 0; ROLE_ADMIN = 1; }} }}",
+                changed
+            );
+            let fingerprints = parse_fingerprints(&input);
+            assert_ne!(original[0], fingerprints[0], "{}", changed);
+            assert_eq!(original[1], fingerprints[1]);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Dependency graph of proto files (imports) and types (field and rpc type
+//! references), rendered as graphviz DOT or JSON.
+
+use crate::json::Json;
+use crate::parser::{Elem, Field, Msg, Proto, ServiceNode};
+use crate::registry::{self, Registry};
+
+pub struct FileNode {
+    pub name: String,
+    pub imports: Vec<String>,
+}
+
+pub struct TypeNode {
+    pub name: String,
+    pub kind: &'static str,
+    pub file: String,
+    /// fully-qualified names of all referenced types (in order, without
+    /// duplicates); references that cannot be resolved are kept as written
+    pub references: Vec<String>,
+}
+
+pub struct Graph {
+    pub files: Vec<FileNode>,
+    pub types: Vec<TypeNode>,
+}
+
+impl Graph {
+    /// Build the graph of the given proto files, usually an input file and all
+    /// of its (transitive) imports.
+    pub fn new<'a, I>(protos: I) -> Graph
+    where
+        I: IntoIterator<Item = &'a Proto> + Clone,
+    {
+        let registry = Registry::new(protos.clone());
+        let mut files = Vec::new();
+        let mut types = Vec::new();
+
+        for proto in protos {
+            let package = proto.package().unwrap_or("");
+            let mut imports = Vec::new();
+
+            for elem in &proto.elems {
+                match elem {
+                    Elem::Import { name, .. } => imports.push(name.clone()),
+                    Elem::Message(msg) => message_nodes(&registry, proto, msg, package, &mut types),
+                    Elem::Enum(e) => types.push(TypeNode {
+                        name: registry::qualify(package, &e.name),
+                        kind: "enum",
+                        file: proto.file.clone(),
+                        references: Vec::new(),
+                    }),
+                    Elem::Service { name, nodes, .. } => {
+                        let mut node = TypeNode {
+                            name: registry::qualify(package, name),
+                            kind: "service",
+                            file: proto.file.clone(),
+                            references: Vec::new(),
+                        };
+                        for rpc in nodes.iter().filter_map(|node| match node {
+                            ServiceNode::Rpc(rpc) => Some(rpc),
+                            _ => None,
+                        }) {
+                            for name in [&rpc.request, &rpc.response] {
+                                add_reference(&registry, &mut node, name, package);
+                            }
+                        }
+                        types.push(node);
+                    }
+                    _ => (),
+                }
+            }
+
+            files.push(FileNode {
+                name: proto.file.clone(),
+                imports,
+            });
+        }
+
+        Graph { files, types }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph protots {\n  node [shape=box];\n");
+
+        for file in &self.files {
+            dot.push_str(&format!("  {:?} [shape=note];\n", file.name));
+            for import in &file.imports {
+                dot.push_str(&format!(
+                    "  {:?} -> {:?} [style=dashed];\n",
+                    file.name, import
+                ));
+            }
+        }
+
+        for node in &self.types {
+            dot.push_str(&format!("  {:?};\n", node.name));
+            for reference in &node.references {
+                dot.push_str(&format!("  {:?} -> {:?};\n", node.name, reference));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> Json {
+        let strings =
+            |values: &[String]| Json::Array(values.iter().map(|v| Json::str(v)).collect());
+
+        Json::object([
+            (
+                "files",
+                Json::Array(
+                    self.files
+                        .iter()
+                        .map(|file| {
+                            Json::object([
+                                ("name", Json::str(&file.name)),
+                                ("imports", strings(&file.imports)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "types",
+                Json::Array(
+                    self.types
+                        .iter()
+                        .map(|node| {
+                            Json::object([
+                                ("name", Json::str(&node.name)),
+                                ("kind", Json::str(node.kind)),
+                                ("file", Json::str(&node.file)),
+                                ("references", strings(&node.references)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+}
+
+fn message_nodes(
+    registry: &Registry,
+    proto: &Proto,
+    msg: &Msg,
+    scope: &str,
+    types: &mut Vec<TypeNode>,
+) {
+    let full_name = registry::qualify(scope, &msg.name);
+    let mut node = TypeNode {
+        name: full_name.clone(),
+        kind: "message",
+        file: proto.file.clone(),
+        references: Vec::new(),
+    };
+    let mut nested = Vec::new();
+
+    field_references(registry, &mut node, &msg.fields, &full_name);
+    types.push(node);
+
+    for field in &msg.fields {
+        match field {
+            Field::SubMessage(sub) => nested.push(sub),
+            Field::SubEnum(e) => types.push(TypeNode {
+                name: registry::qualify(&full_name, &e.name),
+                kind: "enum",
+                file: proto.file.clone(),
+                references: Vec::new(),
+            }),
+            _ => (),
+        }
+    }
+
+    for sub in nested {
+        message_nodes(registry, proto, sub, &full_name, types);
+    }
+}
+
+fn field_references(registry: &Registry, node: &mut TypeNode, fields: &[Field], scope: &str) {
+    for field in fields {
+        match field {
+            Field::Single { field_type, .. } => add_reference(registry, node, field_type, scope),
+            Field::Map {
+                key_type,
+                value_type,
+                ..
+            } => {
+                add_reference(registry, node, key_type, scope);
+                add_reference(registry, node, value_type, scope);
+            }
+            Field::OneOf { fields, .. } => field_references(registry, node, fields, scope),
+            _ => (),
+        }
+    }
+}
+
+fn add_reference(registry: &Registry, node: &mut TypeNode, name: &str, scope: &str) {
+    if registry::is_scalar(name) {
+        return;
+    }
+
+    let reference = registry
+        .resolve(name, scope)
+        .map(|ptype| ptype.full_name.clone())
+        .unwrap_or_else(|| name.to_string());
+
+    if !node.references.contains(&reference) {
+        node.references.push(reference);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Graph;
+    use crate::parser;
+    use crate::Opts;
+
+    fn parse(file: &str, input: &str) -> parser::Proto {
+        parser::parse(&Opts::default(), file, input).unwrap()
+    }
+
+    #[test]
+    fn graph_of_imports_and_references() {
+        let common = parse(
+            "common.proto",
+            "syntax = \"proto3\";\npackage common;\nmessage Address { string city = 1; }",
+        );
+        let user = parse(
+            "user.proto",
+            r#"syntax = "proto3";
+package foo;
+import "common.proto";
+message User {
+  common.Address address = 1;
+  map<string, Role> roles = 2;
+  enum Role {
+    NONE = 0;
+  }
+}
+service Users {
+  rpc Get(User) returns (User);
+}"#,
+        );
+
+        let graph = Graph::new([&user, &common]);
+
+        assert_eq!(
+            graph.to_dot(),
+            r#"digraph protots {
+  node [shape=box];
+  "user.proto" [shape=note];
+  "user.proto" -> "common.proto" [style=dashed];
+  "common.proto" [shape=note];
+  "foo.User";
+  "foo.User" -> "common.Address";
+  "foo.User" -> "foo.User.Role";
+  "foo.User.Role";
+  "foo.Users";
+  "foo.Users" -> "foo.User";
+  "common.Address";
+}
+"#
+        );
+        assert_eq!(
+            graph.to_json().to_string(),
+            concat!(
+                r#"{"files":[{"name":"user.proto","imports":["common.proto"]},"#,
+                r#"{"name":"common.proto","imports":[]}],"types":["#,
+                r#"{"name":"foo.User","kind":"message","file":"user.proto","references":["common.Address","foo.User.Role"]},"#,
+                r#"{"name":"foo.User.Role","kind":"enum","file":"user.proto","references":[]},"#,
+                r#"{"name":"foo.Users","kind":"service","file":"user.proto","references":["foo.User"]},"#,
+                r#"{"name":"common.Address","kind":"message","file":"common.proto","references":[]}]}"#
+            )
+        );
+    }
+}
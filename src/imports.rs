@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::bundle;
+use crate::errors::PtError;
+use crate::parser::{self, Elem, ImportModifier, Proto};
+#[cfg(feature = "remote")]
+use crate::remote::Remote;
+use crate::Opts;
+
+/// Name of the input file as it would be referenced by an `import` statement,
+/// i.e. relative to the include path it is located in.
+pub fn import_name(opts: &Opts) -> String {
+    let path = Path::new(&opts.file);
+
+    opts.include_paths
+        .iter()
+        .find_map(|include| path.strip_prefix(include).ok())
+        .or_else(|| path.file_name().map(Path::new))
+        .map(|name| name.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_else(|| opts.file.clone())
+}
+
+/// Collect all proto files below the given directory, sorted by path.
+pub fn proto_files(opts: &Opts, dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), PtError> {
+    files(opts, dir, "proto", paths)
+}
+
+/// Collect all files with the given extension below the given directory,
+/// sorted by path.
+pub fn files(
+    opts: &Opts,
+    dir: &Path,
+    extension: &str,
+    paths: &mut Vec<PathBuf>,
+) -> Result<(), PtError> {
+    let files = opts.files.list(dir)?;
+    paths.extend(
+        files
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == extension)),
+    );
+    Ok(())
+}
+
+/// Load all (transitive) imports of the given proto file.
+///
+/// Imports that cannot be found on any include path are skipped, as long as
+/// none of their types is referenced this is perfectly fine. Missing `weak`
+/// imports are expected and not even reported.
+pub fn resolve(opts: &Opts, proto: &Proto) -> Result<Vec<Proto>, PtError> {
+    let mut loaded = HashSet::new();
+    let mut stack = vec![proto.file.clone()];
+    let mut deps = Vec::new();
+
+    load_imports(opts, proto, &mut stack, &mut loaded, &mut deps)?;
+
+    Ok(deps)
+}
+
+fn load_imports(
+    opts: &Opts,
+    proto: &Proto,
+    stack: &mut Vec<String>,
+    loaded: &mut HashSet<String>,
+    deps: &mut Vec<Proto>,
+) -> Result<(), PtError> {
+    for (import, modifier) in imports(proto) {
+        // files that are currently being loaded further up the import chain
+        // cannot be loaded again without running in circles
+        if let Some(idx) = stack.iter().position(|file| file == import) {
+            let mut cycle = stack[idx..].to_vec();
+            cycle.push(import.to_string());
+            return Err(PtError::CircularImport(cycle.join(" -> ")));
+        }
+
+        if !loaded.insert(import.to_string()) {
+            continue;
+        }
+
+        let content = match find(opts, import)? {
+            Some(content) => content,
+            None => match bundle::source(import) {
+                Some(content) => content.to_string(),
+                None => {
+                    if opts.verbose > 0 && modifier != ImportModifier::Weak {
+                        opts.messages
+                            .push(format!("could not find import: {}", import));
+                    }
+                    continue;
+                }
+            },
+        };
+        let dep = parser::parse(opts, import, &content)?;
+
+        stack.push(import.to_string());
+        load_imports(opts, &dep, stack, loaded, deps)?;
+        stack.pop();
+
+        deps.push(dep);
+    }
+
+    Ok(())
+}
+
+fn imports(proto: &Proto) -> impl Iterator<Item = (&str, ImportModifier)> {
+    proto.elems.iter().filter_map(|elem| match elem {
+        Elem::Import { name, modifier, .. } => Some((name.as_str(), *modifier)),
+        _ => None,
+    })
+}
+
+/// Content of the given import found on the include paths or next to the
+/// input file.
+fn find(opts: &Opts, import: &str) -> Result<Option<String>, PtError> {
+    for include in &opts.include_paths {
+        #[cfg(feature = "remote")]
+        if let Some(remote) = Remote::parse(opts, include)? {
+            // remote sources are always cached on the local file system
+            if let Some(path) = remote.find(opts, import)? {
+                return Ok(Some(opts.files.read_to_string(&path)?));
+            }
+            continue;
+        }
+
+        let path = Path::new(include).join(import);
+        if opts.files.exists(&path) {
+            return Ok(Some(opts.files.read_to_string(&path)?));
+        }
+    }
+
+    let path = Path::new(&opts.file)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(import);
+    match opts.files.exists(&path) {
+        true => Ok(Some(opts.files.read_to_string(&path)?)),
+        false => Ok(None),
+    }
+}
+
+/// Relative typescript module path to reference the output of proto file `to`
+/// from the output of proto file `from`.
+pub fn module_path(from: &str, to: &str) -> String {
+    let from_dir = from.split('/').collect::<Vec<_>>();
+    let from_dir = &from_dir[..from_dir.len() - 1];
+    let to_parts = to.trim_end_matches(".proto").split('/').collect::<Vec<_>>();
+
+    let to_dir = &to_parts[..to_parts.len() - 1];
+
+    let common = from_dir
+        .iter()
+        .zip(to_dir.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut parts = vec![".."; from_dir.len() - common];
+    if parts.is_empty() {
+        parts.push(".");
+    }
+    parts.extend(&to_parts[common..]);
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    use super::{module_path, resolve};
+    use crate::errors::PtError;
+    use crate::parser;
+    use crate::provider::{Files, Memory};
+    use crate::Opts;
+
+    fn write_protos(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("protots-{}-{}", name, std::process::id()));
+        for (file, content) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn resolve_file(dir: &Path, file: &str) -> Result<Vec<parser::Proto>, PtError> {
+        let opts = Opts {
+            file: dir.join(file).to_string_lossy().to_string(),
+            include_paths: vec![dir.to_string_lossy().to_string()],
+            ..Opts::default()
+        };
+        let content = std::fs::read_to_string(&opts.file).unwrap();
+        let proto = parser::parse(&opts, file, &content)?;
+        resolve(&opts, &proto)
+    }
+
+    #[test]
+    fn resolve_transitive_imports() {
+        let dir = write_protos(
+            "transitive",
+            &[
+                (
+                    "a.proto",
+                    "syntax = \"proto3\";\nimport \"b.proto\";\nimport \"c.proto\";",
+                ),
+                ("b.proto", "syntax = \"proto3\";\nimport \"c.proto\";"),
+                ("c.proto", "syntax = \"proto3\";"),
+            ],
+        );
+
+        let deps = resolve_file(&dir, "a.proto").unwrap();
+        let files = deps.iter().map(|dep| dep.file.as_str()).collect::<Vec<_>>();
+        assert_eq!(files, vec!["c.proto", "b.proto"]);
+    }
+
+    #[test]
+    fn resolve_circular_imports() {
+        let dir = write_protos(
+            "circular",
+            &[
+                ("a.proto", "syntax = \"proto3\";\nimport \"b.proto\";"),
+                ("b.proto", "syntax = \"proto3\";\nimport \"c.proto\";"),
+                ("c.proto", "syntax = \"proto3\";\nimport \"a.proto\";"),
+            ],
+        );
+
+        match resolve_file(&dir, "a.proto") {
+            Err(PtError::CircularImport(cycle)) => {
+                assert_eq!(cycle, "a.proto -> b.proto -> c.proto -> a.proto")
+            }
+            other => panic!("expected circular import error, got {:?}", other),
+        }
+
+        // cycles further down the import chain and files importing themselves
+        let dir = write_protos(
+            "circular-nested",
+            &[
+                ("a.proto", "syntax = \"proto3\";\nimport \"b.proto\";"),
+                ("b.proto", "syntax = \"proto3\";\nimport \"c.proto\";"),
+                ("c.proto", "syntax = \"proto3\";\nimport \"b.proto\";"),
+                ("d.proto", "syntax = \"proto3\";\nimport \"d.proto\";"),
+            ],
+        );
+        assert!(matches!(resolve_file(&dir, "a.proto"),
+            Err(PtError::CircularImport(cycle)) if cycle == "b.proto -> c.proto -> b.proto"));
+        assert!(matches!(resolve_file(&dir, "d.proto"),
+            Err(PtError::CircularImport(cycle)) if cycle == "d.proto -> d.proto"));
+    }
+
+    #[test]
+    #[cfg(feature = "well-known-types")]
+    fn resolve_bundled_imports() {
+        let dir = write_protos(
+            "bundled",
+            &[
+                (
+                    "a.proto",
+                    "syntax = \"proto3\";\nimport \"google/protobuf/api.proto\";",
+                ),
+                (
+                    "google/protobuf/any.proto",
+                    "syntax = \"proto3\";\npackage local;",
+                ),
+            ],
+        );
+
+        let deps = resolve_file(&dir, "a.proto").unwrap();
+        let files = deps.iter().map(|dep| dep.file.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![
+                "google/protobuf/source_context.proto",
+                "google/protobuf/any.proto",
+                "google/protobuf/type.proto",
+                "google/protobuf/api.proto",
+            ]
+        );
+        // files on the include paths take precedence
+        assert_eq!(deps[1].package(), Some("local"));
+    }
+
+    #[test]
+    fn resolve_reports_missing_imports() {
+        let opts = Opts {
+            file: "a.proto".to_string(),
+            verbose: 1,
+            files: Files(Rc::new(Memory::new([(
+                "b.proto",
+                "syntax = \"proto3\";\nimport \"missing.proto\";\nimport weak \"optional.proto\";",
+            )]))),
+            ..Opts::default()
+        };
+        let input = "syntax = \"proto3\";\nimport \"b.proto\";";
+        let proto = parser::parse(&opts, "a.proto", input).unwrap();
+
+        let deps = resolve(&opts, &proto).unwrap();
+        assert_eq!(deps.len(), 1);
+        assert_eq!(
+            opts.messages.take(),
+            ["could not find import: missing.proto"]
+        );
+    }
+
+    #[test]
+    fn module_path_same_directory() {
+        assert_eq!(module_path("user.proto", "address.proto"), "./address");
+        assert_eq!(
+            module_path("foo/user.proto", "foo/address.proto"),
+            "./address"
+        );
+    }
+
+    #[test]
+    fn module_path_other_directory() {
+        assert_eq!(
+            module_path("user.proto", "google/type/date.proto"),
+            "./google/type/date"
+        );
+        assert_eq!(
+            module_path("foo/v1/user.proto", "google/type/date.proto"),
+            "../../google/type/date"
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Decompression of raw DEFLATE streams (RFC 1951) as found in zip archives
+//! and gzip files, along with the CRC-32 checksum both of them use.
+
+use crate::errors::PtError;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// order the code lengths of the code length alphabet are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompress the given raw DEFLATE stream.
+pub(crate) fn inflate(data: &[u8]) -> Result<Vec<u8>, PtError> {
+    let mut input = Bits {
+        data,
+        pos: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::with_capacity(data.len() * 4);
+
+    loop {
+        let last = input.take(1)? == 1;
+        match input.take(2)? {
+            0 => {
+                input.align();
+                let len = input.take(16)? as usize;
+                let nlen = input.take(16)? as usize;
+                if len != !nlen & 0xffff {
+                    return Err(invalid("stored block length mismatch"));
+                }
+                let end = input.pos + len;
+                let bytes = data
+                    .get(input.pos..end)
+                    .ok_or_else(|| invalid("unexpected end of data"))?;
+                out.extend_from_slice(bytes);
+                input.pos = end;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                block(&mut input, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut input)?;
+                block(&mut input, &mut out, &literals, &distances)?;
+            }
+            _ => return Err(invalid("invalid block type")),
+        }
+
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// CRC-32 (IEEE) checksum of the given data.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn invalid(message: &str) -> PtError {
+    PtError::InvalidArchive(format!("invalid deflate data: {}", message))
+}
+
+/// Reader of the bit stream, least significant bits first.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, count: u32) -> Result<u32, PtError> {
+        while self.count < count {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| invalid("unexpected end of data"))?;
+            self.buffer |= (byte as u32) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer = self.buffer.checked_shr(count).unwrap_or(0);
+        self.count -= count;
+        Ok(value)
+    }
+
+    /// Skip the remaining bits of the current byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// Canonical huffman code given by the number of codes of each length and
+/// the symbols ordered by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbols[offsets[*len as usize] as usize] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, input: &mut Bits) -> Result<u16, PtError> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for len in 1..16 {
+            code |= input.take(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid huffman code"))
+    }
+}
+
+fn dynamic_codes(input: &mut Bits) -> Result<(Huffman, Huffman), PtError> {
+    let literal_count = input.take(5)? as usize + 257;
+    let distance_count = input.take(5)? as usize + 1;
+    let code_length_count = input.take(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for idx in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[*idx] = input.take(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (len, repeat) = match code_lengths.decode(input)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| invalid("repeated length without previous one"))?;
+                (previous, 3 + input.take(2)?)
+            }
+            17 => (0, 3 + input.take(3)?),
+            _ => (0, 11 + input.take(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid("too many code lengths"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn block(
+    input: &mut Bits,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), PtError> {
+    loop {
+        let symbol = literals.decode(input)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let idx = symbol - 257;
+                if idx >= LENGTH_BASE.len() {
+                    return Err(invalid("invalid length symbol"));
+                }
+                let len =
+                    LENGTH_BASE[idx] as usize + input.take(LENGTH_EXTRA[idx] as u32)? as usize;
+
+                let idx = distances.decode(input)? as usize;
+                if idx >= DISTANCE_BASE.len() {
+                    return Err(invalid("invalid distance symbol"));
+                }
+                let distance =
+                    DISTANCE_BASE[idx] as usize + input.take(DISTANCE_EXTRA[idx] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(invalid("distance too far back"));
+                }
+
+                // the referenced bytes may overlap with the ones being copied
+                let start = out.len() - distance;
+                for idx in start..start + len {
+                    out.push(out[idx]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, inflate};
+
+    fn hex(input: &str) -> Vec<u8> {
+        (0..input.len())
+            .step_by(2)
+            .map(|idx| u8::from_str_radix(&input[idx..idx + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn inflate_blocks() {
+        // stored
+        assert_eq!(inflate(&hex("010300fcff616263")).unwrap(), b"abc");
+        assert_eq!(inflate(&hex("010000ffff")).unwrap(), b"");
+
+        // fixed huffman codes
+        assert_eq!(
+            inflate(&hex("2baecc2b49ac50b055502a28ca2fc93756b2e60200")).unwrap(),
+            b"syntax = \"proto3\";\n"
+        );
+
+        // dynamic huffman codes
+        let input = "syntax = \"proto3\";
+package foo.v1;
+message User {
+  string name = 1;
+  string email = 2;
+  repeated string tags = 3;
+}
+message Group {
+  string name = 1;
+  repeated User members = 2;
+}
+";
+        let compressed = hex(
+            "758d410a02310c45f739459803083acbe2da137880a8b10c9aa624511499bbdb51eccee57f3f79df9f25\
+             e8815b1caa69e83824a874bc50663cabaeeeeb04c2ee4bde3b1bbe00d1c3a692b190707b6c171db1d0\
+             746d6cb330e3ca147cfa9541d95b372698bb73677aabffa45df0591696039b7fed33bc01",
+        );
+        assert_eq!(inflate(&compressed).unwrap(), input.as_bytes());
+        assert!(inflate(&compressed[..40]).is_err());
+
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+}
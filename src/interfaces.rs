@@ -0,0 +1,412 @@
+//! Plain typescript output (see `--target interfaces`): interfaces of the
+//! messages and string literal unions of the enums, without any runtime
+//! dependency. The types are the same as the ones inferred from the zod
+//! schemas of `typescript`, services are omitted.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::errors::{Location, PtError};
+use crate::fingerprint::{enum_fingerprint, fingerprint};
+use crate::imports;
+use crate::parser::{Elem, Enum, EnumValue, Field, Flag, Msg, Proto};
+use crate::registry::{self, ProtoType, Registry};
+use crate::typescript::{
+    field_location, format_fingerprint, format_proto_name, header, jsdoc, property_key,
+    public_exports, snake_to_camel,
+};
+use crate::verify::{Property, Shape, Shapes};
+use crate::{Bytes, Int64, Opts};
+
+/// Generate the typescript types of the given proto file, skipping the types
+/// and fields that failed to generate (see `typescript::generate`).
+pub fn generate(opts: &Opts, proto: &Proto, deps: &[Proto]) -> (String, Vec<PtError>) {
+    let (types, errors, _) = generate_shapes(opts, proto, deps);
+    (types, errors)
+}
+
+/// Generate the typescript types (see `generate`) along with their shapes.
+pub(crate) fn generate_shapes(
+    opts: &Opts,
+    proto: &Proto,
+    deps: &[Proto],
+) -> (String, Vec<PtError>, Shapes) {
+    let registry = Registry::new([proto].into_iter().chain(deps));
+    let ctx = Context {
+        opts,
+        file: &proto.file,
+        registry: &registry,
+        imports: RefCell::new(BTreeMap::new()),
+        errors: RefCell::new(Vec::new()),
+        shapes: RefCell::new(Shapes::new()),
+    };
+    let scope = proto.package().unwrap_or("");
+    let mut body = String::with_capacity(4096);
+
+    for elem in &proto.elems {
+        let (location, result) = match elem {
+            Elem::Message(msg) => (
+                Location {
+                    line: msg.span.line,
+                    ..Location::new("message", registry::qualify(scope, &msg.name))
+                },
+                format_msg(&ctx, msg, scope),
+            ),
+            Elem::Enum(e) => (
+                Location {
+                    line: e.span.line,
+                    ..Location::new("enum", registry::qualify(scope, &e.name))
+                },
+                format_enum(&ctx, e, scope),
+            ),
+            _ => continue,
+        };
+        if let Some(value) = ctx.recover(location, result) {
+            body.push_str(&value);
+        }
+    }
+
+    let mut str = String::with_capacity(body.len() + 512);
+
+    str.push_str(&header(opts, proto));
+    let mut preamble = String::new();
+    for (module, names) in ctx.imports.borrow().iter() {
+        preamble.push_str(
+            format!(
+                "import type {{ {} }} from \"{}\";\n",
+                names.iter().cloned().collect::<Vec<_>>().join(", "),
+                module
+            )
+            .as_str(),
+        );
+    }
+    preamble.push_str(&public_exports(proto));
+    if !preamble.is_empty() {
+        str.push_str(&preamble);
+        str.push('\n');
+    }
+
+    str.push_str(&body);
+
+    (str, ctx.errors.take(), ctx.shapes.take())
+}
+
+fn format_msg(ctx: &Context, msg: &Msg, scope: &str) -> Result<String, PtError> {
+    let ptype = ctx.declared(&msg.name, scope)?;
+    let mut nested = String::new();
+    let mut properties = Vec::new();
+    let mut shape = Vec::new();
+
+    for field in &msg.fields {
+        let location = field_location(&ptype.full_name, "", field);
+        let value = match field {
+            Field::SubMessage(sub_msg) => {
+                format_msg(ctx, sub_msg, &ptype.full_name).map(|sub_msg| nested.push_str(&sub_msg))
+            }
+            Field::SubEnum(e) => format_enum(ctx, e, &ptype.full_name).map(|e| nested.push_str(&e)),
+            _ => format_field(ctx, field, &ptype.full_name).map(|value| {
+                if let Some((property, value)) = value {
+                    shape.push(property);
+                    properties.push(value);
+                }
+            }),
+        };
+        ctx.recover(location, value);
+    }
+    ctx.shapes
+        .borrow_mut()
+        .insert(ptype.ts_name.clone(), Shape::message(shape));
+
+    let mut str = nested;
+
+    let deprecated = if msg.deprecated {
+        "/** @deprecated */\n"
+    } else {
+        ""
+    };
+    str.push_str(deprecated);
+    str.push_str(format!("export interface {} {{\n", ptype.ts_name).as_str());
+    for property in properties {
+        str.push_str("  ");
+        str.push_str(&property);
+        str.push_str(";\n");
+    }
+    str.push_str("}\n\n");
+
+    if ctx.opts.proto_names {
+        str.push_str(&format_proto_name(
+            "export ",
+            &ptype.ts_name,
+            &ptype.full_name,
+        ));
+    }
+    if ctx.opts.fingerprints {
+        str.push_str(&format_fingerprint(
+            "export ",
+            &ptype.ts_name,
+            fingerprint(msg),
+        ));
+    }
+
+    Ok(str)
+}
+
+/// Property of the given field, keyed like in the JSON representation.
+fn format_field(
+    ctx: &Context,
+    field: &Field,
+    scope: &str,
+) -> Result<Option<(Property, String)>, PtError> {
+    match field {
+        Field::Single {
+            name,
+            field_type,
+            flag,
+            deprecated,
+            json_name,
+            ..
+        } => {
+            let key = match json_name {
+                Some(json_name) => property_key(json_name),
+                None => snake_to_camel(name),
+            };
+            let ts_type = type_name(ctx, field_type, scope)?;
+            let property = match flag {
+                Flag::Optional => format!("{}?: {}", key, ts_type),
+                Flag::Repeated => format!("{}: {}[]", key, ts_type),
+                Flag::None | Flag::Required => format!("{}: {}", key, ts_type),
+            };
+            Ok(Some((
+                Property::new(key, *flag == Flag::Optional),
+                format!("{}{}", jsdoc(*deprecated), property),
+            )))
+        }
+        Field::Map {
+            name,
+            key_type,
+            value_type,
+            deprecated,
+            ..
+        } => {
+            // keys of JSON objects are strings, except for the 32 bit numbers
+            // that zod parses as numbers
+            let key = match type_name(ctx, key_type, scope)? {
+                "number" => "number",
+                _ => "string",
+            };
+            Ok(Some((
+                Property::new(snake_to_camel(name), false),
+                format!(
+                    "{}{}: Record<{}, {}>",
+                    jsdoc(*deprecated),
+                    snake_to_camel(name),
+                    key,
+                    type_name(ctx, value_type, scope)?
+                ),
+            )))
+        }
+        Field::OneOf { name, fields, .. } => {
+            let mut cases = Vec::new();
+            for case in fields {
+                let value = format_field(ctx, case, scope);
+                let prefix = format!("{}.", name);
+                if let Some(value) = ctx.recover(field_location(scope, &prefix, case), value) {
+                    cases.extend(value.map(|(_, value)| format!("{{ {} }}", value)));
+                }
+            }
+            let key = snake_to_camel(name);
+            Ok((!cases.is_empty()).then(|| {
+                let value = format!("{}: {}", key, cases.join(" | "));
+                (Property::new(key, false), value)
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn format_enum(ctx: &Context, value: &Enum, scope: &str) -> Result<String, PtError> {
+    let ptype = ctx.declared(&value.name, scope)?;
+    let mut str = String::with_capacity(256);
+
+    if value.deprecated {
+        str.push_str("/** @deprecated */\n");
+    }
+    str.push_str(format!("export type {} =", ptype.ts_name).as_str());
+    let mut values = Vec::new();
+    for value in &value.values {
+        if let EnumValue::Single { name, .. } = value {
+            str.push_str(format!("\n  | \"{}\"", name).as_str());
+            values.push(name.clone());
+        }
+    }
+    let empty = values.is_empty();
+    ctx.shapes
+        .borrow_mut()
+        .insert(ptype.ts_name.clone(), Shape::enumeration(values));
+    if empty {
+        str.push_str(" never");
+    }
+    str.push_str(";\n\n");
+
+    if ctx.opts.proto_names {
+        str.push_str(&format_proto_name(
+            "export ",
+            &ptype.ts_name,
+            &ptype.full_name,
+        ));
+    }
+    if ctx.opts.fingerprints {
+        str.push_str(&format_fingerprint(
+            "export ",
+            &ptype.ts_name,
+            enum_fingerprint(value),
+        ));
+    }
+
+    Ok(str)
+}
+
+fn type_name<'a>(ctx: &'a Context, type_name: &'a str, scope: &str) -> Result<&'a str, PtError> {
+    match type_name {
+        "string" => Ok("string"),
+        "bytes" => match ctx.opts.bytes {
+            Bytes::Base64 => Ok("string"),
+            Bytes::Uint8Array => Ok("Uint8Array"),
+        },
+        "int32" | "double" | "float" | "uint32" | "sint32" | "fixed32" | "sfixed32" => Ok("number"),
+        "int64" | "uint64" | "fixed64" | "sfixed64" | "sint64" => match ctx.opts.int64() {
+            Int64::Bigint => Ok("bigint"),
+            Int64::String => Ok("string"),
+            Int64::Number => Ok("number"),
+        },
+        "bool" => Ok("boolean"),
+        "google.protobuf.Timestamp" | ".google.protobuf.Timestamp" => Ok("Date"),
+        _ => ctx
+            .resolve(type_name, scope)
+            .map(|ptype| ptype.ts_name.as_str())
+            .ok_or_else(|| ctx.registry.unresolved(type_name, scope)),
+    }
+}
+
+struct Context<'a> {
+    opts: &'a Opts,
+    file: &'a str,
+    registry: &'a Registry,
+    /// types referenced from other modules, grouped by module
+    imports: RefCell<BTreeMap<String, BTreeSet<String>>>,
+    /// errors of the generation phase, see `PtError::Located`
+    errors: RefCell<Vec<PtError>>,
+    /// shapes of the types declared (see `verify`)
+    shapes: RefCell<Shapes>,
+}
+
+impl<'a> Context<'a> {
+    fn recover<T>(&self, location: Location, result: Result<T, PtError>) -> Option<T> {
+        result
+            .map_err(|error| self.errors.borrow_mut().push(error.at(location)))
+            .ok()
+    }
+
+    fn declared(&self, name: &str, scope: &str) -> Result<&'a ProtoType, PtError> {
+        let full_name = registry::qualify(scope, name);
+        self.registry
+            .get(&full_name)
+            .ok_or(PtError::ProtobufTypeNotFound(full_name))
+    }
+
+    /// Resolve a type reference and keep track of the types to be imported.
+    fn resolve(&self, name: &str, scope: &str) -> Option<&'a ProtoType> {
+        let ptype = self.registry.resolve(name, scope)?;
+
+        if ptype.file != self.file {
+            self.imports
+                .borrow_mut()
+                .entry(imports::module_path(self.file, &ptype.file))
+                .or_default()
+                .insert(ptype.ts_name.clone());
+        }
+
+        Some(ptype)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate;
+    use crate::parser::parse;
+    use crate::Opts;
+
+    #[test]
+    fn generate_interfaces() {
+        let opts = Opts::default();
+        let p = parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message User {
+  string name = 1;
+  optional int32 age = 2;
+  repeated string tags = 3;
+  map<string, int64> counts = 4;
+  oneof contact { string email = 5; string phone = 6; }
+  Kind kind = 7 [deprecated = true];
+  message Address { string city = 1; }
+  enum Kind { KIND_UNSPECIFIED = 0; KIND_ADMIN = 1; }
+}
+service Users { rpc Get(User) returns (User); }"#,
+        )
+        .unwrap();
+
+        let (types, errors) = generate(&opts, &p, &[]);
+        assert!(errors.is_empty());
+        assert!(!types.contains("zod"));
+        assert!(!types.contains("Users"));
+        assert!(types.ends_with(
+            r#"//
+
+export interface User_Address {
+  city: string;
+}
+
+export type User_Kind =
+  | "KIND_UNSPECIFIED"
+  | "KIND_ADMIN";
+
+export interface User {
+  name: string;
+  age?: number;
+  tags: string[];
+  counts: Record<string, bigint>;
+  contact: { email: string } | { phone: string };
+  /** @deprecated */
+  kind: User_Kind;
+}
+
+"#
+        ));
+    }
+
+    #[test]
+    fn generate_interfaces_imports() {
+        let opts = Opts::default();
+        let common = parse(
+            &opts,
+            "foo/common.proto",
+            "syntax = \"proto3\";\npackage foo;\nmessage Money { int64 units = 1; }",
+        )
+        .unwrap();
+        let p = parse(
+            &opts,
+            "foo/order.proto",
+            "syntax = \"proto3\";\npackage foo;\nimport \"foo/common.proto\";\n\
+             message Order { Money total = 1; Missing other = 2; }",
+        )
+        .unwrap();
+
+        let (types, errors) = generate(&opts, &p, &[common]);
+        assert!(types.contains("import type { Money } from \"./common\";\n"));
+        assert!(types.contains("export interface Order {\n  total: Money;\n}"));
+        assert_eq!(errors.len(), 1);
+    }
+}
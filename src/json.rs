@@ -0,0 +1,376 @@
+//! Minimal JSON representation used for machine readable outputs and JSON
+//! payloads, along with the special representations of the well-known types
+//! under the proto3 JSON mapping the generators and `payload` share.
+
+use std::fmt;
+
+use nom::branch::alt;
+use nom::bytes::complete::{escaped, is_not, tag};
+use nom::character::complete::{anychar, char, multispace0};
+use nom::combinator::{cut, map, opt};
+use nom::error::VerboseError;
+use nom::multi::separated_list0;
+use nom::number::complete::recognize_float;
+use nom::sequence::{delimited, separated_pair};
+use nom::IResult;
+
+use crate::errors::PtError;
+
+type ParserResult<'a, O> = IResult<&'a str, O, VerboseError<&'a str>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// number as written, so integers beyond the precision of `f64` (e.g.
+    /// int64 values) are preserved
+    Num(String),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn str(value: &str) -> Json {
+        Json::Str(value.to_string())
+    }
+
+    pub fn num(value: f64) -> Json {
+        Json::Num(value.to_string())
+    }
+
+    pub fn object<'a, I>(fields: I) -> Json
+    where
+        I: IntoIterator<Item = (&'a str, Json)>,
+    {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+}
+
+/// Parse a JSON document.
+pub fn parse(input: &str) -> Result<Json, PtError> {
+    let error = |rest: &str| {
+        let line = input[..input.len() - rest.len()].matches('\n').count() + 1;
+        PtError::ParsingError(format!("invalid JSON in line {}", line))
+    };
+
+    match delimited(multispace0, value, multispace0)(input) {
+        Ok(("", json)) => Ok(json),
+        Ok((rest, _)) => Err(error(rest)),
+        Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
+            Err(error(err.errors.first().map_or(input, |(rest, _)| rest)))
+        }
+        Err(nom::Err::Incomplete(_)) => Err(PtError::IncompleteParsing),
+    }
+}
+
+fn value(input: &str) -> ParserResult<'_, Json> {
+    alt((
+        map(tag("null"), |_| Json::Null),
+        map(tag("true"), |_| Json::Bool(true)),
+        map(tag("false"), |_| Json::Bool(false)),
+        map(string, Json::Str),
+        map(recognize_float, |num: &str| Json::Num(num.to_string())),
+        array,
+        object,
+    ))(input)
+}
+
+fn ws<'a, T>(
+    inner: impl FnMut(&'a str) -> ParserResult<'a, T>,
+) -> impl FnMut(&'a str) -> ParserResult<'a, T> {
+    delimited(multispace0, inner, multispace0)
+}
+
+fn array(input: &str) -> ParserResult<'_, Json> {
+    let (input, _) = char('[')(input)?;
+    let (input, values) = separated_list0(char(','), ws(value))(input)?;
+    let (input, _) = ws(char(']'))(input)?;
+    Ok((input, Json::Array(values)))
+}
+
+fn object(input: &str) -> ParserResult<'_, Json> {
+    let (input, _) = char('{')(input)?;
+    let (input, fields) = separated_list0(
+        char(','),
+        ws(separated_pair(string, ws(char(':')), cut(value))),
+    )(input)?;
+    let (input, _) = ws(char('}'))(input)?;
+    Ok((input, Json::Object(fields)))
+}
+
+fn string(input: &str) -> ParserResult<'_, String> {
+    let (input, raw) = delimited(
+        char('"'),
+        opt(escaped(is_not("\\\""), '\\', anychar)),
+        char('"'),
+    )(input)?;
+    Ok((input, unescape(raw.unwrap_or(""))))
+}
+
+fn unescape(raw: &str) -> String {
+    let mut str = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+
+    while let Some(chr) = chars.next() {
+        if chr != '\\' {
+            str.push(chr);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => str.push('\n'),
+            Some('r') => str.push('\r'),
+            Some('t') => str.push('\t'),
+            Some('b') => str.push('\x08'),
+            Some('f') => str.push('\x0c'),
+            Some('u') => {
+                let code = |chars: &mut std::str::Chars| {
+                    let hex = chars.by_ref().take(4).collect::<String>();
+                    u32::from_str_radix(&hex, 16).unwrap_or(0xfffd)
+                };
+                let high = code(&mut chars);
+                let value = if (0xd800..0xdc00).contains(&high) && chars.as_str().starts_with("\\u")
+                {
+                    chars.nth(1);
+                    let low = code(&mut chars);
+                    0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+                } else {
+                    high
+                };
+                str.push(char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            Some(other) => str.push(other),
+            None => (),
+        }
+    }
+
+    str
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Indented rendering of the value (`{:#}`), e.g. for files meant to be
+/// read and diffed by humans.
+fn write_pretty(f: &mut fmt::Formatter<'_>, json: &Json, indent: usize) -> fmt::Result {
+    let (open, close, items) = match json {
+        Json::Array(values) if !values.is_empty() => {
+            ('[', ']', values.iter().map(|value| (None, value)).collect())
+        }
+        Json::Object(fields) if !fields.is_empty() => (
+            '{',
+            '}',
+            fields
+                .iter()
+                .map(|(key, value)| (Some(key), value))
+                .collect::<Vec<_>>(),
+        ),
+        json => return write!(f, "{}", json),
+    };
+
+    writeln!(f, "{}", open)?;
+    for (idx, (key, value)) in items.iter().enumerate() {
+        write!(f, "{:width$}", "", width = indent + 2)?;
+        if let Some(key) = key {
+            write_str(f, key)?;
+            write!(f, ": ")?;
+        }
+        write_pretty(f, value, indent + 2)?;
+        writeln!(f, "{}", if idx + 1 < items.len() { "," } else { "" })?;
+    }
+    write!(f, "{:width$}{}", "", close, width = indent)
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return write_pretty(f, self, 0);
+        }
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Num(value) => write!(f, "{}", value),
+            Json::Str(value) => write_str(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_str(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Fully-qualified name of a well-known type with a special JSON
+/// representation, `None` for all other names.
+pub(crate) fn well_known(name: &str) -> Option<&str> {
+    let name = name.trim_start_matches('.');
+    is_well_known(name).then_some(name)
+}
+
+pub(crate) fn is_well_known(name: &str) -> bool {
+    matches!(
+        name.strip_prefix("google.protobuf."),
+        Some(
+            "Any"
+                | "Duration"
+                | "Empty"
+                | "FieldMask"
+                | "ListValue"
+                | "NullValue"
+                | "Struct"
+                | "Timestamp"
+                | "Value"
+        )
+    ) || wrapped(name).is_some()
+}
+
+/// Scalar type of the wrapper types, e.g. `int64` of `Int64Value`.
+pub(crate) fn wrapped(name: &str) -> Option<&'static str> {
+    match name.strip_prefix("google.protobuf.")? {
+        "DoubleValue" => Some("double"),
+        "FloatValue" => Some("float"),
+        "Int64Value" => Some("int64"),
+        "UInt64Value" => Some("uint64"),
+        "Int32Value" => Some("int32"),
+        "UInt32Value" => Some("uint32"),
+        "BoolValue" => Some("bool"),
+        "StringValue" => Some("string"),
+        "BytesValue" => Some("bytes"),
+        _ => None,
+    }
+}
+
+#[cfg(any(feature = "zod", feature = "payloads"))]
+/// Canonical RFC 3339 representation in UTC of seconds and nanoseconds since
+/// the unix epoch.
+pub(crate) fn format_timestamp(seconds: i64, nanos: u32) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        fraction(nanos)
+    )
+}
+
+#[cfg(any(feature = "zod", feature = "payloads"))]
+/// Date of the proleptic gregorian calendar of the days since the unix epoch.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(any(feature = "zod", feature = "payloads"))]
+/// Fractional digits of the nanoseconds in groups of three, as used by the
+/// canonical representation of timestamps and durations.
+pub(crate) fn fraction(nanos: u32) -> String {
+    match nanos {
+        0 => String::new(),
+        _ if nanos.is_multiple_of(1_000_000) => format!(".{:03}", nanos / 1_000_000),
+        _ if nanos.is_multiple_of(1_000) => format!(".{:06}", nanos / 1_000),
+        _ => format!(".{:09}", nanos),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Json};
+
+    #[test]
+    fn display_json() {
+        let json = Json::object([
+            ("name", Json::str("a \"b\"\n")),
+            ("values", Json::Array(vec![Json::num(1.0), Json::num(2.5)])),
+            ("ok", Json::Bool(true)),
+            ("none", Json::Null),
+        ]);
+
+        assert_eq!(
+            json.to_string(),
+            r#"{"name":"a \"b\"\n","values":[1,2.5],"ok":true,"none":null}"#
+        );
+        assert_eq!(
+            format!("{:#}", json),
+            "{\n  \"name\": \"a \\\"b\\\"\\n\",\n  \"values\": [\n    1,\n    2.5\n  ],\n  \"ok\": true,\n  \"none\": null\n}"
+        );
+    }
+
+    #[test]
+    fn parse_json() {
+        let json = parse(
+            r#" {"name": "a \"b\"\n\u00e4\ud83d\ude00", "values": [1, -2.5e3, 9007199254740993],
+  "ok": true, "none": null, "nested": {}} "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            json,
+            Json::object([
+                ("name", Json::str("a \"b\"\n\u{e4}\u{1f600}")),
+                (
+                    "values",
+                    Json::Array(vec![
+                        Json::Num("1".to_string()),
+                        Json::Num("-2.5e3".to_string()),
+                        Json::Num("9007199254740993".to_string())
+                    ])
+                ),
+                ("ok", Json::Bool(true)),
+                ("none", Json::Null),
+                ("nested", Json::Object(vec![])),
+            ])
+        );
+        assert_eq!(
+            parse("{\n  \"a\": 1,\n  \"b\": }").unwrap_err().to_string(),
+            "proto parsing failed: invalid JSON in line 3"
+        );
+    }
+}
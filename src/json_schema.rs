@@ -0,0 +1,390 @@
+//! JSON Schema (draft 2020-12) documents of the proto3 JSON representation
+//! (see `--emit json-schema`): one document per message and enum of a file,
+//! named after the fully-qualified type (e.g. `foo.v1.User.schema.json`) and
+//! referencing the documents of other types, nested or imported, by `$ref`.
+//!
+//! Members of oneofs are properties of the message itself, at most one of
+//! them may be given (`oneOf`). Well-known types with a special JSON mapping
+//! are described inline.
+
+use crate::errors::PtError;
+use crate::json::{self, Json};
+use crate::parser::{self, Elem, Enum, EnumValue, Field, Flag, Msg, Proto};
+use crate::registry::{self, Registry};
+
+pub const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Documents of all messages and enums of the given file, along with their
+/// file names.
+pub fn documents(proto: &Proto, deps: &[Proto]) -> Result<Vec<(String, Json)>, PtError> {
+    let registry = Registry::new([proto].into_iter().chain(deps));
+    let definitions = Definitions {
+        registry: &registry,
+        reference: file_name,
+    };
+
+    Ok(definitions
+        .of(proto)?
+        .into_iter()
+        .map(|(full_name, schema)| {
+            let Json::Object(fields) = schema else {
+                unreachable!("schemas are objects");
+            };
+            let document = [
+                ("$schema".to_string(), Json::str(DRAFT)),
+                ("$id".to_string(), Json::str(&file_name(&full_name))),
+                ("title".to_string(), Json::str(&full_name)),
+            ];
+            let document = document.into_iter().chain(fields).collect();
+            (file_name(&full_name), Json::Object(document))
+        })
+        .collect())
+}
+
+/// File name of the document of the given type.
+pub fn file_name(full_name: &str) -> String {
+    format!("{}.schema.json", full_name)
+}
+
+/// Schemas of messages and enums, referencing each other by the URI the
+/// reference function gives for a fully-qualified type name, e.g. the file
+/// names of the documents or components of an OpenAPI document.
+pub(crate) struct Definitions<'a> {
+    pub registry: &'a Registry,
+    pub reference: fn(&str) -> String,
+}
+
+impl Definitions<'_> {
+    /// Schemas of all messages and enums of the given file (nested ones
+    /// first), by fully-qualified name.
+    pub fn of(&self, proto: &Proto) -> Result<Vec<(String, Json)>, PtError> {
+        let scope = proto.package().unwrap_or("");
+        let mut schemas = Vec::new();
+
+        for elem in &proto.elems {
+            match elem {
+                Elem::Message(msg) => self.message(msg, scope, &mut schemas)?,
+                Elem::Enum(e) => schemas.push(enumeration(e, scope)),
+                _ => (),
+            }
+        }
+
+        Ok(schemas)
+    }
+
+    fn message(
+        &self,
+        msg: &Msg,
+        scope: &str,
+        schemas: &mut Vec<(String, Json)>,
+    ) -> Result<(), PtError> {
+        let full_name = registry::qualify(scope, &msg.name);
+        let mut properties = Vec::new();
+        let mut required = Vec::new();
+        let mut oneofs = Vec::new();
+
+        for field in &msg.fields {
+            match field {
+                Field::Single { .. } | Field::Map { .. } => {
+                    let (name, schema) = self.property(field, &full_name)?;
+                    if let Field::Single {
+                        flag: Flag::Required,
+                        ..
+                    } = field
+                    {
+                        required.push(Json::str(&name));
+                    }
+                    properties.push((name, schema));
+                }
+                Field::OneOf { fields, .. } => {
+                    let mut members = Vec::new();
+                    for field in fields {
+                        let (name, schema) = self.property(field, &full_name)?;
+                        members.push(Json::object([(
+                            "required",
+                            Json::Array(vec![Json::str(&name)]),
+                        )]));
+                        properties.push((name, schema));
+                    }
+                    // one of the members or none of them
+                    let none = Json::object([(
+                        "not",
+                        Json::object([("anyOf", Json::Array(members.clone()))]),
+                    )]);
+                    members.push(none);
+                    oneofs.push(Json::object([("oneOf", Json::Array(members))]));
+                }
+                Field::SubMessage(sub_msg) => self.message(sub_msg, &full_name, schemas)?,
+                Field::SubEnum(e) => schemas.push(enumeration(e, &full_name)),
+                _ => (),
+            }
+        }
+
+        let mut schema = Vec::new();
+        if let Some(doc) = &msg.doc {
+            schema.push(("description".to_string(), Json::str(doc)));
+        }
+        if msg.deprecated {
+            schema.push(("deprecated".to_string(), Json::Bool(true)));
+        }
+        schema.push(("type".to_string(), Json::str("object")));
+        schema.push(("properties".to_string(), Json::Object(properties)));
+        if !required.is_empty() {
+            schema.push(("required".to_string(), Json::Array(required)));
+        }
+        match oneofs.len() {
+            0 => (),
+            1 => schema.extend(match oneofs.remove(0) {
+                Json::Object(fields) => fields,
+                _ => Vec::new(),
+            }),
+            _ => schema.push(("allOf".to_string(), Json::Array(oneofs))),
+        }
+
+        schemas.push((full_name, Json::Object(schema)));
+        Ok(())
+    }
+
+    /// JSON name and schema of the field.
+    pub fn property(&self, field: &Field, scope: &str) -> Result<(String, Json), PtError> {
+        let (name, schema, deprecated, doc) = match field {
+            Field::Single {
+                name,
+                field_type,
+                flag,
+                deprecated,
+                json_name,
+                doc,
+                ..
+            } => {
+                let name = json_name
+                    .clone()
+                    .unwrap_or_else(|| parser::default_json_name(name));
+                let schema = self.schema(field_type, scope)?;
+                let schema = match flag {
+                    Flag::Repeated => {
+                        Json::object([("type", Json::str("array")), ("items", schema)])
+                    }
+                    _ => schema,
+                };
+                (name, schema, *deprecated, doc)
+            }
+            Field::Map {
+                name,
+                value_type,
+                deprecated,
+                doc,
+                ..
+            } => (
+                parser::default_json_name(name),
+                Json::object([
+                    ("type", Json::str("object")),
+                    ("additionalProperties", self.schema(value_type, scope)?),
+                ]),
+                *deprecated,
+                doc,
+            ),
+            _ => unreachable!("no property of {:?}", field),
+        };
+
+        let Json::Object(mut fields) = schema else {
+            unreachable!("schemas are objects");
+        };
+        if let Some(doc) = doc {
+            fields.insert(0, ("description".to_string(), Json::str(doc)));
+        }
+        if deprecated {
+            fields.push(("deprecated".to_string(), Json::Bool(true)));
+        }
+        Ok((name, Json::Object(fields)))
+    }
+
+    /// Schema of the given scalar or referenced type.
+    pub fn schema(&self, type_name: &str, scope: &str) -> Result<Json, PtError> {
+        let integer = |pattern: &str| {
+            Json::object([
+                (
+                    "type",
+                    Json::Array(vec![Json::str("integer"), Json::str("string")]),
+                ),
+                ("pattern", Json::str(pattern)),
+            ])
+        };
+        let typed = |name: &str| Json::object([("type", Json::str(name))]);
+        let reference =
+            |full_name: &str| Json::object([("$ref", Json::Str((self.reference)(full_name)))]);
+
+        Ok(match type_name {
+            "string" => typed("string"),
+            "bytes" => Json::object([
+                ("type", Json::str("string")),
+                ("contentEncoding", Json::str("base64")),
+            ]),
+            "int32" | "sint32" | "sfixed32" | "uint32" | "fixed32" => typed("integer"),
+            // 64 bit integers are decimal strings, numbers are accepted as well
+            "int64" | "sint64" | "sfixed64" => integer("^-?[0-9]+$"),
+            "uint64" | "fixed64" => integer("^[0-9]+$"),
+            // including "NaN", "Infinity" and "-Infinity"
+            "double" | "float" => Json::object([(
+                "anyOf",
+                Json::Array(vec![
+                    typed("number"),
+                    Json::object([(
+                        "enum",
+                        Json::Array(vec![
+                            Json::str("NaN"),
+                            Json::str("Infinity"),
+                            Json::str("-Infinity"),
+                        ]),
+                    )]),
+                ]),
+            )]),
+            "bool" => typed("boolean"),
+            // well-known types need not be imported to be described
+            _ if type_name
+                .trim_start_matches('.')
+                .starts_with("google.protobuf.") =>
+            {
+                let full_name = type_name.trim_start_matches('.');
+                match self.well_known(full_name, scope)? {
+                    Some(schema) => schema,
+                    None => reference(full_name),
+                }
+            }
+            _ => {
+                let ptype = self
+                    .registry
+                    .resolve(type_name, scope)
+                    .ok_or_else(|| self.registry.unresolved(type_name, scope))?;
+                match self.well_known(&ptype.full_name, scope)? {
+                    Some(schema) => schema,
+                    None => reference(&ptype.full_name),
+                }
+            }
+        })
+    }
+
+    /// Schema of a well-known type with a special JSON mapping.
+    fn well_known(&self, full_name: &str, scope: &str) -> Result<Option<Json>, PtError> {
+        let string = |format: &str| {
+            Json::object([("type", Json::str("string")), ("format", Json::str(format))])
+        };
+        if let Some(scalar) = json::wrapped(full_name) {
+            return self.schema(scalar, scope).map(Some);
+        }
+
+        Ok(Some(match full_name {
+            "google.protobuf.Timestamp" => string("date-time"),
+            "google.protobuf.Duration" => Json::object([
+                ("type", Json::str("string")),
+                ("pattern", Json::str("^-?[0-9]+(\\.[0-9]{1,9})?s$")),
+            ]),
+            "google.protobuf.FieldMask" => Json::object([("type", Json::str("string"))]),
+            "google.protobuf.Any" => Json::object([
+                ("type", Json::str("object")),
+                (
+                    "properties",
+                    Json::object([("@type", Json::object([("type", Json::str("string"))]))]),
+                ),
+                ("required", Json::Array(vec![Json::str("@type")])),
+            ]),
+            "google.protobuf.Struct" => Json::object([("type", Json::str("object"))]),
+            "google.protobuf.ListValue" => Json::object([("type", Json::str("array"))]),
+            "google.protobuf.NullValue" => Json::object([("type", Json::str("null"))]),
+            // any JSON value
+            "google.protobuf.Value" => Json::Object(Vec::new()),
+            _ => return Ok(None),
+        }))
+    }
+}
+
+/// Enums are given by the name or the number of their values.
+fn enumeration(e: &Enum, scope: &str) -> (String, Json) {
+    let full_name = registry::qualify(scope, &e.name);
+    let mut names = Vec::new();
+    let mut numbers = Vec::new();
+    for value in &e.values {
+        if let EnumValue::Single { name, idx, .. } = value {
+            names.push(Json::str(name));
+            numbers.push(Json::num(*idx as f64));
+        }
+    }
+
+    let mut schema = Vec::new();
+    if e.deprecated {
+        schema.push(("deprecated".to_string(), Json::Bool(true)));
+    }
+    names.extend(numbers);
+    schema.push(("enum".to_string(), Json::Array(names)));
+    (full_name, Json::Object(schema))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::documents;
+    use crate::parser::parse;
+    use crate::Opts;
+
+    #[test]
+    fn json_schema_documents() {
+        let opts = Opts::default();
+        let common = parse(
+            &opts,
+            "common.proto",
+            "syntax = \"proto3\";\npackage common;\nmessage Money { int64 units = 1; }",
+        )
+        .unwrap();
+        let p = parse(
+            &opts,
+            "user.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+import "common.proto";
+// A user of the system.
+message User {
+  string name = 1 [json_name = "fullName"];
+  repeated common.Money balances = 2;
+  map<string, Kind> kinds = 3;
+  oneof contact { string email = 4; string phone_number = 5; }
+  message Address { google.protobuf.Timestamp moved_at = 1; }
+}
+enum Kind { KIND_UNSPECIFIED = 0; KIND_ADMIN = 2; }"#,
+        )
+        .unwrap();
+
+        let documents = documents(&p, &[common]).unwrap();
+        let names = documents
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "foo.v1.User.Address.schema.json",
+                "foo.v1.User.schema.json",
+                "foo.v1.Kind.schema.json"
+            ]
+        );
+        assert_eq!(
+            documents[1].1.to_string(),
+            concat!(
+                r#"{"$schema":"https://json-schema.org/draft/2020-12/schema","$id":"foo.v1.User.schema.json","title":"foo.v1.User","#,
+                r#""description":"A user of the system.","type":"object","properties":{"#,
+                r#""fullName":{"type":"string"},"#,
+                r#""balances":{"type":"array","items":{"$ref":"common.Money.schema.json"}},"#,
+                r#""kinds":{"type":"object","additionalProperties":{"$ref":"foo.v1.Kind.schema.json"}},"#,
+                r#""email":{"type":"string"},"phoneNumber":{"type":"string"}},"#,
+                r#""oneOf":[{"required":["email"]},{"required":["phoneNumber"]},"#,
+                r#"{"not":{"anyOf":[{"required":["email"]},{"required":["phoneNumber"]}]}}]}"#
+            )
+        );
+        assert!(documents[0]
+            .1
+            .to_string()
+            .contains(r#""movedAt":{"type":"string","format":"date-time"}"#));
+        assert_eq!(
+            documents[2].1.to_string(),
+            r#"{"$schema":"https://json-schema.org/draft/2020-12/schema","$id":"foo.v1.Kind.schema.json","title":"foo.v1.Kind","enum":["KIND_UNSPECIFIED","KIND_ADMIN",0,2]}"#
+        );
+    }
+}
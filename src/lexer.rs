@@ -0,0 +1,324 @@
+//! Tokenizer of proto files, the first stage of parsing: identifiers,
+//! literals and punctuation, each along with the whitespace and comments
+//! preceding it. Comments are no tokens of their own, the leading comment of
+//! a definition is taken from the whitespace before its first token instead.
+//!
+//! Keywords are contextual in proto files (a field may be named `message`),
+//! so they are lexed as identifiers and told apart by the parser.
+
+use std::fmt;
+
+use crate::parser::Doc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// identifier or keyword, e.g. `message` or `My_Message`
+    Ident,
+    /// integer or floating point literal without sign, e.g. `0x1F` or `1e3`;
+    /// validated by the parser
+    Number,
+    /// string literal including its quotes
+    Str,
+    /// single punctuation character, e.g. `;` or `{`
+    Punct,
+    /// end of the input
+    Eof,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Token<'a> {
+    pub kind: Kind,
+    pub text: &'a str,
+    /// byte offset of the token in the input
+    pub start: usize,
+    /// whitespace and comments preceding the token
+    pub space: &'a str,
+}
+
+impl Token<'_> {
+    /// Byte offset following the token.
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    /// Whether the token is the given identifier or punctuation.
+    pub fn is(&self, text: &str) -> bool {
+        matches!(self.kind, Kind::Ident | Kind::Punct) && self.text == text
+    }
+
+    /// Leading comment of the definition starting with this token.
+    pub fn doc(&self) -> Doc {
+        // nothing precedes the whitespace at the start of the file
+        let start_of_file = self.space.len() == self.start;
+        leading_comment(self.space, start_of_file)
+    }
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Kind::Eof => write!(f, "end of file"),
+            Kind::Str => write!(f, "string {}", self.text),
+            _ => write!(f, "`{}`", self.text),
+        }
+    }
+}
+
+/// Invalid input the tokenizer skipped, e.g. an unterminated comment.
+#[derive(Debug)]
+pub struct LexError {
+    /// byte offset the error occurred at
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+/// Tokens of the given input, terminated by an `Eof` token, along with the
+/// errors found on the way.
+pub fn tokenize(input: &str) -> (Vec<Token<'_>>, Vec<LexError>) {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        let space_start = pos;
+        pos = skip_space(input, pos, &mut errors);
+        let space = &input[space_start..pos];
+
+        let start = pos;
+        let kind = match bytes.get(pos) {
+            None => {
+                tokens.push(Token {
+                    kind: Kind::Eof,
+                    text: "",
+                    start,
+                    space,
+                });
+                return (tokens, errors);
+            }
+            Some(b'a'..=b'z' | b'A'..=b'Z' | b'_') => {
+                pos = end_of(bytes, pos, |byte| {
+                    byte.is_ascii_alphanumeric() || byte == b'_'
+                });
+                Kind::Ident
+            }
+            Some(b'0'..=b'9') => {
+                pos = number_end(bytes, pos);
+                Kind::Number
+            }
+            Some(b'.') if bytes.get(pos + 1).is_some_and(u8::is_ascii_digit) => {
+                pos = number_end(bytes, pos);
+                Kind::Number
+            }
+            Some(b'"' | b'\'') => {
+                pos = string_end(input, pos, &mut errors);
+                Kind::Str
+            }
+            Some(_) => {
+                pos += input[pos..].chars().next().map_or(1, char::len_utf8);
+                Kind::Punct
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &input[start..pos],
+            start,
+            space,
+        });
+    }
+}
+
+fn end_of(bytes: &[u8], mut pos: usize, matches: impl Fn(u8) -> bool) -> usize {
+    while bytes.get(pos).is_some_and(|byte| matches(*byte)) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Offset following the whitespace and comments at the given offset.
+fn skip_space(input: &str, mut pos: usize, errors: &mut Vec<LexError>) -> usize {
+    let bytes = input.as_bytes();
+    loop {
+        let rest = &input[pos..];
+        if rest.starts_with("//") {
+            pos += rest.find('\n').unwrap_or(rest.len());
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            match comment.find("*/") {
+                Some(end) => pos += end + 4,
+                None => {
+                    errors.push(LexError {
+                        offset: pos,
+                        message: "unterminated comment",
+                    });
+                    return input.len();
+                }
+            }
+        } else if bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+            pos += 1;
+        } else {
+            return pos;
+        }
+    }
+}
+
+/// Offset following the number literal at the given offset. Letters and
+/// underscores are taken along, so e.g. `5to` is a single (invalid) number
+/// instead of a number followed by an identifier.
+fn number_end(bytes: &[u8], mut pos: usize) -> usize {
+    let hex = bytes[pos..].starts_with(b"0x") || bytes[pos..].starts_with(b"0X");
+    while let Some(byte) = bytes.get(pos) {
+        let exponent = !hex && matches!(byte, b'e' | b'E');
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'.') {
+            pos += 1;
+        } else {
+            break;
+        }
+        if exponent && matches!(bytes.get(pos), Some(b'+' | b'-')) {
+            pos += 1;
+        }
+    }
+    pos
+}
+
+/// Offset following the string literal at the given offset, literals must
+/// not span multiple lines.
+fn string_end(input: &str, start: usize, errors: &mut Vec<LexError>) -> usize {
+    let bytes = input.as_bytes();
+    let quote = bytes[start];
+    let mut pos = start + 1;
+    loop {
+        match bytes.get(pos) {
+            Some(b'\\') => pos += 2,
+            Some(byte) if *byte == quote => return pos + 1,
+            Some(b'\n') | None => {
+                errors.push(LexError {
+                    offset: start,
+                    message: "unterminated string literal",
+                });
+                return pos.min(input.len());
+            }
+            Some(_) => pos += 1,
+        }
+    }
+}
+
+/// Last block of comments in the given whitespace, unless it is separated
+/// from its end by a blank line. Comments on the same line as the preceding
+/// definition belong to that one instead.
+fn leading_comment(space: &str, start_of_file: bool) -> Doc {
+    let mut block: Vec<&str> = Vec::new();
+    let mut newlines = 0;
+    let mut first_line = !start_of_file;
+    let mut rest = space;
+
+    while let Some(chr) = rest.chars().next() {
+        let comment = if let Some(line) = rest.strip_prefix("//") {
+            let end = line.find('\n').unwrap_or(line.len());
+            rest = &line[end..];
+            line[..end].trim_end_matches('\r')
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            let end = comment.find("*/").unwrap_or(comment.len());
+            rest = &comment[(end + 2).min(comment.len())..];
+            &comment[..end]
+        } else {
+            if chr == '\n' {
+                newlines += 1;
+                first_line = false;
+            }
+            rest = &rest[chr.len_utf8()..];
+            continue;
+        };
+
+        if first_line {
+            continue;
+        }
+        if newlines > 1 {
+            block.clear();
+        }
+        block.push(comment);
+        newlines = 0;
+    }
+
+    if block.is_empty() || newlines > 1 {
+        return None;
+    }
+
+    let lines = block
+        .iter()
+        .flat_map(|comment| comment.lines())
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect::<Vec<_>>();
+    let start = lines.iter().position(|line| !line.is_empty())?;
+    let end = lines.iter().rposition(|line| !line.is_empty())?;
+
+    Some(lines[start..=end].join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, Kind};
+
+    #[test]
+    fn tokenize_statement() {
+        let (tokens, errors) =
+            tokenize("\n// doc\noptional .foo.Bar bar_1 = 0x1F [default = -1.5e+3, x = 'a\\'b'];");
+        assert!(errors.is_empty());
+
+        let kinds = tokens
+            .iter()
+            .map(|token| (token.kind, token.text))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                (Kind::Ident, "optional"),
+                (Kind::Punct, "."),
+                (Kind::Ident, "foo"),
+                (Kind::Punct, "."),
+                (Kind::Ident, "Bar"),
+                (Kind::Ident, "bar_1"),
+                (Kind::Punct, "="),
+                (Kind::Number, "0x1F"),
+                (Kind::Punct, "["),
+                (Kind::Ident, "default"),
+                (Kind::Punct, "="),
+                (Kind::Punct, "-"),
+                (Kind::Number, "1.5e+3"),
+                (Kind::Punct, ","),
+                (Kind::Ident, "x"),
+                (Kind::Punct, "="),
+                (Kind::Str, "'a\\'b'"),
+                (Kind::Punct, "]"),
+                (Kind::Punct, ";"),
+                (Kind::Eof, ""),
+            ]
+        );
+        assert_eq!(tokens[0].space, "\n// doc\n");
+        assert_eq!(tokens[0].doc().as_deref(), Some("doc"));
+        assert_eq!(tokens[5].start, 26);
+    }
+
+    #[test]
+    fn tokenize_errors() {
+        let (tokens, errors) = tokenize("a = \"open\nb /* never closed");
+        let texts = tokens.iter().map(|token| token.text).collect::<Vec<_>>();
+        assert_eq!(texts, ["a", "=", "\"open", "b", ""]);
+
+        let errors = errors
+            .iter()
+            .map(|error| (error.offset, error.message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                (4, "unterminated string literal"),
+                (12, "unterminated comment")
+            ]
+        );
+    }
+}
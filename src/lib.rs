@@ -0,0 +1,2270 @@
+use std::io::{Read, Write};
+#[cfg(feature = "cli")]
+use std::process::ExitCode;
+
+#[cfg(any(feature = "cli", feature = "plugin"))]
+use self::errors::PtError;
+
+#[cfg(feature = "api")]
+pub mod api;
+pub mod ast;
+#[cfg(feature = "cli")]
+pub mod buf;
+pub mod bundle;
+pub mod compat;
+pub mod config;
+#[cfg(feature = "plugin")]
+pub mod conformance;
+#[cfg(feature = "payloads")]
+pub mod coverage;
+#[cfg(feature = "plugin")]
+pub mod descriptor;
+#[cfg(feature = "cli")]
+pub mod diff;
+pub mod errors;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fingerprint;
+#[cfg(feature = "cli")]
+pub mod graph;
+pub mod imports;
+mod inflate;
+#[cfg(feature = "interfaces")]
+pub mod interfaces;
+pub mod json;
+#[cfg(feature = "json-schema")]
+pub mod json_schema;
+mod lexer;
+#[cfg(feature = "cli")]
+pub mod lint;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+pub mod parser;
+#[cfg(feature = "payloads")]
+pub mod payload;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+pub mod provider;
+pub mod registry;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "zod")]
+pub mod rename;
+#[cfg(feature = "zod")]
+pub mod repl;
+#[cfg(feature = "payloads")]
+pub mod sample;
+pub mod scaffold;
+#[cfg(any(feature = "effect", feature = "typebox", feature = "yup"))]
+pub mod schemas;
+#[cfg(feature = "plugin")]
+pub mod stages;
+#[cfg(feature = "payloads")]
+pub mod textproto;
+#[cfg(feature = "zod")]
+pub mod typescript;
+#[cfg(feature = "zod")]
+pub mod verify;
+#[cfg(feature = "cli")]
+pub mod watch;
+#[cfg(feature = "plugin")]
+mod wire;
+
+#[derive(Clone, Default)]
+pub struct Opts {
+    pub command: Command,
+    pub file: String,
+    /// local directories or remote sources (see `remote`) to search imports in
+    pub include_paths: Vec<String>,
+    /// directory remote include paths are cached in
+    pub cache_dir: Option<String>,
+    /// fetch remote include paths over plain http as well
+    pub allow_http: bool,
+    /// verbosity level: `-v` for verbose output, `-vv` for parse timings
+    pub verbose: u8,
+    pub emit: Emit,
+    /// version of the JSON AST emitted, the latest if not given (see
+    /// `ast::VERSION`)
+    pub ast_version: Option<u32>,
+    /// flavor of the generated typescript
+    pub target: Target,
+    /// major version of zod the generated schemas are written for
+    pub zod_version: ZodVersion,
+    /// custom rpc options that are surfaced in the generated service metadata
+    pub method_options: Vec<String>,
+    /// generate clients of services
+    pub clients: bool,
+    /// module path generated clients import the runtime helpers from
+    pub runtime_module: String,
+    /// generated clients warn when deprecated methods are called
+    pub deprecation_warnings: bool,
+    /// casing of the methods of generated clients
+    pub method_case: MethodCase,
+    /// put before the names of the methods of generated clients
+    pub method_prefix: String,
+    /// appended to the names of the methods of generated clients
+    pub method_suffix: String,
+    /// emit the full paths (`/package.Service/Method`) of every service
+    pub method_paths: bool,
+    /// emit a route table of every service
+    pub routes: bool,
+    /// register the message schemas for the `google.protobuf.Any` helpers of
+    /// the runtime module
+    pub any_helpers: bool,
+    /// module exporting an `onValidationFailure` hook called on failed parses
+    pub validation_hook: Option<String>,
+    /// do not export nested messages used solely within their parent
+    pub private_nested: bool,
+    /// emit the structural fingerprints of all types as constants
+    pub fingerprints: bool,
+    /// emit the fully-qualified protobuf names of all types as constants
+    pub proto_names: bool,
+    /// emit adapters named like the code of ts-proto delegating to the
+    /// schemas, for codebases migrating from it
+    pub ts_proto_compat: bool,
+    /// generate the few types used of imported files into the importing
+    /// module instead of importing them
+    pub inline_deps: bool,
+    /// license header or banner template put atop generated files
+    pub banner: Option<String>,
+    /// report syntax and generation errors as warnings, skipping invalid
+    /// statements, and emit the types that succeeded
+    pub lenient: bool,
+    /// generate strict objects of messages with proto2 `required` fields
+    pub strict_required: bool,
+    /// check that the types generated for both targets agree in their shapes
+    pub verify_targets: Option<(Target, Target)>,
+    /// check that the model compiled by `protoc` agrees with the parsed one
+    pub conformance: bool,
+    /// run as `protoc` plugin reading a `CodeGeneratorRequest` from stdin
+    pub plugin: bool,
+    /// directory the intermediate stages of the generation are written to
+    pub debug_stages: Option<String>,
+    /// file the output is written to instead of stdout
+    pub out: Option<String>,
+    /// only print the changes of the output file instead of writing it
+    pub diff: bool,
+    /// print the changes of the output file and ask before writing it
+    pub interactive: bool,
+    /// leave output files whose content is unchanged untouched
+    pub skip_unchanged: bool,
+    /// emission style of the generated code (see `--compat`)
+    pub compat: compat::Compat,
+    /// language level of the runtimes the generated code has to run on
+    pub ts_lib_target: TsLibTarget,
+    /// representation of 64 bit integers, see `Opts::int64`
+    pub int64: Option<Int64>,
+    /// representation of bytes fields
+    pub bytes: Bytes,
+    /// declaration of enums
+    pub enum_style: EnumStyle,
+    /// strip the enum name prefix shared by all values from the member names
+    pub strip_enum_prefix: bool,
+    /// base schemas merged into the schemas of matching messages
+    pub base_schemas: Vec<BaseSchema>,
+    /// schemas of well-known types replacing the default mappings
+    pub wkt_schemas: Vec<WktSchema>,
+    /// project configuration (see `config`), loaded from the current
+    /// directory by the command line only
+    pub config: config::Config,
+    /// files the input and imports are read from and the output written to
+    pub files: provider::Files,
+    /// lint rules run in addition to the built-in ones (see `lint`)
+    #[cfg(feature = "cli")]
+    pub lint_rules: lint::Rules,
+    /// warnings and progress collected for the caller to write to stderr
+    pub messages: errors::Messages,
+}
+
+impl Opts {
+    /// Representation of 64 bit integers: the one given via `--int64`,
+    /// otherwise `bigint` unless the language level lacks it.
+    pub fn int64(&self) -> Int64 {
+        self.int64.unwrap_or(match self.ts_lib_target {
+            TsLibTarget::Es2017 => Int64::String,
+            TsLibTarget::Es2020 => Int64::Bigint,
+        })
+    }
+}
+
+/// Subcommand to run, generating code by default.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub enum Command {
+    #[default]
+    Generate,
+    /// dependency graph of files and types
+    Graph(GraphFormat),
+    /// rename a type or field in all proto files of the include paths
+    Rename {
+        from: String,
+        to: String,
+        dry_run: bool,
+    },
+    /// interactive playground generating code of proto snippets
+    Repl,
+    /// create a new proto file from the project template
+    New {
+        kind: scaffold::Kind,
+        package: Option<String>,
+        name: Option<String>,
+    },
+    /// check a text format file against the definition of a message
+    #[cfg(feature = "payloads")]
+    ValidateTextproto { message: String },
+    /// check a JSON payload against the definition of a message
+    #[cfg(feature = "payloads")]
+    ValidateJson { payload: String, message: String },
+    /// print the canonical form of a JSON payload of a message
+    #[cfg(feature = "payloads")]
+    NormalizeJson { payload: String, message: String },
+    /// random JSON payloads of a message
+    #[cfg(feature = "payloads")]
+    Sample {
+        message: String,
+        count: usize,
+        seed: u64,
+        knobs: sample::Knobs,
+        /// wrap the payloads along with the inputs regenerating them
+        envelope: bool,
+    },
+    /// check the file against the enabled lint rules (see `lint`)
+    Lint,
+    /// fields and enum values not exercised by JSON fixtures, given as
+    /// directories and the message type of their fixtures
+    #[cfg(feature = "payloads")]
+    Coverage { fixtures: Vec<(String, String)> },
+    /// regenerate the outputs of all proto files below the input directory
+    /// on changes
+    Watch {
+        out_dir: String,
+        /// milliseconds without further changes before regenerating
+        debounce: u64,
+        /// write the current errors to a typescript module of the outputs
+        error_overlay: bool,
+    },
+    /// generate the outputs of all proto files of the `buf` workspace in the
+    /// input directory (see `buf`)
+    Workspace { out_dir: String },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum GraphFormat {
+    Dot,
+    Json,
+}
+
+/// Kind of output that is generated.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub enum Emit {
+    #[default]
+    Typescript,
+    /// binary encoded `google.protobuf.FileDescriptorSet`
+    #[cfg(feature = "plugin")]
+    Descriptor,
+    /// runtime helper module the generated service clients are based on
+    Runtime,
+    /// AST of the input file as JSON (see `ast`)
+    Ast,
+    /// JSON Schema documents of the messages and enums (see `json_schema`)
+    #[cfg(feature = "json-schema")]
+    JsonSchema,
+    /// OpenAPI document of the services' HTTP bindings (see `openapi`)
+    #[cfg(feature = "openapi")]
+    OpenApi,
+}
+
+/// Flavor of the generated typescript (see `--target`).
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum Target {
+    /// zod schemas along with the types inferred from them
+    #[default]
+    Zod,
+    /// plain interfaces and string literal unions without runtime dependency
+    /// (see `interfaces`)
+    Interfaces,
+    /// schemas of Effect Schema (see `schemas::EFFECT`)
+    Effect,
+    /// JSON schemas built with TypeBox (see `schemas::TYPEBOX`)
+    TypeBox,
+    /// schemas of yup (see `schemas::YUP`)
+    Yup,
+}
+
+impl Target {
+    /// All targets enabled in this build (see the cargo features of the same
+    /// name) by their name given via `--target`.
+    pub const ALL: &'static [(&'static str, Target)] = &[
+        ("zod", Target::Zod),
+        #[cfg(feature = "interfaces")]
+        ("interfaces", Target::Interfaces),
+        #[cfg(feature = "effect")]
+        ("effect", Target::Effect),
+        #[cfg(feature = "typebox")]
+        ("typebox", Target::TypeBox),
+        #[cfg(feature = "yup")]
+        ("yup", Target::Yup),
+    ];
+
+    pub fn parse(name: &str) -> Option<Target> {
+        Target::ALL
+            .iter()
+            .find_map(|(target_name, target)| (*target_name == name).then_some(*target))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Zod => "zod",
+            Target::Interfaces => "interfaces",
+            Target::Effect => "effect",
+            Target::TypeBox => "typebox",
+            Target::Yup => "yup",
+        }
+    }
+}
+
+/// Major version of zod the schemas are generated for (see `--zod-version`).
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum ZodVersion {
+    #[default]
+    V3,
+    /// `z.enum` instead of `z.nativeEnum`, spreading base schemas instead of
+    /// `.merge()` and `z.strictObject` instead of `.strict()`
+    V4,
+}
+
+/// Casing of the methods of generated clients (see `--method-case`), derived
+/// from the PascalCase rpc names.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum MethodCase {
+    /// `getUser`
+    #[default]
+    Camel,
+    /// `GetUser`, the rpc name as declared
+    Pascal,
+    /// `get_user`
+    Snake,
+}
+
+/// Representation of 64 bit integers in the generated code (see `--int64`).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Int64 {
+    /// parsed from the decimal strings (or numbers) of JSON
+    Bigint,
+    /// decimal strings like in the proto3 JSON mapping
+    String,
+    /// numbers, exact up to 2^53 only
+    Number,
+}
+
+/// Representation of bytes in the generated code (see `--bytes`).
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum Bytes {
+    /// base64 strings like in the proto3 JSON mapping, validated as such
+    #[default]
+    Base64,
+    /// `Uint8Array`, decoded from the base64 strings of JSON
+    Uint8Array,
+}
+
+/// Declaration of enums in the generated code (see `--enum-style`).
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum EnumStyle {
+    /// TypeScript `enum` with the value names as values
+    #[default]
+    NativeEnum,
+    /// `as const` object and a type of its values
+    ConstObject,
+    /// `z.enum` of the value names and its inferred string union
+    StringUnion,
+    /// TypeScript `enum` with the numbers as values, as decoded from the
+    /// binary wire format
+    Numeric,
+}
+
+/// Language level the generated code targets (see `--ts-lib-target`).
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum TsLibTarget {
+    /// ES2017 up to ES2019: no `bigint`, optional chaining or nullish
+    /// coalescing, 64 bit integers are represented as decimal strings
+    Es2017,
+    #[default]
+    Es2020,
+}
+
+/// Schema exported by a user module that the schemas of all messages
+/// matching the pattern are merged into (see `--base-schema`).
+#[derive(Clone, PartialEq, Debug)]
+pub struct BaseSchema {
+    /// message name, or fully-qualified name if containing a dot, `*`
+    /// matches any number of characters
+    pub pattern: String,
+    pub module: String,
+    pub schema: String,
+}
+
+impl BaseSchema {
+    /// Parse a base schema given as `<PATTERN>=<MODULE>#<SCHEMA>`, e.g.
+    /// `*Event=@/events#BaseEventSchema`.
+    pub fn parse(value: &str) -> Result<BaseSchema, errors::PtError> {
+        let invalid = || {
+            errors::PtError::InvalidArgument(format!(
+                "invalid base schema (expected <PATTERN>=<MODULE>#<SCHEMA>): {}",
+                value
+            ))
+        };
+        let (pattern, target) = value.split_once('=').ok_or_else(invalid)?;
+        let (module, schema) = target.rsplit_once('#').ok_or_else(invalid)?;
+        let (pattern, module, schema) = (pattern.trim(), module.trim(), schema.trim());
+        if pattern.is_empty() || module.is_empty() || schema.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(BaseSchema {
+            pattern: pattern.to_string(),
+            module: module.to_string(),
+            schema: schema.to_string(),
+        })
+    }
+
+    /// Whether the message of the given name and fully-qualified name
+    /// matches the pattern.
+    pub fn matches(&self, name: &str, full_name: &str) -> bool {
+        if self.pattern.contains('.') {
+            wildcard_match(&self.pattern, full_name)
+        } else {
+            wildcard_match(&self.pattern, name)
+        }
+    }
+}
+
+/// Schema fields of a well-known type are generated with instead of its
+/// default mapping (see `--wkt-schema`).
+#[derive(Clone, PartialEq, Debug)]
+pub struct WktSchema {
+    /// fully-qualified name, e.g. `google.protobuf.Duration`
+    pub name: String,
+    /// module the schema is imported from, expressions like `z.string()` are
+    /// used as they are
+    pub module: Option<String>,
+    pub schema: String,
+}
+
+impl WktSchema {
+    /// Parse a schema given as `<TYPE>=<MODULE>#<SCHEMA>` or as zod
+    /// expression `<TYPE>=z.<...>`, e.g. `Duration=@/time#DurationSchema`.
+    /// Types are in the `google.protobuf` package unless qualified.
+    pub fn parse(value: &str) -> Result<WktSchema, errors::PtError> {
+        let invalid = |reason: &str| {
+            errors::PtError::InvalidArgument(format!(
+                "invalid well-known type schema ({}): {}",
+                reason, value
+            ))
+        };
+        let expected = "expected <TYPE>=<MODULE>#<SCHEMA> or <TYPE>=z.<...>";
+        let (name, target) = value.split_once('=').ok_or_else(|| invalid(expected))?;
+        let (name, target) = (name.trim(), target.trim());
+        let name = match name.contains('.') {
+            true => name.to_string(),
+            false => format!("google.protobuf.{}", name),
+        };
+        if json::well_known(&name).is_none() {
+            return Err(invalid("no well-known type with a special JSON mapping"));
+        }
+
+        let (module, schema) = match target.rsplit_once('#') {
+            _ if target.starts_with("z.") => (None, target),
+            Some((module, schema)) => (Some(module.trim().to_string()), schema.trim()),
+            None => return Err(invalid(expected)),
+        };
+        if schema.is_empty() || module.as_ref().is_some_and(String::is_empty) {
+            return Err(invalid(expected));
+        }
+
+        Ok(WktSchema {
+            name,
+            module,
+            schema: schema.to_string(),
+        })
+    }
+}
+
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|&idx| text.is_char_boundary(idx))
+                .any(|idx| wildcard_match(rest, &text[idx..]))
+        }
+    }
+}
+
+/// Streams a `run` reads from and writes to.
+pub struct Io<'a> {
+    pub stdin: &'a mut dyn Read,
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+    /// whether stdout is a terminal, so the output may be colorized
+    pub color: bool,
+    pub files: provider::Files,
+    /// lint rules of the library consumer run by the `lint` command
+    #[cfg(feature = "cli")]
+    pub lint_rules: lint::Rules,
+}
+
+#[cfg(feature = "cli")]
+fn read(opts: &Opts, input_file: &str) -> Result<String, PtError> {
+    let path = std::path::Path::new(input_file);
+    if !opts.files.exists(path) {
+        return Err(PtError::FileNotFound(input_file.to_owned()));
+    }
+
+    opts.files.read_to_string(path)
+}
+
+#[cfg(feature = "cli")]
+const USAGE: &str = "\
+{program} <FILE> [OPTIONS]
+{program} graph <FILE> [--format dot|json] [-I <DIR>]
+{program} rename <OLD> <NEW> [--dry-run] [-I <DIR>]
+{program} repl
+{program} textproto validate <FILE> --message <NAME> [-I <DIR>]
+{program} validate-json <PAYLOAD> --proto <FILE> --message <NAME> [-I <DIR>]
+{program} normalize-json <PAYLOAD> --proto <FILE> --message <NAME> [-I <DIR>]
+{program} sample --proto <FILE> --message <NAME> [--count <N>] [--seed <N>]
+        [--max-items <N>] [--max-len <N>] [--envelope] [--out <FILE>] [-I <DIR>]
+{program} coverage --proto <FILE> --fixtures <DIR>=<NAME>... [-I <DIR>]
+{program} lint <FILE> [-I <DIR>]
+{program} new <message|enum|service> <FILE> [--package <PKG>] [--<KIND> <NAME>]
+{program} watch <DIR> --out-dir <DIR> [--debounce <MS>] [--error-overlay] [-I <DIR>]
+{program} generate [<DIR>] --out-dir <DIR> [-I <DIR>]
+
+OPTIONS:
+  -I <DIR>               add directory to the import search path, may be
+                         a https URL or a git repository pinned to a ref
+                         (git+https://host/repo.git#<REF>[:<SUBDIR>])
+                         or a .zip/.tar.gz archive read like a directory
+  --cache-dir <DIR>      directory remote imports are cached in
+  --allow-http           allow remote imports over plain http, which are
+                         rejected otherwise
+  --emit <KIND>          output to generate: ts (default), descriptor,
+                         runtime (helper module of generated clients),
+                         ast (JSON of the syntax tree of the input file),
+                         json-schema (a JSON Schema of every message and
+                         enum, written to the directory given by --out)
+                         or openapi (OpenAPI 3.1 document of the methods
+                         with google.api.http bindings)
+  --ast-version <N>      version of the shape of the JSON AST emitted,
+                         1 or 2 (default: latest)
+  --target <TARGET>      flavor of the generated typescript: zod (default),
+                         interfaces (plain types without runtime
+                         dependency), effect (Effect Schema), typebox or
+                         yup; services are generated for zod only
+  --zod-version <N>      major version of zod the schemas are generated
+                         for, 3 (default) or 4
+  --method-option <NAME> include custom rpc option in service metadata
+  --clients              generate service clients
+  --runtime-module <PATH>
+                         module the generated clients import the runtime
+                         helpers from (default: ./protots-runtime)
+  --deprecation-warnings warn on calls of deprecated methods in
+                         non-production builds of the generated clients
+  --method-case <CASE>   casing of the methods of generated clients:
+                         camel (default), pascal or snake
+  --method-prefix <PREFIX>
+                         put before the method names of generated clients
+  --method-suffix <SUFFIX>
+                         append to the method names of generated clients
+  --method-paths         emit the full paths (/package.Service/Method) of
+                         the methods of every service as `<SERVICE>Paths`
+  --routes               emit the path, schemas and streaming flags of the
+                         methods of every service as `<SERVICE>Routes`
+  --any-helpers          register the message schemas in the runtime module
+                         for its `packAny` and `unpackAny` helpers of
+                         `google.protobuf.Any` payloads
+  --validation-hook <MODULE>
+                         report failed parses of message schemas to the
+                         `onValidationFailure(typeName, issues)` function
+                         exported by the given module
+  --base-schema <PATTERN>=<MODULE>#<SCHEMA>
+                         merge the schemas of the messages matching the
+                         pattern (e.g. *Event) into the given schema of a
+                         user module, may be given multiple times
+                         (default: `base_schema` of .prototsrc)
+  --wkt-schema <TYPE>=<MODULE>#<SCHEMA>
+                         generate fields of the given well-known type
+                         (e.g. Duration) with the schema of a user module
+                         or a zod expression (<TYPE>=z.string()) instead
+                         of the default mapping, may be given multiple
+                         times (default: `wkt_schema` of .prototsrc)
+  --private-nested       do not export the schemas of nested messages that
+                         are used solely as field types of their parent
+  --fingerprints         emit the structural fingerprint of every type
+                         as `<TYPE>Fingerprint` constant
+  --proto-names          emit the fully-qualified protobuf name of every
+                         type as `<TYPE>ProtoName` constant
+  --ts-proto-compat      emit adapters named like the code of ts-proto,
+                         e.g. `User.fromJSON` and `colorToJSON`, that
+                         delegate to the schemas (zod target)
+  --inline-deps          declare the types used of imported files that
+                         contribute at most two of them in the generated
+                         module instead of importing them (zod target)
+  --banner <FILE>        put the license header or banner of the given file
+                         atop generated files, `{year}` and `{file}` are
+                         replaced (default: `banner` of .prototsrc)
+  --lenient              report syntax errors and errors of the code
+                         generation as warnings, skipping invalid statements,
+                         and still emit the types that could be generated
+  --strict-required      generate messages with proto2 `required` fields as
+                         strict objects rejecting unknown keys
+  --verify-targets <A>,<B>
+                         generate the typescript of both targets (e.g.
+                         zod,interfaces) and check that the shapes of all
+                         types agree: fields, optionality, enum values
+  --conformance          compile the input file with the protoc found on
+                         the PATH and check that names, numbers, labels
+                         and nesting of all definitions agree
+  --debug-stages <DIR>   write the tokens, AST, resolved model and outputs
+                         of the generators to the given directory, e.g.
+                         to attach them to bug reports
+  --out <FILE>           write the output to the given file
+  --diff                 print the changes of the output file instead of
+                         writing it
+  --interactive          print the changes of the output file and confirm
+                         before writing it
+  --skip-unchanged       do not write output files whose content did not
+                         change, keeping their modification time
+  --compat <VERSION>     generate code the way the given protots version
+                         did, e.g. 0.1
+  --ts-lib-target <ES>   language level of the targeted runtimes, es2017
+                         to es2019 represent 64 bit integers as strings
+                         instead of bigint (default: es2020)
+  --int64 <TYPE>         representation of 64 bit integers: bigint
+                         (default), string (decimal strings like in JSON)
+                         or number (exact up to 2^53 only)
+  --bytes <TYPE>         representation of bytes: base64 (default,
+                         validated base64 strings like in JSON) or
+                         uint8array (decoded from base64)
+  --enum-style <STYLE>   declaration of enums: native-enum (default),
+                         const-object, string-union or numeric (values
+                         as in the binary wire format)
+  --strip-enum-prefix    strip the prefix of the enum name shared by all
+                         values from the member names, e.g. RED instead
+                         of COLOR_RED, keeping the names as values
+  --plugin               run as protoc plugin (implied when invoked as
+                         protoc-gen-protots)
+  -v                     verbose output
+  -vv                    verbose output including parse timings
+";
+
+#[cfg(feature = "cli")]
+fn usage(program: &str, out: &mut dyn Write) -> std::io::Result<()> {
+    write!(out, "{}", USAGE.replace("{program}", program))
+}
+
+#[cfg(any(feature = "cli", feature = "plugin"))]
+fn has_arg(args: &mut Vec<String>, opt: &str) -> bool {
+    if let Some(idx) = args.iter().position(|val| val == opt) {
+        args.remove(idx);
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(any(feature = "cli", feature = "plugin"))]
+fn arg_value(args: &mut Vec<String>, opt: &str) -> Result<Option<String>, PtError> {
+    match args.iter().position(|val| val == opt) {
+        Some(idx) if idx + 1 < args.len() => {
+            args.remove(idx);
+            Ok(Some(args.remove(idx)))
+        }
+        Some(_) => Err(PtError::InvalidArgument(format!(
+            "missing value for {}",
+            opt
+        ))),
+        None => Ok(None),
+    }
+}
+
+#[cfg(any(feature = "cli", feature = "plugin"))]
+fn arg_values(args: &mut Vec<String>, opt: &str) -> Result<Vec<String>, PtError> {
+    let mut values = Vec::new();
+    while let Some(value) = arg_value(args, opt)? {
+        values.push(value);
+    }
+    Ok(values)
+}
+
+#[cfg(any(feature = "cli", feature = "plugin"))]
+fn arg_number(args: &mut Vec<String>, opt: &str) -> Result<Option<u64>, PtError> {
+    arg_value(args, opt)?
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| PtError::InvalidArgument(format!("invalid {} value: {}", opt, value)))
+        })
+        .transpose()
+}
+
+#[cfg(any(feature = "cli", feature = "plugin"))]
+fn command(args: &mut Vec<String>) -> Result<Command, PtError> {
+    match args.get(1).map(String::as_str) {
+        Some("graph") => {
+            args.remove(1);
+            let format = match arg_value(args, "--format")?.as_deref() {
+                None | Some("dot") => GraphFormat::Dot,
+                Some("json") => GraphFormat::Json,
+                Some(other) => {
+                    return Err(PtError::InvalidArgument(format!(
+                        "unknown --format value: {}",
+                        other
+                    )))
+                }
+            };
+            Ok(Command::Graph(format))
+        }
+        Some("rename") => {
+            args.remove(1);
+            Ok(Command::Rename {
+                from: String::new(),
+                to: String::new(),
+                dry_run: has_arg(args, "--dry-run"),
+            })
+        }
+        Some("new") if args.len() > 2 => {
+            args.remove(1);
+            let kind = args.remove(1);
+            Ok(Command::New {
+                package: arg_value(args, "--package")?,
+                name: arg_value(args, &format!("--{}", kind))?,
+                kind: scaffold::Kind::parse(&kind)?,
+            })
+        }
+        #[cfg(feature = "payloads")]
+        Some("textproto") => {
+            args.remove(1);
+            match args.get(1).map(String::as_str) {
+                Some("validate") => args.remove(1),
+                other => {
+                    return Err(PtError::InvalidArgument(format!(
+                        "unknown textproto command: {}",
+                        other.unwrap_or("")
+                    )))
+                }
+            };
+            let message = arg_value(args, "--message")?.ok_or_else(|| {
+                PtError::InvalidArgument("missing --message of textproto validate".to_string())
+            })?;
+            Ok(Command::ValidateTextproto { message })
+        }
+        #[cfg(feature = "payloads")]
+        Some(name @ ("validate-json" | "normalize-json")) => {
+            let name = name.to_string();
+            args.remove(1);
+            let missing =
+                |arg: &str| PtError::InvalidArgument(format!("missing {} of {}", arg, name));
+            let proto = arg_value(args, "--proto")?.ok_or_else(|| missing("--proto"))?;
+            let message = arg_value(args, "--message")?.ok_or_else(|| missing("--message"))?;
+            // the proto file is the input file, followed by the payload
+            args.insert(1, proto);
+            let payload = String::new();
+            Ok(if name == "validate-json" {
+                Command::ValidateJson { payload, message }
+            } else {
+                Command::NormalizeJson { payload, message }
+            })
+        }
+        #[cfg(feature = "payloads")]
+        Some("sample") => {
+            args.remove(1);
+            let missing =
+                |arg: &str| PtError::InvalidArgument(format!("missing {} of sample", arg));
+            let proto = arg_value(args, "--proto")?.ok_or_else(|| missing("--proto"))?;
+            let message = arg_value(args, "--message")?.ok_or_else(|| missing("--message"))?;
+            args.insert(1, proto);
+
+            let defaults = sample::Knobs::default();
+            let seed = match arg_number(args, "--seed")? {
+                Some(seed) => seed,
+                None => std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |time| time.as_nanos() as u64),
+            };
+            Ok(Command::Sample {
+                message,
+                count: arg_number(args, "--count")?.unwrap_or(1) as usize,
+                seed,
+                knobs: sample::Knobs {
+                    max_items: arg_number(args, "--max-items")?
+                        .map_or(defaults.max_items, |value| value as usize),
+                    max_len: arg_number(args, "--max-len")?
+                        .map_or(defaults.max_len, |value| value as usize),
+                },
+                envelope: has_arg(args, "--envelope"),
+            })
+        }
+        #[cfg(feature = "payloads")]
+        Some("coverage") => {
+            args.remove(1);
+            let proto = arg_value(args, "--proto")?.ok_or_else(|| {
+                PtError::InvalidArgument("missing --proto of coverage".to_string())
+            })?;
+            let fixtures = arg_values(args, "--fixtures")?
+                .into_iter()
+                .map(|value| match value.split_once('=') {
+                    Some((dir, message)) => Ok((dir.to_string(), message.to_string())),
+                    None => Err(PtError::InvalidArgument(format!(
+                        "expected --fixtures <DIR>=<NAME>, got {}",
+                        value
+                    ))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if fixtures.is_empty() {
+                return Err(PtError::InvalidArgument(
+                    "missing --fixtures of coverage".to_string(),
+                ));
+            }
+            args.insert(1, proto);
+            Ok(Command::Coverage { fixtures })
+        }
+        Some("repl") => {
+            args.remove(1);
+            Ok(Command::Repl)
+        }
+        Some("lint") => {
+            args.remove(1);
+            Ok(Command::Lint)
+        }
+        Some("watch") => {
+            args.remove(1);
+            let out_dir = arg_value(args, "--out-dir")?.ok_or_else(|| {
+                PtError::InvalidArgument("missing --out-dir of watch".to_string())
+            })?;
+            Ok(Command::Watch {
+                out_dir,
+                debounce: arg_number(args, "--debounce")?.unwrap_or(200),
+                error_overlay: has_arg(args, "--error-overlay"),
+            })
+        }
+        Some("generate") => {
+            args.remove(1);
+            // a single file is generated like without the command
+            Ok(match arg_value(args, "--out-dir")? {
+                Some(out_dir) => Command::Workspace { out_dir },
+                None => Command::Generate,
+            })
+        }
+        _ => Ok(Command::Generate),
+    }
+}
+
+#[cfg(any(feature = "cli", feature = "plugin"))]
+fn opts(
+    mut args: Vec<String>,
+    files: &provider::Files,
+    config: &config::Config,
+) -> Result<Option<Opts>, PtError> {
+    let invoked_as_plugin = args
+        .first()
+        .and_then(|program| std::path::Path::new(program).file_name())
+        .is_some_and(|name| name.to_string_lossy().starts_with("protoc-gen-"));
+    let plugin = has_arg(&mut args, "--plugin") || invoked_as_plugin;
+
+    let mut command = command(&mut args)?;
+
+    let verbose = if has_arg(&mut args, "-vv") {
+        2
+    } else {
+        has_arg(&mut args, "-v") as u8
+    };
+
+    let include_paths = arg_values(&mut args, "-I")?;
+    let cache_dir = arg_value(&mut args, "--cache-dir")?;
+    let allow_http = has_arg(&mut args, "--allow-http");
+    let method_options = arg_values(&mut args, "--method-option")?;
+
+    let clients = has_arg(&mut args, "--clients");
+    let deprecation_warnings = has_arg(&mut args, "--deprecation-warnings");
+    let method_case = match arg_value(&mut args, "--method-case")?.as_deref() {
+        None | Some("camel") => MethodCase::Camel,
+        Some("pascal") => MethodCase::Pascal,
+        Some("snake") => MethodCase::Snake,
+        Some(other) => {
+            return Err(PtError::InvalidArgument(format!(
+                "unknown --method-case {}, supported are camel, pascal and snake",
+                other
+            )))
+        }
+    };
+    let method_prefix = arg_value(&mut args, "--method-prefix")?.unwrap_or_default();
+    let method_suffix = arg_value(&mut args, "--method-suffix")?.unwrap_or_default();
+    let method_paths = has_arg(&mut args, "--method-paths");
+    let routes = has_arg(&mut args, "--routes");
+    let any_helpers = has_arg(&mut args, "--any-helpers");
+    let validation_hook = arg_value(&mut args, "--validation-hook")?;
+    let private_nested = has_arg(&mut args, "--private-nested");
+    let fingerprints = has_arg(&mut args, "--fingerprints");
+    let proto_names = has_arg(&mut args, "--proto-names");
+    let ts_proto_compat = has_arg(&mut args, "--ts-proto-compat");
+    let inline_deps = has_arg(&mut args, "--inline-deps");
+    let lenient = has_arg(&mut args, "--lenient");
+    let strict_required = has_arg(&mut args, "--strict-required");
+    let verify_targets = match arg_value(&mut args, "--verify-targets")? {
+        Some(targets) => {
+            let pair = targets.split_once(',').and_then(|(first, second)| {
+                Some((Target::parse(first.trim())?, Target::parse(second.trim())?))
+            });
+            Some(pair.ok_or_else(|| {
+                PtError::InvalidArgument(format!("invalid --verify-targets value: {}", targets))
+            })?)
+        }
+        None => None,
+    };
+    let conformance = has_arg(&mut args, "--conformance");
+    let banner = match arg_value(&mut args, "--banner")? {
+        Some(path) => Some(files.read_to_string(std::path::Path::new(&path))?),
+        None => config.banner(&**files)?,
+    };
+    // the ones given on the command line take precedence over the config
+    let base_schemas = arg_values(&mut args, "--base-schema")?
+        .iter()
+        .map(String::as_str)
+        .chain(config.get_all("base_schema"))
+        .map(BaseSchema::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    let wkt_schemas = arg_values(&mut args, "--wkt-schema")?
+        .iter()
+        .map(String::as_str)
+        .chain(config.get_all("wkt_schema"))
+        .map(WktSchema::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    let runtime_module = arg_value(&mut args, "--runtime-module")?
+        .unwrap_or_else(|| "./protots-runtime".to_string());
+
+    let compat = match arg_value(&mut args, "--compat")? {
+        Some(version) => compat::Compat::parse(&version)?,
+        None => compat::Compat::default(),
+    };
+
+    let target = match arg_value(&mut args, "--target")? {
+        None => Target::Zod,
+        Some(name) => Target::parse(&name)
+            .ok_or_else(|| PtError::InvalidArgument(format!("unknown --target value: {}", name)))?,
+    };
+    if target != Target::Zod && (clients || any_helpers || routes || ts_proto_compat) {
+        return Err(PtError::InvalidArgument(
+            "--clients, --any-helpers, --routes and --ts-proto-compat require the zod target"
+                .to_string(),
+        ));
+    }
+
+    let zod_version = match arg_value(&mut args, "--zod-version")?.as_deref() {
+        None | Some("3") => ZodVersion::V3,
+        Some("4") => ZodVersion::V4,
+        Some(other) => {
+            return Err(PtError::InvalidArgument(format!(
+                "unsupported --zod-version {}, supported are 3 and 4",
+                other
+            )))
+        }
+    };
+
+    let ts_lib_target = match arg_value(&mut args, "--ts-lib-target")?.as_deref() {
+        None => TsLibTarget::default(),
+        Some("es2017" | "es2018" | "es2019") => TsLibTarget::Es2017,
+        Some("es2020" | "es2021" | "es2022" | "es2023" | "esnext") => TsLibTarget::Es2020,
+        Some(other) => {
+            return Err(PtError::InvalidArgument(format!(
+                "unknown --ts-lib-target value: {}",
+                other
+            )))
+        }
+    };
+
+    let int64 = match arg_value(&mut args, "--int64")?.as_deref() {
+        None => None,
+        Some("bigint") if ts_lib_target == TsLibTarget::Es2017 => {
+            return Err(PtError::InvalidArgument(
+                "--int64 bigint requires --ts-lib-target es2020 or later".to_string(),
+            ))
+        }
+        Some("bigint") => Some(Int64::Bigint),
+        Some("string") => Some(Int64::String),
+        Some("number") => Some(Int64::Number),
+        Some(other) => {
+            return Err(PtError::InvalidArgument(format!(
+                "unknown --int64 {}, supported are bigint, string and number",
+                other
+            )))
+        }
+    };
+
+    let bytes = match arg_value(&mut args, "--bytes")?.as_deref() {
+        None | Some("base64") => Bytes::Base64,
+        Some("uint8array") => Bytes::Uint8Array,
+        Some(other) => {
+            return Err(PtError::InvalidArgument(format!(
+                "unknown --bytes {}, supported are base64 and uint8array",
+                other
+            )))
+        }
+    };
+    if bytes == Bytes::Uint8Array && !matches!(target, Target::Zod | Target::Interfaces) {
+        return Err(PtError::InvalidArgument(
+            "--bytes uint8array requires the zod or interfaces target".to_string(),
+        ));
+    }
+
+    let enum_style = match arg_value(&mut args, "--enum-style")?.as_deref() {
+        None | Some("native-enum") => EnumStyle::NativeEnum,
+        Some("const-object") => EnumStyle::ConstObject,
+        Some("string-union") => EnumStyle::StringUnion,
+        Some("numeric") => EnumStyle::Numeric,
+        Some(other) => {
+            return Err(PtError::InvalidArgument(format!(
+                "unknown --enum-style {}, supported are native-enum, const-object, string-union and numeric",
+                other
+            )))
+        }
+    };
+    let strip_enum_prefix = has_arg(&mut args, "--strip-enum-prefix");
+    if (enum_style != EnumStyle::NativeEnum || strip_enum_prefix) && target != Target::Zod {
+        return Err(PtError::InvalidArgument(
+            "--enum-style and --strip-enum-prefix require the zod target".to_string(),
+        ));
+    }
+
+    let debug_stages = arg_value(&mut args, "--debug-stages")?;
+    let out = arg_value(&mut args, "--out")?;
+    let diff = has_arg(&mut args, "--diff");
+    let interactive = has_arg(&mut args, "--interactive");
+    let skip_unchanged = has_arg(&mut args, "--skip-unchanged");
+    if (diff || interactive) && out.is_none() {
+        return Err(PtError::InvalidArgument(
+            "--diff and --interactive require --out".to_string(),
+        ));
+    }
+
+    let emit = match arg_value(&mut args, "--emit")?.as_deref() {
+        None | Some("ts") => Emit::Typescript,
+        Some("descriptor") => Emit::Descriptor,
+        Some("runtime") => Emit::Runtime,
+        Some("ast") => Emit::Ast,
+        #[cfg(feature = "json-schema")]
+        Some("json-schema") => Emit::JsonSchema,
+        #[cfg(feature = "openapi")]
+        Some("openapi") => Emit::OpenApi,
+        Some(other) => {
+            return Err(PtError::InvalidArgument(format!(
+                "unknown --emit value: {}",
+                other
+            )))
+        }
+    };
+
+    let ast_version = match arg_number(&mut args, "--ast-version")? {
+        None => None,
+        Some(version) if (1..=ast::VERSION as u64).contains(&version) => Some(version as u32),
+        Some(version) => {
+            return Err(PtError::InvalidArgument(format!(
+                "unsupported --ast-version {}, supported are 1 to {}",
+                version,
+                ast::VERSION
+            )))
+        }
+    };
+
+    if let Command::Rename { from, to, .. } = &mut command {
+        if args.len() != 3 {
+            return Ok(None);
+        }
+        *to = args.remove(2);
+        *from = args.remove(1);
+    }
+
+    #[cfg(feature = "payloads")]
+    if let Command::ValidateJson { payload, .. } | Command::NormalizeJson { payload, .. } =
+        &mut command
+    {
+        if args.len() != 3 {
+            return Ok(None);
+        }
+        *payload = args.remove(2);
+    }
+
+    // the runtime module does not depend on any input and plugins receive
+    // their input on stdin
+    let needs_file = match command {
+        Command::Generate => emit != Emit::Runtime && !plugin,
+        Command::Graph(_) | Command::New { .. } | Command::Lint | Command::Watch { .. } => true,
+        #[cfg(feature = "payloads")]
+        Command::ValidateTextproto { .. }
+        | Command::ValidateJson { .. }
+        | Command::NormalizeJson { .. }
+        | Command::Sample { .. }
+        | Command::Coverage { .. } => true,
+        Command::Rename { .. } | Command::Repl | Command::Workspace { .. } => false,
+    };
+    if needs_file && args.len() < 2 {
+        return Ok(None);
+    }
+
+    Ok(Some(Opts {
+        command,
+        file: args.get(1).cloned().unwrap_or_default(),
+        include_paths,
+        cache_dir,
+        allow_http,
+        verbose,
+        emit,
+        ast_version,
+        target,
+        zod_version,
+        method_options,
+        clients,
+        runtime_module,
+        deprecation_warnings,
+        method_case,
+        method_prefix,
+        method_suffix,
+        method_paths,
+        routes,
+        any_helpers,
+        validation_hook,
+        private_nested,
+        fingerprints,
+        proto_names,
+        ts_proto_compat,
+        inline_deps,
+        banner,
+        lenient,
+        strict_required,
+        verify_targets,
+        conformance,
+        plugin,
+        debug_stages,
+        out,
+        diff,
+        interactive,
+        skip_unchanged,
+        compat,
+        ts_lib_target,
+        int64,
+        bytes,
+        enum_style,
+        strip_enum_prefix,
+        base_schemas,
+        wkt_schemas,
+        config: config.clone(),
+        files: files.clone(),
+        #[cfg(feature = "cli")]
+        lint_rules: lint::Rules::default(),
+        messages: errors::Messages::default(),
+    }))
+}
+
+#[cfg(feature = "cli")]
+/// Parse the input file and all of its imports.
+fn load(opts: &Opts) -> Result<(parser::Proto, Vec<parser::Proto>), PtError> {
+    let input = read(opts, &opts.file)?;
+    let proto = parser::parse(opts, &imports::import_name(opts), &input)?;
+    let deps = imports::resolve(opts, &proto)?;
+
+    Ok((proto, deps))
+}
+
+#[cfg(feature = "cli")]
+/// Project directories of commands working on all proto files: the include
+/// paths or the current directory.
+fn roots(opts: &Opts) -> Vec<String> {
+    if opts.include_paths.is_empty() {
+        vec![".".to_string()]
+    } else {
+        opts.include_paths.clone()
+    }
+}
+
+#[cfg(feature = "cli")]
+fn process(opts: &Opts, io: &mut Io) -> Result<(), PtError> {
+    if opts.plugin {
+        return plugin::run(io, &opts.config);
+    }
+
+    if let Command::Rename { from, to, dry_run } = &opts.command {
+        let result = rename::rename(opts, &roots(opts), from, to)?;
+
+        for (path, content, count) in &result.files {
+            if !dry_run {
+                opts.files.write(path, content.as_bytes())?;
+            }
+            writeln!(io.stdout, "{}: {} occurrence(s)", path.display(), count)?;
+        }
+        for (old, new) in &result.identifiers {
+            writeln!(io.stdout, "{} -> {}", old, new)?;
+        }
+        return Ok(());
+    }
+
+    if let Command::ValidateTextproto { message } = &opts.command {
+        let mut protos = Vec::new();
+        for root in roots(opts) {
+            let mut paths = Vec::new();
+            imports::proto_files(opts, std::path::Path::new(&root), &mut paths)?;
+            for path in paths {
+                // named the way they are imported, relative to their root
+                let name = path.strip_prefix(&root).unwrap_or(&path);
+                let content = opts.files.read_to_string(&path)?;
+                let name = name.to_string_lossy().replace('\\', "/");
+                protos.push(parser::parse(opts, &name, &content)?);
+            }
+        }
+
+        let fields = textproto::parse(&opts.file, &read(opts, &opts.file)?)?;
+        let issues = textproto::validate(&protos, message, &fields)?;
+        for issue in &issues {
+            writeln!(io.stderr, "{}:{}: {}", opts.file, issue.line, issue.message)?;
+        }
+        if !issues.is_empty() {
+            return Err(PtError::InvalidTextproto(format!(
+                "{} issue(s) in {}",
+                issues.len(),
+                opts.file
+            )));
+        }
+        return Ok(());
+    }
+
+    if let Command::ValidateJson { payload, message } = &opts.command {
+        let (proto, mut protos) = load(opts)?;
+        protos.push(proto);
+
+        let json = json::parse(&read(opts, payload)?)
+            .map_err(|err| PtError::InvalidJson(format!("{}: {}", payload, err)))?;
+        let issues = payload::validate(&protos, message, &json)?;
+        for issue in &issues {
+            writeln!(io.stderr, "{}: {}: {}", payload, issue.path, issue.message)?;
+        }
+        if !issues.is_empty() {
+            return Err(PtError::InvalidJson(format!(
+                "{} issue(s) in {}",
+                issues.len(),
+                payload
+            )));
+        }
+        return Ok(());
+    }
+
+    if let Command::NormalizeJson { payload, message } = &opts.command {
+        let (proto, mut protos) = load(opts)?;
+        protos.push(proto);
+
+        let json = json::parse(&read(opts, payload)?)
+            .map_err(|err| PtError::InvalidJson(format!("{}: {}", payload, err)))?;
+        writeln!(
+            io.stdout,
+            "{}",
+            payload::normalize(&protos, message, &json)?
+        )?;
+        return Ok(());
+    }
+
+    if let Command::Sample {
+        message,
+        count,
+        seed,
+        knobs,
+        envelope,
+    } = &opts.command
+    {
+        let (proto, mut protos) = load(opts)?;
+        protos.push(proto);
+
+        let payloads = sample::sample(&protos, message, *count, *seed, *knobs)?;
+        let mut output = String::new();
+        if *envelope {
+            let envelope = sample::Envelope {
+                proto: &opts.file,
+                include_paths: &opts.include_paths,
+                message,
+                count: *count,
+                seed: *seed,
+                knobs: *knobs,
+                out: opts.out.as_deref(),
+            };
+            output.push_str(&format!("{}\n", envelope.wrap(payloads)));
+        } else {
+            // reported so that runs without `--seed` can be reproduced, the
+            // envelope records it otherwise
+            opts.messages.push(format!("seed: {}", seed));
+            for json in payloads {
+                output.push_str(&format!("{}\n", json));
+            }
+        }
+
+        return match &opts.out {
+            Some(path) => write_output(opts, io, path, output.as_bytes()).map(|_| ()),
+            None => Ok(io.stdout.write_all(output.as_bytes())?),
+        };
+    }
+
+    if let Command::Watch {
+        out_dir,
+        debounce,
+        error_overlay,
+    } = &opts.command
+    {
+        let dir = std::path::Path::new(&opts.file);
+        let out_dir = std::path::Path::new(out_dir);
+        let mut watcher = watch::Watcher::new(opts, dir, out_dir, *error_overlay);
+        return watcher.run(io, std::time::Duration::from_millis(*debounce));
+    }
+
+    if let Command::Workspace { out_dir } = &opts.command {
+        let dir = match opts.file.as_str() {
+            "" => ".",
+            dir => dir,
+        };
+        let workspace = buf::Workspace::load(opts, std::path::Path::new(dir))?;
+        // the files of all modules import each other just like in buf
+        let mut opts = opts.clone();
+        let roots = workspace
+            .roots()
+            .map(|root| root.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        opts.include_paths.splice(0..0, roots);
+
+        let mut written = Written::default();
+        for path in workspace.files(&opts)? {
+            opts.file = path.to_string_lossy().to_string();
+            let (proto, deps) = load(&opts)?;
+            let output = typescript::to_schema(&opts, &proto, &deps)?;
+            let target = std::path::Path::new(out_dir)
+                .join(format!("{}.ts", proto.file.trim_end_matches(".proto")));
+            let target = target.to_string_lossy();
+            if written.count(write_output(
+                &opts,
+                io,
+                &target,
+                format!("{}\n", output).as_bytes(),
+            )?) {
+                writeln!(io.stdout, "wrote {}", target)?;
+            }
+        }
+        return written.report(&opts, io);
+    }
+
+    if let Command::Coverage { fixtures } = &opts.command {
+        let (proto, mut protos) = load(opts)?;
+        protos.push(proto);
+
+        let mut paths = Vec::new();
+        let mut payloads = Vec::new();
+        for (dir, message) in fixtures {
+            let mut files = Vec::new();
+            imports::files(opts, std::path::Path::new(dir), "json", &mut files)?;
+            for path in files {
+                let path = path.to_string_lossy().to_string();
+                let json = json::parse(&read(opts, &path)?)
+                    .map_err(|err| PtError::InvalidJson(format!("{}: {}", path, err)))?;
+                payloads.push((message.clone(), json));
+                paths.push(path);
+            }
+        }
+
+        let report = coverage::coverage(&protos, &payloads)?;
+        for gap in &report.gaps {
+            writeln!(io.stdout, "{}", gap)?;
+        }
+        writeln!(
+            io.stdout,
+            "coverage: {}/{} ({:.1}%)",
+            report.covered(),
+            report.total,
+            report.covered() as f64 * 100.0 / report.total.max(1) as f64
+        )?;
+
+        for (idx, issue) in &report.issues {
+            writeln!(
+                io.stderr,
+                "{}: {}: {}",
+                paths[*idx], issue.path, issue.message
+            )?;
+        }
+        if !report.issues.is_empty() {
+            return Err(PtError::InvalidJson(format!(
+                "{} issue(s) in fixtures",
+                report.issues.len()
+            )));
+        }
+        return Ok(());
+    }
+
+    if opts.command == Command::Lint {
+        let (proto, deps) = load(opts)?;
+        let mut selection = lint::Selection::from_config(&opts.config);
+        // the configuration of the buf module the file is part of applies too
+        let dir = std::path::Path::new("");
+        if opts.files.exists(&dir.join(buf::WORK_FILE))
+            || opts.files.exists(&dir.join(buf::MODULE_FILE))
+        {
+            let workspace = buf::Workspace::load(opts, dir)?;
+            // roots are given relative to the directory, e.g. `./protos`
+            let relative = |path: &std::path::Path| {
+                path.components()
+                    .filter(|part| *part != std::path::Component::CurDir)
+                    .collect::<std::path::PathBuf>()
+            };
+            let file = relative(std::path::Path::new(&opts.file));
+            let module = workspace
+                .modules
+                .iter()
+                .find(|module| file.starts_with(relative(&module.root)));
+            if let Some(module) = module {
+                selection = selection.with_buf(&module.lint);
+            }
+        }
+
+        let findings = lint::lint(
+            &lint::Model::new(&proto, &deps),
+            &opts.lint_rules,
+            &selection,
+        );
+        for finding in &findings {
+            writeln!(io.stdout, "{}", finding)?;
+        }
+        if !findings.is_empty() {
+            return Err(PtError::LintFindings(findings.len()));
+        }
+        return Ok(());
+    }
+
+    if opts.command == Command::Repl {
+        return repl::run(opts, io);
+    }
+
+    if let Command::New {
+        kind,
+        package,
+        name,
+    } = &opts.command
+    {
+        let path = std::path::Path::new(&opts.file);
+        if opts.files.exists(path) {
+            return Err(PtError::InvalidArgument(format!(
+                "file already exists: {}",
+                opts.file
+            )));
+        }
+
+        let content = scaffold::scaffold(
+            &opts.config,
+            kind,
+            &opts.file,
+            package.as_deref(),
+            name.as_deref(),
+        )?;
+
+        opts.files.write(path, content.as_bytes())?;
+        writeln!(io.stdout, "created {}", opts.file)?;
+        return Ok(());
+    }
+
+    if let Command::Graph(format) = &opts.command {
+        let (proto, deps) = load(opts)?;
+        let graph = graph::Graph::new([&proto].into_iter().chain(&deps));
+        match format {
+            GraphFormat::Dot => write!(io.stdout, "{}", graph.to_dot())?,
+            GraphFormat::Json => writeln!(io.stdout, "{}", graph.to_json())?,
+        }
+        return Ok(());
+    }
+
+    if let Some(targets) = opts.verify_targets {
+        let (proto, deps) = load(opts)?;
+        let (types, mismatches) = verify::verify_targets(opts, targets, &proto, &deps)?;
+        for mismatch in &mismatches {
+            writeln!(io.stderr, "{}", mismatch)?;
+        }
+        if !mismatches.is_empty() {
+            return Err(PtError::TargetMismatch(mismatches.len()));
+        }
+        writeln!(
+            io.stdout,
+            "{} and {} agree on {} type(s)",
+            targets.0.name(),
+            targets.1.name(),
+            types
+        )?;
+        return Ok(());
+    }
+
+    if let Some(dir) = &opts.debug_stages {
+        let input = read(opts, &opts.file)?;
+        let file = imports::import_name(opts);
+        let written = stages::dump(opts, std::path::Path::new(dir), &file, &input)?;
+        for path in written {
+            writeln!(io.stdout, "wrote {}", path.display())?;
+        }
+        return Ok(());
+    }
+
+    if opts.conformance {
+        let (proto, deps) = load(opts)?;
+        // the directory of the input file is searched last, just like imports
+        let mut include_paths = opts.include_paths.clone();
+        let dir = std::path::Path::new(&opts.file).parent();
+        match dir.map(|dir| dir.to_string_lossy().to_string()) {
+            Some(dir) if !dir.is_empty() => include_paths.push(dir),
+            _ => include_paths.push(".".to_string()),
+        }
+        let divergences =
+            conformance::check(&include_paths, &imports::import_name(opts), &proto, &deps)?;
+        for divergence in &divergences {
+            writeln!(io.stderr, "{}", divergence)?;
+        }
+        if !divergences.is_empty() {
+            return Err(PtError::ConformanceMismatch(divergences.len()));
+        }
+        writeln!(io.stdout, "protots agrees with protoc")?;
+        return Ok(());
+    }
+
+    // every document is written to a file of its own below the directory
+    #[cfg(feature = "json-schema")]
+    if let (Emit::JsonSchema, Some(dir)) = (&opts.emit, &opts.out) {
+        let (proto, deps) = load(opts)?;
+        let mut written = Written::default();
+        for (name, document) in json_schema::documents(&proto, &deps)? {
+            let path = std::path::Path::new(dir).join(name);
+            let output = format!("{:#}\n", document);
+            written.count(write_output(
+                opts,
+                io,
+                &path.to_string_lossy(),
+                output.as_bytes(),
+            )?);
+        }
+        return written.report(opts, io);
+    }
+
+    let output = match opts.emit {
+        Emit::Typescript => {
+            let (proto, deps) = load(opts)?;
+            let ts_schema = typescript::to_schema(opts, &proto, &deps)?;
+            format!("{}\n", ts_schema).into_bytes()
+        }
+        Emit::Descriptor => {
+            let (proto, deps) = load(opts)?;
+            descriptor::to_descriptor_set(&proto, &deps)?
+        }
+        Emit::Ast => {
+            let input = read(opts, &opts.file)?;
+            let proto = parser::parse(opts, &imports::import_name(opts), &input)?;
+            format!(
+                "{:#}\n",
+                ast::to_json_version(&proto, opts.ast_version.unwrap_or(ast::VERSION))
+            )
+            .into_bytes()
+        }
+        Emit::Runtime => {
+            let banner = opts.banner.as_deref().map_or(String::new(), |template| {
+                typescript::banner(template, opts.out.as_deref().unwrap_or_default())
+            });
+            format!("{}{}", banner, typescript::runtime(opts)).into_bytes()
+        }
+        #[cfg(feature = "json-schema")]
+        Emit::JsonSchema => {
+            let (proto, deps) = load(opts)?;
+            json_schema::documents(&proto, &deps)?
+                .iter()
+                .map(|(_, document)| format!("{:#}\n", document))
+                .collect::<String>()
+                .into_bytes()
+        }
+        #[cfg(feature = "openapi")]
+        Emit::OpenApi => {
+            let (proto, deps) = load(opts)?;
+            format!("{:#}\n", openapi::document(&proto, &deps)?).into_bytes()
+        }
+    };
+
+    match &opts.out {
+        Some(path) => write_output(opts, io, path, &output).map(|_| ()),
+        None => Ok(io.stdout.write_all(&output)?),
+    }
+}
+
+#[cfg(feature = "cli")]
+/// Write the output file, printing the changes to the existing file first
+/// with `--diff` (without writing) or `--interactive` (asking to confirm).
+/// Files of the same content are left untouched with any of these and
+/// `--skip-unchanged`. Returns whether the file was written.
+fn write_output(opts: &Opts, io: &mut Io, path: &str, output: &[u8]) -> Result<Outcome, PtError> {
+    let file = std::path::Path::new(path);
+    if !opts.diff && !opts.interactive && !opts.skip_unchanged {
+        opts.files.write(file, output)?;
+        return Ok(Outcome::Written);
+    }
+
+    let old = match opts.files.exists(file) {
+        true => Some(opts.files.read(file)?),
+        false => None,
+    };
+    if old.as_deref() == Some(output) {
+        writeln!(io.stdout, "{}: unchanged", path)?;
+        return Ok(Outcome::Unchanged);
+    }
+    if !opts.diff && !opts.interactive {
+        opts.files.write(file, output)?;
+        return Ok(Outcome::Written);
+    }
+
+    let old_str = old.as_deref().map(String::from_utf8_lossy);
+    let new_str = String::from_utf8_lossy(output);
+    match diff::unified(path, old_str.as_deref(), &new_str, io.color) {
+        Some(diff) if std::str::from_utf8(output).is_ok() => write!(io.stdout, "{}", diff)?,
+        _ => writeln!(io.stdout, "binary file {} differs", path)?,
+    }
+
+    if !opts.interactive {
+        return Ok(Outcome::Diffed);
+    }
+    write!(io.stdout, "write {}? [y/N] ", path)?;
+    io.stdout.flush()?;
+    if read_line(io.stdin)?.trim().eq_ignore_ascii_case("y") {
+        opts.files.write(file, output)?;
+        return Ok(Outcome::Written);
+    }
+    Ok(Outcome::Declined)
+}
+
+#[cfg(feature = "cli")]
+/// What became of an output file (see `write_output`).
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Outcome {
+    Written,
+    /// the file's content is the generated one already
+    Unchanged,
+    /// only the changes were printed (see `--diff`)
+    Diffed,
+    /// writing the changes was declined (see `--interactive`)
+    Declined,
+}
+
+#[cfg(feature = "cli")]
+/// Number of the output files of a command by what became of them (see
+/// `--skip-unchanged`, `--diff` and `--interactive`).
+#[derive(Default)]
+struct Written {
+    written: usize,
+    unchanged: usize,
+    diffed: usize,
+    declined: usize,
+}
+
+#[cfg(feature = "cli")]
+impl Written {
+    /// Count the outcome, whether the file was written.
+    fn count(&mut self, outcome: Outcome) -> bool {
+        match outcome {
+            Outcome::Written => self.written += 1,
+            Outcome::Unchanged => self.unchanged += 1,
+            Outcome::Diffed => self.diffed += 1,
+            Outcome::Declined => self.declined += 1,
+        }
+        outcome == Outcome::Written
+    }
+
+    fn report(&self, opts: &Opts, io: &mut Io) -> Result<(), PtError> {
+        if !opts.skip_unchanged && !opts.diff && !opts.interactive {
+            return Ok(());
+        }
+        let mut summary = format!(
+            "{} file(s) written, {} unchanged",
+            self.written, self.unchanged
+        );
+        if opts.diff {
+            summary.push_str(&format!(", {} differing", self.diffed));
+        }
+        if opts.interactive {
+            summary.push_str(&format!(", {} declined", self.declined));
+        }
+        writeln!(io.stdout, "{}", summary)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cli")]
+/// Read a single line without consuming any input beyond it.
+fn read_line(input: &mut dyn Read) -> Result<String, PtError> {
+    let mut line = Vec::new();
+    let mut byte = [0u8];
+    while input.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+#[cfg(feature = "cli")]
+/// Write the warnings and progress collected so far to stderr.
+fn report(messages: &errors::Messages, io: &mut Io) -> Result<(), PtError> {
+    for message in messages.take() {
+        writeln!(io.stderr, "{}", message)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+/// Run protots with the given command line arguments (including the program
+/// name) and return the exit code the process should terminate with.
+pub fn run(args: Vec<String>, io: &mut Io) -> ExitCode {
+    let program = args
+        .first()
+        .cloned()
+        .unwrap_or_else(|| "protots".to_string());
+
+    // only the command line depends on the current directory, embedders
+    // pass their configuration explicitly (see `api`)
+    let config = std::env::current_dir()
+        .map_err(PtError::from)
+        .and_then(|dir| config::Config::load(&*io.files, &dir));
+    let opts = match config.and_then(|config| opts(args, &io.files, &config)) {
+        Ok(Some(opts)) => opts,
+        Ok(None) => {
+            let _ = usage(&program, io.stdout);
+            return ExitCode::from(2);
+        }
+        Err(err) => {
+            let _ = writeln!(io.stderr, "{}", err);
+            return ExitCode::from(2);
+        }
+    };
+
+    // archives among the include paths are read as if they were directories
+    let messages = opts.messages.clone();
+    let result = provider::mount(&io.files, &opts.include_paths).and_then(|files| {
+        let lint_rules = io.lint_rules.clone();
+        process(
+            &Opts {
+                files,
+                lint_rules,
+                ..opts
+            },
+            io,
+        )
+    });
+    let _ = report(&messages, io);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            let _ = writeln!(io.stderr, "{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use std::process::ExitCode;
+
+    use std::path::Path;
+    use std::rc::Rc;
+
+    use super::{run, BaseSchema, Io, WktSchema};
+    use crate::errors::Location;
+    use crate::lint::{Diagnostics, LintRule, Model, Rules};
+    use crate::provider::{FileProvider, Files, Memory};
+
+    fn run_args(args: &[&str]) -> (ExitCode, String, String) {
+        run_with_input(args, b"")
+    }
+
+    fn run_with_input(args: &[&str], mut stdin: &[u8]) -> (ExitCode, String, String) {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut io = Io {
+            stdin: &mut stdin,
+            stdout: &mut stdout,
+            stderr: &mut stderr,
+            color: false,
+            files: Default::default(),
+            lint_rules: Default::default(),
+        };
+
+        let code = run(args.iter().map(|arg| arg.to_string()).collect(), &mut io);
+
+        (
+            code,
+            String::from_utf8(stdout).unwrap(),
+            String::from_utf8(stderr).unwrap(),
+        )
+    }
+
+    #[test]
+    fn run_without_file_prints_usage() {
+        let (code, stdout, stderr) = run_args(&["protots", "-v"]);
+
+        assert_eq!(code, ExitCode::from(2));
+        assert!(stdout.starts_with("protots <FILE> [OPTIONS]"));
+        assert!(stderr.is_empty());
+    }
+
+    #[test]
+    fn run_missing_file_reports_error() {
+        let (code, stdout, stderr) = run_args(&["protots", "does/not/exist.proto"]);
+
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(stdout.is_empty());
+        assert_eq!(stderr, "input file does not exist: does/not/exist.proto\n");
+    }
+
+    #[test]
+    fn run_example_file() {
+        let (code, stdout, stderr) = run_args(&["protots", "assets/example.proto"]);
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert!(stdout.contains("export const DefaultMessageSchema = z.object({"));
+        assert!(stderr.is_empty());
+    }
+
+    #[test]
+    fn run_diff_and_interactive() {
+        let out = std::env::temp_dir().join(format!("protots-diff-{}.ts", std::process::id()));
+        let out_path = out.to_str().unwrap();
+        std::fs::write(&out, "// old\n").unwrap();
+
+        let args = ["protots", "assets/example.proto", "--out", out_path];
+        let (code, stdout, _) = run_args(&[&args[..], &["--diff"]].concat());
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert!(stdout.starts_with(&format!(
+            "--- a/{}\n+++ b/{}\n@@ -1 +1,",
+            out_path, out_path
+        )));
+        assert!(stdout.contains("\n-// old\n"));
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "// old\n");
+
+        let (_, stdout, _) = run_with_input(&[&args[..], &["--interactive"]].concat(), b"n\n");
+        assert!(stdout.ends_with(&format!("write {}? [y/N] ", out_path)));
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "// old\n");
+
+        run_with_input(&[&args[..], &["--interactive"]].concat(), b"y\n");
+        let (_, stdout, _) = run_args(&[&args[..], &["--diff"]].concat());
+        assert_eq!(stdout, format!("{}: unchanged\n", out_path));
+
+        std::fs::remove_file(&out).unwrap();
+    }
+
+    #[test]
+    fn run_validate_json() {
+        let payload =
+            std::env::temp_dir().join(format!("protots-payload-{}.json", std::process::id()));
+        let payload_path = payload.to_str().unwrap();
+        let args = [
+            "protots",
+            "validate-json",
+            payload_path,
+            "--proto",
+            "assets/example.proto",
+            "--message",
+            "whatever.something.DefaultMessage",
+        ];
+
+        std::fs::write(
+            &payload,
+            r#"{"stringValue": "a", "intValue": 3, "listOfStrings": []}"#,
+        )
+        .unwrap();
+        let (code, _, stderr) = run_args(&args);
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert!(stderr.is_empty());
+
+        std::fs::write(&payload, r#"{"intValue": "x", "listOfStrings": "a"}"#).unwrap();
+        let (code, _, stderr) = run_args(&args);
+        assert_eq!(code, ExitCode::FAILURE);
+        assert_eq!(
+            stderr,
+            format!(
+                "{path}: $.intValue: invalid int32 value \"x\"\n\
+                 {path}: $.listOfStrings: expected array for repeated list_of_strings, got \"a\"\n\
+                 invalid JSON payload: 2 issue(s) in {path}\n",
+                path = payload_path
+            )
+        );
+
+        std::fs::remove_file(&payload).unwrap();
+    }
+
+    #[test]
+    fn run_reports_warnings_to_stderr() {
+        let files = Rc::new(Memory::new([(
+            "user.proto",
+            "syntax = \"proto2\";\nmessage User {\n  required string name = 1;\n  \
+             optional Missing missing = 2;\n}",
+        )]));
+        let mut stdin: &[u8] = &[];
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut io = Io {
+            stdin: &mut stdin,
+            stdout: &mut stdout,
+            stderr: &mut stderr,
+            color: false,
+            files: Files(files),
+            lint_rules: Default::default(),
+        };
+
+        let args = "protots user.proto --lenient";
+        let code = run(args.split(' ').map(String::from).collect(), &mut io);
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(
+            String::from_utf8(stderr).unwrap(),
+            "warning: 1 `required` field(s) generated as non-optional fields of objects \
+             accepting unknown keys, first in message User, field name (line 3); \
+             see --strict-required\n\
+             warning: could not find type named: Missing in message User, field missing (line 4)\n"
+        );
+        assert!(String::from_utf8(stdout)
+            .unwrap()
+            .contains("export const UserSchema"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "interfaces", feature = "yup"))]
+    fn run_verify_targets() {
+        let files = Rc::new(Memory::new([(
+            "user.proto",
+            "syntax = \"proto3\";\nmessage User {\n  Tag tag = 1;\n  \
+             message Tag { string label = 1; }\n}",
+        )]));
+        let run_with = |args: &str| {
+            let mut stdin: &[u8] = &[];
+            let mut stdout = Vec::new();
+            let mut stderr = Vec::new();
+            let mut io = Io {
+                stdin: &mut stdin,
+                stdout: &mut stdout,
+                stderr: &mut stderr,
+                color: false,
+                files: Files(files.clone()),
+                lint_rules: Default::default(),
+            };
+            let code = run(args.split(' ').map(String::from).collect(), &mut io);
+            (
+                code,
+                String::from_utf8(stdout).unwrap(),
+                String::from_utf8(stderr).unwrap(),
+            )
+        };
+
+        let (code, stdout, _) = run_with("protots user.proto --verify-targets zod,yup");
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(stdout, "zod and yup agree on 2 type(s)\n");
+
+        let (code, _, stderr) =
+            run_with("protots user.proto --verify-targets interfaces,zod --private-nested");
+        assert_eq!(code, ExitCode::FAILURE);
+        assert_eq!(
+            stderr,
+            "User_Tag: missing in zod\ngeneration targets disagree in 1 place(s)\n"
+        );
+
+        let (code, _, stderr) = run_with("protots user.proto --verify-targets zod");
+        assert_eq!(code, ExitCode::from(2));
+        assert_eq!(
+            stderr,
+            "invalid argument: invalid --verify-targets value: zod\n"
+        );
+    }
+
+    #[test]
+    fn run_new_with_file_provider() {
+        let files = Rc::new(Memory::new([("foo/v1/old.proto", "")]));
+        let run_with = |args: &str| {
+            let mut stdin: &[u8] = &[];
+            let mut stdout = Vec::new();
+            let mut stderr = Vec::new();
+            let mut io = Io {
+                stdin: &mut stdin,
+                stdout: &mut stdout,
+                stderr: &mut stderr,
+                color: false,
+                files: Files(files.clone()),
+                lint_rules: Default::default(),
+            };
+            let code = run(args.split(' ').map(String::from).collect(), &mut io);
+            (code, String::from_utf8(stderr).unwrap())
+        };
+
+        let (code, _) =
+            run_with("protots new message foo/v1/user.proto --package foo.v1 --message User");
+        assert_eq!(code, ExitCode::SUCCESS);
+        let content = files
+            .read_to_string(Path::new("foo/v1/user.proto"))
+            .unwrap();
+        assert!(content.contains("message User {"));
+
+        let (code, stderr) =
+            run_with("protots new message foo/v1/old.proto --package foo.v1 --message Old");
+        assert_eq!(code, ExitCode::FAILURE);
+        assert_eq!(
+            stderr,
+            "invalid argument: file already exists: foo/v1/old.proto\n"
+        );
+        assert_eq!(files.read(Path::new("foo/v1/old.proto")).unwrap(), b"");
+    }
+
+    #[test]
+    fn run_sample_reports_seed() {
+        let files = Rc::new(Memory::new([(
+            "user.proto",
+            "syntax = \"proto3\";\nmessage User { string name = 1; repeated int32 ids = 2; }",
+        )]));
+        let run_with = |args: &str| {
+            let mut stdin: &[u8] = &[];
+            let mut stdout = Vec::new();
+            let mut stderr = Vec::new();
+            let mut io = Io {
+                stdin: &mut stdin,
+                stdout: &mut stdout,
+                stderr: &mut stderr,
+                color: false,
+                files: Files(files.clone()),
+                lint_rules: Default::default(),
+            };
+            let code = run(args.split(' ').map(String::from).collect(), &mut io);
+            assert_eq!(code, ExitCode::SUCCESS);
+            (
+                String::from_utf8(stdout).unwrap(),
+                String::from_utf8(stderr).unwrap(),
+            )
+        };
+
+        let (payloads, stderr) =
+            run_with("protots sample --proto user.proto --message User --count 3");
+        let seed = stderr.strip_prefix("seed: ").unwrap().trim_end();
+        let (again, _) = run_with(&format!(
+            "protots sample --proto user.proto --message User --count 3 --seed {}",
+            seed
+        ));
+        assert_eq!(again, payloads);
+    }
+
+    #[test]
+    fn run_with_file_provider() {
+        let files = Rc::new(Memory::new([
+            (
+                "protos/foo/v1/user.proto",
+                "syntax = \"proto3\";\npackage foo.v1;\nimport \"common/date.proto\";\n\
+                 message User { common.Date birthday = 1; }",
+            ),
+            (
+                "protos/common/date.proto",
+                "syntax = \"proto3\";\npackage common;\nmessage Date {}",
+            ),
+            ("LICENSE.txt", "Copyright ACME"),
+        ]));
+        let mut stdin: &[u8] = &[];
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut io = Io {
+            stdin: &mut stdin,
+            stdout: &mut stdout,
+            stderr: &mut stderr,
+            color: false,
+            files: Files(files.clone()),
+            lint_rules: Default::default(),
+        };
+
+        let args =
+            "protots protos/foo/v1/user.proto -I protos --out out/user.ts --banner LICENSE.txt";
+        let code = run(args.split(' ').map(String::from).collect(), &mut io);
+        assert_eq!(
+            code,
+            ExitCode::SUCCESS,
+            "{}",
+            String::from_utf8_lossy(&stderr)
+        );
+
+        let output = files.read_to_string(Path::new("out/user.ts")).unwrap();
+        assert!(output.contains("import { DateSchema } from \"../../common/date\";"));
+        assert!(output.contains("Copyright ACME"));
+    }
+
+    #[test]
+    fn run_lint_with_buf_config_and_registered_rules() {
+        struct NoUsers;
+
+        impl LintRule for NoUsers {
+            fn name(&self) -> &str {
+                "NO_USERS"
+            }
+
+            fn check(&self, model: &Model, diags: &mut Diagnostics) {
+                for (full_name, _) in model.messages() {
+                    if full_name.ends_with("User") {
+                        diags.report(Location::new("message", full_name), "users are forbidden");
+                    }
+                }
+            }
+        }
+
+        let files = Rc::new(Memory::new([
+            (
+                "buf.yaml",
+                "version: v1\nlint:\n  except:\n    - FIELD_LOWER_SNAKE_CASE\n",
+            ),
+            (
+                "user.proto",
+                "syntax = \"proto3\";\nmessage User { string Name = 1; }\nenum Kind { KIND = 0; }",
+            ),
+        ]));
+        let mut lint_rules = Rules::default();
+        lint_rules.register(NoUsers);
+
+        let mut stdin: &[u8] = &[];
+        let mut stdout = Vec::new();
+        let mut io = Io {
+            stdin: &mut stdin,
+            stdout: &mut stdout,
+            stderr: &mut Vec::new(),
+            color: false,
+            files: Files(files),
+            lint_rules,
+        };
+        let code = run(
+            vec!["protots".into(), "lint".into(), "user.proto".into()],
+            &mut io,
+        );
+        assert_eq!(code, ExitCode::FAILURE);
+        assert_eq!(
+            String::from_utf8_lossy(&stdout),
+            "user.proto: enum zero value should be suffixed with _UNSPECIFIED in enum Kind, value KIND (line 3) [ENUM_ZERO_VALUE_SUFFIX]\n\
+             user.proto: users are forbidden in message User [NO_USERS]\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json-schema")]
+    fn run_skips_unchanged_outputs() {
+        let files = Rc::new(Memory::new([(
+            "user.proto",
+            "syntax = \"proto3\";\nmessage User {}\nenum Kind { KIND_UNSPECIFIED = 0; }",
+        )]));
+        let run_twice = |args: &str| {
+            let mut outputs = Vec::new();
+            for _ in 0..2 {
+                let mut stdin: &[u8] = &[];
+                let mut stdout = Vec::new();
+                let mut io = Io {
+                    stdin: &mut stdin,
+                    stdout: &mut stdout,
+                    stderr: &mut Vec::new(),
+                    color: false,
+                    files: Files(files.clone()),
+                    lint_rules: Default::default(),
+                };
+                let code = run(args.split(' ').map(String::from).collect(), &mut io);
+                assert_eq!(code, ExitCode::SUCCESS);
+                outputs.push(String::from_utf8(stdout).unwrap());
+            }
+            outputs
+        };
+
+        let outputs =
+            run_twice("protots user.proto --emit json-schema --out schemas --skip-unchanged");
+        assert_eq!(outputs[0], "2 file(s) written, 0 unchanged\n");
+        assert_eq!(
+            outputs[1],
+            "schemas/User.schema.json: unchanged\n\
+             schemas/Kind.schema.json: unchanged\n\
+             0 file(s) written, 2 unchanged\n"
+        );
+
+        let outputs = run_twice("protots user.proto --out user.ts --skip-unchanged");
+        assert_eq!(outputs, ["", "user.ts: unchanged\n"]);
+    }
+
+    #[test]
+    #[cfg(feature = "json-schema")]
+    fn run_reports_diffed_and_declined_outputs() {
+        let files = Rc::new(Memory::new([
+            (
+                "user.proto",
+                "syntax = \"proto3\";\nmessage User {}\nenum Kind { KIND_UNSPECIFIED = 0; }",
+            ),
+            ("schemas/User.schema.json", "{}\n"),
+        ]));
+        let run_with = |args: &str, input: &str| {
+            let mut stdin = input.as_bytes();
+            let mut stdout = Vec::new();
+            let mut io = Io {
+                stdin: &mut stdin,
+                stdout: &mut stdout,
+                stderr: &mut Vec::new(),
+                color: false,
+                files: Files(files.clone()),
+                lint_rules: Default::default(),
+            };
+            let code = run(args.split(' ').map(String::from).collect(), &mut io);
+            assert_eq!(code, ExitCode::SUCCESS);
+            String::from_utf8(stdout).unwrap()
+        };
+
+        let output = run_with(
+            "protots user.proto --emit json-schema --out schemas --diff",
+            "",
+        );
+        assert!(output.ends_with("\n0 file(s) written, 0 unchanged, 2 differing\n"));
+        assert_eq!(
+            files
+                .read_to_string(Path::new("schemas/User.schema.json"))
+                .unwrap(),
+            "{}\n"
+        );
+
+        let output = run_with(
+            "protots user.proto --emit json-schema --out schemas --interactive",
+            "n\ny\n",
+        );
+        assert!(output.ends_with("[y/N] 1 file(s) written, 0 unchanged, 1 declined\n"));
+        assert!(files.exists(Path::new("schemas/Kind.schema.json")));
+    }
+
+    #[test]
+    fn base_schema_patterns() {
+        let base = BaseSchema::parse("*Event = #events#BaseEventSchema").unwrap();
+        assert_eq!(base.module, "#events");
+        assert_eq!(base.schema, "BaseEventSchema");
+        assert!(base.matches("UserCreatedEvent", "foo.v1.UserCreatedEvent"));
+        assert!(base.matches("Event", "Event"));
+        assert!(!base.matches("EventLog", "foo.v1.EventLog"));
+
+        let base = BaseSchema::parse("foo.*.User=./base#BaseSchema").unwrap();
+        assert!(base.matches("User", "foo.v1.User"));
+        assert!(!base.matches("User", "bar.v1.User"));
+
+        assert!(BaseSchema::parse("*Event=./events").is_err());
+        assert!(BaseSchema::parse("=./events#BaseEventSchema").is_err());
+    }
+
+    #[test]
+    fn wkt_schemas() {
+        let wkt = WktSchema::parse("Duration = @/time#DurationSchema").unwrap();
+        assert_eq!(wkt.name, "google.protobuf.Duration");
+        assert_eq!(wkt.module.as_deref(), Some("@/time"));
+        assert_eq!(wkt.schema, "DurationSchema");
+
+        let wkt = WktSchema::parse("google.protobuf.Timestamp=z.string().datetime()").unwrap();
+        assert_eq!(wkt.module, None);
+        assert_eq!(wkt.schema, "z.string().datetime()");
+
+        assert!(WktSchema::parse("Int64Value=z.string()").is_ok());
+        assert!(WktSchema::parse("Durations=z.string()").is_err());
+        assert!(WktSchema::parse("Duration=DurationSchema").is_err());
+    }
+}
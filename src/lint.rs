@@ -0,0 +1,373 @@
+//! Lint rules checking proto files against style conventions (see the `lint`
+//! command). Besides the built-in rules, library consumers register their
+//! own ones (see `Io::lint_rules`), e.g. naming conventions or forbidden
+//! types of an organization.
+//!
+//! Rules are enabled by name via `lint` and disabled via `lint_except` of
+//! `.prototsrc`, or `use` and `except` of the `lint` section of a `buf.yaml`
+//! (see `buf::Module`). All rules are enabled if none is given.
+
+use std::rc::Rc;
+
+use crate::config::Config;
+use crate::errors::Location;
+use crate::parser::{Elem, Enum, EnumValue, Field, Msg, Proto};
+use crate::registry::{qualify, Registry};
+
+/// Rule checking the definitions of a proto file.
+pub trait LintRule {
+    /// Identifier of the rule in the configuration, in the upper snake case
+    /// of `buf`, e.g. `FIELD_LOWER_SNAKE_CASE`.
+    fn name(&self) -> &str;
+
+    /// Report the violations of the rule in the given file.
+    fn check(&self, model: &Model, diags: &mut Diagnostics);
+}
+
+/// Shared handles of the rules registered in addition to the built-in ones.
+#[derive(Clone, Default)]
+pub struct Rules(pub Vec<Rc<dyn LintRule>>);
+
+impl Rules {
+    pub fn register(&mut self, rule: impl LintRule + 'static) {
+        self.0.push(Rc::new(rule));
+    }
+}
+
+/// Proto file checked by the rules along with its imports.
+pub struct Model<'a> {
+    pub proto: &'a Proto,
+    pub deps: &'a [Proto],
+    /// types of the file and its imports
+    pub registry: Registry,
+}
+
+impl<'a> Model<'a> {
+    pub fn new(proto: &'a Proto, deps: &'a [Proto]) -> Model<'a> {
+        Model {
+            proto,
+            deps,
+            registry: Registry::new([proto].into_iter().chain(deps)),
+        }
+    }
+
+    /// All messages of the file including nested ones, by fully-qualified
+    /// name.
+    pub fn messages(&self) -> Vec<(String, &'a Msg)> {
+        fn collect<'a>(msg: &'a Msg, scope: &str, into: &mut Vec<(String, &'a Msg)>) {
+            let name = qualify(scope, &msg.name);
+            into.push((name.clone(), msg));
+            for field in &msg.fields {
+                if let Field::SubMessage(sub) = field {
+                    collect(sub, &name, into);
+                }
+            }
+        }
+
+        let package = self.proto.package().unwrap_or("");
+        let mut messages = Vec::new();
+        for elem in &self.proto.elems {
+            if let Elem::Message(msg) = elem {
+                collect(msg, package, &mut messages);
+            }
+        }
+        messages
+    }
+
+    /// All enums of the file including nested ones, by fully-qualified name.
+    pub fn enums(&self) -> Vec<(String, &'a Enum)> {
+        let package = self.proto.package().unwrap_or("");
+        let top = self.proto.elems.iter().filter_map(|elem| match elem {
+            Elem::Enum(e) => Some((qualify(package, &e.name), e)),
+            _ => None,
+        });
+        let nested = self.messages().into_iter().flat_map(|(scope, msg)| {
+            msg.fields.iter().filter_map(move |field| match field {
+                Field::SubEnum(e) => Some((qualify(&scope, &e.name), e)),
+                _ => None,
+            })
+        });
+        top.chain(nested).collect()
+    }
+}
+
+/// Violation of a lint rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub rule: String,
+    pub file: String,
+    pub location: Location,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} {} [{}]",
+            self.file, self.message, self.location, self.rule
+        )
+    }
+}
+
+/// Findings of the rules run so far.
+pub struct Diagnostics {
+    file: String,
+    rule: String,
+    findings: Vec<Finding>,
+}
+
+impl Diagnostics {
+    /// Report a violation of the rule being checked at the given location.
+    pub fn report(&mut self, location: Location, message: impl Into<String>) {
+        self.findings.push(Finding {
+            rule: self.rule.clone(),
+            file: self.file.clone(),
+            location,
+            message: message.into(),
+        });
+    }
+}
+
+/// Rules enabled by the configuration.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Selection {
+    pub uses: Vec<String>,
+    pub excepts: Vec<String>,
+}
+
+/// Categories of `buf` that are taken as all rules.
+const CATEGORIES: [&str; 4] = ["MINIMAL", "BASIC", "STANDARD", "DEFAULT"];
+
+impl Selection {
+    /// The rules enabled via `lint` and disabled via `lint_except` of
+    /// `.prototsrc`.
+    pub fn from_config(config: &Config) -> Selection {
+        Selection {
+            uses: config.get_all("lint").map(String::from).collect(),
+            excepts: config.get_all("lint_except").map(String::from).collect(),
+        }
+    }
+
+    /// Add the rules of the `lint` section of a `buf.yaml`.
+    pub fn with_buf(mut self, lint: &[(String, Vec<String>)]) -> Selection {
+        for (key, values) in lint {
+            match key.as_str() {
+                "use" => self.uses.extend(values.iter().cloned()),
+                "except" => self.excepts.extend(values.iter().cloned()),
+                _ => (),
+            }
+        }
+        self
+    }
+
+    pub fn enabled(&self, rule: &str) -> bool {
+        let used = self.uses.is_empty()
+            || self
+                .uses
+                .iter()
+                .any(|name| name == rule || CATEGORIES.contains(&name.as_str()));
+        used && !self.excepts.iter().any(|name| name == rule)
+    }
+}
+
+/// Run the built-in and the given rules that are enabled on the file, the
+/// findings in order of the rules.
+pub fn lint(model: &Model, rules: &Rules, selection: &Selection) -> Vec<Finding> {
+    let builtin: [&dyn LintRule; 3] = [
+        &MessagePascalCase,
+        &FieldLowerSnakeCase,
+        &EnumZeroValueSuffix,
+    ];
+    let mut diags = Diagnostics {
+        file: model.proto.file.clone(),
+        rule: String::new(),
+        findings: Vec::new(),
+    };
+    let registered = rules.0.iter().map(|rule| rule.as_ref());
+    for rule in builtin.into_iter().chain(registered) {
+        if selection.enabled(rule.name()) {
+            diags.rule = rule.name().to_string();
+            rule.check(model, &mut diags);
+        }
+    }
+    diags.findings
+}
+
+/// Message names are PascalCase, e.g. `UserProfile`.
+struct MessagePascalCase;
+
+impl LintRule for MessagePascalCase {
+    fn name(&self) -> &str {
+        "MESSAGE_PASCAL_CASE"
+    }
+
+    fn check(&self, model: &Model, diags: &mut Diagnostics) {
+        for (full_name, msg) in model.messages() {
+            let pascal = msg.name.starts_with(|chr: char| chr.is_ascii_uppercase())
+                && msg.name.chars().all(|chr| chr.is_ascii_alphanumeric());
+            if !pascal {
+                let mut location = Location::new("message", full_name);
+                location.line = msg.span.line;
+                diags.report(location, "message name should be PascalCase");
+            }
+        }
+    }
+}
+
+/// Field names are lower_snake_case, e.g. `display_name`.
+struct FieldLowerSnakeCase;
+
+impl LintRule for FieldLowerSnakeCase {
+    fn name(&self) -> &str {
+        "FIELD_LOWER_SNAKE_CASE"
+    }
+
+    fn check(&self, model: &Model, diags: &mut Diagnostics) {
+        fn check(full_name: &str, fields: &[Field], diags: &mut Diagnostics) {
+            for field in fields {
+                let (name, line) = match field {
+                    Field::Single { name, span, .. } | Field::Map { name, span, .. } => {
+                        (name, span.line)
+                    }
+                    Field::OneOf { fields, .. } => {
+                        check(full_name, fields, diags);
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let snake = name.starts_with(|chr: char| chr.is_ascii_lowercase())
+                    && name
+                        .chars()
+                        .all(|chr| chr.is_ascii_lowercase() || chr.is_ascii_digit() || chr == '_');
+                if !snake {
+                    diags.report(
+                        Location::new("message", full_name).member("field", name, line),
+                        "field name should be lower_snake_case",
+                    );
+                }
+            }
+        }
+
+        for (full_name, msg) in model.messages() {
+            check(&full_name, &msg.fields, diags);
+        }
+    }
+}
+
+/// The zero value of enums is named `<ENUM>_UNSPECIFIED`, so it is not
+/// mistaken for a meaningful value.
+struct EnumZeroValueSuffix;
+
+impl LintRule for EnumZeroValueSuffix {
+    fn name(&self) -> &str {
+        "ENUM_ZERO_VALUE_SUFFIX"
+    }
+
+    fn check(&self, model: &Model, diags: &mut Diagnostics) {
+        for (full_name, e) in model.enums() {
+            for value in &e.values {
+                if let EnumValue::Single {
+                    name, idx: 0, span, ..
+                } = value
+                {
+                    if !name.ends_with("_UNSPECIFIED") {
+                        diags.report(
+                            Location::new("enum", full_name.as_str())
+                                .member("value", name, span.line),
+                            "enum zero value should be suffixed with _UNSPECIFIED",
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::Location;
+    use crate::Opts;
+
+    use super::{lint, Diagnostics, LintRule, Model, Rules, Selection};
+
+    const SOURCE: &str = r#"syntax = "proto3";
+package foo;
+enum Status { UNKNOWN = 0; ACTIVE = 1; }
+message User_Profile {
+  string displayName = 1;
+  message Address { string street = 1; }
+  float score = 2;
+}"#;
+
+    #[test]
+    fn lint_builtin_rules() {
+        let proto = crate::parser::parse(&Opts::default(), "file.proto", SOURCE).unwrap();
+        let model = Model::new(&proto, &[]);
+
+        let findings = lint(&model, &Rules::default(), &Selection::default())
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            vec![
+                "file.proto: message name should be PascalCase in message foo.User_Profile (line 4) [MESSAGE_PASCAL_CASE]",
+                "file.proto: field name should be lower_snake_case in message foo.User_Profile, field displayName (line 5) [FIELD_LOWER_SNAKE_CASE]",
+                "file.proto: enum zero value should be suffixed with _UNSPECIFIED in enum foo.Status, value UNKNOWN (line 3) [ENUM_ZERO_VALUE_SUFFIX]",
+            ]
+        );
+
+        let selection = Selection {
+            uses: vec!["STANDARD".to_string()],
+            excepts: vec!["MESSAGE_PASCAL_CASE".to_string()],
+        }
+        .with_buf(&[(
+            "except".to_string(),
+            vec!["ENUM_ZERO_VALUE_SUFFIX".to_string()],
+        )]);
+        let findings = lint(&model, &Rules::default(), &selection);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, "FIELD_LOWER_SNAKE_CASE");
+    }
+
+    struct NoFloats;
+
+    impl LintRule for NoFloats {
+        fn name(&self) -> &str {
+            "NO_FLOATS"
+        }
+
+        fn check(&self, model: &Model, diags: &mut Diagnostics) {
+            for (full_name, msg) in model.messages() {
+                let mut fields = Vec::new();
+                crate::registry::declared(&msg.fields, None, &mut fields);
+                for field in fields.iter().filter(|field| field.field_type == "float") {
+                    diags.report(
+                        Location::new("message", full_name.as_str()).member("field", field.name, 0),
+                        "use double instead of float",
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lint_registered_rules() {
+        let proto = crate::parser::parse(&Opts::default(), "file.proto", SOURCE).unwrap();
+        let model = Model::new(&proto, &[]);
+        let mut rules = Rules::default();
+        rules.register(NoFloats);
+
+        let selection = Selection {
+            uses: vec!["NO_FLOATS".to_string()],
+            excepts: Vec::new(),
+        };
+        let findings = lint(&model, &rules, &selection);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].to_string(),
+            "file.proto: use double instead of float in message foo.User_Profile, field score [NO_FLOATS]"
+        );
+    }
+}
@@ -0,0 +1,464 @@
+//! OpenAPI 3.1 document of the HTTP bindings of a file's services (see
+//! `--emit openapi`): each method annotated with `google.api.http` is an
+//! operation of the path and verb of its rule (and additional bindings), the
+//! messages involved are component schemas just like the documents of
+//! `--emit json-schema`.
+//!
+//! Like grpc-gateway maps requests, the fields named in the path template are
+//! path parameters, the body is the request message (`body: "*"`) or one of
+//! its fields, and the remaining scalar fields are query parameters. OpenAPI
+//! has no notion of streams, streaming methods are marked by the
+//! `x-streaming` extension and exchange newline-delimited JSON.
+
+use std::collections::BTreeSet;
+
+use crate::errors::{Location, PtError};
+use crate::json::{self, Json};
+use crate::json_schema::Definitions;
+use crate::parser::{Elem, Field, Msg, OptionValue, Proto, Rpc, ServiceNode};
+use crate::registry::{self, Kind, Registry};
+
+pub const VERSION: &str = "3.1.0";
+
+/// Verbs of the rules' patterns, `custom` ones are accepted if they are
+/// verbs of OpenAPI as well.
+const VERBS: [&str; 5] = ["get", "put", "post", "delete", "patch"];
+const CUSTOM_VERBS: [&str; 3] = ["head", "options", "trace"];
+
+/// OpenAPI document of the methods of the given file bound to HTTP.
+pub fn document(proto: &Proto, deps: &[Proto]) -> Result<Json, PtError> {
+    let protos = [proto].into_iter().chain(deps).collect::<Vec<_>>();
+    let registry = Registry::new(protos.iter().copied());
+    let ctx = Context {
+        protos: &protos,
+        registry: &registry,
+        definitions: Definitions {
+            registry: &registry,
+            reference: component,
+        },
+    };
+    let scope = proto.package().unwrap_or("");
+
+    let mut paths: Vec<(String, Json)> = Vec::new();
+    for elem in &proto.elems {
+        let Elem::Service { name, nodes, .. } = elem else {
+            continue;
+        };
+        let service = registry::qualify(scope, name);
+        for node in nodes {
+            let ServiceNode::Rpc(rpc) = node else {
+                continue;
+            };
+            let location =
+                || Location::new("service", &service).member("method", &rpc.name, rpc.span.line);
+            for (index, rule) in bindings(rpc).into_iter().enumerate() {
+                let Some((verb, template)) = pattern(rule) else {
+                    continue;
+                };
+                let (path, operation) = ctx
+                    .operation(&service, scope, rpc, rule, template, index)
+                    .map_err(|error| error.at(location()))?;
+                let Json::Object(operation) = operation else {
+                    unreachable!("operations are objects");
+                };
+                match paths.iter_mut().find(|(other, _)| *other == path) {
+                    Some((_, Json::Object(verbs))) => verbs.push((verb, Json::Object(operation))),
+                    _ => paths.push((path, Json::Object(vec![(verb, Json::Object(operation))]))),
+                }
+            }
+        }
+    }
+
+    let title = proto.package().unwrap_or(&proto.file);
+    let components = ctx.components(&Json::Object(paths.clone()))?;
+    Ok(Json::object([
+        ("openapi", Json::str(VERSION)),
+        (
+            "info",
+            Json::object([("title", Json::str(title)), ("version", Json::str("1.0.0"))]),
+        ),
+        ("paths", Json::Object(paths)),
+        (
+            "components",
+            Json::object([("schemas", Json::Object(components))]),
+        ),
+    ]))
+}
+
+/// Reference of the component schema of the given type.
+fn component(full_name: &str) -> String {
+    format!("#/components/schemas/{}", full_name)
+}
+
+/// Rules of the method: its `google.api.http` rule followed by the rule's
+/// additional bindings.
+fn bindings(rpc: &Rpc) -> Vec<&OptionValue> {
+    let Some(rule) = rpc
+        .options
+        .iter()
+        .find(|opt| opt.name.extension() == Some("google.api.http"))
+        .map(|opt| &opt.value)
+    else {
+        return Vec::new();
+    };
+
+    let mut rules = vec![rule];
+    for binding in rule.get_all("additional_bindings") {
+        match binding {
+            OptionValue::Array(values) => rules.extend(values),
+            value => rules.push(value),
+        }
+    }
+    rules
+}
+
+/// Lowercase verb and path template of the rule.
+fn pattern(rule: &OptionValue) -> Option<(String, &str)> {
+    fn template(value: Option<&OptionValue>) -> Option<&str> {
+        match value {
+            Some(OptionValue::Str { value }) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+    if let Some(verb) = VERBS.iter().find(|verb| rule.get(verb).is_some()) {
+        return template(rule.get(verb)).map(|path| (verb.to_string(), path));
+    }
+
+    let custom = rule.get("custom")?;
+    let verb = template(custom.get("kind"))?.to_lowercase();
+    CUSTOM_VERBS
+        .contains(&verb.as_str())
+        .then_some(())
+        .and(template(custom.get("path")))
+        .map(|path| (verb, path))
+}
+
+/// OpenAPI path of the template along with the field paths of its variables,
+/// e.g. `/v1/{name}` and `name` of `/v1/{name=users/*}`.
+fn path_variables(template: &str) -> (String, Vec<String>) {
+    let mut path = String::with_capacity(template.len());
+    let mut variables = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        path.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let variable = &rest[start + 1..start + end];
+        let field = variable.split('=').next().unwrap_or_default().trim();
+        path.push_str(&format!("{{{}}}", field));
+        variables.push(field.to_string());
+        rest = &rest[start + end + 1..];
+    }
+    path.push_str(rest);
+    (path, variables)
+}
+
+struct Context<'a> {
+    protos: &'a [&'a Proto],
+    registry: &'a Registry,
+    definitions: Definitions<'a>,
+}
+
+impl Context<'_> {
+    fn message(&self, name: &str, scope: &str) -> Result<(String, &Msg), PtError> {
+        let ptype = self
+            .registry
+            .resolve(name, scope)
+            .ok_or_else(|| self.registry.unresolved(name, scope))?;
+        registry::find_message(self.protos.iter().copied(), &ptype.full_name)
+            .map(|msg| (ptype.full_name.clone(), msg))
+            .ok_or_else(|| PtError::ProtobufTypeNotFound(ptype.full_name.clone()))
+    }
+
+    /// Path and operation of one of the method's rules.
+    fn operation(
+        &self,
+        service: &str,
+        scope: &str,
+        rpc: &Rpc,
+        rule: &OptionValue,
+        template: &str,
+        index: usize,
+    ) -> Result<(String, Json), PtError> {
+        let (request, msg) = self.message(&rpc.request, scope)?;
+        let (path, variables) = path_variables(template);
+        let body = match rule.get("body") {
+            Some(OptionValue::Str { value }) if !value.is_empty() => Some(value.as_str()),
+            _ => None,
+        };
+
+        let mut parameters = Vec::new();
+        for variable in &variables {
+            let schema = self.field_path(&request, msg, variable)?;
+            parameters.push(Json::object([
+                ("name", Json::str(variable)),
+                ("in", Json::str("path")),
+                ("required", Json::Bool(true)),
+                ("schema", schema),
+            ]));
+        }
+        // fields bound to neither the path nor the body
+        if body != Some("*") {
+            for field in fields(msg) {
+                let Field::Single {
+                    name, field_type, ..
+                } = field
+                else {
+                    continue;
+                };
+                let bound = variables
+                    .iter()
+                    .any(|variable| variable.split('.').next() == Some(name));
+                if bound || body == Some(name) || !self.is_query(field_type, &request) {
+                    continue;
+                }
+                let (name, schema) = self.definitions.property(field, &request)?;
+                parameters.push(Json::object([
+                    ("name", Json::Str(name)),
+                    ("in", Json::str("query")),
+                    ("schema", schema),
+                ]));
+            }
+        }
+
+        let streaming = match (rpc.stream_request, rpc.stream_response) {
+            (true, true) => Some("bidi"),
+            (true, false) => Some("client"),
+            (false, true) => Some("server"),
+            (false, false) => None,
+        };
+        let content = |stream: bool, schema: Json| {
+            let media_type = match stream {
+                true => "application/x-ndjson",
+                false => "application/json",
+            };
+            Json::object([(media_type, Json::object([("schema", schema)]))])
+        };
+
+        let method = rpc.name.as_str();
+        let short_name = service.rsplit('.').next().unwrap_or(service);
+        let operation_id = match index {
+            0 => format!("{}_{}", short_name, method),
+            _ => format!("{}_{}{}", short_name, method, index + 1),
+        };
+        let mut operation = vec![
+            ("tags".to_string(), Json::Array(vec![Json::str(service)])),
+            ("operationId".to_string(), Json::Str(operation_id)),
+        ];
+        if let Some(doc) = &rpc.doc {
+            operation.push(("description".to_string(), Json::str(doc)));
+        }
+        if !parameters.is_empty() {
+            operation.push(("parameters".to_string(), Json::Array(parameters)));
+        }
+        if let Some(body) = body {
+            let schema = match body {
+                "*" => Json::object([("$ref", Json::Str(component(&request)))]),
+                field => self.field_path(&request, msg, field)?,
+            };
+            operation.push((
+                "requestBody".to_string(),
+                Json::object([
+                    ("required", Json::Bool(true)),
+                    ("content", content(rpc.stream_request, schema)),
+                ]),
+            ));
+        }
+
+        let (response, response_msg) = self.message(&rpc.response, scope)?;
+        let schema = match rule.get("response_body") {
+            Some(OptionValue::Str { value }) if !value.is_empty() => {
+                self.field_path(&response, response_msg, value)?
+            }
+            _ => self.definitions.schema(&format!(".{}", response), "")?,
+        };
+        operation.push((
+            "responses".to_string(),
+            Json::object([(
+                "200",
+                Json::object([
+                    ("description", Json::str("A successful response.")),
+                    ("content", content(rpc.stream_response, schema)),
+                ]),
+            )]),
+        ));
+        if rpc.deprecated {
+            operation.push(("deprecated".to_string(), Json::Bool(true)));
+        }
+        if let Some(streaming) = streaming {
+            operation.push(("x-streaming".to_string(), Json::str(streaming)));
+        }
+
+        Ok((path, Json::Object(operation)))
+    }
+
+    /// Schema of the field at the given (dotted) path below the message.
+    fn field_path(&self, full_name: &str, msg: &Msg, path: &str) -> Result<Json, PtError> {
+        let (name, rest) = match path.split_once('.') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        let field = fields(msg)
+            .find(|field| matches!(field, Field::Single { name: other, .. } if other == name))
+            .ok_or_else(|| {
+                PtError::ParsingError(format!(
+                    "field {} of the HTTP rule is no field of {}",
+                    path, full_name
+                ))
+            })?;
+
+        match (field, rest) {
+            (Field::Single { field_type, .. }, Some(rest)) => {
+                let (full_name, msg) = self.message(field_type, full_name)?;
+                self.field_path(&full_name, msg, rest)
+            }
+            _ => Ok(self.definitions.property(field, full_name)?.1),
+        }
+    }
+
+    /// Whether fields of the given type can be given as query parameters,
+    /// i.e. scalars, enums and the well-known types given by strings.
+    fn is_query(&self, field_type: &str, scope: &str) -> bool {
+        // scalars, unresolved types fail as properties
+        let Some(ptype) = self.registry.resolve(field_type, scope) else {
+            return true;
+        };
+        ptype.kind == Kind::Enum
+            || json::wrapped(&ptype.full_name).is_some()
+            || matches!(
+                ptype.full_name.as_str(),
+                "google.protobuf.Timestamp"
+                    | "google.protobuf.Duration"
+                    | "google.protobuf.FieldMask"
+            )
+    }
+
+    /// Schemas of the types referenced by the paths, directly or by other
+    /// schemas.
+    fn components(&self, paths: &Json) -> Result<Vec<(String, Json)>, PtError> {
+        let mut definitions = Vec::new();
+        for proto in self.protos {
+            definitions.extend(self.definitions.of(proto)?);
+        }
+
+        let mut pending = Vec::new();
+        references(paths, &mut pending);
+        let mut referenced = BTreeSet::new();
+        while let Some(full_name) = pending.pop() {
+            if !referenced.insert(full_name.clone()) {
+                continue;
+            }
+            if let Some((_, schema)) = definitions.iter().find(|(name, _)| *name == full_name) {
+                references(schema, &mut pending);
+            }
+        }
+
+        Ok(definitions
+            .into_iter()
+            .filter(|(name, _)| referenced.contains(name))
+            .collect())
+    }
+}
+
+/// Fields of the message including the members of its oneofs.
+fn fields(msg: &Msg) -> impl Iterator<Item = &Field> {
+    msg.fields.iter().flat_map(|field| match field {
+        Field::OneOf { fields, .. } => fields.iter().collect::<Vec<_>>(),
+        field => vec![field],
+    })
+}
+
+/// Types of the components referenced by the given schema.
+fn references(json: &Json, names: &mut Vec<String>) {
+    match json {
+        Json::Object(fields) => {
+            for (key, value) in fields {
+                match (key.as_str(), value) {
+                    ("$ref", Json::Str(reference)) => {
+                        if let Some(name) = reference.strip_prefix("#/components/schemas/") {
+                            names.push(name.to_string());
+                        }
+                    }
+                    _ => references(value, names),
+                }
+            }
+        }
+        Json::Array(values) => values.iter().for_each(|value| references(value, names)),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{document, path_variables};
+    use crate::parser::parse;
+    use crate::Opts;
+
+    #[test]
+    fn openapi_path_variables() {
+        assert_eq!(
+            path_variables("/v1/{name=users/*}/books/{book.id}:read"),
+            (
+                "/v1/{name}/books/{book.id}:read".to_string(),
+                vec!["name".to_string(), "book.id".to_string()]
+            )
+        );
+        assert_eq!(
+            path_variables("/v1/users"),
+            ("/v1/users".to_string(), vec![])
+        );
+    }
+
+    #[test]
+    fn openapi_document() {
+        let opts = Opts::default();
+        let p = parse(
+            &opts,
+            "users.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message User { string name = 1; Kind kind = 2; }
+enum Kind { KIND_UNSPECIFIED = 0; }
+message GetUserRequest { string name = 1; bool full = 2; User filter = 3; }
+message UpdateUserRequest { User user = 1; string etag = 2; }
+message Unused {}
+service Users {
+  // Get a user by name.
+  rpc GetUser(GetUserRequest) returns (User) {
+    option (google.api.http) = {
+      get: "/v1/{name=users/*}"
+      additional_bindings { post: "/v1/users:get" body: "*" }
+    };
+  }
+  rpc UpdateUser(UpdateUserRequest) returns (User) {
+    option (google.api.http) = { patch: "/v1/{user.name}" body: "user" };
+  }
+  rpc WatchUsers(GetUserRequest) returns (stream User) {
+    option (google.api.http) = { get: "/v1/users:watch" };
+  }
+  rpc Internal(GetUserRequest) returns (User);
+}"#,
+        )
+        .unwrap();
+
+        let document = document(&p, &[]).unwrap().to_string();
+        assert!(document.starts_with(
+            r##"{"openapi":"3.1.0","info":{"title":"foo.v1","version":"1.0.0"},"paths":{"/v1/{name}":{"get":{"tags":["foo.v1.Users"],"operationId":"Users_GetUser","description":"Get a user by name.","parameters":[{"name":"name","in":"path","required":true,"schema":{"type":"string"}},{"name":"full","in":"query","schema":{"type":"boolean"}}],"##
+        ), "{}", document);
+        assert!(document.contains(
+            r#""/v1/users:get":{"post":{"tags":["foo.v1.Users"],"operationId":"Users_GetUser2","#
+        ));
+        assert!(document.contains(r##""requestBody":{"required":true,"content":{"application/json":{"schema":{"$ref":"#/components/schemas/foo.v1.GetUserRequest"}}}}"##));
+        // the user is the body, its name the path parameter
+        assert!(document.contains(r##""/v1/{user.name}":{"patch":{"tags":["foo.v1.Users"],"operationId":"Users_UpdateUser","parameters":[{"name":"user.name","in":"path","required":true,"schema":{"type":"string"}},{"name":"etag","in":"query","schema":{"type":"string"}}],"requestBody":{"required":true,"content":{"application/json":{"schema":{"$ref":"#/components/schemas/foo.v1.User"}}}}"##));
+        assert!(document.contains(r##""content":{"application/x-ndjson":{"schema":{"$ref":"#/components/schemas/foo.v1.User"}}}}},"x-streaming":"server"}"##));
+        assert!(!document.contains("Internal"));
+
+        let components = &document[document.find(r#""components""#).unwrap()..];
+        assert!(components.contains(r#""foo.v1.User":{"type":"object","#));
+        assert!(components.contains(r#""foo.v1.Kind":{"enum":["KIND_UNSPECIFIED",0]}"#));
+        assert!(components.contains(r#""foo.v1.GetUserRequest""#));
+        assert!(!components.contains("Unused"));
+    }
+}
@@ -1,42 +1,61 @@
-use nom::branch::alt;
-use nom::bytes::complete::escaped;
-use nom::bytes::complete::is_not;
-use nom::bytes::complete::tag;
-use nom::bytes::complete::take_until;
-use nom::bytes::complete::take_while;
-use nom::character::complete::alpha1;
-use nom::character::complete::alphanumeric1;
-use nom::character::complete::char;
-use nom::character::complete::multispace1;
-use nom::character::complete::one_of;
-use nom::character::complete::space0;
-use nom::combinator::map_res;
-use nom::combinator::opt;
-use nom::combinator::recognize;
-use nom::error::VerboseError;
-use nom::multi::many0;
-use nom::multi::many1;
-use nom::multi::separated_list0;
-use nom::multi::separated_list1;
-use nom::sequence::delimited;
-use nom::sequence::pair;
-use nom::sequence::preceded;
-use nom::IResult;
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{Duration, Instant};
 
 use crate::errors;
 use crate::errors::PtError;
+use crate::lexer;
+use crate::lexer::{Kind, Token};
 use crate::Opts;
 
-type ParserResult<'a, O> = IResult<&'a str, O, VerboseError<&'a str>>;
+/// Top-level elements taking longer than this are reported as slow.
+const SLOW_ELEMENT: Duration = Duration::from_millis(10);
 
-#[derive(Debug)]
+struct ElemStats {
+    line: usize,
+    elem: String,
+    elapsed: Duration,
+    tokens: usize,
+}
+
+/// Leading comment of a definition with the comment markers stripped, i.e.
+/// the comments directly preceding it without a blank line in between.
+pub type Doc = std::option::Option<String>;
+
+/// Position of a definition in the source file: byte offsets of its start
+/// and end along with the 1-based line and (byte) column it starts at. All
+/// zero for definitions not parsed from source, e.g. decoded descriptors.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug)]
 pub struct Proto {
     pub file: String,
+    /// `proto2`, `proto3` or `editions`
     pub syntax: String,
+    /// edition of files using `edition = "2023"` instead of a syntax
+    pub edition: std::option::Option<String>,
     pub elems: Vec<Elem>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+impl Proto {
+    pub fn package(&self) -> std::option::Option<&str> {
+        self.elems.iter().find_map(|elem| match elem {
+            Elem::Package { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Flag {
     None,
     Optional,
@@ -45,592 +64,2573 @@ pub enum Flag {
     Required,
 }
 
-#[derive(Debug)]
+/// Editions whose features are understood, i.e. their defaults are the
+/// ones of edition 2023.
+pub const EDITIONS: [&str; 2] = ["2023", "2024"];
+
+/// Highest valid field number, `max` of reserved ranges.
+pub const MAX_FIELD_NUMBER: i32 = 536_870_911;
+
+#[derive(Clone, Debug)]
 pub enum ReservedField {
-    Idx { idx: Vec<i32> },
-    Name { name: Vec<String> },
+    Idx {
+        idx: Vec<i32>,
+    },
+    /// ranges of field numbers with inclusive ends, e.g. `5 to 10, 100 to
+    /// max`; single numbers given along with ranges are ranges of one number
+    Range {
+        ranges: Vec<(i32, i32)>,
+    },
+    Name {
+        name: Vec<String>,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Field {
     Single {
         name: String,
         field_type: String,
         idx: i32,
         flag: Flag,
+        options: Vec<Option>,
+        /// given via `[deprecated = true]`
+        deprecated: bool,
+        /// name of the field in JSON given via the `json_name` option, the
+        /// lowerCamelCase name is used otherwise
+        json_name: std::option::Option<String>,
+        doc: Doc,
+        span: Span,
     },
     Map {
         name: String,
         key_type: String,
         value_type: String,
         idx: i32,
+        options: Vec<Option>,
+        deprecated: bool,
+        doc: Doc,
+        span: Span,
     },
     OneOf {
         name: String,
         fields: Vec<Field>,
+        options: Vec<Option>,
+        span: Span,
     },
     SubMessage(Msg),
     SubEnum(Enum),
     Reserved(ReservedField),
-    Extensions(String, String),
+    /// ranges of extension field numbers with inclusive ends, e.g. `100 to
+    /// 199, 300, 1000 to max`
+    Extensions {
+        ranges: Vec<(i32, i32)>,
+        options: Vec<Option>,
+        span: Span,
+    },
+    /// extension fields declared in the scope of the message, e.g. `extend
+    /// Foo { optional Bar bar = 100; }` of proto2 files
+    Extend {
+        name: String,
+        fields: Vec<Field>,
+        span: Span,
+    },
     Option(Option),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Rpc {
     pub name: String,
     pub request: String,
     pub stream_request: bool,
     pub response: String,
     pub stream_response: bool,
+    pub deprecated: bool,
+    pub options: Vec<Option>,
+    pub doc: Doc,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum EnumValue {
-    Single { name: String, idx: i32 },
-    Reserved { idx: i32 },
+    Single {
+        name: String,
+        idx: i32,
+        options: Vec<Option>,
+        deprecated: bool,
+        doc: Doc,
+        span: Span,
+    },
+    Reserved {
+        idx: i32,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum OptionValue {
-    Str { value: String },
-    Constant { value: String },
-    Num { value: i32 },
-    Bool { value: bool },
+    Str {
+        value: String,
+    },
+    Constant {
+        value: String,
+    },
+    Num {
+        value: Number,
+    },
+    Bool {
+        value: bool,
+    },
+    /// list literal, e.g. `[1, 2, 3]`, of an option or aggregate field
     Array(Vec<OptionValue>),
-    Msg,
+    /// aggregate (message literal) value with its fields in order, extension
+    /// fields are named in brackets, e.g. `[foo.v1.ext]`
+    Msg(Vec<(String, OptionValue)>),
 }
 
-#[derive(Debug)]
+impl OptionValue {
+    /// First value of the given field of an aggregate value.
+    pub fn get<'a>(&'a self, field: &'a str) -> std::option::Option<&'a OptionValue> {
+        self.get_all(field).next()
+    }
+
+    /// All values of the given (repeated) field of an aggregate value, in
+    /// order; the elements of list values are returned one by one.
+    pub fn get_all<'a>(&'a self, field: &'a str) -> impl Iterator<Item = &'a OptionValue> + 'a {
+        let fields = match self {
+            OptionValue::Msg(fields) => fields.as_slice(),
+            _ => &[],
+        };
+        fields
+            .iter()
+            .filter(move |(name, _)| name == field)
+            .flat_map(|(_, value)| match value {
+                OptionValue::Array(values) => values.iter().collect::<Vec<_>>(),
+                value => vec![value],
+            })
+    }
+}
+
+/// Numeric constant: integers may be given in decimal, hex (`0x1F`) or octal
+/// (`017`) notation, floating point numbers include `inf` and `nan`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    /// integer of any protobuf integer type, from `i64::MIN` to `u64::MAX`
+    Int(i128),
+    Float(f64),
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Int(value) => write!(f, "{}", value),
+            Number::Float(value) if value.is_nan() => write!(f, "nan"),
+            Number::Float(value) if value.is_infinite() => {
+                write!(f, "{}inf", if *value < 0.0 { "-" } else { "" })
+            }
+            Number::Float(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Msg {
     pub name: String,
     pub fields: Vec<Field>,
+    /// given via `option deprecated = true;` in the message body
+    pub deprecated: bool,
+    pub doc: Doc,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+impl Msg {
+    /// Options given in the message body, e.g. `option deprecated = true;`.
+    pub fn options(&self) -> impl Iterator<Item = &Option> {
+        self.fields.iter().filter_map(|field| match field {
+            Field::Option(opt) => Some(opt),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Enum {
     pub name: String,
     pub values: Vec<EnumValue>,
+    pub options: Vec<Option>,
+    /// given via `option deprecated = true;`
+    pub deprecated: bool,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+impl Enum {
+    /// Name of the value fields of the enum default to: the value numbered
+    /// zero, which proto3 enums start with, or else the first value, e.g. the
+    /// negative `UNKNOWN = -1` of a proto2 enum.
+    pub fn default_value(&self) -> std::option::Option<&str> {
+        let mut values = self.values.iter().filter_map(|value| match value {
+            EnumValue::Single { name, idx, .. } => Some((name.as_str(), *idx)),
+            EnumValue::Reserved { .. } => None,
+        });
+        let first = values.clone().next()?;
+        let zero = values.find(|(_, idx)| *idx == 0);
+        Some(zero.unwrap_or(first).0)
+    }
+
+    /// Whether multiple values may share the same number.
+    pub fn allow_alias(&self) -> bool {
+        self.options.iter().any(|opt| {
+            opt.name == "allow_alias" && matches!(opt.value, OptionValue::Bool { value: true })
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Option {
-    pub name: String,
+    pub name: OptionName,
     pub value: OptionValue,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+/// Name of an option as a sequence of simple names and (parenthesized)
+/// extension names, e.g. `(validate.rules).string.min_len`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptionName {
+    pub parts: Vec<OptionNamePart>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionNamePart {
+    Name(String),
+    /// name of an extension field, fully-qualified if starting with a dot
+    Extension(String),
+}
+
+impl OptionName {
+    /// Dotted name with the parentheses of extension names (and their
+    /// leading dots) stripped, e.g. `validate.rules.string.min_len`.
+    pub fn path(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                OptionNamePart::Name(name) => name.as_str(),
+                OptionNamePart::Extension(name) => name.trim_start_matches('.'),
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Name of the extension the option starts with, if any.
+    pub fn extension(&self) -> std::option::Option<&str> {
+        match self.parts.first() {
+            Some(OptionNamePart::Extension(name)) => Some(name.trim_start_matches('.')),
+            _ => None,
+        }
+    }
+}
+
+/// Option name of simple (not extension) dotted names.
+impl From<&str> for OptionName {
+    fn from(name: &str) -> OptionName {
+        OptionName {
+            parts: name
+                .split('.')
+                .map(|part| OptionNamePart::Name(part.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl PartialEq<str> for OptionName {
+    fn eq(&self, other: &str) -> bool {
+        self.path() == other
+    }
+}
+
+impl PartialEq<&str> for OptionName {
+    fn eq(&self, other: &&str) -> bool {
+        self.path() == *other
+    }
+}
+
+impl std::fmt::Display for OptionName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, part) in self.parts.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ".")?;
+            }
+            match part {
+                OptionNamePart::Name(name) => write!(f, "{}", name)?,
+                OptionNamePart::Extension(name) => write!(f, "({})", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum ServiceNode {
     Rpc(Rpc),
     Option(Option),
 }
 
-#[derive(Debug)]
+/// Modifier of an import statement, e.g. `import public "a.proto";`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportModifier {
+    None,
+    /// types of the imported file are re-exported to the importers of the
+    /// importing file
+    Public,
+    /// the imported file may be missing
+    Weak,
+}
+
+#[derive(Clone, Debug)]
 pub enum Elem {
     Message(Msg),
     Enum(Enum),
     Option(Option),
     Import {
         name: String,
+        modifier: ImportModifier,
+        span: Span,
     },
     Package {
         name: String,
+        span: Span,
     },
     Extend {
         name: String,
         fields: Vec<Field>,
+        span: Span,
     },
     Service {
         name: String,
         nodes: Vec<ServiceNode>,
+        span: Span,
     },
 }
 
-fn import(input: &str) -> ParserResult<Elem> {
-    let (input, _) = tag("import")(input)?;
-    let (input, import) = ws(str)(input)?;
-    let (input, _) = tag(";")(input)?;
+/// Scalar types allowed as map keys: integral types and strings, i.e. not
+/// floating point numbers, bytes, enums or messages.
+const MAP_KEY_TYPES: [&str; 12] = [
+    "int32", "int64", "uint32", "uint64", "sint32", "sint64", "fixed32", "fixed64", "sfixed32",
+    "sfixed64", "bool", "string",
+];
 
-    Ok((
-        input,
-        Elem::Import {
-            name: import.to_string(),
-        },
-    ))
+/// Syntax error at the token of the given index.
+#[derive(Debug)]
+struct Error {
+    token: usize,
+    message: String,
 }
 
-fn package(input: &str) -> ParserResult<Elem> {
-    let (input, _) = tag("package")(input)?;
-    let (input, package) = ws(is_not(";"))(input)?;
-    let (input, _) = tag(";")(input)?;
+type Parsed<T> = Result<T, Error>;
 
-    Ok((
-        input,
-        Elem::Package {
-            name: package.to_string(),
-        },
-    ))
+/// Statement of an enum body, values and options may be interleaved.
+enum EnumStatement {
+    Value(EnumValue),
+    Option(Option),
 }
 
-fn option_map_value(input: &str) -> ParserResult<&str> {
-    let (input, _name) = identifier(input)?;
-    let (input, _) = opt(ws(tag(":")))(input)?;
-    let (input, _value) = ws(option_value)(input)?;
-    let (input, _) = opt(one_of(",;"))(input)?;
-
-    Ok((input, ""))
+/// Recursive descent parser working on the tokens of a proto file (see
+/// `lexer`). Keywords are contextual, so statements are told apart by
+/// looking ahead at the tokens following them instead of backtracking.
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    /// offsets the lines of the input start at
+    lines: Vec<usize>,
+    /// syntax errors of the statements skipped, see `recovering`
+    diagnostics: Vec<errors::Diagnostic>,
+    /// problems of the file that do not fail the parsing
+    warnings: Vec<String>,
+    /// parse timings of the top-level elements, if enabled (see `-vv`)
+    stats: std::option::Option<Vec<ElemStats>>,
 }
 
-fn option_value<'a>(input: &'a str) -> ParserResult<OptionValue> {
-    let str = |i| {
-        let (i, value) = str(i)?;
-        Ok((
-            i,
-            OptionValue::Str {
-                value: value.to_string(),
-            },
-        ))
-    };
-    let num = |i| {
-        let (i, value) = number(i)?;
-        Ok((i, OptionValue::Num { value }))
-    };
-    let bool = |i| {
-        let (i, value) = boolean(i)?;
-        Ok((i, OptionValue::Bool { value }))
-    };
-    let constant = |i: &'a str| {
-        let (i, value) = constant(i)?;
-        Ok((
-            i,
-            OptionValue::Constant {
-                value: value.to_string(),
-            },
-        ))
-    };
-    let array = |i| {
-        let (i, _) = ws(tag("["))(i)?;
-        let (i, vs) = separated_list0(tag(","), ws(option_value))(i)?;
-        let (i, _) = ws(tag("]"))(i)?;
-        Ok((i, OptionValue::Array(vs)))
-    };
-    let msg = |i| {
-        let (i, _) = tag("{")(i)?;
-        let (i, _values) = many0(ws(option_map_value))(i)?;
-        let (i, _) = ws(tag("}"))(i)?;
-        Ok((i, OptionValue::Msg))
-    };
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        let (tokens, lex_errors) = lexer::tokenize(input);
+        let lines = std::iter::once(0)
+            .chain(input.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            lines,
+            diagnostics: Vec::new(),
+            warnings: Vec::new(),
+            stats: None,
+        };
+        for error in lex_errors {
+            parser.diagnose(error.offset, error.message.to_string());
+        }
+        parser
+    }
 
-    alt((str, num, bool, msg, array, constant))(input)
-}
+    fn peek(&self) -> Token<'a> {
+        self.tokens[self.pos]
+    }
+
+    /// Token the given number of tokens ahead of the current one.
+    fn nth(&self, n: usize) -> Token<'a> {
+        self.tokens[(self.pos + n).min(self.tokens.len() - 1)]
+    }
 
-fn option_name(input: &str) -> ParserResult<&str> {
-    let (input, _) = opt(tag("("))(input)?;
-    let (input, val) = ws(identifier)(input)?;
-    let (input, _) = opt(tag(")"))(input)?;
+    fn bump(&mut self) -> Token<'a> {
+        let token = self.peek();
+        if token.kind != Kind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
 
-    Ok((input, val))
-}
+    fn is(&self, text: &str) -> bool {
+        self.peek().is(text)
+    }
 
-fn option(input: &str) -> ParserResult<Option> {
-    let (input, _) = tag("option")(input)?;
-    let (input, option_name) = ws(option_name)(input)?;
-    let (input, _) = tag("=")(input)?;
-    let (input, value) = ws(option_value)(input)?;
-    let (input, _) = tag(";")(input)?;
+    fn eat(&mut self, text: &str) -> bool {
+        let is = self.is(text);
+        if is {
+            self.pos += 1;
+        }
+        is
+    }
 
-    Ok((
-        input,
-        Option {
-            name: option_name.to_string(),
-            value,
-        },
-    ))
-}
+    /// Error at the current token, which is not what was expected.
+    fn error<T>(&self, expected: &str) -> Parsed<T> {
+        Err(Error {
+            token: self.pos,
+            message: format!("expected {}, found {}", expected, self.peek()),
+        })
+    }
 
-fn syntax(input: &str) -> ParserResult<&str> {
-    let (input, _) = tag("syntax")(input)?;
-    let (input, _) = ws(tag("="))(input)?;
-    let (input, version) = ws(str)(input)?;
-    let (input, _) = tag(";")(input)?;
+    /// Consume the given punctuation or keyword, e.g. `;` after a field.
+    fn expect(&mut self, text: &str, context: &str) -> Parsed<()> {
+        if self.eat(text) {
+            return Ok(());
+        }
+        self.error(&format!("`{}` {}", text, context))
+    }
 
-    Ok((input, version))
-}
+    fn ident(&mut self, what: &str) -> Parsed<&'a str> {
+        match self.peek().kind {
+            Kind::Ident => Ok(self.bump().text),
+            _ => self.error(what),
+        }
+    }
 
-fn field_flag(input: &str) -> ParserResult<Flag> {
-    let (input, flag0) = opt(alt((tag("optional"), tag("repeated"), tag("required"))))(input)?;
-    let flag = match flag0 {
-        Some("optional") => Flag::Optional,
-        Some("repeated") => Flag::Repeated,
-        Some("required") => Flag::Required,
-        _ => Flag::None,
-    };
+    /// Possibly qualified name, e.g. `foo.v1.User`.
+    fn full_ident(&mut self, what: &str) -> Parsed<String> {
+        let mut name = self.ident(what)?.to_string();
+        while self.is(".") && self.nth(1).kind == Kind::Ident {
+            name.push('.');
+            name.push_str(self.nth(1).text);
+            self.pos += 2;
+        }
+        Ok(name)
+    }
 
-    Ok((input, flag))
-}
+    /// Type reference, fully-qualified if starting with a dot, e.g.
+    /// `.google.protobuf.Any`.
+    fn type_reference(&mut self, what: &str) -> Parsed<String> {
+        if self.is(".") && self.nth(1).kind == Kind::Ident {
+            self.pos += 1;
+            return Ok(format!(".{}", self.full_ident(what)?));
+        }
+        self.full_ident(what)
+    }
 
-fn enum_reserved_value(input: &str) -> ParserResult<EnumValue> {
-    let (input, _) = tag("reserved")(input)?;
-    let (input, idx) = ws(number)(input)?;
-    let (input, _) = tag(";")(input)?;
+    /// Number of tokens of the type reference the given number of tokens
+    /// ahead, if there is one.
+    fn type_len(&self, ahead: usize) -> std::option::Option<usize> {
+        let mut len = usize::from(self.nth(ahead).is("."));
+        loop {
+            if self.nth(ahead + len).kind != Kind::Ident {
+                return None;
+            }
+            if !(self.nth(ahead + len + 1).is(".") && self.nth(ahead + len + 2).kind == Kind::Ident)
+            {
+                return Some(len + 1);
+            }
+            len += 2;
+        }
+    }
 
-    Ok((input, EnumValue::Reserved { idx }))
-}
+    /// String literal, adjacent literals (e.g. spanning multiple lines) are
+    /// joined like protoc does.
+    fn string(&mut self, what: &str) -> Parsed<String> {
+        if self.peek().kind != Kind::Str {
+            return self.error(what);
+        }
+        let mut value = String::new();
+        while self.peek().kind == Kind::Str {
+            value.push_str(&unquote(self.bump().text));
+        }
+        Ok(value)
+    }
 
-fn enum_value(input: &str) -> ParserResult<EnumValue> {
-    let (input, name) = ws(identifier)(input)?;
-    let (input, _) = tag("=")(input)?;
-    let (input, idx) = ws(number)(input)?;
-    let (input, _) = opt(field_options)(input)?;
-    let (input, _) = space0(input)?;
-    let (input, _) = tag(";")(input)?;
+    /// Span from the token of the given index to the last token consumed.
+    fn span(&self, start: usize) -> Span {
+        let end = self.tokens[self.pos.max(start + 1) - 1].end();
+        self.span_of(self.tokens[start].start, end)
+    }
 
-    Ok((
-        input,
-        EnumValue::Single {
-            name: name.to_string(),
-            idx,
-        },
-    ))
-}
-
-fn enum_field(input: &str) -> ParserResult<Enum> {
-    let (input, _) = tag("enum")(input)?;
-    let (input, name) = ws(identifier)(input)?;
-    let (input, _) = tag("{")(input)?;
-    let (input, values) = many0(ws(alt((enum_reserved_value, enum_value))))(input)?;
-    let (input, _) = ws(tag("}"))(input)?;
-    let (input, _) = opt(tag(";"))(input)?;
-
-    Ok((
-        input,
-        Enum {
-            name: name.to_string(),
-            values,
-        },
-    ))
-}
-
-fn proto_map(input: &str) -> ParserResult<Field> {
-    let (input, _) = tag("map")(input)?;
-    let (input, _) = space0(input)?;
-    let (input, _) = tag("<")(input)?;
-    let (input, key_type) = ws(identifier)(input)?;
-    let (input, _) = tag(",")(input)?;
-    let (input, value_type) = ws(identifier)(input)?;
-    let (input, _) = tag(">")(input)?;
-    let (input, name) = ws(identifier)(input)?;
-    let (input, _) = tag("=")(input)?;
-    let (input, idx) = ws(number)(input)?;
-    let (input, _) = opt(field_options)(input)?;
-    let (input, _) = tag(";")(input)?;
-
-    Ok((
-        input,
-        Field::Map {
-            name: name.to_string(),
-            key_type: key_type.to_string(),
-            value_type: value_type.to_string(),
-            idx,
-        },
-    ))
-}
-
-fn oneof(input: &str) -> ParserResult<Field> {
-    let (input, _) = tag("oneof")(input)?;
-    let (input, name) = ws(identifier)(input)?;
-    let (input, _) = tag("{")(input)?;
-    let (input, fields) = many0(ws(field))(input)?;
-    let (input, _) = ws(tag("}"))(input)?;
-    let (input, _) = opt(tag(";"))(input)?;
-
-    Ok((
-        input,
-        Field::OneOf {
-            name: name.to_string(),
-            fields,
-        },
-    ))
-}
+    fn span_of(&self, start: usize, end: usize) -> Span {
+        let line = self
+            .lines
+            .partition_point(|line_start| *line_start <= start);
+        Span {
+            start,
+            end,
+            line,
+            column: start - self.lines[line - 1] + 1,
+        }
+    }
 
-fn extend(input: &str) -> ParserResult<Elem> {
-    let (input, _) = tag("extend")(input)?;
-    let (input, name) = ws(identifier)(input)?;
-    let (input, _) = tag("{")(input)?;
-    let (input, fields) = many0(ws(message_field))(input)?;
-    let (input, _) = tag("}")(input)?;
+    fn diagnose(&mut self, offset: usize, message: String) {
+        let span = self.span_of(offset, offset);
+        self.diagnostics.push(errors::Diagnostic {
+            line: span.line,
+            column: span.column,
+            message,
+        });
+    }
 
-    Ok((
-        input,
-        Elem::Extend {
-            name: name.to_string(),
-            fields,
-        },
-    ))
-}
-
-fn field_option(input: &str) -> ParserResult<Option> {
-    let (input, name) = ws(option_name)(input)?;
-    let (input, _) = tag("=")(input)?;
-    let (input, value) = ws(option_value)(input)?;
-    Ok((
-        input,
-        Option {
-            name: name.to_string(),
-            value,
-        },
-    ))
-}
+    /// Statements of a block up to its closing brace. Empty statements
+    /// (stray semicolons) in between are skipped, protoc accepts them as well.
+    fn statements<T>(
+        &mut self,
+        mut statement: impl FnMut(&mut Self) -> Parsed<T>,
+    ) -> Parsed<Vec<T>> {
+        let mut statements = Vec::new();
+        loop {
+            if self.eat(";") {
+                continue;
+            }
+            if self.is("}") || self.peek().kind == Kind::Eof {
+                return Ok(statements);
+            }
+            statements.push(statement(self)?);
+        }
+    }
 
-fn field_options(input: &str) -> ParserResult<Vec<Option>> {
-    let (input, _) = tag("[")(input)?;
-    let (input, vs) = separated_list0(tag(","), field_option)(input)?;
-    let (input, _) = tag("]")(input)?;
+    /// Statements like `statements`, but invalid statements are recorded as
+    /// diagnostics and skipped instead, so all syntax errors are reported at
+    /// once. Top-level statements end at the end of the input only.
+    fn recovering<T>(
+        &mut self,
+        nested: bool,
+        mut statement: impl FnMut(&mut Self) -> Parsed<T>,
+    ) -> Vec<T> {
+        let mut statements = Vec::new();
+        loop {
+            if self.eat(";") {
+                continue;
+            }
+            let token = self.peek();
+            if token.kind == Kind::Eof || (nested && token.is("}")) {
+                return statements;
+            }
 
-    Ok((input, vs))
-}
+            let start = self.pos;
+            match statement(self) {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    self.diagnose(self.tokens[error.token].start, error.message);
+                    self.skip_statement(start, error.token);
+                }
+            }
+        }
+    }
 
-fn message_field(input: &str) -> ParserResult<Field> {
-    let (input, flag) = field_flag(input)?;
-    let (input, field_type) = ws(identifier)(input)?;
-    let (input, name) = ws(identifier)(input)?;
-    let (input, _) = tag("=")(input)?;
-    let (input, idx) = ws(number)(input)?;
-    let (input, _) = opt(field_options)(input)?;
-    let (input, _) = space0(input)?;
-    let (input, _) = tag(";")(input)?;
+    /// Continue after the invalid statement starting at the given token: at
+    /// the token the error occurred at if it begins a new line (e.g. after a
+    /// missing semicolon), past the semicolon or block of the statement
+    /// otherwise. Closing braces of enclosing blocks are not skipped.
+    fn skip_statement(&mut self, start: usize, error: usize) {
+        let skipped = &self.tokens[start..error.max(start)];
+        let opened = skipped.iter().filter(|token| token.is("{")).count();
+        let closed = skipped.iter().filter(|token| token.is("}")).count();
+        if error > start && opened == closed && self.tokens[error].space.contains('\n') {
+            self.pos = error;
+            return;
+        }
 
-    Ok((
-        input,
-        Field::Single {
-            field_type: field_type.to_string(),
-            name: name.to_string(),
-            idx,
-            flag,
-        },
-    ))
-}
+        self.pos = start;
+        let mut depth = 0;
+        loop {
+            let token = self.peek();
+            if token.kind == Kind::Eof {
+                return;
+            }
+            if token.is("}") && depth == 0 {
+                // stray closing brace at the top level
+                if self.pos == start {
+                    self.pos += 1;
+                }
+                return;
+            }
+
+            self.pos += 1;
+            if token.is("{") {
+                depth += 1;
+            } else if token.is("}") {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            } else if token.is(";") && depth == 0 {
+                return;
+            }
+        }
+    }
 
-fn extensions_field(input: &str) -> ParserResult<Field> {
-    let (input, _) = tag("extensions")(input)?;
-    let (input, from) = ws(alphanumeric1)(input)?;
-    let (input, _) = tag("to")(input)?;
-    let (input, to) = ws(alphanumeric1)(input)?;
-    let (input, _) = tag(";")(input)?;
+    fn proto(&mut self, file: &str, input: &str) -> Proto {
+        let (syntax, edition) = if !self.is("syntax") && !self.is("edition") {
+            // protoc defaults files without a syntax statement to proto2 as well
+            self.warnings.push(format!(
+                "{}: no syntax specified, defaulting to proto2 \
+                 (add `syntax = \"proto2\";` to silence this warning)",
+                file
+            ));
+            ("proto2".to_string(), None)
+        } else {
+            match self.syntax() {
+                Ok(syntax) => syntax,
+                Err(error) => {
+                    self.diagnose(self.tokens[error.token].start, error.message);
+                    self.skip_statement(0, error.token);
+                    ("proto2".to_string(), None)
+                }
+            }
+        };
+        let mut elems = self.recovering(false, Parser::timed_element);
+
+        if edition.is_some() {
+            let file_options = elems
+                .iter()
+                .filter_map(|elem| match elem {
+                    Elem::Option(opt) => Some(opt.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let default = field_presence(&file_options)
+                .unwrap_or("EXPLICIT")
+                .to_string();
+
+            for elem in elems.iter_mut() {
+                match elem {
+                    Elem::Message(msg) => apply_field_presence(&mut msg.fields, &default),
+                    Elem::Extend { fields, .. } => apply_field_presence(fields, &default),
+                    _ => (),
+                }
+            }
+        }
 
-    Ok((input, Field::Extensions(from.to_string(), to.to_string())))
-}
+        Proto {
+            file: file.to_string(),
+            syntax,
+            edition,
+            elems,
+            span: self.span_of(0, input.len()),
+        }
+    }
+
+    /// Syntax or edition statement the file starts with.
+    fn syntax(&mut self) -> Parsed<(String, std::option::Option<String>)> {
+        let keyword = self.peek().text;
+        self.pos += 1;
+        self.expect("=", &format!("after `{}`", keyword))?;
+        let version_start = self.pos;
+        let version = self.string(&format!("{} string", keyword))?;
+        self.expect(";", &format!("after {}", keyword))?;
+
+        if keyword == "edition" && !EDITIONS.contains(&version.as_str()) {
+            return Err(Error {
+                token: version_start,
+                message: format!(
+                    "unsupported edition \"{}\", supported are {}",
+                    version,
+                    EDITIONS.join(", ")
+                ),
+            });
+        }
+        if keyword == "edition" {
+            Ok(("editions".to_string(), Some(version)))
+        } else {
+            Ok((version, None))
+        }
+    }
+
+    /// Top-level element, its parse time is recorded in case statistics are
+    /// enabled (see `-vv`).
+    fn timed_element(&mut self) -> Parsed<Elem> {
+        if self.stats.is_none() {
+            return self.element();
+        }
+
+        let start = self.pos;
+        let started = Instant::now();
+        let result = self.element();
+        let elapsed = started.elapsed();
+
+        if let Ok(elem) = &result {
+            let stats = ElemStats {
+                line: self.span(start).line,
+                elem: describe(elem),
+                elapsed,
+                tokens: self.pos - start,
+            };
+            if let Some(all) = self.stats.as_mut() {
+                all.push(stats);
+            }
+        }
+
+        result
+    }
+
+    fn element(&mut self) -> Parsed<Elem> {
+        match self.peek().text {
+            "import" => self.import(),
+            "package" => self.package(),
+            "option" => self.option().map(Elem::Option),
+            "message" => self.message().map(Elem::Message),
+            "enum" => self.enum_definition().map(Elem::Enum),
+            "service" => self.service(),
+            "extend" => {
+                let (name, fields, span) = self.extend_block()?;
+                Ok(Elem::Extend { name, fields, span })
+            }
+            _ => self
+                .error("`message`, `enum`, `service`, `extend`, `option`, `import` or `package`"),
+        }
+    }
+
+    fn import(&mut self) -> Parsed<Elem> {
+        let start = self.pos;
+        self.pos += 1;
+        let modifier = match self.peek().text {
+            "public" if self.nth(1).kind == Kind::Str => ImportModifier::Public,
+            "weak" if self.nth(1).kind == Kind::Str => ImportModifier::Weak,
+            _ => ImportModifier::None,
+        };
+        if modifier != ImportModifier::None {
+            self.pos += 1;
+        }
+        let name = self.string("file name after `import`")?;
+        self.expect(";", "after import")?;
 
-fn reserved_field(input: &str) -> ParserResult<ReservedField> {
-    let by_idx = map_res(separated_list1(ws(char(',')), number), |v| {
-        Ok::<ReservedField, &str>(ReservedField::Idx { idx: v })
-    });
-    let by_name = map_res(separated_list1(ws(char(',')), str), |v| {
-        Ok::<ReservedField, &str>(ReservedField::Name {
-            name: v.into_iter().map(|v| v.to_string()).collect(),
+        Ok(Elem::Import {
+            name,
+            modifier,
+            span: self.span(start),
         })
-    });
-
-    alt((by_idx, by_name))(input)
-}
-
-fn message_field_reserved(input: &str) -> ParserResult<Field> {
-    let (input, _) = tag("reserved")(input)?;
-    let (input, reserved) = ws(reserved_field)(input)?;
-    let (input, _) = tag(";")(input)?;
-
-    Ok((input, Field::Reserved(reserved)))
-}
-
-fn field(input: &str) -> ParserResult<Field> {
-    alt((
-        oneof,
-        message_field_reserved,
-        message_field,
-        proto_map,
-        extensions_field,
-        map_res(option, |v| Ok::<Field, &str>(Field::Option(v))),
-        map_res(message, |v| Ok::<Field, &str>(Field::SubMessage(v))),
-        map_res(enum_field, |v| Ok::<Field, &str>(Field::SubEnum(v))),
-    ))(input)
-}
-
-fn rpc_opts(input: &str) -> ParserResult<&str> {
-    let (input, _) = tag("{")(input)?;
-    let (input, _) = whitespace(input)?;
-    let (input, _options) = many0(ws(option))(input)?;
-    let (input, _) = ws(tag("}"))(input)?;
-
-    Ok((input, ""))
-}
-
-fn rpc(input: &str) -> ParserResult<ServiceNode> {
-    let (input, _) = tag("rpc")(input)?;
-    let (input, name) = ws(alphanumeric1)(input)?;
-    let (input, _) = ws(tag("("))(input)?;
-    let (input, stream_request) = opt(tag("stream"))(input)?;
-    let (input, request) = ws(identifier)(input)?;
-    let (input, _) = ws(tag(")"))(input)?;
-    let (input, _) = tag("returns")(input)?;
-    let (input, _) = ws(tag("("))(input)?;
-    let (input, stream_response) = opt(tag("stream"))(input)?;
-    let (input, response) = ws(identifier)(input)?;
-    let (input, _) = ws(tag(")"))(input)?;
-    let (input, _) = opt(rpc_opts)(input)?;
-    let (input, _) = opt(tag(";"))(input)?;
-
-    Ok((
-        input,
-        ServiceNode::Rpc(Rpc {
-            name: name.to_string(),
-            request: request.to_string(),
-            stream_request: stream_request.is_some(),
-            response: response.to_string(),
-            stream_response: stream_response.is_some(),
-        }),
-    ))
-}
-
-fn service_option(input: &str) -> ParserResult<ServiceNode> {
-    let (input, opt) = option(input)?;
-    Ok((input, ServiceNode::Option(opt)))
-}
-
-fn service(input: &str) -> ParserResult<Elem> {
-    let (input, _) = tag("service")(input)?;
-    let (input, name) = ws(alphanumeric1)(input)?;
-    let (input, _) = ws(tag("{"))(input)?;
-    let (input, _) = whitespace(input)?;
-    let (input, nodes) = many0(ws(alt((rpc, service_option))))(input)?;
-    let (input, _) = ws(tag("}"))(input)?;
-
-    Ok((
-        input,
-        Elem::Service {
-            name: name.to_string(),
-            nodes,
-        },
-    ))
-}
-
-fn message(input: &str) -> ParserResult<Msg> {
-    let (input, _) = tag("message")(input)?;
-    let (input, name) = ws(alphanumeric1)(input)?;
-    let (input, _) = ws(tag("{"))(input)?;
-    let (input, fields) = many0(ws(field))(input)?;
-    let (input, _) = ws(tag("}"))(input)?;
-    let (input, _) = opt(tag(";"))(input)?;
-
-    Ok((
-        input,
-        Msg {
-            name: name.to_string(),
-            fields,
-        },
-    ))
-}
+    }
 
-fn number(input: &str) -> ParserResult<i32> {
-    map_res(recognize(many1(one_of("01234567890-"))), str::parse)(input)
-}
+    fn package(&mut self) -> Parsed<Elem> {
+        let start = self.pos;
+        self.pos += 1;
+        let name = self.full_ident("package name")?;
+        self.expect(";", "after package name")?;
 
-fn boolean(input: &str) -> ParserResult<bool> {
-    let (input, value) = alt((tag("true"), tag("false")))(input)?;
-    let val = match value {
-        "true" => true,
-        "false" => false,
-        _ => unreachable!(),
-    };
-    Ok((input, val))
-}
+        Ok(Elem::Package {
+            name,
+            span: self.span(start),
+        })
+    }
 
-// I don't know if this "has" to be that complicated...
-fn ws<'a, T, F>(mut inner: F) -> impl FnMut(&'a str) -> ParserResult<T>
-where
-    F: FnMut(&'a str) -> ParserResult<T>,
-{
-    move |i| delimited(whitespace, &mut inner, whitespace)(i)
-}
+    fn option(&mut self) -> Parsed<Option> {
+        let start = self.pos;
+        self.expect("option", "statement")?;
+        let name = self.option_name()?;
+        self.expect("=", "after option name")?;
+        let value = self.option_value()?;
+        self.expect(";", "after option value")?;
 
-fn whitespace(input: &str) -> ParserResult<&str> {
-    let single_line_comment = preceded(tag("//"), take_while(|chr| chr != '\r' && chr != '\n'));
-    let multiline_comment = delimited(tag("/*"), take_until("*/"), tag("*/"));
-    recognize(many0(alt((
-        single_line_comment,
-        multiline_comment,
-        multispace1,
-    ))))(input)
-}
+        Ok(Option {
+            name,
+            value,
+            span: self.span(start),
+        })
+    }
 
-fn constant(input: &str) -> ParserResult<&str> {
-    recognize(pair(alpha1, many0(alt((alphanumeric1, tag("_"))))))(input)
-}
+    fn option_name(&mut self) -> Parsed<OptionName> {
+        let mut parts = vec![self.option_name_part()?];
+        while self.eat(".") {
+            parts.push(self.option_name_part()?);
+        }
+        Ok(OptionName { parts })
+    }
 
-fn identifier(input: &str) -> ParserResult<&str> {
-    recognize(pair(
-        alpha1,
-        many0(alt((alphanumeric1, tag("."), tag("_")))),
-    ))(input)
-}
+    /// Part of an option name: a simple name or a parenthesized extension name
+    /// that may be fully-qualified, e.g. `(.foo.v1.opt)`.
+    fn option_name_part(&mut self) -> Parsed<OptionNamePart> {
+        if self.eat("(") {
+            let name = self.type_reference("extension name")?;
+            self.expect(")", "after extension name")?;
+            return Ok(OptionNamePart::Extension(name));
+        }
+        let name = self.ident("option name")?;
+        Ok(OptionNamePart::Name(name.to_string()))
+    }
 
-fn str(input: &str) -> ParserResult<&str> {
-    delimited(
-        char('"'),
-        escaped(is_not("\\\""), '\\', one_of("\"\n\\")),
-        char('"'),
-    )(input)
-}
+    fn option_value(&mut self) -> Parsed<OptionValue> {
+        let token = self.peek();
+        match token.kind {
+            Kind::Str => {
+                let value = self.string("option value")?;
+                return Ok(OptionValue::Str { value });
+            }
+            Kind::Number => {
+                return Ok(OptionValue::Num {
+                    value: self.numeric()?,
+                })
+            }
+            _ => (),
+        }
+
+        match token.text {
+            "-" | "inf" | "infinity" | "nan" => Ok(OptionValue::Num {
+                value: self.numeric()?,
+            }),
+            "true" | "false" => {
+                self.pos += 1;
+                Ok(OptionValue::Bool {
+                    value: token.text == "true",
+                })
+            }
+            "{" => self.aggregate(),
+            "[" => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                while !self.is("]") {
+                    values.push(self.option_value()?);
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                self.expect("]", "after list values")?;
+                Ok(OptionValue::Array(values))
+            }
+            _ if token.kind == Kind::Ident => {
+                self.pos += 1;
+                Ok(OptionValue::Constant {
+                    value: token.text.to_string(),
+                })
+            }
+            _ => self.error("option value"),
+        }
+    }
 
-fn parse0<'a>(file_name: &'a str, input: &'a str) -> ParserResult<'a, Proto> {
-    let (input, syntax) = ws(syntax)(input)?;
-    let (input, elems) = many0(ws(alt((
-        import,
-        package,
-        extend,
-        map_res(option, |v| Ok::<Elem, &str>(Elem::Option(v))),
-        map_res(message, |v| Ok::<Elem, &str>(Elem::Message(v))),
-        map_res(enum_field, |v| Ok::<Elem, &str>(Elem::Enum(v))),
-        service,
-    ))))(input)?;
+    /// Aggregate (message literal) value in text format, e.g. `{ get: "/v1" }`.
+    fn aggregate(&mut self) -> Parsed<OptionValue> {
+        self.expect("{", "of message value")?;
+        let mut fields = Vec::new();
+        while !self.is("}") {
+            let name = self.aggregate_key()?;
+            self.eat(":");
+            let value = self.option_value()?;
+            if !self.eat(",") {
+                self.eat(";");
+            }
+            fields.push((name, value));
+        }
+        self.expect("}", "after message value")?;
 
-    let fname = file_name.to_string();
+        Ok(OptionValue::Msg(fields))
+    }
 
-    Ok((
-        input,
-        Proto {
-            file: fname,
-            syntax: syntax.to_string(),
-            elems,
-        },
-    ))
-}
+    /// Field of an aggregate value: a field name, an extension name or an `Any`
+    /// type URL in brackets.
+    fn aggregate_key(&mut self) -> Parsed<String> {
+        if !self.eat("[") {
+            return self.full_ident("field name");
+        }
+
+        let mut key = String::from("[");
+        while matches!(self.peek().kind, Kind::Ident | Kind::Number) || self.is(".") || self.is("/")
+        {
+            key.push_str(self.bump().text);
+        }
+        self.expect("]", "after extension name")?;
+        key.push(']');
+        Ok(key)
+    }
+
+    /// Options of a field or enum value, e.g. `[deprecated = true]`, if any.
+    fn field_options(&mut self) -> Parsed<Vec<Option>> {
+        let mut options = Vec::new();
+        if !self.eat("[") {
+            return Ok(options);
+        }
 
-pub fn parse(opts: &Opts, input: &str) -> Result<Proto, PtError> {
-    match parse0(&opts.file, input) {
-        Ok(("", proto)) => Ok(proto),
-        Ok((_, incomplete_proto)) => {
-            if opts.verbose {
-                eprintln!("{:?}", incomplete_proto);
+        while !self.is("]") {
+            let start = self.pos;
+            let name = self.option_name()?;
+            self.expect("=", "after option name")?;
+            let value = self.option_value()?;
+            options.push(Option {
+                name,
+                value,
+                span: self.span(start),
+            });
+            if !self.eat(",") {
+                break;
             }
-            Err(errors::PtError::IncompleteParsing)
         }
-        Err(err) => Err(errors::PtError::ParsingError(err.to_string())),
+        self.expect("]", "after field options")?;
+
+        Ok(options)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::Opts;
+    /// Numeric constant: integers may be given in decimal, hex (`0x1F`) or octal
+    /// (`017`) notation, floating point numbers include `inf` and `nan`.
+    fn numeric(&mut self) -> Parsed<Number> {
+        let negative = self.eat("-");
+        let token = self.peek();
+        let literal = match token.kind {
+            Kind::Number => token.text,
+            Kind::Ident if matches!(token.text, "inf" | "infinity" | "nan") => token.text,
+            _ => return self.error("number"),
+        };
 
-    const TEST_INPUT: &str = std::include_str!("../assets/example.proto");
+        match number_literal(literal, negative) {
+            Some(number) => {
+                self.pos += 1;
+                Ok(number)
+            }
+            None => Err(Error {
+                token: self.pos,
+                message: format!("invalid number {}", token),
+            }),
+        }
+    }
 
-    #[test]
-    fn parse_example_file_is_ok() {
-        let opts = Opts {
-            file: "".to_string(),
-            verbose: false,
+    /// Field or enum value number.
+    fn number(&mut self, what: &str) -> Parsed<i32> {
+        let start = self.pos;
+        if self.peek().kind != Kind::Number && !self.is("-") {
+            return self.error(what);
+        }
+
+        let invalid = |message: String| Error {
+            token: start,
+            message,
         };
-        let parsed = super::parse(&opts, TEST_INPUT);
-        assert_eq!(parsed.is_ok(), true);
+        match self.numeric()? {
+            Number::Int(value) => i32::try_from(value)
+                .map_err(|_| invalid(format!("{} {} out of range", what, value))),
+            Number::Float(value) => Err(invalid(format!(
+                "expected integer {}, found `{}`",
+                what,
+                Number::Float(value)
+            ))),
+        }
+    }
+
+    fn enum_definition(&mut self) -> Parsed<Enum> {
+        let start = self.pos;
+        self.pos += 1;
+        let name = self.ident("enum name")?.to_string();
+        self.expect("{", "after enum name")?;
+        let statements = self.statements(Parser::enum_statement)?;
+        self.expect("}", "at end of enum")?;
+        self.eat(";");
+
+        let mut values = Vec::new();
+        let mut options = Vec::new();
+        for statement in statements {
+            match statement {
+                EnumStatement::Value(value) => values.push(value),
+                EnumStatement::Option(opt) => options.push(opt),
+            }
+        }
+
+        Ok(Enum {
+            name,
+            values,
+            deprecated: is_deprecated(&options),
+            options,
+            span: self.span(start),
+        })
+    }
+
+    /// Option, reserved number or value of an enum; values may be named like
+    /// the keywords, e.g. `option = 1;`.
+    fn enum_statement(&mut self) -> Parsed<EnumStatement> {
+        if self.nth(1).is("=") {
+            return self.enum_value().map(EnumStatement::Value);
+        }
+        match self.peek().text {
+            "option" => self.option().map(EnumStatement::Option),
+            "reserved" => {
+                self.pos += 1;
+                let idx = self.number("reserved number")?;
+                self.expect(";", "after reserved number")?;
+                Ok(EnumStatement::Value(EnumValue::Reserved { idx }))
+            }
+            _ => self.enum_value().map(EnumStatement::Value),
+        }
+    }
+
+    fn enum_value(&mut self) -> Parsed<EnumValue> {
+        let start = self.pos;
+        let doc = self.peek().doc();
+        let name = self.ident("enum value name")?.to_string();
+        self.expect("=", "after enum value name")?;
+        let idx = self.number("enum value number")?;
+        let options = self.field_options()?;
+        self.expect(";", "after enum value")?;
+
+        Ok(EnumValue::Single {
+            name,
+            idx,
+            deprecated: is_deprecated(&options),
+            options,
+            doc,
+            span: self.span(start),
+        })
+    }
+
+    fn message(&mut self) -> Parsed<Msg> {
+        let start = self.pos;
+        let doc = self.peek().doc();
+        self.pos += 1;
+        let name = self.ident("message name")?.to_string();
+        self.expect("{", "after message name")?;
+        let fields = self.recovering(true, Parser::field);
+        self.expect("}", "at end of message")?;
+        self.eat(";");
+
+        let deprecated = fields.iter().any(|field| match field {
+            Field::Option(opt) => is_deprecated(std::slice::from_ref(opt)),
+            _ => false,
+        });
+
+        Ok(Msg {
+            name,
+            deprecated,
+            fields,
+            doc,
+            span: self.span(start),
+        })
+    }
+
+    /// Statement of a message body. Fields of types named like keywords, e.g.
+    /// `message service = 4;`, take precedence over the keyword statements.
+    fn field(&mut self) -> Parsed<Field> {
+        if self.is_field() {
+            return self.single_field();
+        }
+
+        match self.peek().text {
+            "oneof" => self.oneof(),
+            "reserved" => self.reserved(),
+            "extensions" => self.extensions(),
+            "map" if self.nth(1).is("<") => self.map_field(),
+            "option" => self.option().map(Field::Option),
+            "extend" => {
+                let (name, fields, span) = self.extend_block()?;
+                Ok(Field::Extend { name, fields, span })
+            }
+            "message" => self.message().map(Field::SubMessage),
+            "enum" => self.enum_definition().map(Field::SubEnum),
+            _ => self.single_field(),
+        }
+    }
+
+    /// Whether a field definition follows: `[label] type name = number`.
+    fn is_field(&self) -> bool {
+        let label = usize::from(self.field_label().is_some());
+        self.type_len(label).is_some_and(|len| {
+            let number = self.nth(label + len + 2);
+            self.nth(label + len).kind == Kind::Ident
+                && self.nth(label + len + 1).is("=")
+                && (number.kind == Kind::Number || number.is("-"))
+        })
+    }
+
+    /// Label of the field definition at the current token, unless the label
+    /// is the type of the field instead, e.g. `optional reserved = 2;` or
+    /// `optional.Type value = 3;`.
+    fn field_label(&self) -> std::option::Option<Flag> {
+        let flag = match self.peek().text {
+            "optional" => Flag::Optional,
+            "repeated" => Flag::Repeated,
+            "required" => Flag::Required,
+            _ => return None,
+        };
+        if self.nth(1).is(".") && self.nth(1).space.is_empty() {
+            return None;
+        }
+        self.type_len(1)
+            .filter(|len| self.nth(1 + len).kind == Kind::Ident)
+            .map(|_| flag)
+    }
+
+    fn single_field(&mut self) -> Parsed<Field> {
+        let start = self.pos;
+        let doc = self.peek().doc();
+        let flag = match self.field_label() {
+            Some(flag) => {
+                self.pos += 1;
+                flag
+            }
+            None => Flag::None,
+        };
+        let field_type = self.type_reference("field type")?;
+        let name = self.ident("field name")?.to_string();
+        self.expect("=", "after field name")?;
+        let idx = self.number("field number")?;
+        let options = self.field_options()?;
+        self.expect(";", "after field")?;
+
+        Ok(Field::Single {
+            name,
+            field_type,
+            idx,
+            flag,
+            json_name: json_name(&options),
+            deprecated: is_deprecated(&options),
+            options,
+            doc,
+            span: self.span(start),
+        })
+    }
+
+    fn map_field(&mut self) -> Parsed<Field> {
+        let start = self.pos;
+        let doc = self.peek().doc();
+        self.pos += 2;
+        let key_start = self.pos;
+        let key_type = self.full_ident("map key type")?;
+        if !MAP_KEY_TYPES.contains(&key_type.as_str()) {
+            // enums are a common attempt, their JSON keys would be the value
+            // names, which is what string keys are for
+            return Err(Error {
+                token: key_start,
+                message: format!(
+                    "invalid map key type `{}`, keys are of integral or string type \
+                     (use `string` keys holding the value names for enum keys)",
+                    key_type
+                ),
+            });
+        }
+        self.expect(",", "after map key type")?;
+        let value_type = self.type_reference("map value type")?;
+        self.expect(">", "after map value type")?;
+        let name = self.ident("field name")?.to_string();
+        self.expect("=", "after field name")?;
+        let idx = self.number("field number")?;
+        let options = self.field_options()?;
+        self.expect(";", "after field")?;
+
+        Ok(Field::Map {
+            name,
+            key_type,
+            value_type,
+            idx,
+            deprecated: is_deprecated(&options),
+            options,
+            doc,
+            span: self.span(start),
+        })
+    }
+
+    fn oneof(&mut self) -> Parsed<Field> {
+        let start = self.pos;
+        self.pos += 1;
+        let name = self.ident("oneof name")?.to_string();
+        self.expect("{", "after oneof name")?;
+        let statements = self.statements(Parser::field)?;
+        self.expect("}", "at end of oneof")?;
+        self.eat(";");
+
+        let mut fields = Vec::new();
+        let mut options = Vec::new();
+        for statement in statements {
+            match statement {
+                Field::Option(opt) => options.push(opt),
+                field => fields.push(field),
+            }
+        }
+
+        Ok(Field::OneOf {
+            name,
+            fields,
+            options,
+            span: self.span(start),
+        })
+    }
+
+    /// Extended message and extension fields of an `extend` block, at the top
+    /// level or nested in a message.
+    fn extend_block(&mut self) -> Parsed<(String, Vec<Field>, Span)> {
+        let start = self.pos;
+        self.pos += 1;
+        let name = self.type_reference("extended message")?;
+        self.expect("{", "after extended message")?;
+        let fields = self.statements(Parser::single_field)?;
+        self.expect("}", "at end of extend block")?;
+
+        Ok((name, fields, self.span(start)))
+    }
+
+    fn reserved(&mut self) -> Parsed<Field> {
+        self.pos += 1;
+        let reserved = if self.peek().kind == Kind::Str {
+            let mut name = vec![self.string("reserved name")?];
+            while self.eat(",") {
+                name.push(self.string("reserved name")?);
+            }
+            ReservedField::Name { name }
+        } else {
+            let ranges = self.ranges()?;
+            if ranges.iter().all(|(_, end)| end.is_none()) {
+                ReservedField::Idx {
+                    idx: ranges.into_iter().map(|(start, _)| start).collect(),
+                }
+            } else {
+                ReservedField::Range {
+                    ranges: ranges
+                        .into_iter()
+                        .map(|(start, end)| (start, end.unwrap_or(start)))
+                        .collect(),
+                }
+            }
+        };
+        self.expect(";", "after reserved")?;
+
+        Ok(Field::Reserved(reserved))
+    }
+
+    fn extensions(&mut self) -> Parsed<Field> {
+        let start = self.pos;
+        self.pos += 1;
+        let ranges = self
+            .ranges()?
+            .into_iter()
+            .map(|(start, end)| (start, end.unwrap_or(start)))
+            .collect();
+        let options = self.field_options()?;
+        self.expect(";", "after extensions")?;
+
+        Ok(Field::Extensions {
+            ranges,
+            options,
+            span: self.span(start),
+        })
+    }
+
+    /// Field numbers or ranges of field numbers, e.g. `5, 8 to 10, 100 to
+    /// max`, of reserved and extensions statements.
+    fn ranges(&mut self) -> Parsed<Vec<(i32, std::option::Option<i32>)>> {
+        let mut ranges = Vec::new();
+        loop {
+            let token = self.pos;
+            let start = self.number("field number")?;
+            let end = if !self.eat("to") {
+                None
+            } else if self.eat("max") {
+                Some(MAX_FIELD_NUMBER)
+            } else {
+                Some(self.number("end of range")?)
+            };
+            if let Some(end) = end.filter(|end| *end < start) {
+                return Err(Error {
+                    token,
+                    message: format!(
+                        "invalid range `{} to {}`, the start is greater than the end",
+                        start, end
+                    ),
+                });
+            }
+            ranges.push((start, end));
+            if !self.eat(",") {
+                return Ok(ranges);
+            }
+        }
+    }
+
+    fn service(&mut self) -> Parsed<Elem> {
+        let start = self.pos;
+        self.pos += 1;
+        let name = self.ident("service name")?.to_string();
+        self.expect("{", "after service name")?;
+        let nodes = self.statements(|p| match p.peek().text {
+            "option" => p.option().map(ServiceNode::Option),
+            _ => p.rpc(),
+        })?;
+        self.expect("}", "at end of service")?;
+
+        Ok(Elem::Service {
+            name,
+            nodes,
+            span: self.span(start),
+        })
+    }
+
+    fn rpc(&mut self) -> Parsed<ServiceNode> {
+        let start = self.pos;
+        let doc = self.peek().doc();
+        if !self.eat("rpc") {
+            return self.error("`rpc` or `option`");
+        }
+        let name = self.ident("method name")?.to_string();
+        self.expect("(", "after method name")?;
+        let (stream_request, request) = self.rpc_type("request type")?;
+        self.expect(")", "after request type")?;
+        self.expect("returns", "after request type")?;
+        self.expect("(", "after `returns`")?;
+        let (stream_response, response) = self.rpc_type("response type")?;
+        self.expect(")", "after response type")?;
+
+        let mut options = Vec::new();
+        if self.eat("{") {
+            options = self.statements(|p| {
+                if p.is("option") {
+                    p.option()
+                } else {
+                    p.error("`option`")
+                }
+            })?;
+            self.expect("}", "at end of method options")?;
+        }
+        self.eat(";");
+
+        Ok(ServiceNode::Rpc(Rpc {
+            name,
+            request,
+            stream_request,
+            response,
+            stream_response,
+            deprecated: is_deprecated(&options),
+            options,
+            doc,
+            span: self.span(start),
+        }))
+    }
+
+    /// Request or response type of a method, along with whether it is
+    /// streamed; `stream` is a type name of its own if no type follows.
+    fn rpc_type(&mut self, what: &str) -> Parsed<(bool, String)> {
+        let stream = self.is("stream") && self.type_len(1).is_some();
+        if stream {
+            self.pos += 1;
+        }
+        Ok((stream, self.type_reference(what)?))
+    }
+}
+
+/// Value of the number literal, if valid.
+fn number_literal(literal: &str, negative: bool) -> std::option::Option<Number> {
+    let sign = if negative { -1 } else { 1 };
+    let number = if let Some(hex) = literal.strip_prefix("0x").or(literal.strip_prefix("0X")) {
+        i128::from_str_radix(hex, 16).ok().map(Number::Int)
+    } else if literal.starts_with(['i', 'n']) || literal.contains(['.', 'e', 'E']) {
+        let value = match literal {
+            "inf" | "infinity" => Some(f64::INFINITY),
+            "nan" => Some(f64::NAN),
+            _ => literal.parse().ok(),
+        };
+        value.map(|value: f64| Number::Float(value * f64::from(sign)))
+    } else if literal.len() > 1 && literal.starts_with('0') {
+        i128::from_str_radix(&literal[1..], 8).ok().map(Number::Int)
+    } else {
+        literal.parse().ok().map(Number::Int)
+    };
+
+    match number {
+        Some(Number::Int(value)) => Some(Number::Int(value * sign as i128)),
+        number => number,
+    }
+}
+
+/// Value of the (double or single quoted) string literal with its escape
+/// sequences resolved.
+fn unquote(literal: &str) -> String {
+    let (quote, inner) = literal.split_at(1);
+    unescape(inner.strip_suffix(quote).unwrap_or(inner))
+}
+
+/// Resolve the escape sequences of a string literal, octal and hex escapes
+/// denote single bytes. String literals of the text format use the same
+/// escapes (see `textproto`).
+pub(crate) fn unescape(literal: &str) -> String {
+    let mut str = String::with_capacity(literal.len());
+    let mut chars = literal.chars().peekable();
+
+    while let Some(chr) = chars.next() {
+        if chr != '\\' {
+            str.push(chr);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => str.push('\n'),
+            Some('r') => str.push('\r'),
+            Some('t') => str.push('\t'),
+            Some('a') => str.push('\x07'),
+            Some('b') => str.push('\x08'),
+            Some('f') => str.push('\x0c'),
+            Some('v') => str.push('\x0b'),
+            Some('x') => str.push(code(&mut chars, 16, 2, 0)),
+            Some('u') => str.push(code(&mut chars, 16, 4, 0)),
+            Some('U') => str.push(code(&mut chars, 16, 8, 0)),
+            Some(digit @ '0'..='7') => str.push(code(&mut chars, 8, 2, digit as u32 - '0' as u32)),
+            Some(other) => str.push(other),
+            None => (),
+        }
+    }
+
+    str
+}
+
+/// Character of the escape code consisting of up to `max` further digits
+/// following the digits of `value`.
+fn code(chars: &mut Peekable<Chars>, radix: u32, max: usize, mut value: u32) -> char {
+    for _ in 0..max {
+        match chars.peek().and_then(|chr| chr.to_digit(radix)) {
+            Some(digit) => {
+                value = value * radix + digit;
+                chars.next();
+            }
+            None => break,
+        }
+    }
+    char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// Value of the `json_name` option, if given.
+fn json_name(options: &[Option]) -> std::option::Option<String> {
+    options.iter().rev().find_map(|opt| match &opt.value {
+        OptionValue::Str { value } if opt.name == "json_name" => Some(value.clone()),
+        _ => None,
+    })
+}
+
+/// Value of the `features.field_presence` option, if given.
+fn field_presence(options: &[Option]) -> std::option::Option<&str> {
+    options.iter().rev().find_map(|opt| match &opt.value {
+        OptionValue::Constant { value } if opt.name == "features.field_presence" => {
+            Some(value.as_str())
+        }
+        _ => None,
+    })
+}
+
+/// Editions do not have `optional` and `required` labels but determine the
+/// presence of fields via the (inherited) `field_presence` feature instead,
+/// which is mapped onto the respective flags.
+fn apply_field_presence(fields: &mut [Field], inherited: &str) {
+    let message_options = fields
+        .iter()
+        .filter_map(|field| match field {
+            Field::Option(opt) => Some(opt.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let default = field_presence(&message_options)
+        .map(str::to_string)
+        .unwrap_or_else(|| inherited.to_string());
+
+    for field in fields {
+        match field {
+            Field::Single {
+                flag: flag @ Flag::None,
+                options,
+                ..
+            } => {
+                *flag = match field_presence(options).unwrap_or(&default) {
+                    "IMPLICIT" => Flag::None,
+                    "LEGACY_REQUIRED" => Flag::Required,
+                    _ => Flag::Optional,
+                };
+            }
+            Field::SubMessage(msg) => apply_field_presence(&mut msg.fields, &default),
+            Field::Extend { fields, .. } => apply_field_presence(fields, &default),
+            _ => (),
+        }
+    }
+}
+
+pub(crate) fn is_deprecated(options: &[Option]) -> bool {
+    options.iter().any(|opt| {
+        opt.name == "deprecated" && matches!(opt.value, OptionValue::Bool { value: true })
+    })
+}
+
+fn describe(elem: &Elem) -> String {
+    match elem {
+        Elem::Message(msg) => format!("message {}", msg.name),
+        Elem::Enum(e) => format!("enum {}", e.name),
+        Elem::Option(opt) => format!("option {}", opt.name),
+        Elem::Import { name, .. } => format!("import {}", name),
+        Elem::Package { name, .. } => format!("package {}", name),
+        Elem::Extend { name, .. } => format!("extend {}", name),
+        Elem::Service { name, .. } => format!("service {}", name),
+    }
+}
+
+fn report_stats(opts: &Opts, file: &str, stats: &[ElemStats], total: Duration) {
+    opts.messages.push(format!("parse timings of {}:", file));
+    for stat in stats {
+        let slow = stat.elapsed > SLOW_ELEMENT;
+        opts.messages.push(format!(
+            "  line {:>5}: {:<40} {:>10.3?} {:>8} tokens{}",
+            stat.line,
+            stat.elem,
+            stat.elapsed,
+            stat.tokens,
+            if slow { "  <- slow" } else { "" }
+        ));
+    }
+    opts.messages.push(format!("  total: {:.3?}", total));
+}
+
+/// Check that the values of (nested) enums are distinct unless aliases are
+/// allowed explicitly.
+fn check_aliases(file: &str, fields: &[Field]) -> Result<(), PtError> {
+    for field in fields {
+        match field {
+            Field::SubMessage(msg) => check_aliases(file, &msg.fields)?,
+            Field::SubEnum(e) => check_enum_aliases(file, e)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn check_enum_aliases(file: &str, e: &Enum) -> Result<(), PtError> {
+    if e.allow_alias() {
+        return Ok(());
+    }
+
+    let mut numbers = HashMap::new();
+    for value in &e.values {
+        if let EnumValue::Single { name, idx, .. } = value {
+            if let Some(other) = numbers.insert(*idx, name) {
+                return Err(errors::PtError::ParsingError(format!(
+                    "{}: enum value {} uses the same number {} as {}, \
+                     set `option allow_alias = true;` to allow aliases",
+                    file, name, idx, other
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that no two fields of a message (or its nested messages) map to the
+/// same JSON name, which protoc rejects and which would result in duplicate
+/// keys of the generated schemas. Like protoc, conflicts of default JSON
+/// names are only warned about in proto2 files.
+fn check_json_names(
+    file: &str,
+    syntax: &str,
+    msg: &Msg,
+    warnings: &mut Vec<String>,
+) -> Result<(), PtError> {
+    let mut names: HashMap<String, (&str, bool)> = HashMap::new();
+    let mut members = msg.fields.iter().rev().collect::<Vec<_>>();
+    while let Some(member) = members.pop() {
+        let (name, custom) = match member {
+            Field::Single {
+                name, json_name, ..
+            } => (name, json_name.clone()),
+            Field::Map { name, options, .. } => (name, json_name(options)),
+            Field::OneOf { fields, .. } => {
+                members.extend(fields.iter().rev());
+                continue;
+            }
+            Field::SubMessage(sub_msg) => {
+                check_json_names(file, syntax, sub_msg, warnings)?;
+                continue;
+            }
+            _ => continue,
+        };
+
+        let is_custom = custom.is_some();
+        let json_name = custom.unwrap_or_else(|| default_json_name(name));
+        let Some((other, other_custom)) = names.insert(json_name.clone(), (name, is_custom)) else {
+            continue;
+        };
+        let message = format!(
+            "{}: fields {} and {} of message {} map to the same JSON name \"{}\"",
+            file, other, name, msg.name, json_name
+        );
+        if syntax == "proto2" && !is_custom && !other_custom {
+            warnings.push(message);
+        } else {
+            return Err(errors::PtError::ParsingError(message));
+        }
+    }
+    Ok(())
+}
+
+/// Default JSON name of a field as protoc derives it: underscores removed
+/// and the letters following them capitalized, e.g. `foo_bar` as `fooBar`.
+pub(crate) fn default_json_name(name: &str) -> String {
+    let mut json_name = String::with_capacity(name.len());
+    let mut capitalize = false;
+    for chr in name.chars() {
+        match chr {
+            '_' => capitalize = true,
+            _ if capitalize => {
+                json_name.push(chr.to_ascii_uppercase());
+                capitalize = false;
+            }
+            _ => json_name.push(chr),
+        }
+    }
+    json_name
+}
+
+/// Result of parsing a proto file with its invalid statements skipped (see
+/// `parse_partial`).
+pub struct Partial {
+    /// AST of the valid statements
+    pub proto: Proto,
+    /// syntax errors of the statements skipped
+    pub diagnostics: Vec<errors::Diagnostic>,
+    /// problems that do not fail the parsing, e.g. a missing syntax
+    pub warnings: Vec<String>,
+}
+
+/// Parse the given proto file, failing with all of its syntax errors in
+/// case any statement is invalid. In lenient mode (see `--lenient`) invalid
+/// statements are skipped with a warning instead. Warnings are passed on to
+/// the caller via `Opts::messages`.
+pub fn parse(opts: &Opts, file: &str, input: &str) -> Result<Proto, PtError> {
+    let Partial {
+        proto,
+        diagnostics,
+        warnings,
+    } = parse_partial(opts, file, input)?;
+    if !diagnostics.is_empty() && !opts.lenient {
+        return Err(PtError::Syntax {
+            file: file.to_string(),
+            diagnostics,
+        });
+    }
+    for warning in warnings {
+        opts.messages.push(format!("warning: {}", warning));
+    }
+    for diagnostic in diagnostics {
+        opts.messages.push(format!(
+            "warning: {}:{} (statement skipped)",
+            file, diagnostic
+        ));
+    }
+    Ok(proto)
+}
+
+/// Parse the given proto file, skipping invalid statements. The resulting
+/// AST consists of the valid statements only, the skipped ones are returned
+/// as diagnostics.
+pub fn parse_partial(opts: &Opts, file: &str, input: &str) -> Result<Partial, PtError> {
+    let start = Instant::now();
+    let mut parser = Parser::new(input);
+    if opts.verbose > 1 {
+        parser.stats = Some(Vec::new());
+    }
+    let proto = parser.proto(file, input);
+
+    if let Some(stats) = &parser.stats {
+        report_stats(opts, file, stats, start.elapsed());
+    }
+
+    let mut warnings = parser.warnings;
+    for elem in &proto.elems {
+        match elem {
+            Elem::Message(msg) => {
+                check_aliases(file, &msg.fields)?;
+                check_json_names(file, &proto.syntax, msg, &mut warnings)?;
+            }
+            Elem::Enum(e) => check_enum_aliases(file, e)?,
+            _ => (),
+        }
+    }
+
+    let mut diagnostics = parser.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    Ok(Partial {
+        proto,
+        diagnostics,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Elem, EnumValue, Field, Flag, ImportModifier, Number, OptionNamePart, OptionValue, Parsed,
+        Parser, ReservedField, ServiceNode, MAX_FIELD_NUMBER,
+    };
+    use crate::Opts;
+
+    const TEST_INPUT: &str = std::include_str!("../assets/example.proto");
+
+    /// Result of the given grammar rule applied to the input.
+    fn rule<'a, T>(input: &'a str, rule: impl FnOnce(&mut Parser<'a>) -> Parsed<T>) -> Parsed<T> {
+        rule(&mut Parser::new(input))
+    }
+
+    #[test]
+    fn parse_example_file_is_ok() {
+        let opts = Opts::default();
+        let parsed = super::parse(&opts, "example.proto", TEST_INPUT);
+        assert!(parsed.is_ok());
+    }
+
+    #[test]
+    fn parse_deprecated_rpc() {
+        let node = rule(
+            "rpc Old(Req) returns (Res) {\n  option deprecated = true;\n}",
+            |p| p.rpc(),
+        )
+        .unwrap();
+
+        match node {
+            ServiceNode::Rpc(rpc) => assert!(rpc.deprecated),
+            other => panic!("expected rpc, got {:?}", other),
+        }
+
+        let node = rule("rpc New(Req) returns (Res);", |p| p.rpc()).unwrap();
+
+        match node {
+            ServiceNode::Rpc(rpc) => assert!(!rpc.deprecated),
+            other => panic!("expected rpc, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_deprecated_flags() {
+        let input = r#"syntax = "proto3";
+message Old {
+  option deprecated = true;
+  string name = 1 [deprecated = true];
+  map<string, int32> labels = 2 [deprecated = false];
+}
+enum Status {
+  option deprecated = true;
+  ACTIVE = 0 [deprecated = true];
+  INACTIVE = 1;
+}"#;
+        let proto = super::parse(&Opts::default(), "old.proto", input).unwrap();
+
+        let Elem::Message(msg) = &proto.elems[0] else {
+            panic!("expected message, got {:?}", proto.elems[0]);
+        };
+        assert!(msg.deprecated);
+        assert!(matches!(
+            msg.fields[1],
+            Field::Single {
+                deprecated: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            msg.fields[2],
+            Field::Map {
+                deprecated: false,
+                ..
+            }
+        ));
+
+        let Elem::Enum(e) = &proto.elems[1] else {
+            panic!("expected enum, got {:?}", proto.elems[1]);
+        };
+        assert!(e.deprecated);
+        let deprecated = e
+            .values
+            .iter()
+            .map(|value| {
+                matches!(
+                    value,
+                    EnumValue::Single {
+                        deprecated: true,
+                        ..
+                    }
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(deprecated, [true, false]);
+    }
+
+    #[test]
+    fn parse_unknown_edition() {
+        let error = super::parse(
+            &Opts::default(),
+            "user.proto",
+            "edition = \"2099\";\nmessage User {}",
+        )
+        .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("1:11: unsupported edition \"2099\", supported are 2023, 2024"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn parse_edition_field_presence() {
+        let opts = Opts::default();
+        let input = r#"edition = "2023";
+option features.field_presence = IMPLICIT;
+message User {
+  string name = 1;
+  string nickname = 2 [features.field_presence = EXPLICIT];
+  string id = 3 [features.field_presence = LEGACY_REQUIRED];
+  repeated string tags = 4;
+  message Address {
+    option features.field_presence = EXPLICIT;
+    string city = 1;
+  }
+}"#;
+        let proto = super::parse(&opts, "user.proto", input).unwrap();
+
+        assert_eq!(proto.syntax, "editions");
+        assert_eq!(proto.edition.as_deref(), Some("2023"));
+
+        let Some(Elem::Message(msg)) = proto.elems.get(1) else {
+            panic!("expected message, got {:?}", proto.elems);
+        };
+        let flags = msg
+            .fields
+            .iter()
+            .map(|field| match field {
+                Field::Single { flag, .. } => format!("{:?}", flag),
+                Field::SubMessage(sub) => match &sub.fields[1] {
+                    Field::Single { flag, .. } => format!("{:?}", flag),
+                    other => panic!("expected field, got {:?}", other),
+                },
+                other => panic!("unexpected field {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flags,
+            ["None", "Optional", "Required", "Repeated", "Optional"]
+        );
+    }
+
+    #[test]
+    fn parse_extension_option_names() {
+        let opt = rule("option (my.custom.opt).nested.field = 5;", |p| p.option()).unwrap();
+        assert_eq!(
+            opt.name.parts,
+            vec![
+                OptionNamePart::Extension("my.custom.opt".to_string()),
+                OptionNamePart::Name("nested".to_string()),
+                OptionNamePart::Name("field".to_string()),
+            ]
+        );
+        assert_eq!(opt.name.extension(), Some("my.custom.opt"));
+        assert_eq!(opt.name.path(), "my.custom.opt.nested.field");
+
+        let opts = rule(
+            "[(validate.rules).string.min_len = 1, deprecated = true]",
+            |p| p.field_options(),
+        )
+        .unwrap();
+        assert_eq!(opts[0].name.to_string(), "(validate.rules).string.min_len");
+        assert!(opts[1].name == "deprecated");
+
+        let opt = rule("option foo.( .bar.baz ).(qux) = true;", |p| p.option()).unwrap();
+        assert_eq!(opt.name.to_string(), "foo.(.bar.baz).(qux)");
+        assert_eq!(opt.name.path(), "foo.bar.baz.qux");
+
+        assert!(rule("option (unclosed.opt = 1;", |p| p.option()).is_err());
+    }
+
+    #[test]
+    fn parse_aggregate_option_value() {
+        let opt = rule(
+            r#"option (google.api.http) = {
+  post: "/v1/{name=users/*}:activate"
+  body: "*"
+  additional_bindings { get: "/v1/users/{id}" }
+  additional_bindings: [{ put: "/v1/users" }, { patch: "/v1/users" }]
+  [foo.v1.ext]: { enabled: true }
+};"#,
+            |p| p.option(),
+        )
+        .unwrap();
+
+        let value = &opt.value;
+        assert!(matches!(
+            value.get("post"),
+            Some(OptionValue::Str { value }) if value == "/v1/{name=users/*}:activate"
+        ));
+        assert!(value.get("missing").is_none());
+
+        let bindings = value
+            .get_all("additional_bindings")
+            .filter_map(|binding| match binding {
+                OptionValue::Msg(fields) => Some(fields[0].0.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(bindings, ["get", "put", "patch"]);
+
+        assert!(matches!(
+            value.get("[foo.v1.ext]").and_then(|ext| ext.get("enabled")),
+            Some(OptionValue::Bool { value: true })
+        ));
+    }
+
+    #[test]
+    fn parse_adjacent_string_literals() {
+        let opt = rule(
+            "option (foo) = \"part one \" \"part two\"\n  // continued\n  \", part three\";",
+            |p| p.option(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            opt.value,
+            OptionValue::Str { value } if value == "part one part two, part three"
+        ));
+    }
+
+    #[test]
+    fn parse_single_quoted_strings() {
+        let elem = rule("import 'google/api/annotations.proto';", |p| p.import()).unwrap();
+        assert!(
+            matches!(elem, Elem::Import { name, .. } if name == "google/api/annotations.proto")
+        );
+
+        let opt = rule(
+            r#"option (foo) = 'it\'s "quoted"' "\tand\x41\101\n";"#,
+            |p| p.option(),
+        )
+        .unwrap();
+        assert!(matches!(
+            opt.value,
+            OptionValue::Str { value } if value == "it's \"quoted\"\tandAA\n"
+        ));
+    }
+
+    #[test]
+    fn parse_doc_comments() {
+        let input = r#"syntax = "proto3";
+
+/* detached */
+
+// A user
+// of the system.
+message User {
+  string name = 1; // trailing comment of name
+
+  /**
+   * Unique id.
+   */
+  int64 id = 2;
+  map<string, string> labels = 3;
+  enum Kind {
+    // regular user
+    USER = 0;
+    ADMIN = 1;
+  }
+}
+
+service Users {
+  // Fetch a user.
+  rpc Get(User) returns (User);
+}
+"#;
+        let proto = super::parse(&Opts::default(), "user.proto", input).unwrap();
+        let Elem::Message(msg) = &proto.elems[0] else {
+            panic!("expected message");
+        };
+        assert_eq!(msg.doc.as_deref(), Some("A user\nof the system."));
+
+        let docs = msg
+            .fields
+            .iter()
+            .filter_map(|field| match field {
+                Field::Single { doc, .. } | Field::Map { doc, .. } => Some(doc.as_deref()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(docs, vec![None, Some("Unique id."), None]);
+
+        let Some(Field::SubEnum(e)) = msg.fields.last() else {
+            panic!("expected enum");
+        };
+        assert!(matches!(&e.values[0],
+            EnumValue::Single { doc: Some(doc), .. } if doc == "regular user"));
+        assert!(matches!(&e.values[1], EnumValue::Single { doc: None, .. }));
+
+        let Elem::Service { nodes, .. } = &proto.elems[1] else {
+            panic!("expected service");
+        };
+        assert!(matches!(&nodes[0],
+            ServiceNode::Rpc(rpc) if rpc.doc.as_deref() == Some("Fetch a user.")));
+    }
+
+    #[test]
+    fn parse_identifier_names() {
+        let input = r#"syntax = "proto3";
+message My_Message {
+  message _Inner_1 {}
+  _Inner_1 inner = 1;
+}
+service My_Service {
+  rpc Get_Message(My_Message) returns (My_Message._Inner_1);
+}
+"#;
+        let proto = super::parse(&Opts::default(), "names.proto", input).unwrap();
+        assert!(matches!(&proto.elems[0], Elem::Message(msg) if msg.name == "My_Message"));
+        let Elem::Service { name, nodes, .. } = &proto.elems[1] else {
+            panic!("expected service");
+        };
+        assert_eq!(name, "My_Service");
+        assert!(matches!(&nodes[0], ServiceNode::Rpc(rpc)
+            if rpc.name == "Get_Message" && rpc.response == "My_Message._Inner_1"));
+    }
+
+    #[test]
+    fn parse_multiple_field_options() {
+        let input = r#"syntax = "proto3";
+enum Kind { KIND_NONE = 0 [deprecated = true, (my.label) = "none"]; }
+message Foo {
+  int32 x = 1 [deprecated = true, (validate.rules).int32.gt = 0];
+  map<string, int32> y = 2 [ json_name = "why" , deprecated = false ];
+}
+"#;
+        let proto = super::parse(&Opts::default(), "foo.proto", input).unwrap();
+        let Elem::Enum(e) = &proto.elems[0] else {
+            panic!("expected enum");
+        };
+        assert!(matches!(&e.values[0], EnumValue::Single { options, .. }
+            if options.len() == 2 && super::is_deprecated(options)));
+
+        let Elem::Message(msg) = &proto.elems[1] else {
+            panic!("expected message");
+        };
+        let Field::Single { options, .. } = &msg.fields[0] else {
+            panic!("expected field");
+        };
+        assert_eq!(options.len(), 2);
+        assert_eq!(options[1].name.path(), "validate.rules.int32.gt");
+        assert!(matches!(options[1].value, OptionValue::Num { .. }));
+        assert!(matches!(&msg.fields[1], Field::Map { options, .. }
+            if options.len() == 2 && !super::is_deprecated(options)));
+    }
+
+    #[test]
+    fn parse_negative_enum_values() {
+        let input = r#"syntax = "proto2";
+enum Status { UNKNOWN = -1; reserved -5; ACTIVE = 1; }"#;
+        let proto = super::parse(&Opts::default(), "status.proto", input).unwrap();
+
+        let Elem::Enum(e) = &proto.elems[0] else {
+            panic!("expected enum");
+        };
+        assert!(matches!(&e.values[0], EnumValue::Single { idx: -1, .. }));
+        assert!(matches!(&e.values[1], EnumValue::Reserved { idx: -5 }));
+        assert_eq!(e.default_value(), Some("UNKNOWN"));
+    }
+
+    #[test]
+    fn parse_enum_options() {
+        let input = r#"syntax = "proto3";
+enum Status {
+  option allow_alias = true;
+  STATUS_UNKNOWN = 0;
+  STATUS_STARTED = 1;
+  option deprecated = true;
+  STATUS_RUNNING = 1;
+}
+"#;
+        let proto = super::parse(&Opts::default(), "status.proto", input).unwrap();
+        let Elem::Enum(e) = &proto.elems[0] else {
+            panic!("expected enum");
+        };
+        assert_eq!(e.values.len(), 3);
+        assert_eq!(e.options.len(), 2);
+        assert!(e.allow_alias());
+
+        let aliased = input.replace("option allow_alias = true;", "");
+        let err = super::parse(&Opts::default(), "status.proto", &aliased).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("enum value STATUS_RUNNING uses the same number 1 as STATUS_STARTED"));
+    }
+
+    #[test]
+    fn parse_oneof_options() {
+        let input = r#"syntax = "proto3";
+message Shape {
+  oneof kind {
+    option (my.opt) = true;
+    string circle = 1;
+    option (my.label) = "kind";
+    string square = 2;
+  }
+}
+"#;
+        let proto = super::parse(&Opts::default(), "shape.proto", input).unwrap();
+        let Elem::Message(msg) = &proto.elems[0] else {
+            panic!("expected message");
+        };
+        let Field::OneOf {
+            name,
+            fields,
+            options,
+            ..
+        } = &msg.fields[0]
+        else {
+            panic!("expected oneof");
+        };
+        assert_eq!(name, "kind");
+        assert_eq!(fields.len(), 2);
+        assert!(fields
+            .iter()
+            .all(|field| matches!(field, Field::Single { .. })));
+        assert_eq!(options.len(), 2);
+        assert_eq!(options[0].name.to_string(), "(my.opt)");
+    }
+
+    #[test]
+    fn parse_nested_extend() {
+        let input = r#"syntax = "proto2";
+message Foo {
+  extensions 100 to max;
+}
+message Bar {
+  extend Foo {
+    optional Bar bar = 100;
+  }
+  optional string name = 1;
+  extend .Foo { repeated string tags = 101; };
+}
+"#;
+        let proto = super::parse(&Opts::default(), "foo.proto", input).unwrap();
+        let Elem::Message(msg) = &proto.elems[1] else {
+            panic!("expected message");
+        };
+        assert_eq!(msg.fields.len(), 3);
+        assert!(matches!(&msg.fields[0],
+            Field::Extend { name, fields, .. } if name == "Foo" && fields.len() == 1));
+        assert!(matches!(&msg.fields[1], Field::Single { name, .. } if name == "name"));
+        assert!(matches!(&msg.fields[2], Field::Extend { name, .. } if name == ".Foo"));
+    }
+
+    #[test]
+    fn parse_recovers_from_invalid_statements() {
+        let input = r#"syntax = "proto3";
+
+message User {
+  string name = ;
+  int32 age = 2;
+  repeated = 3;
+}
+enum Role { ROLE_ = "none"; }
+message Address { string city = 1; }
+"#;
+        let opts = Opts::default();
+        let super::Partial {
+            proto, diagnostics, ..
+        } = super::parse_partial(&opts, "user.proto", input).unwrap();
+
+        let at = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column))
+            .collect::<Vec<_>>();
+        assert_eq!(at, vec![(4, 17), (6, 12), (8, 21)]);
+        let messages = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "expected field number, found `;`",
+                "expected field name, found `=`",
+                "expected enum value number, found string \"none\"",
+            ]
+        );
+
+        assert_eq!(proto.elems.len(), 2);
+        assert!(matches!(&proto.elems[0], Elem::Message(msg) if msg.fields.len() == 1));
+        assert!(matches!(&proto.elems[1], Elem::Message(msg) if msg.name == "Address"));
+
+        let error = super::parse(&opts, "user.proto", input).unwrap_err();
+        assert!(error.to_string().starts_with(
+            "proto parsing failed with 3 error(s):\n  user.proto:4:17: expected field number"
+        ));
+    }
+
+    #[test]
+    fn parse_returns_warnings() {
+        let opts = Opts {
+            lenient: true,
+            ..Opts::default()
+        };
+        let input = "message User {\n  optional string foo_bar = 1;\n  optional string fooBar = 2;\n}\nunknown_statement foo;";
+        let partial = super::parse_partial(&opts, "user.proto", input).unwrap();
+        assert_eq!(
+            partial.warnings,
+            [
+                "user.proto: no syntax specified, defaulting to proto2 \
+                 (add `syntax = \"proto2\";` to silence this warning)",
+                "user.proto: fields foo_bar and fooBar of message User map to the same JSON name \"fooBar\"",
+            ]
+        );
+        assert!(opts.messages.take().is_empty());
+
+        // the warnings and skipped statements are passed on for the caller to report
+        super::parse(&opts, "user.proto", input).unwrap();
+        let messages = opts.messages.take();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[0].starts_with("warning: user.proto: no syntax specified"));
+        assert_eq!(
+            messages[2],
+            "warning: user.proto:5:1: expected `message`, `enum`, `service`, `extend`, `option`, \
+             `import` or `package`, found `unknown_statement` (statement skipped)"
+        );
+    }
+
+    #[test]
+    fn parse_reports_timings() {
+        let opts = Opts {
+            verbose: 2,
+            ..Opts::default()
+        };
+        super::parse(&opts, "user.proto", "syntax = \"proto3\";\nmessage User {}").unwrap();
+
+        let messages = opts.messages.take();
+        assert_eq!(messages[0], "parse timings of user.proto:");
+        assert!(messages[1].starts_with("  line     2: message User "));
+        assert!(messages[2].starts_with("  total: "));
+        assert_eq!(messages.len(), 3);
+    }
+
+    #[test]
+    fn parse_lenient_skips_unknown_statements() {
+        let input = r#"syntax = "proto2";
+message SearchResponse {
+  repeated group Result = 1 {
+    required string url = 2;
+  }
+  optional int32 total = 3;
+}
+unknown_statement foo;
+"#;
+        let opts = Opts::default();
+        assert!(super::parse(&opts, "search.proto", input).is_err());
+
+        let opts = Opts {
+            lenient: true,
+            ..Opts::default()
+        };
+        let proto = super::parse(&opts, "search.proto", input).unwrap();
+        assert_eq!(proto.elems.len(), 1);
+        assert!(matches!(&proto.elems[0], Elem::Message(msg)
+            if matches!(&msg.fields[..], [Field::Single { name, .. }] if name == "total")));
+    }
+
+    #[test]
+    fn parse_spans() {
+        let input = r#"syntax = "proto3";
+package foo.v1;
+
+// users
+message User {
+  string name = 1;
+    map<string, int32> labels = 2 [deprecated = true];
+  oneof contact { string email = 3; }
+}
+enum Role { ROLE_UNSPECIFIED = 0; }
+service Users {
+  rpc Get(User) returns (User);
+}
+"#;
+        let proto = super::parse(&Opts::default(), "user.proto", input).unwrap();
+        let text = |span: &super::Span| &input[span.start..span.end];
+        let at = |span: &super::Span| (span.line, span.column);
+
+        assert_eq!(text(&proto.span).trim_end(), input.trim_end());
+        assert!(matches!(&proto.elems[0],
+            Elem::Package { span, .. } if text(span) == "package foo.v1;" && at(span) == (2, 1)));
+
+        let Elem::Message(msg) = &proto.elems[1] else {
+            panic!("expected message");
+        };
+        assert_eq!(at(&msg.span), (5, 1));
+        assert!(text(&msg.span).starts_with("message User {") && text(&msg.span).ends_with('}'));
+        let Field::Map { span, options, .. } = &msg.fields[1] else {
+            panic!("expected map");
+        };
+        assert_eq!(
+            text(span),
+            "map<string, int32> labels = 2 [deprecated = true];"
+        );
+        assert_eq!(at(span), (7, 5));
+        assert_eq!(text(&options[0].span), "deprecated = true");
+        assert!(matches!(&msg.fields[2],
+            Field::OneOf { span, .. } if text(span) == "oneof contact { string email = 3; }"));
+
+        let Elem::Enum(e) = &proto.elems[2] else {
+            panic!("expected enum");
+        };
+        assert_eq!(text(&e.span), "enum Role { ROLE_UNSPECIFIED = 0; }");
+        assert!(matches!(&e.values[0],
+            EnumValue::Single { span, .. } if text(span) == "ROLE_UNSPECIFIED = 0;"));
+
+        let Elem::Service { nodes, span, .. } = &proto.elems[3] else {
+            panic!("expected service");
+        };
+        assert_eq!(at(span), (11, 1));
+        assert!(matches!(&nodes[0], ServiceNode::Rpc(rpc)
+            if text(&rpc.span) == "rpc Get(User) returns (User);" && at(&rpc.span) == (12, 3)));
+    }
+
+    #[test]
+    fn parse_keyword_names() {
+        let input = r#"syntax = "proto3";
+message optional {}
+message message {
+  string option = 1;
+  optional reserved = 2;
+  repeated message = 3;
+  optional message service = 4;
+  optionalValue map = 5;
+  oneof enum { string rpc = 6; }
+}
+enum enum { reserved = 0; option = 1; }
+"#;
+        let proto = super::parse(&Opts::default(), "keywords.proto", input).unwrap();
+        let Elem::Message(msg) = &proto.elems[1] else {
+            panic!("expected message");
+        };
+        let fields = msg
+            .fields
+            .iter()
+            .filter_map(|field| match field {
+                Field::Single {
+                    field_type,
+                    name,
+                    flag,
+                    ..
+                } => Some((field_type.as_str(), name.as_str(), flag.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                ("string", "option", Flag::None),
+                ("optional", "reserved", Flag::None),
+                ("repeated", "message", Flag::None),
+                ("message", "service", Flag::Optional),
+                ("optionalValue", "map", Flag::None),
+            ]
+        );
+        assert!(matches!(&msg.fields[5], Field::OneOf { name, .. } if name == "enum"));
+        assert!(matches!(&proto.elems[2], Elem::Enum(e) if e.values.len() == 2));
+    }
+
+    #[test]
+    fn parse_fully_qualified_references() {
+        let input = r#"syntax = "proto3";
+import "google/protobuf/any.proto";
+extend .google.protobuf.FieldOptions { string label = 50000; }
+message Foo {
+  .google.protobuf.Any any = 1;
+  map<string, .google.protobuf.Any> anys = 2;
+}
+service Foos { rpc Get(.Foo) returns (stream .Foo); }
+"#;
+        let proto = super::parse(&Opts::default(), "foo.proto", input).unwrap();
+        assert!(
+            matches!(&proto.elems[1], Elem::Extend { name, .. } if name == ".google.protobuf.FieldOptions")
+        );
+        let Elem::Message(msg) = &proto.elems[2] else {
+            panic!("expected message");
+        };
+        assert!(matches!(&msg.fields[0],
+            Field::Single { field_type, .. } if field_type == ".google.protobuf.Any"));
+        assert!(matches!(&msg.fields[1],
+            Field::Map { value_type, .. } if value_type == ".google.protobuf.Any"));
+        assert!(matches!(&proto.elems[3], Elem::Service { nodes, .. }
+            if matches!(&nodes[0], ServiceNode::Rpc(rpc) if rpc.request == ".Foo" && rpc.stream_response)));
+    }
+
+    #[test]
+    fn parse_empty_statements() {
+        let input = r#"syntax = "proto3";;
+package foo;;
+message Foo {
+  ;
+  string name = 1;;
+  enum Kind { ; NONE = 0;; }
+};
+service Foos { ; rpc Get(Foo) returns (Foo) { ; }; }
+"#;
+        let proto = super::parse(&Opts::default(), "foo.proto", input).unwrap();
+        assert_eq!(proto.elems.len(), 3);
+        let Elem::Message(msg) = &proto.elems[1] else {
+            panic!("expected message");
+        };
+        assert_eq!(msg.fields.len(), 2);
+    }
+
+    #[test]
+    fn parse_import_modifiers() {
+        let modifier = |input: &str| match rule(input, |p| p.import()).unwrap() {
+            Elem::Import { modifier, .. } => modifier,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(modifier("import \"a.proto\";"), ImportModifier::None);
+        assert_eq!(
+            modifier("import public \"a.proto\";"),
+            ImportModifier::Public
+        );
+        assert_eq!(modifier("import  weak\n'b.proto';"), ImportModifier::Weak);
+        assert!(rule("import publicity \"a.proto\";", |p| p.import()).is_err());
+    }
+
+    #[test]
+    fn parse_map_key_types() {
+        assert!(rule("map<sfixed64, string> a = 1;", |p| p.map_field()).is_ok());
+        assert!(rule("map<bool, Kind> a = 1;", |p| p.map_field()).is_ok());
+        assert!(rule("map<double, string> a = 1;", |p| p.map_field()).is_err());
+
+        let error = rule("map<Kind, string> a = 1;", |p| p.map_field()).unwrap_err();
+        assert_eq!(error.token, 2);
+        assert!(error
+            .message
+            .starts_with("invalid map key type `Kind`, keys are of integral or string type"));
+    }
+
+    #[test]
+    fn parse_numeric_literals() {
+        let number = |input: &str| match rule(input, |p| p.option()).unwrap().value {
+            OptionValue::Num { value } => value,
+            other => panic!("unexpected value {:?}", other),
+        };
+        assert_eq!(number("option (a) = 0x1F;"), Number::Int(31));
+        assert_eq!(number("option (a) = -017;"), Number::Int(-15));
+        assert_eq!(number("option (a) = 0;"), Number::Int(0));
+        assert_eq!(
+            number("option (a) = 18446744073709551615;"),
+            Number::Int(u64::MAX.into())
+        );
+        assert_eq!(number("option (a) = 1.5;"), Number::Float(1.5));
+        assert_eq!(number("option (a) = -2e10;"), Number::Float(-2e10));
+        assert_eq!(number("option (a) = .5E-1;"), Number::Float(0.05));
+        assert_eq!(
+            number("option (a) = -inf;"),
+            Number::Float(f64::NEG_INFINITY)
+        );
+        assert!(matches!(number("option (a) = nan;"), Number::Float(value) if value.is_nan()));
+        assert!(matches!(
+            rule("option (a) = infinite;", |p| p.option()).unwrap().value,
+            OptionValue::Constant { value } if value == "infinite"
+        ));
+
+        let field = rule("int32 a = 0x10 [default = 1e3];", |p| p.field()).unwrap();
+        assert!(matches!(field, Field::Single { idx: 16, options, .. }
+            if matches!(options[0].value, OptionValue::Num { value: Number::Float(value) } if value == 1000.0)));
+        assert!(rule("int32 a = 1.5;", |p| p.field()).is_err());
+    }
+
+    #[test]
+    fn parse_reserved_ranges() {
+        let reserved = |input: &str| match rule(input, |p| p.field()).unwrap() {
+            Field::Reserved(reserved) => reserved,
+            other => panic!("unexpected field {:?}", other),
+        };
+
+        assert!(matches!(reserved("reserved 1, 3;"), ReservedField::Idx { idx } if idx == [1, 3]));
+        assert!(matches!(
+            reserved("reserved 2, 5 to 10, 100 to max;"),
+            ReservedField::Range { ranges } if ranges == [(2, 2), (5, 10), (100, MAX_FIELD_NUMBER)]
+        ));
+        assert!(matches!(
+            reserved("reserved 'foo', \"bar\";"),
+            ReservedField::Name { name } if name == ["foo", "bar"]
+        ));
+        assert!(rule("reserved 5 to;", |p| p.field()).is_err());
+
+        let error = rule("reserved 1, 10 to 5;", |p| p.field()).unwrap_err();
+        assert_eq!(error.token, 3);
+        assert_eq!(
+            error.message,
+            "invalid range `10 to 5`, the start is greater than the end"
+        );
+    }
+
+    #[test]
+    fn parse_extensions_ranges() {
+        let field = rule(
+            r#"extensions 100 to 199, 300, 1000 to max [
+    declaration = { number: 100, full_name: ".foo.v1.ext", type: "string" },
+    verification = DECLARATION
+  ];"#,
+            |p| p.field(),
+        )
+        .unwrap();
+
+        let Field::Extensions {
+            ranges, options, ..
+        } = field
+        else {
+            panic!("unexpected field {:?}", field);
+        };
+        assert_eq!(ranges, [(100, 199), (300, 300), (1000, MAX_FIELD_NUMBER)]);
+        assert_eq!(options.len(), 2);
+        assert!(matches!(&options[0].value, OptionValue::Msg(fields) if fields.len() == 3));
+        assert!(
+            matches!(rule("extensions 100 to 199;", |p| p.field()).unwrap(),
+            Field::Extensions { ranges, options, .. } if ranges == [(100, 199)] && options.is_empty())
+        );
+    }
+
+    #[test]
+    fn parse_json_name_conflicts() {
+        let opts = Opts::default();
+        let parse = |input: &str| super::parse(&opts, "file.proto", input).map(|_| ());
+
+        let err = parse(
+            r#"syntax = "proto3";
+message Foo { message Bar { string foo_bar = 1; oneof kind { int32 fooBar = 2; } } }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "proto parsing failed: file.proto: fields foo_bar and fooBar of message Bar map to the \
+             same JSON name \"fooBar\""
+        );
+
+        assert!(parse(
+            r#"syntax = "proto3";
+message Foo { string a = 1 [json_name = "b"]; map<string, string> c = 2 [json_name = "b"]; }"#
+        )
+        .is_err());
+        assert!(parse(
+            r#"syntax = "proto2";
+message Foo { optional string a = 1 [json_name = "fooBar"]; optional string foo_bar = 2; }"#
+        )
+        .is_err());
+        // protoc only warns about default JSON names of proto2 files
+        assert!(parse(
+            r#"syntax = "proto2";
+message Foo { optional string foo_bar = 1; optional string fooBar = 2; }"#
+        )
+        .is_ok());
+        assert!(parse(
+            r#"syntax = "proto3";
+message Foo { string foo_bar = 1 [json_name = "foo_bar"]; string fooBar = 2; }"#
+        )
+        .is_ok());
     }
 }
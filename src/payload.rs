@@ -0,0 +1,960 @@
+//! JSON payloads of message types following the proto3 JSON mapping.
+//!
+//! Fields are accepted by their JSON name (lowerCamelCase or the `json_name`
+//! option) as well as their proto name, `null` counts as unset, 64 bit
+//! integers may be given as strings and the well-known types use their
+//! special representations (e.g. RFC 3339 strings for `Timestamp`).
+//!
+//! Payloads can be checked with `validate` and brought into their canonical
+//! form with `normalize`, so equal messages have the same JSON text.
+
+use std::collections::HashSet;
+
+use crate::errors::PtError;
+use crate::json::{format_timestamp, fraction, is_well_known, well_known, wrapped, Json};
+use crate::parser::{default_json_name, EnumValue, Flag, OptionValue, Proto};
+use crate::registry::{self, declared, Declared, Kind, Registry};
+
+/// Problem of a JSON payload found by `validate`.
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    /// location of the value, e.g. `$.items[0].name`
+    pub path: String,
+    pub message: String,
+}
+
+/// Check the payload against the definition of the fully-qualified message
+/// type: unknown fields, values not matching the field types, fields given
+/// more than once, multiple members of a oneof and missing required fields.
+pub fn validate(protos: &[Proto], message: &str, json: &Json) -> Result<Vec<Issue>, PtError> {
+    if registry::find_message(protos, message).is_none() && !is_well_known(message) {
+        return Err(Registry::new(protos).unresolved(message, ""));
+    }
+
+    Ok(traverse(protos, message, json, &mut Exercised::default()))
+}
+
+/// Parts of the message definitions a payload makes use of.
+#[derive(Default, Debug)]
+pub(crate) struct Exercised {
+    /// fully-qualified message name and name of the fields that are set, to
+    /// a non-empty value in case of repeated and map fields
+    pub fields: HashSet<(String, String)>,
+    /// fully-qualified enum name and name of the values that are used
+    pub enum_values: HashSet<(String, String)>,
+}
+
+/// Validate the payload like `validate`, recording the fields and enum
+/// values it makes use of.
+pub(crate) fn traverse(
+    protos: &[Proto],
+    message: &str,
+    json: &Json,
+    exercised: &mut Exercised,
+) -> Vec<Issue> {
+    let mut validator = Validator {
+        protos,
+        registry: Registry::new(protos),
+        issues: Vec::new(),
+        exercised,
+    };
+    validator.value(message, "", json, "$");
+    validator.issues
+}
+
+/// JSON name of a field: the `json_name` option or the lowerCamelCase name.
+pub(crate) fn json_name(decl: &Declared) -> String {
+    let option = || {
+        decl.options
+            .iter()
+            .find(|opt| opt.name == "json_name")
+            .and_then(|opt| match &opt.value {
+                OptionValue::Str { value } => Some(value.clone()),
+                _ => None,
+            })
+    };
+    decl.json_name
+        .map(str::to_string)
+        .or_else(option)
+        .unwrap_or_else(|| default_json_name(decl.name))
+}
+
+/// Type a field refers to.
+pub(crate) enum Target {
+    Scalar,
+    Message(String),
+    Enum(String),
+    /// well-known type with a special JSON representation
+    WellKnown(String),
+}
+
+pub(crate) fn target(registry: &Registry, field_type: &str, scope: &str) -> Option<Target> {
+    if registry::is_scalar(field_type) {
+        return Some(Target::Scalar);
+    }
+    match registry.resolve(field_type, scope) {
+        Some(ptype) if is_well_known(&ptype.full_name) => {
+            Some(Target::WellKnown(ptype.full_name.clone()))
+        }
+        Some(ptype) if ptype.kind == Kind::Message => {
+            Some(Target::Message(ptype.full_name.clone()))
+        }
+        Some(ptype) => Some(Target::Enum(ptype.full_name.clone())),
+        None => well_known(field_type).map(|name| Target::WellKnown(name.to_string())),
+    }
+}
+
+struct Validator<'a> {
+    protos: &'a [Proto],
+    registry: Registry,
+    issues: Vec<Issue>,
+    exercised: &'a mut Exercised,
+}
+
+impl Validator<'_> {
+    fn issue(&mut self, path: &str, message: String) {
+        self.issues.push(Issue {
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    fn message(&mut self, full_name: &str, json: &Json, path: &str) {
+        let Some(msg) = registry::find_message(self.protos, full_name) else {
+            self.issue(path, format!("unknown message type {}", full_name));
+            return;
+        };
+        let Json::Object(entries) = json else {
+            self.issue(
+                path,
+                format!("expected object of {}, got {}", full_name, describe(json)),
+            );
+            return;
+        };
+        let mut decls = Vec::new();
+        declared(&msg.fields, None, &mut decls);
+
+        let mut seen: Vec<&Declared> = Vec::new();
+        for (key, value) in entries {
+            let field_path = format!("{}.{}", path, key);
+            let Some(decl) = decls
+                .iter()
+                .find(|decl| decl.name == key || json_name(decl) == *key)
+            else {
+                self.issue(
+                    &field_path,
+                    format!("unknown field {} of {}", key, full_name),
+                );
+                continue;
+            };
+
+            if seen.iter().any(|other| other.name == decl.name) {
+                self.issue(
+                    &field_path,
+                    format!("field {} is given more than once", decl.name),
+                );
+                continue;
+            }
+            // null is the default value of all fields except for
+            // `google.protobuf.Value`, which represents it as `NullValue`
+            if *value == Json::Null && well_known(decl.field_type) != Some("google.protobuf.Value")
+            {
+                continue;
+            }
+            if let Some(other) = seen
+                .iter()
+                .find(|other| decl.oneof.is_some() && other.oneof == decl.oneof)
+            {
+                self.issue(
+                    &field_path,
+                    format!(
+                        "fields {} and {} of oneof {} are both set",
+                        other.name,
+                        decl.name,
+                        decl.oneof.unwrap_or_default()
+                    ),
+                );
+            }
+            seen.push(decl);
+            if !matches!(value, Json::Array(values) if values.is_empty())
+                && !matches!(value, Json::Object(entries) if entries.is_empty() && decl.key_type.is_some())
+            {
+                self.exercised
+                    .fields
+                    .insert((full_name.to_string(), decl.name.to_string()));
+            }
+
+            match (decl.key_type, value) {
+                (Some(key_type), Json::Object(entries)) => {
+                    for (key, value) in entries {
+                        let entry_path = format!("{}[{:?}]", field_path, key);
+                        if !valid_scalar(key_type, &Json::Str(key.clone()), true) {
+                            self.issue(
+                                &entry_path,
+                                format!("invalid {} map key {:?}", key_type, key),
+                            );
+                        }
+                        self.value(decl.field_type, full_name, value, &entry_path);
+                    }
+                }
+                (Some(_), value) => self.issue(
+                    &field_path,
+                    format!(
+                        "expected object for map {}, got {}",
+                        decl.name,
+                        describe(value)
+                    ),
+                ),
+                (None, Json::Array(values)) if matches!(decl.flag, Flag::Repeated) => {
+                    for (idx, value) in values.iter().enumerate() {
+                        self.value(
+                            decl.field_type,
+                            full_name,
+                            value,
+                            &format!("{}[{}]", field_path, idx),
+                        );
+                    }
+                }
+                (None, value) if matches!(decl.flag, Flag::Repeated) => self.issue(
+                    &field_path,
+                    format!(
+                        "expected array for repeated {}, got {}",
+                        decl.name,
+                        describe(value)
+                    ),
+                ),
+                (None, value) => self.value(decl.field_type, full_name, value, &field_path),
+            }
+        }
+
+        for decl in &decls {
+            if matches!(decl.flag, Flag::Required)
+                && !seen.iter().any(|seen| seen.name == decl.name)
+            {
+                self.issue(
+                    path,
+                    format!("missing required field {} of {}", decl.name, full_name),
+                );
+            }
+        }
+    }
+
+    fn value(&mut self, field_type: &str, scope: &str, json: &Json, path: &str) {
+        match target(&self.registry, field_type, scope) {
+            Some(Target::Scalar) if !valid_scalar(field_type, json, false) => self.issue(
+                path,
+                format!("invalid {} value {}", field_type, describe(json)),
+            ),
+            Some(Target::Scalar) => (),
+            Some(Target::Message(full_name)) => self.message(&full_name, json, path),
+            Some(Target::Enum(full_name)) => self.enum_value(&full_name, json, path),
+            Some(Target::WellKnown(full_name)) => self.well_known(&full_name, json, path),
+            None => self.issue(path, format!("unknown type {}", field_type)),
+        }
+    }
+
+    fn enum_value(&mut self, full_name: &str, json: &Json, path: &str) {
+        let values = registry::find_enum(self.protos, full_name).map_or(&[][..], |e| &e.values);
+        let used = values.iter().find_map(|value| match (value, json) {
+            (EnumValue::Single { name, .. }, Json::Str(str)) if name == str => Some(name),
+            (EnumValue::Single { name, idx, .. }, Json::Num(num))
+                if integer(num) == Some(i128::from(*idx)) =>
+            {
+                Some(name)
+            }
+            _ => None,
+        });
+        if let Some(name) = used {
+            self.exercised
+                .enum_values
+                .insert((full_name.to_string(), name.clone()));
+        }
+
+        let valid = match json {
+            Json::Str(_) => used.is_some(),
+            Json::Num(_) => valid_scalar("int32", json, false),
+            _ => false,
+        };
+        if !valid {
+            self.issue(
+                path,
+                format!("invalid value {} of enum {}", describe(json), full_name),
+            );
+        }
+    }
+
+    fn well_known(&mut self, full_name: &str, json: &Json, path: &str) {
+        if let Some(scalar) = wrapped(full_name) {
+            return self.value(scalar, "", json, path);
+        }
+
+        if !valid_well_known(full_name, json) {
+            self.issue(
+                path,
+                format!("invalid {} value {}", full_name, describe(json)),
+            );
+        }
+    }
+}
+
+/// Whether the JSON value is valid for the well-known type, except for the
+/// wrapper types.
+fn valid_well_known(full_name: &str, json: &Json) -> bool {
+    match (full_name.trim_start_matches("google.protobuf."), json) {
+        ("Timestamp", Json::Str(str)) => timestamp(str).is_some(),
+        ("Duration", Json::Str(str)) => duration(str).is_some(),
+        ("FieldMask", Json::Str(str)) => str.split(',').all(|path| {
+            path.is_empty()
+                || path.split('.').all(|part| {
+                    part.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+                        && part.chars().all(|c| c.is_ascii_alphanumeric())
+                })
+        }),
+        ("Struct", Json::Object(_)) | ("ListValue", Json::Array(_)) | ("Value", _) => true,
+        ("NullValue", Json::Null) => true,
+        ("Empty", Json::Object(entries)) => entries.is_empty(),
+        ("Any", Json::Object(entries)) => entries
+            .iter()
+            .any(|(key, value)| key == "@type" && matches!(value, Json::Str(_))),
+        _ => false,
+    }
+}
+
+/// Canonical proto3 JSON of a payload of the fully-qualified message type:
+/// fields by their JSON name in declaration order, enum values by name, 64 bit
+/// integers as strings, standard padded base64 for bytes and timestamps in
+/// UTC. Unknown fields and fields without presence holding their default
+/// value are dropped, values not matching their field type are an error.
+pub fn normalize(protos: &[Proto], message: &str, json: &Json) -> Result<Json, PtError> {
+    if registry::find_message(protos, message).is_none() && !is_well_known(message) {
+        return Err(Registry::new(protos).unresolved(message, ""));
+    }
+
+    let normalizer = Normalizer {
+        protos,
+        registry: Registry::new(protos),
+    };
+    normalizer.value(message, "", json, "$")
+}
+
+fn invalid(path: &str, message: String) -> PtError {
+    PtError::InvalidJson(format!("{}: {}", path, message))
+}
+
+struct Normalizer<'a> {
+    protos: &'a [Proto],
+    registry: Registry,
+}
+
+impl Normalizer<'_> {
+    fn message(&self, full_name: &str, json: &Json, path: &str) -> Result<Json, PtError> {
+        let msg = registry::find_message(self.protos, full_name)
+            .ok_or_else(|| PtError::ProtobufTypeNotFound(full_name.to_string()))?;
+        let Json::Object(entries) = json else {
+            return Err(invalid(
+                path,
+                format!("expected object of {}, got {}", full_name, describe(json)),
+            ));
+        };
+        let mut decls = Vec::new();
+        declared(&msg.fields, None, &mut decls);
+
+        let mut fields = Vec::new();
+        for decl in &decls {
+            let name = json_name(decl);
+            let target = target(&self.registry, decl.field_type, full_name)
+                .ok_or_else(|| PtError::ProtobufTypeNotFound(decl.field_type.to_string()))?;
+            let keeps_null =
+                matches!(&target, Target::WellKnown(name) if name == "google.protobuf.Value");
+            let Some((key, value)) = entries.iter().find(|(key, value)| {
+                (*key == name || key == decl.name) && (*value != Json::Null || keeps_null)
+            }) else {
+                continue;
+            };
+            let field_path = format!("{}.{}", path, key);
+
+            let value = match (decl.key_type, value) {
+                (Some(key_type), Json::Object(entries)) => Json::Object(
+                    entries
+                        .iter()
+                        .map(|(key, value)| {
+                            let entry_path = format!("{}[{:?}]", field_path, key);
+                            Ok((
+                                self.map_key(key_type, key, &entry_path)?,
+                                self.value(decl.field_type, full_name, value, &entry_path)?,
+                            ))
+                        })
+                        .collect::<Result<_, PtError>>()?,
+                ),
+                (Some(_), value) => {
+                    return Err(invalid(
+                        &field_path,
+                        format!(
+                            "expected object for map {}, got {}",
+                            decl.name,
+                            describe(value)
+                        ),
+                    ))
+                }
+                (None, Json::Array(values)) if matches!(decl.flag, Flag::Repeated) => Json::Array(
+                    values
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, value)| {
+                            let path = format!("{}[{}]", field_path, idx);
+                            self.value(decl.field_type, full_name, value, &path)
+                        })
+                        .collect::<Result<_, _>>()?,
+                ),
+                (None, value) if matches!(decl.flag, Flag::Repeated) => {
+                    return Err(invalid(
+                        &field_path,
+                        format!(
+                            "expected array for repeated {}, got {}",
+                            decl.name,
+                            describe(value)
+                        ),
+                    ))
+                }
+                (None, value) => self.value(decl.field_type, full_name, value, &field_path)?,
+            };
+
+            if !self.is_default(decl, &target, &value) {
+                fields.push((name, value));
+            }
+        }
+
+        Ok(Json::Object(fields))
+    }
+
+    /// Whether the field is omitted from the canonical form: empty repeated
+    /// and map fields and fields without presence holding their default.
+    fn is_default(&self, decl: &Declared, target: &Target, value: &Json) -> bool {
+        match value {
+            Json::Array(values) => return values.is_empty(),
+            Json::Object(entries) if decl.key_type.is_some() => return entries.is_empty(),
+            _ => (),
+        }
+        if !matches!(decl.flag, Flag::None) || decl.oneof.is_some() {
+            return false;
+        }
+
+        match (target, value) {
+            (Target::Scalar, Json::Num(num)) => num == "0",
+            (Target::Scalar, Json::Str(str)) => str.is_empty() || str == "0",
+            (Target::Scalar, Json::Bool(value)) => !value,
+            (Target::Enum(full_name), Json::Str(str)) => self.enum_name(full_name, 0) == Some(str),
+            _ => false,
+        }
+    }
+
+    fn map_key(&self, key_type: &str, key: &str, path: &str) -> Result<String, PtError> {
+        match self.scalar(key_type, &Json::Str(key.to_string()), path) {
+            Ok(Json::Num(key) | Json::Str(key)) => Ok(key),
+            Ok(Json::Bool(key)) => Ok(key.to_string()),
+            _ if key_type == "bool" && (key == "true" || key == "false") => Ok(key.to_string()),
+            _ => Err(invalid(
+                path,
+                format!("invalid {} map key {:?}", key_type, key),
+            )),
+        }
+    }
+
+    fn value(
+        &self,
+        field_type: &str,
+        scope: &str,
+        json: &Json,
+        path: &str,
+    ) -> Result<Json, PtError> {
+        match target(&self.registry, field_type, scope) {
+            Some(Target::Scalar) => self.scalar(field_type, json, path),
+            Some(Target::Message(full_name)) => self.message(&full_name, json, path),
+            Some(Target::Enum(full_name)) => self.enum_value(&full_name, json, path),
+            Some(Target::WellKnown(full_name)) => self.well_known(&full_name, json, path),
+            None => Err(PtError::ProtobufTypeNotFound(field_type.to_string())),
+        }
+    }
+
+    fn scalar(&self, field_type: &str, json: &Json, path: &str) -> Result<Json, PtError> {
+        if !valid_scalar(field_type, json, false) {
+            return Err(invalid(
+                path,
+                format!("invalid {} value {}", field_type, describe(json)),
+            ));
+        }
+        let integer = || match json {
+            Json::Num(num) | Json::Str(num) => integer(num).unwrap_or_default().to_string(),
+            _ => String::new(),
+        };
+
+        Ok(match (field_type, json) {
+            ("bytes", Json::Str(str)) => {
+                Json::Str(encode_base64(&decode_base64(str).unwrap_or_default()))
+            }
+            ("double" | "float", Json::Num(num) | Json::Str(num)) => match num.parse::<f64>() {
+                Ok(value) => Json::Num(float(value, field_type == "float")),
+                // NaN and infinite values
+                Err(_) => json.clone(),
+            },
+            ("int32" | "sint32" | "sfixed32" | "uint32" | "fixed32", _) => Json::Num(integer()),
+            ("int64" | "sint64" | "sfixed64" | "uint64" | "fixed64", _) => Json::Str(integer()),
+            _ => json.clone(),
+        })
+    }
+
+    /// Name of the enum value with the given number.
+    fn enum_name(&self, full_name: &str, number: i32) -> Option<&String> {
+        registry::find_enum(self.protos, full_name)?
+            .values
+            .iter()
+            .find_map(|value| match value {
+                EnumValue::Single { name, idx, .. } if *idx == number => Some(name),
+                _ => None,
+            })
+    }
+
+    fn enum_value(&self, full_name: &str, json: &Json, path: &str) -> Result<Json, PtError> {
+        let known = |str: &str| {
+            registry::find_enum(self.protos, full_name).is_some_and(|e| {
+                e.values
+                    .iter()
+                    .any(|value| matches!(value, EnumValue::Single { name, .. } if name == str))
+            })
+        };
+
+        match json {
+            Json::Str(str) if known(str) => Ok(json.clone()),
+            Json::Num(num) if valid_scalar("int32", json, false) => {
+                let number = integer(num).unwrap_or_default() as i32;
+                // unknown values of open enums are kept by their number
+                Ok(match self.enum_name(full_name, number) {
+                    Some(name) => Json::Str(name.clone()),
+                    None => Json::Num(number.to_string()),
+                })
+            }
+            _ => Err(invalid(
+                path,
+                format!("invalid value {} of enum {}", describe(json), full_name),
+            )),
+        }
+    }
+
+    fn well_known(&self, full_name: &str, json: &Json, path: &str) -> Result<Json, PtError> {
+        if let Some(scalar) = wrapped(full_name) {
+            return self.scalar(scalar, json, path);
+        }
+        if !valid_well_known(full_name, json) {
+            return Err(invalid(
+                path,
+                format!("invalid {} value {}", full_name, describe(json)),
+            ));
+        }
+
+        Ok(
+            match (full_name.trim_start_matches("google.protobuf."), json) {
+                ("Timestamp", Json::Str(str)) => {
+                    let (seconds, nanos) = timestamp(str).unwrap_or_default();
+                    Json::Str(format_timestamp(seconds, nanos))
+                }
+                ("Duration", Json::Str(str)) => {
+                    let (negative, seconds, nanos) = duration(str).unwrap_or_default();
+                    let sign = if negative && (seconds, nanos) != (0, 0) {
+                        "-"
+                    } else {
+                        ""
+                    };
+                    Json::Str(format!("{}{}{}s", sign, seconds, fraction(nanos)))
+                }
+                ("Any", Json::Object(entries)) => self.any(entries, path)?,
+                _ => json.clone(),
+            },
+        )
+    }
+
+    /// `Any` values of known message types are normalized as well, the
+    /// others are kept as they are.
+    fn any(&self, entries: &[(String, Json)], path: &str) -> Result<Json, PtError> {
+        let Some((_, Json::Str(url))) = entries.iter().find(|(key, _)| key == "@type") else {
+            return Ok(Json::Object(entries.to_vec()));
+        };
+        let type_name = url.rsplit('/').next().unwrap_or_default();
+        let rest = entries
+            .iter()
+            .filter(|(key, _)| key != "@type")
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let value = match target(&self.registry, type_name, "") {
+            Some(Target::WellKnown(full_name)) => {
+                let value = rest
+                    .iter()
+                    .find(|(key, _)| key == "value")
+                    .map_or(Json::Null, |(_, value)| value.clone());
+                vec![(
+                    "value".to_string(),
+                    self.well_known(&full_name, &value, &format!("{}.value", path))?,
+                )]
+            }
+            Some(Target::Message(full_name)) => {
+                match self.message(&full_name, &Json::Object(rest), path)? {
+                    Json::Object(fields) => fields,
+                    _ => Vec::new(),
+                }
+            }
+            _ => rest,
+        };
+
+        Ok(Json::Object(
+            [("@type".to_string(), Json::Str(url.clone()))]
+                .into_iter()
+                .chain(value)
+                .collect(),
+        ))
+    }
+}
+
+/// Shortest representation of a floating point number, in exponent notation
+/// for very large and very small values like in javascript.
+pub(crate) fn float(value: f64, single: bool) -> String {
+    let abs = value.abs();
+    let exponent = abs >= 1e21 || (abs != 0.0 && abs < 1e-6);
+
+    match (single, exponent) {
+        (true, true) => format!("{:e}", value as f32),
+        (true, false) => (value as f32).to_string(),
+        (false, true) => format!("{:e}", value),
+        (false, false) => value.to_string(),
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Bytes of standard or URL-safe base64, with or without padding.
+fn decode_base64(str: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(str.len() * 3 / 4);
+    let mut buf = 0u32;
+    let mut bits = 0;
+
+    for chr in str.trim_end_matches('=').bytes() {
+        let value = match chr {
+            b'-' => 62,
+            b'_' => 63,
+            _ => BASE64.iter().position(|c| *c == chr)? as u32,
+        };
+        buf = buf << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buf >> bits) as u8);
+        }
+    }
+
+    Some(bytes)
+}
+
+/// Standard base64 with padding.
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut str = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let buf = chunk.iter().enumerate().fold(0u32, |buf, (idx, byte)| {
+            buf | u32::from(*byte) << (16 - idx * 8)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                str.push(BASE64[(buf >> (18 - idx * 6) & 0x3f) as usize] as char);
+            } else {
+                str.push('=');
+            }
+        }
+    }
+
+    str
+}
+
+fn describe(json: &Json) -> String {
+    match json {
+        Json::Null => "null".to_string(),
+        Json::Bool(value) => value.to_string(),
+        Json::Num(num) => num.clone(),
+        Json::Str(str) => format!("{:?}", str),
+        Json::Array(_) => "[...]".to_string(),
+        Json::Object(_) => "{...}".to_string(),
+    }
+}
+
+/// Value of an integer given as JSON number or string, which may use
+/// exponent notation as long as the value is integral.
+pub(crate) fn integer(num: &str) -> Option<i128> {
+    num.parse::<i128>().ok().or_else(|| {
+        let value = num.parse::<f64>().ok()?;
+        (value.is_finite() && value.fract() == 0.0 && value.abs() < 1e38).then_some(value as i128)
+    })
+}
+
+/// Whether the JSON value is valid for the scalar type. Map keys are always
+/// strings, so booleans and numbers are accepted as such.
+fn valid_scalar(field_type: &str, json: &Json, key: bool) -> bool {
+    let number = match json {
+        Json::Num(num) => Some(num.as_str()),
+        Json::Str(str) if field_type != "string" && field_type != "bytes" => Some(str.as_str()),
+        _ => None,
+    };
+    let range = |min: i128, max: i128| {
+        number
+            .and_then(integer)
+            .is_some_and(|value| (min..=max).contains(&value))
+    };
+
+    match field_type {
+        "string" => matches!(json, Json::Str(_)),
+        "bytes" => matches!(json, Json::Str(str) if base64(str)),
+        "bool" if key => matches!(json, Json::Str(str) if str == "true" || str == "false"),
+        "bool" => matches!(json, Json::Bool(_)),
+        "double" | "float" => match json {
+            Json::Str(str) if matches!(str.as_str(), "NaN" | "Infinity" | "-Infinity") => true,
+            _ => number
+                .and_then(|num| num.parse::<f64>().ok())
+                .is_some_and(|value| {
+                    value.is_finite() && (field_type == "double" || value.abs() <= f32::MAX.into())
+                }),
+        },
+        "int32" | "sint32" | "sfixed32" => range(i32::MIN.into(), i32::MAX.into()),
+        "uint32" | "fixed32" => range(0, u32::MAX.into()),
+        "int64" | "sint64" | "sfixed64" => range(i64::MIN.into(), i64::MAX.into()),
+        "uint64" | "fixed64" => range(0, u64::MAX.into()),
+        _ => false,
+    }
+}
+
+/// Standard or URL-safe base64, with or without padding.
+fn base64(str: &str) -> bool {
+    let data = str.trim_end_matches('=');
+    str.len() - data.len() <= 2
+        && data.len() % 4 != 1
+        && data
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '-' | '_'))
+}
+
+/// Seconds and nanoseconds since the unix epoch of an RFC 3339 date-time,
+/// e.g. `1972-01-01T10:00:20.021Z` or `2024-05-01T12:00:00+02:00`.
+fn timestamp(str: &str) -> Option<(i64, u32)> {
+    let number = |part: &str, len: usize, max: u32| {
+        let valid = part.len() == len && part.chars().all(|c| c.is_ascii_digit());
+        valid
+            .then(|| part.parse::<u32>().ok())
+            .flatten()
+            .filter(|value| *value <= max)
+    };
+
+    let (date, time) = str.split_once(['T', 't'])?;
+    let (year, month, day) = match date.split('-').collect::<Vec<_>>().as_slice() {
+        [year, month, day] => (
+            number(year, 4, 9999).filter(|year| *year > 0)?,
+            number(month, 2, 12).filter(|month| *month > 0)?,
+            number(day, 2, 31).filter(|day| *day > 0)?,
+        ),
+        _ => return None,
+    };
+
+    let (time, offset) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, 0),
+        None => {
+            let idx = time.rfind(['+', '-'])?;
+            let (hours, minutes) = time[idx + 1..].split_once(':')?;
+            let offset = i64::from(number(hours, 2, 23)? * 60 + number(minutes, 2, 59)?) * 60;
+            (
+                &time[..idx],
+                if &time[idx..=idx] == "-" {
+                    -offset
+                } else {
+                    offset
+                },
+            )
+        }
+    };
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let (hour, minute, second) = match time.split(':').collect::<Vec<_>>().as_slice() {
+        [hour, minute, second] => (
+            number(hour, 2, 23)?,
+            number(minute, 2, 59)?,
+            number(second, 2, 59)?,
+        ),
+        _ => return None,
+    };
+
+    let seconds = days_from_civil(year.into(), month, day) * 86400
+        + i64::from(hour * 3600 + minute * 60 + second)
+        - offset;
+    Some((seconds, nanos(fraction)?))
+}
+
+/// Days since the unix epoch of a date of the proleptic gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Nanoseconds of up to nine fractional digits of a second.
+fn nanos(fraction: &str) -> Option<u32> {
+    let valid = (1..=9).contains(&fraction.len()) && fraction.chars().all(|c| c.is_ascii_digit());
+    valid
+        .then(|| format!("{:0<9}", fraction).parse().ok())
+        .flatten()
+}
+
+/// Sign, seconds and nanoseconds of seconds with up to nine fractional
+/// digits and an `s` suffix, e.g. `1.5s`.
+fn duration(str: &str) -> Option<(bool, u64, u32)> {
+    let seconds = str.strip_suffix('s')?;
+    let (negative, seconds) = match seconds.strip_prefix('-') {
+        Some(seconds) => (true, seconds),
+        None => (false, seconds),
+    };
+    let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, "0"));
+
+    let valid = !whole.is_empty() && whole.chars().all(|c| c.is_ascii_digit());
+    let whole = valid
+        .then(|| whole.parse::<u64>().ok())
+        .flatten()
+        .filter(|value| *value <= 315_576_000_000)?;
+    Some((negative, whole, nanos(fraction)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, validate, Issue};
+    use crate::json;
+    use crate::parser::parse;
+    use crate::Opts;
+
+    #[test]
+    fn validate_json_payload() {
+        let proto = parse(
+            &Opts::default(),
+            "user.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+import "google/protobuf/timestamp.proto";
+enum Role { ROLE_UNSPECIFIED = 0; ROLE_ADMIN = 1; }
+message Address { string city = 1; }
+message User {
+  string user_name = 1;
+  int64 id = 2;
+  int32 age = 3;
+  repeated Address addresses = 4;
+  map<int32, Role> roles = 5;
+  google.protobuf.Timestamp created = 6;
+  bytes avatar = 7;
+  oneof contact {
+    string email = 8;
+    string phone = 9 [json_name = "tel"];
+  }
+}"#,
+        )
+        .unwrap();
+
+        let valid = json::parse(
+            r#"{"userName": "jane", "id": "9007199254740993", "age": 1e2,
+"addresses": [{"city": "Berlin"}], "roles": {"1": "ROLE_ADMIN", "2": 0},
+"created": "2024-05-01T12:00:00.5+02:00", "avatar": "aGk=", "tel": "123", "email": null}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate(std::slice::from_ref(&proto), "foo.v1.User", &valid).unwrap(),
+            vec![]
+        );
+
+        let invalid = json::parse(
+            r#"{"user_name": 1, "userName": "x", "age": 2147483648, "addresses": [{"town": "x"}, 1],
+"roles": {"a": "ROLE_USER"}, "created": "2024-05-01", "email": "a", "phone": "b", "extra": true}"#,
+        )
+        .unwrap();
+        let issue = |path: &str, message: &str| Issue {
+            path: path.to_string(),
+            message: message.to_string(),
+        };
+        assert_eq!(
+            validate(&[proto], "foo.v1.User", &invalid).unwrap(),
+            vec![
+                issue("$.user_name", "invalid string value 1"),
+                issue("$.userName", "field user_name is given more than once"),
+                issue("$.age", "invalid int32 value 2147483648"),
+                issue(
+                    "$.addresses[0].town",
+                    "unknown field town of foo.v1.Address"
+                ),
+                issue("$.addresses[1]", "expected object of foo.v1.Address, got 1"),
+                issue("$.roles[\"a\"]", "invalid int32 map key \"a\""),
+                issue(
+                    "$.roles[\"a\"]",
+                    "invalid value \"ROLE_USER\" of enum foo.v1.Role"
+                ),
+                issue(
+                    "$.created",
+                    "invalid google.protobuf.Timestamp value \"2024-05-01\""
+                ),
+                issue(
+                    "$.phone",
+                    "fields email and phone of oneof contact are both set"
+                ),
+                issue("$.extra", "unknown field extra of foo.v1.User"),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalize_json_payload() {
+        let proto = parse(
+            &Opts::default(),
+            "event.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+enum Kind { KIND_UNSPECIFIED = 0; KIND_CLICK = 1; }
+message Event {
+  string event_id = 1;
+  int64 sequence = 2;
+  Kind kind = 3;
+  bytes payload = 4;
+  google.protobuf.Timestamp at = 5;
+  google.protobuf.Duration took = 6;
+  map<int32, double> scores = 7;
+  repeated string tags = 8;
+  optional int32 count = 9;
+  float ratio = 10;
+  google.protobuf.Any details = 11;
+}"#,
+        )
+        .unwrap();
+        let protos = [proto];
+
+        let json = json::parse(
+            r#"{"took": "1.5s", "at": "2024-03-01T01:30:00.1+02:00", "payload": "-_8",
+"kind": 1, "sequence": 42, "event_id": "e1", "unknown": 1, "scores": {"07": 1.50},
+"tags": [], "count": 0, "ratio": 0.1, "details": {"@type": "type.googleapis.com/foo.v1.Event",
+"kind": "KIND_UNSPECIFIED", "sequence": "1e3"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            normalize(&protos, "foo.v1.Event", &json)
+                .unwrap()
+                .to_string(),
+            r#"{"eventId":"e1","sequence":"42","kind":"KIND_CLICK","payload":"+/8=","at":"2024-02-29T23:30:00.100Z","took":"1.500s","scores":{"7":1.5},"count":0,"ratio":0.1,"details":{"@type":"type.googleapis.com/foo.v1.Event","sequence":"1000"}}"#
+        );
+
+        let json = json::parse(r#"{"sequence": 1.5}"#).unwrap();
+        assert_eq!(
+            normalize(&protos, "foo.v1.Event", &json)
+                .unwrap_err()
+                .to_string(),
+            "invalid JSON payload: $.sequence: invalid int64 value 1.5"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Support for running as `protoc` plugin (`protoc-gen-protots`), which is
+//! what `buf generate` uses as well.
+//!
+//! The plugin reads a `CodeGeneratorRequest` from stdin and writes a
+//! `CodeGeneratorResponse` to stdout. Instead of parsing proto files on its
+//! own, the already compiled file descriptors of the request are used.
+
+use crate::config::Config;
+use crate::descriptor::from_file_descriptor;
+use crate::errors::{Messages, PtError};
+use crate::parser::Proto;
+use crate::provider::Files;
+use crate::wire::{Decoder, Encoder};
+use crate::{typescript, Io, Opts};
+
+/// `CodeGeneratorResponse.Feature.FEATURE_PROTO3_OPTIONAL`
+const FEATURE_PROTO3_OPTIONAL: i32 = 1;
+
+struct Request {
+    files_to_generate: Vec<String>,
+    parameter: String,
+    protos: Vec<Proto>,
+}
+
+impl Request {
+    fn decode(bytes: &[u8]) -> Result<Request, PtError> {
+        let mut request = Request {
+            files_to_generate: Vec::new(),
+            parameter: String::new(),
+            protos: Vec::new(),
+        };
+
+        for entry in Decoder::new(bytes) {
+            let (number, value) = entry?;
+            match number {
+                1 => request.files_to_generate.push(value.string()?),
+                2 => request.parameter = value.string()?,
+                15 => request.protos.push(from_file_descriptor(value.bytes()?)?),
+                _ => (),
+            }
+        }
+
+        Ok(request)
+    }
+
+    /// Options given via the plugin parameter, e.g. `clients,runtime-module=./rt`
+    /// which is translated into `--clients --runtime-module ./rt`.
+    fn opts(&self, files: &Files, config: &Config) -> Result<Opts, PtError> {
+        let mut args = vec!["protoc-gen-protots".to_string(), "--plugin".to_string()];
+
+        for param in self.parameter.split(',').filter(|param| !param.is_empty()) {
+            match param.split_once('=') {
+                Some((key, value)) => {
+                    args.push(format!("--{}", key.trim()));
+                    args.push(value.trim().to_string());
+                }
+                None => args.push(format!("--{}", param.trim())),
+            }
+        }
+
+        let opts = crate::opts(args, files, config)?.unwrap_or_default();
+        // any argument that is not an option is taken as input file
+        if !opts.file.is_empty() {
+            return Err(PtError::InvalidArgument(format!(
+                "unknown plugin parameter: {}",
+                opts.file
+            )));
+        }
+        Ok(opts)
+    }
+}
+
+fn generate(
+    request: &Request,
+    files: &Files,
+    config: &Config,
+    messages: &Messages,
+) -> Result<Vec<(String, String)>, PtError> {
+    let opts = Opts {
+        messages: messages.clone(),
+        ..request.opts(files, config)?
+    };
+
+    request
+        .files_to_generate
+        .iter()
+        .map(|file| {
+            let proto = request
+                .protos
+                .iter()
+                .find(|proto| proto.file == *file)
+                .ok_or_else(|| PtError::FileNotFound(file.clone()))?;
+            let deps = request
+                .protos
+                .iter()
+                .filter(|dep| dep.file != *file)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let content = typescript::to_schema(&opts, proto, &deps)?;
+            let name = format!("{}.ts", file.trim_end_matches(".proto"));
+            Ok((name, content))
+        })
+        .collect()
+}
+
+/// Handle a single plugin invocation.
+///
+/// Errors of the code generation are reported in the response itself, as
+/// expected by `protoc`; only failures to read or write the streams are
+/// returned. Warnings are written to stderr, which `protoc` passes through.
+pub fn run(io: &mut Io, config: &Config) -> Result<(), PtError> {
+    let mut input = Vec::new();
+    io.stdin.read_to_end(&mut input)?;
+
+    let messages = Messages::default();
+    let mut response = Encoder::default();
+    let generated = Request::decode(&input)
+        .and_then(|request| generate(&request, &io.files, config, &messages));
+    for message in messages.take() {
+        writeln!(io.stderr, "{}", message)?;
+    }
+    match generated {
+        Ok(files) => {
+            response.int32(2, FEATURE_PROTO3_OPTIONAL);
+            for (name, content) in files {
+                response.message(15, |e| {
+                    e.string(1, &name);
+                    e.string(15, &content);
+                    Ok(())
+                })?;
+            }
+        }
+        Err(err) => response.string(1, &err.to_string()),
+    }
+
+    io.stdout.write_all(&response.buf)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use crate::config::Config;
+    use crate::descriptor::to_descriptor_set;
+    use crate::wire::{Decoder, Encoder, Value};
+    use crate::{parser, Io, Opts};
+
+    /// Encoded file descriptors of the given files, dependencies first.
+    fn file_descriptors(files: &[(&str, &str)]) -> Vec<Vec<u8>> {
+        let mut protos = files
+            .iter()
+            .map(|(file, input)| parser::parse(&Opts::default(), file, input).unwrap())
+            .collect::<Vec<_>>();
+        let proto = protos.pop().unwrap();
+        let set = to_descriptor_set(&proto, &protos).unwrap();
+
+        Decoder::new(&set)
+            .map(|entry| match entry.unwrap() {
+                (1, Value::Bytes(bytes)) => bytes.to_vec(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn run_plugin(request: &[u8]) -> Vec<(u32, String)> {
+        let mut stdin = request;
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut io = Io {
+            stdin: &mut stdin,
+            stdout: &mut stdout,
+            stderr: &mut stderr,
+            color: false,
+            files: Default::default(),
+            #[cfg(feature = "cli")]
+            lint_rules: Default::default(),
+        };
+
+        run(&mut io, &Config::default()).unwrap();
+
+        let mut fields = Vec::new();
+        for entry in Decoder::new(&stdout) {
+            match entry.unwrap() {
+                (15, Value::Bytes(file)) => {
+                    for entry in Decoder::new(file) {
+                        let (number, value) = entry.unwrap();
+                        fields.push((number, value.string().unwrap()));
+                    }
+                }
+                (1, value) => fields.push((1, value.string().unwrap())),
+                (number, value) => fields.push((number, value.int32().unwrap().to_string())),
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn plugin_generates_requested_files() {
+        let files = file_descriptors(&[
+            (
+                "common/v1/address.proto",
+                "syntax = \"proto3\";\npackage common.v1;\nmessage Address { string city = 1; }",
+            ),
+            (
+                "foo/v1/user.proto",
+                r#"syntax = "proto3";
+package foo.v1;
+import "common/v1/address.proto";
+message User {
+  optional string name = 1;
+  common.v1.Address address = 2;
+}
+service Users {
+  rpc Get(User) returns (User);
+}"#,
+            ),
+        ]);
+
+        let mut request = Encoder::default();
+        request.string(1, "foo/v1/user.proto");
+        request.string(2, "clients");
+        for file in &files {
+            request.bytes(15, file);
+        }
+
+        let fields = run_plugin(&request.buf);
+
+        assert_eq!(fields[0], (2, "1".to_string()));
+        assert_eq!(fields[1], (1, "foo/v1/user.ts".to_string()));
+        assert_eq!(fields[2].0, 15);
+        let content = &fields[2].1;
+        assert!(content.contains("import { AddressSchema } from \"../../common/v1/address\";"));
+        assert!(content.contains("name: z.optional(z.string()),"));
+        assert!(content.contains("export class UsersClient {"));
+        assert_eq!(fields.len(), 3);
+    }
+
+    #[test]
+    fn plugin_reports_unknown_parameter() {
+        let mut request = Encoder::default();
+        request.string(2, "no-such-option");
+
+        let fields = run_plugin(&request.buf);
+
+        assert_eq!(
+            fields,
+            vec![(
+                1,
+                "invalid argument: unknown plugin parameter: --no-such-option".to_string()
+            )]
+        );
+    }
+}
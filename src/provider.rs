@@ -0,0 +1,687 @@
+//! Access to the files read and written by protots, so inputs may come from
+//! elsewhere than the local file system: memory (e.g. tests or WASM builds)
+//! or archives.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use crate::errors::PtError;
+use crate::inflate;
+
+pub trait FileProvider {
+    /// Content of the file at the given path.
+    fn read(&self, path: &Path) -> Result<Vec<u8>, PtError>;
+
+    /// Whether there is a file at the given path.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// All files below the given directory, recursively and sorted by path.
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>, PtError>;
+
+    /// Write the file, creating missing parent directories.
+    fn write(&self, path: &Path, content: &[u8]) -> Result<(), PtError>;
+
+    fn read_to_string(&self, path: &Path) -> Result<String, PtError> {
+        String::from_utf8(self.read(path)?).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: stream did not contain valid UTF-8", path.display()),
+            )
+            .into()
+        })
+    }
+}
+
+/// Shared handle of the file provider in use, the local file system by
+/// default.
+#[derive(Clone)]
+pub struct Files(pub Rc<dyn FileProvider>);
+
+impl Default for Files {
+    fn default() -> Files {
+        Files(Rc::new(Fs))
+    }
+}
+
+impl Deref for Files {
+    type Target = dyn FileProvider;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// The local file system.
+pub struct Fs;
+
+impl FileProvider for Fs {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, PtError> {
+        Ok(std::fs::read(path)?)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>, PtError> {
+        let mut entries = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+
+        let mut files = Vec::new();
+        for path in entries {
+            if path.is_dir() {
+                files.extend(self.list(&path)?);
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    fn write(&self, path: &Path, content: &[u8]) -> Result<(), PtError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(std::fs::write(path, content)?)
+    }
+}
+
+/// Files held in memory.
+#[derive(Default)]
+pub struct Memory {
+    files: RefCell<BTreeMap<PathBuf, Vec<u8>>>,
+}
+
+impl Memory {
+    pub fn new<P, C>(files: impl IntoIterator<Item = (P, C)>) -> Memory
+    where
+        P: AsRef<Path>,
+        C: Into<Vec<u8>>,
+    {
+        let files = files
+            .into_iter()
+            .map(|(path, content)| (normalize(path.as_ref()), content.into()))
+            .collect();
+        Memory {
+            files: RefCell::new(files),
+        }
+    }
+}
+
+impl FileProvider for Memory {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, PtError> {
+        self.files
+            .borrow()
+            .get(&normalize(path))
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.borrow().contains_key(&normalize(path))
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>, PtError> {
+        Ok(below(self.files.borrow().keys(), dir))
+    }
+
+    fn write(&self, path: &Path, content: &[u8]) -> Result<(), PtError> {
+        self.files
+            .borrow_mut()
+            .insert(normalize(path), content.to_vec());
+        Ok(())
+    }
+}
+
+/// Read-only files of a zip archive, stored or deflated.
+pub struct Zip {
+    data: Vec<u8>,
+    entries: BTreeMap<PathBuf, ZipEntry>,
+}
+
+struct ZipEntry {
+    method: u16,
+    encrypted: bool,
+    crc: u32,
+    compressed_size: usize,
+    size: usize,
+    header_offset: usize,
+}
+
+const ZIP_END_OF_DIRECTORY: u32 = 0x0605_4b50;
+const ZIP_DIRECTORY_ENTRY: u32 = 0x0201_4b50;
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+
+impl Zip {
+    /// Index the entries of the given archive.
+    pub fn new(data: Vec<u8>) -> Result<Zip, PtError> {
+        // the end of central directory record is followed by a comment of
+        // up to 64k bytes
+        let end = (0..data.len().saturating_sub(21))
+            .rev()
+            .take(u16::MAX as usize + 1)
+            .find(|pos| u32_at(&data, *pos) == Some(ZIP_END_OF_DIRECTORY))
+            .ok_or_else(|| invalid_zip("end of central directory not found"))?;
+
+        let count = u16_at(&data, end + 10).unwrap_or(0) as usize;
+        let mut pos = u32_at(&data, end + 16).unwrap_or(0) as usize;
+        if pos == u32::MAX as usize {
+            return Err(invalid_zip("zip64 archives are not supported"));
+        }
+
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            if u32_at(&data, pos) != Some(ZIP_DIRECTORY_ENTRY) {
+                return Err(invalid_zip("invalid central directory entry"));
+            }
+            let field = |offset: usize| u32_at(&data, pos + offset).unwrap_or(0) as usize;
+            let short = |offset: usize| u16_at(&data, pos + offset).unwrap_or(0) as usize;
+
+            let name_len = short(28);
+            let name = data
+                .get(pos + 46..pos + 46 + name_len)
+                .ok_or_else(|| invalid_zip("truncated central directory"))?;
+            let name = String::from_utf8_lossy(name).to_string();
+            let entry = ZipEntry {
+                method: short(10) as u16,
+                encrypted: short(8) & 1 == 1,
+                crc: field(16) as u32,
+                compressed_size: field(20),
+                size: field(24),
+                header_offset: field(42),
+            };
+            if !name.ends_with('/') {
+                entries.insert(normalize(Path::new(&name)), entry);
+            }
+
+            pos += 46 + name_len + short(30) + short(32);
+        }
+
+        Ok(Zip { data, entries })
+    }
+}
+
+impl FileProvider for Zip {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, PtError> {
+        let entry = self
+            .entries
+            .get(&normalize(path))
+            .ok_or_else(|| not_found(path))?;
+        let invalid = |message: &str| invalid_zip(&format!("{}: {}", path.display(), message));
+
+        if entry.encrypted {
+            return Err(invalid("encrypted entries are not supported"));
+        }
+        let pos = entry.header_offset;
+        if u32_at(&self.data, pos) != Some(ZIP_LOCAL_HEADER) {
+            return Err(invalid("invalid local file header"));
+        }
+        let start = pos
+            + 30
+            + u16_at(&self.data, pos + 26).unwrap_or(0) as usize
+            + u16_at(&self.data, pos + 28).unwrap_or(0) as usize;
+        let raw = self
+            .data
+            .get(start..start + entry.compressed_size)
+            .ok_or_else(|| invalid("truncated entry"))?;
+
+        let content = match entry.method {
+            0 => raw.to_vec(),
+            8 => inflate::inflate(raw)?,
+            method => return Err(invalid(&format!("unsupported compression {}", method))),
+        };
+        if content.len() != entry.size || inflate::crc32(&content) != entry.crc {
+            return Err(invalid("checksum mismatch"));
+        }
+        Ok(content)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.entries.contains_key(&normalize(path))
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>, PtError> {
+        Ok(below(self.entries.keys(), dir))
+    }
+
+    fn write(&self, path: &Path, _content: &[u8]) -> Result<(), PtError> {
+        Err(PtError::InvalidArgument(format!(
+            "cannot write {}: zip archives are read-only",
+            path.display()
+        )))
+    }
+}
+
+/// Read-only files of a tar archive, optionally gzip compressed.
+pub struct Tar {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+const TAR_BLOCK: usize = 512;
+
+impl Tar {
+    /// Extract the regular files of the given archive.
+    pub fn new(data: Vec<u8>) -> Result<Tar, PtError> {
+        let data = match data.starts_with(&[0x1f, 0x8b]) {
+            true => gunzip(&data)?,
+            false => data,
+        };
+
+        let mut files = BTreeMap::new();
+        // name of the next entry given by a GNU long name or pax header
+        let mut long_name: Option<String> = None;
+        let mut pos = 0;
+        while let Some(header) = data.get(pos..pos + TAR_BLOCK) {
+            // the archive ends with two zero blocks
+            if header.iter().all(|byte| *byte == 0) {
+                break;
+            }
+
+            let size = octal(&header[124..136]).ok_or_else(|| invalid_tar("invalid entry size"))?;
+            let start = pos + TAR_BLOCK;
+            let content = data
+                .get(start..start + size)
+                .ok_or_else(|| invalid_tar("truncated entry"))?;
+            pos = start + size.div_ceil(TAR_BLOCK) * TAR_BLOCK;
+
+            match header[156] {
+                b'L' => long_name = Some(text(content)),
+                b'x' => {
+                    long_name = text(content)
+                        .lines()
+                        .find_map(|record| record.split_once(" path=").map(|(_, path)| path))
+                        .map(str::to_string)
+                        .or(long_name)
+                }
+                b'0' | 0 => {
+                    let name = long_name.take().unwrap_or_else(|| {
+                        let (name, prefix) = (text(&header[..100]), text(&header[345..500]));
+                        match &header[257..262] == b"ustar" && !prefix.is_empty() {
+                            true => format!("{}/{}", prefix, name),
+                            false => name,
+                        }
+                    });
+                    files.insert(normalize(Path::new(&name)), content.to_vec());
+                }
+                // directories, links and other special entries
+                _ => long_name = None,
+            }
+        }
+
+        Ok(Tar { files })
+    }
+}
+
+impl FileProvider for Tar {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, PtError> {
+        self.files
+            .get(&normalize(path))
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.contains_key(&normalize(path))
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>, PtError> {
+        Ok(below(self.files.keys(), dir))
+    }
+
+    fn write(&self, path: &Path, _content: &[u8]) -> Result<(), PtError> {
+        Err(PtError::InvalidArgument(format!(
+            "cannot write {}: tar archives are read-only",
+            path.display()
+        )))
+    }
+}
+
+/// Decompress a single member gzip file (RFC 1952).
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, PtError> {
+    let invalid = |message: &str| PtError::InvalidArchive(format!("gzip: {}", message));
+    if data.len() < 18 || data[2] != 8 {
+        return Err(invalid("unsupported compression"));
+    }
+
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & 0x04 != 0 {
+        pos += 2 + u16_at(data, pos).unwrap_or(0) as usize;
+    }
+    // zero terminated file name and comment
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            pos += data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|byte| *byte == 0))
+                .ok_or_else(|| invalid("truncated header"))?
+                + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+
+    let trailer = data.len() - 8;
+    let content = inflate::inflate(data.get(pos..trailer).unwrap_or_default())?;
+    if u32_at(data, trailer) != Some(inflate::crc32(&content))
+        || u32_at(data, trailer + 4) != Some(content.len() as u32)
+    {
+        return Err(invalid("checksum mismatch"));
+    }
+    Ok(content)
+}
+
+/// Null terminated string of a tar header field.
+fn text(field: &[u8]) -> String {
+    let end = field
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+fn octal(field: &[u8]) -> Option<usize> {
+    let digits = text(field);
+    usize::from_str_radix(digits.trim(), 8).ok()
+}
+
+fn invalid_tar(message: &str) -> PtError {
+    PtError::InvalidArchive(format!("tar: {}", message))
+}
+
+/// Whether the given include path refers to an archive by its extension.
+pub fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    [".zip", ".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+/// Files of the given provider with the archives among the include paths
+/// mounted, so their members appear as files below the path of the archive,
+/// just like the files of a directory would.
+pub fn mount(files: &Files, include_paths: &[String]) -> Result<Files, PtError> {
+    let mut archives: Vec<(PathBuf, Box<dyn FileProvider>)> = Vec::new();
+    for include in include_paths {
+        let path = Path::new(include);
+        if !is_archive(path) || !files.exists(path) {
+            continue;
+        }
+        let data = files.read(path)?;
+        let archive: Box<dyn FileProvider> = match include.ends_with(".zip") {
+            true => Box::new(Zip::new(data)?),
+            false => Box::new(Tar::new(data)?),
+        };
+        archives.push((normalize(path), archive));
+    }
+
+    match archives.is_empty() {
+        true => Ok(files.clone()),
+        false => Ok(Files(Rc::new(Mounted {
+            base: files.clone(),
+            archives,
+        }))),
+    }
+}
+
+/// Archives mounted on top of another provider.
+struct Mounted {
+    base: Files,
+    archives: Vec<(PathBuf, Box<dyn FileProvider>)>,
+}
+
+impl Mounted {
+    /// Archive containing the given path along with the path of the member,
+    /// if any.
+    fn archive(&self, path: &Path) -> Option<(&dyn FileProvider, PathBuf)> {
+        let path = normalize(path);
+        self.archives.iter().find_map(|(root, archive)| {
+            path.strip_prefix(root)
+                .ok()
+                .map(|member| (archive.as_ref(), member.to_path_buf()))
+        })
+    }
+}
+
+impl FileProvider for Mounted {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, PtError> {
+        match self.archive(path) {
+            Some((archive, member)) => archive.read(&member),
+            None => self.base.read(path),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        match self.archive(path) {
+            Some((archive, member)) => archive.exists(&member),
+            None => self.base.exists(path),
+        }
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>, PtError> {
+        let Some((archive, member)) = self.archive(dir) else {
+            return self.base.list(dir);
+        };
+        Ok(archive
+            .list(&member)?
+            .iter()
+            .filter_map(|path| path.strip_prefix(&member).ok())
+            .map(|path| dir.join(path))
+            .collect())
+    }
+
+    fn write(&self, path: &Path, content: &[u8]) -> Result<(), PtError> {
+        match self.archive(path) {
+            Some((archive, member)) => archive.write(&member, content),
+            None => self.base.write(path, content),
+        }
+    }
+}
+
+/// Path without `.` components, as archives and memory store them.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+/// Paths of the given sorted files below `dir`, relative to `dir` like the
+/// file system lists them.
+fn below<'a>(files: impl Iterator<Item = &'a PathBuf>, dir: &Path) -> Vec<PathBuf> {
+    let prefix = normalize(dir);
+    files
+        .filter_map(|path| path.strip_prefix(&prefix).ok())
+        .map(|path| dir.join(path))
+        .collect()
+}
+
+fn not_found(path: &Path) -> PtError {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("{}: no such file", path.display()),
+    )
+    .into()
+}
+
+fn invalid_zip(message: &str) -> PtError {
+    PtError::InvalidArchive(format!("zip: {}", message))
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    let bytes = data.get(pos..pos + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{mount, FileProvider, Files, Memory, Tar, Zip};
+    use crate::inflate::crc32;
+
+    /// Zip archive of the given files, stored without compression.
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut directory = Vec::new();
+        for (name, content) in files {
+            let offset = data.len() as u32;
+            let mut header = Vec::new();
+            header.extend(0u16.to_le_bytes()); // flags
+            header.extend(0u16.to_le_bytes()); // method
+            header.extend(0u32.to_le_bytes()); // time and date
+            header.extend(crc32(content).to_le_bytes());
+            header.extend((content.len() as u32).to_le_bytes());
+            header.extend((content.len() as u32).to_le_bytes());
+            header.extend((name.len() as u16).to_le_bytes());
+            header.extend(0u16.to_le_bytes()); // extra length
+
+            data.extend(0x0403_4b50u32.to_le_bytes());
+            data.extend(20u16.to_le_bytes());
+            data.extend(&header);
+            data.extend(name.as_bytes());
+            data.extend(*content);
+
+            directory.extend(0x0201_4b50u32.to_le_bytes());
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(&header);
+            directory.extend([0; 6]); // comment length, disk, internal attributes
+            directory.extend(0u32.to_le_bytes()); // external attributes
+            directory.extend(offset.to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+
+        let directory_offset = data.len() as u32;
+        let count = (files.len() as u16).to_le_bytes();
+        data.extend(&directory);
+        data.extend(0x0605_4b50u32.to_le_bytes());
+        data.extend([0; 4]);
+        data.extend(count);
+        data.extend(count);
+        data.extend((directory.len() as u32).to_le_bytes());
+        data.extend(directory_offset.to_le_bytes());
+        data.extend(0u16.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn zip_provider() {
+        let files: &[(&str, &[u8])] = &[
+            ("foo/", b""),
+            ("foo/v1/user.proto", b"syntax = \"proto3\";"),
+            ("readme.md", b"# protos"),
+        ];
+        let zip = Zip::new(zip(files)).unwrap();
+
+        assert!(zip.exists(Path::new("./foo/v1/user.proto")));
+        assert!(!zip.exists(Path::new("foo")));
+        assert_eq!(
+            zip.read_to_string(Path::new("foo/v1/user.proto")).unwrap(),
+            "syntax = \"proto3\";"
+        );
+        assert_eq!(
+            zip.list(Path::new(".")).unwrap(),
+            vec![
+                PathBuf::from("./foo/v1/user.proto"),
+                PathBuf::from("./readme.md")
+            ]
+        );
+        assert_eq!(
+            zip.list(Path::new("foo")).unwrap(),
+            vec![PathBuf::from("foo/v1/user.proto")]
+        );
+        assert!(zip.read(Path::new("missing.proto")).is_err());
+        assert!(zip.write(Path::new("out.ts"), b"").is_err());
+        assert!(Zip::new(b"no archive".to_vec()).is_err());
+    }
+
+    /// Gzip compressed ustar archive of the given files, `(prefix, name)`.
+    fn tar_gz(files: &[(&str, &str, &[u8])]) -> Vec<u8> {
+        let mut tar = Vec::new();
+        for (prefix, name, content) in files {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+            header[156] = b'0';
+            header[257..263].copy_from_slice(b"ustar\0");
+            header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+            tar.extend(header);
+            tar.extend(*content);
+            tar.resize(tar.len().div_ceil(512) * 512, 0);
+        }
+        tar.extend([0; 1024]);
+
+        // a single stored deflate block
+        let mut data = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff, 1];
+        data.extend((tar.len() as u16).to_le_bytes());
+        data.extend((!(tar.len() as u16)).to_le_bytes());
+        data.extend(&tar);
+        data.extend(crc32(&tar).to_le_bytes());
+        data.extend((tar.len() as u32).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn tar_provider() {
+        let archive = tar_gz(&[
+            ("", "foo/v1/user.proto", b"syntax = \"proto3\";"),
+            ("common", "date.proto", b"syntax = \"proto2\";"),
+        ]);
+        let tar = Tar::new(archive.clone()).unwrap();
+        assert_eq!(
+            tar.list(Path::new("")).unwrap(),
+            vec![
+                PathBuf::from("common/date.proto"),
+                PathBuf::from("foo/v1/user.proto")
+            ]
+        );
+        assert!(Tar::new(archive[..100].to_vec()).is_err());
+
+        // mounted below the path of the archive
+        let base = Memory::new([("protos.tar.gz", archive), ("local.proto", b"".to_vec())]);
+        let files = mount(
+            &Files(std::rc::Rc::new(base)),
+            &["protos.tar.gz".to_string(), "missing.zip".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            files
+                .read_to_string(Path::new("./protos.tar.gz/common/date.proto"))
+                .unwrap(),
+            "syntax = \"proto2\";"
+        );
+        assert!(files.exists(Path::new("local.proto")));
+        assert_eq!(
+            files.list(Path::new("protos.tar.gz/foo")).unwrap(),
+            vec![PathBuf::from("protos.tar.gz/foo/v1/user.proto")]
+        );
+        assert!(files.write(Path::new("protos.tar.gz/a.ts"), b"").is_err());
+        files.write(Path::new("out/a.ts"), b"").unwrap();
+    }
+
+    #[test]
+    fn memory_provider() {
+        let memory = Memory::new([("a.proto", "syntax = \"proto3\";")]);
+        memory
+            .write(Path::new("./out/a.ts"), b"export {};")
+            .unwrap();
+
+        assert!(memory.exists(Path::new("out/a.ts")));
+        assert_eq!(memory.read(Path::new("out/a.ts")).unwrap(), b"export {};");
+        assert_eq!(
+            memory.list(Path::new(".")).unwrap(),
+            vec![PathBuf::from("./a.proto"), PathBuf::from("./out/a.ts")]
+        );
+    }
+}
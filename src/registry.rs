@@ -0,0 +1,605 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::errors::PtError;
+use crate::parser::{Elem, Enum, Field, ImportModifier, Msg, Proto};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Message,
+    Enum,
+}
+
+pub struct ProtoType {
+    pub kind: Kind,
+    pub full_name: String,
+    pub ts_name: String,
+    pub schema: String,
+    pub file: String,
+}
+
+impl ProtoType {
+    fn new(
+        kind: Kind,
+        package: Option<&str>,
+        parents: &[String],
+        name: &str,
+        file: &str,
+    ) -> ProtoType {
+        let parts = parents
+            .iter()
+            .cloned()
+            .chain([name.to_string()])
+            .collect::<Vec<_>>();
+        let full_name = qualify(package.unwrap_or(""), &parts.join("."));
+        let ts_name = parts.join("_");
+        let schema = format!("{}Schema", ts_name);
+
+        ProtoType {
+            kind,
+            full_name,
+            ts_name,
+            schema,
+            file: file.to_string(),
+        }
+    }
+}
+
+/// Registry of all message and enum types known to a generation run, keyed by
+/// their fully-qualified (package including) protobuf name.
+///
+/// Like protoc, a file sees the types of the files it imports only, along
+/// with the ones these re-export via `import public` (see `visible`).
+pub struct Registry {
+    types: HashMap<String, ProtoType>,
+    /// imports of every file, whether they are public
+    imports: HashMap<String, Vec<(String, bool)>>,
+    /// files by the package they declare
+    packages: HashMap<String, Vec<String>>,
+}
+
+impl Registry {
+    pub fn new<'a, I>(protos: I) -> Registry
+    where
+        I: IntoIterator<Item = &'a Proto>,
+    {
+        let mut map = HashMap::new();
+        let mut imports = HashMap::new();
+        let mut packages = HashMap::<_, Vec<_>>::new();
+
+        for proto in protos {
+            let package = proto.package();
+            packages
+                .entry(package.unwrap_or("").to_string())
+                .or_default()
+                .push(proto.file.clone());
+            let entry: &mut Vec<_> = imports.entry(proto.file.clone()).or_default();
+
+            for elem in &proto.elems {
+                match elem {
+                    Elem::Import { name, modifier, .. } => {
+                        entry.push((name.clone(), *modifier == ImportModifier::Public));
+                    }
+                    Elem::Message(msg) => {
+                        let ptype =
+                            ProtoType::new(Kind::Message, package, &[], &msg.name, &proto.file);
+                        map.insert(ptype.full_name.clone(), ptype);
+
+                        for ptype in msg.fields.iter().flat_map(|fld| {
+                            Self::collect(fld, package, vec![msg.name.clone()], &proto.file)
+                        }) {
+                            map.insert(ptype.full_name.clone(), ptype);
+                        }
+                    }
+                    Elem::Enum(e) => {
+                        let ptype = ProtoType::new(Kind::Enum, package, &[], &e.name, &proto.file);
+                        map.insert(ptype.full_name.clone(), ptype);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Registry {
+            types: map,
+            imports,
+            packages,
+        }
+    }
+
+    /// Lookup a type by its fully-qualified name.
+    pub fn get(&self, full_name: &str) -> Option<&ProtoType> {
+        self.types.get(full_name)
+    }
+
+    /// All known types in no particular order.
+    pub fn types(&self) -> impl Iterator<Item = &ProtoType> {
+        self.types.values()
+    }
+
+    /// Resolve a (possibly partially qualified) type reference the same way
+    /// protoc does: starting at the innermost `scope` and walking outwards
+    /// until the package root is reached. Names starting with a dot are
+    /// fully-qualified already.
+    pub fn resolve(&self, name: &str, scope: &str) -> Option<&ProtoType> {
+        let visible = self.visible(scope);
+        let visible = |ptype: &&ProtoType| {
+            visible
+                .as_ref()
+                .is_none_or(|visible| visible.contains(ptype.file.as_str()))
+        };
+        if let Some(full_name) = name.strip_prefix('.') {
+            return self.types.get(full_name).filter(visible);
+        }
+
+        let mut scope = scope;
+
+        loop {
+            if let Some(ptype) = self.types.get(&qualify(scope, name)).filter(visible) {
+                return Some(ptype);
+            }
+
+            if scope.is_empty() {
+                return None;
+            }
+
+            scope = scope.rfind('.').map(|idx| &scope[..idx]).unwrap_or("");
+        }
+    }
+
+    /// Files whose types are visible from the given scope: the file of the
+    /// type, or the files of the package, and their imports along with the
+    /// files re-exported by them via `import public`, transitively. All types
+    /// are visible from the root scope, i.e. to lookups outside of any file.
+    fn visible(&self, scope: &str) -> Option<HashSet<&str>> {
+        let files = match self.types.get(scope) {
+            Some(ptype) => vec![ptype.file.as_str()],
+            None if scope.is_empty() => return None,
+            None => self
+                .packages
+                .get(scope)?
+                .iter()
+                .map(String::as_str)
+                .collect(),
+        };
+
+        let mut visible = HashSet::new();
+        let mut pending = Vec::new();
+        for file in files {
+            visible.insert(file);
+            pending.extend(self.imports.get(file).into_iter().flatten());
+        }
+        while let Some((import, _)) = pending.pop() {
+            if visible.insert(import.as_str()) {
+                let public = self.imports.get(import).into_iter().flatten();
+                pending.extend(public.filter(|(_, public)| *public));
+            }
+        }
+        Some(visible)
+    }
+
+    /// Closest match of a type reference that cannot be resolved among the
+    /// scalar and known types, by edit distance of the fully-qualified and
+    /// the short names. The match is given the way it is written in the
+    /// scope, e.g. `UserProfile` for `UserProfil`.
+    pub fn suggest(&self, name: &str, scope: &str) -> Option<String> {
+        let name = name.strip_prefix('.').unwrap_or(name);
+        let short = short_name(name);
+        // allow roughly one typo every three characters
+        let max_distance = (short.chars().count() / 3).max(1);
+
+        let scalars = SCALARS
+            .iter()
+            .map(|scalar| (edit_distance(name, scalar), scalar.to_string()));
+        let types = self.types.values().map(|ptype| {
+            let type_short = short_name(&ptype.full_name);
+            let distance =
+                edit_distance(name, &ptype.full_name).min(edit_distance(short, type_short));
+            let written = match self.resolve(type_short, scope) {
+                Some(found) if found.full_name == ptype.full_name => type_short.to_string(),
+                _ => ptype.full_name.clone(),
+            };
+            (distance, written)
+        });
+
+        scalars
+            .chain(types)
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, written)| written)
+    }
+
+    /// Error of a type reference that cannot be resolved in the given scope,
+    /// suggesting the closest match if there is one.
+    pub fn unresolved(&self, name: &str, scope: &str) -> PtError {
+        match self.suggest(name, scope) {
+            Some(suggestion) => PtError::UnknownType {
+                name: name.to_string(),
+                suggestion,
+            },
+            None => PtError::ProtobufTypeNotFound(name.to_string()),
+        }
+    }
+
+    fn collect(
+        field: &Field,
+        package: Option<&str>,
+        mut parent: Vec<String>,
+        file: &str,
+    ) -> Vec<ProtoType> {
+        let mut types = Vec::new();
+        match field {
+            Field::SubMessage(msg) => {
+                types.push(ProtoType::new(
+                    Kind::Message,
+                    package,
+                    &parent,
+                    &msg.name,
+                    file,
+                ));
+
+                parent.push(msg.name.clone());
+                types.extend(
+                    msg.fields
+                        .iter()
+                        .flat_map(|fld| Self::collect(fld, package, parent.clone(), file)),
+                );
+            }
+            Field::SubEnum(e) => {
+                types.push(ProtoType::new(Kind::Enum, package, &parent, &e.name, file))
+            }
+            _ => (),
+        }
+        types
+    }
+}
+
+/// Declaration of the message with the given fully-qualified name.
+pub fn find_message<'a, I>(protos: I, full_name: &str) -> Option<&'a Msg>
+where
+    I: IntoIterator<Item = &'a Proto>,
+{
+    fn find<'a>(msg: &'a Msg, scope: &str, full_name: &str) -> Option<&'a Msg> {
+        let name = qualify(scope, &msg.name);
+        if name == full_name {
+            return Some(msg);
+        }
+        msg.fields.iter().find_map(|field| match field {
+            Field::SubMessage(sub) => find(sub, &name, full_name),
+            _ => None,
+        })
+    }
+
+    protos.into_iter().find_map(|proto| {
+        let package = proto.package().unwrap_or("");
+        proto.elems.iter().find_map(|elem| match elem {
+            Elem::Message(msg) => find(msg, package, full_name),
+            _ => None,
+        })
+    })
+}
+
+/// Declaration of the enum with the given fully-qualified name.
+pub fn find_enum<'a, I>(protos: I, full_name: &str) -> Option<&'a Enum>
+where
+    I: IntoIterator<Item = &'a Proto> + Clone,
+{
+    let (scope, name) = full_name.rsplit_once('.').unwrap_or(("", full_name));
+    let nested = find_message(protos.clone(), scope).and_then(|msg| {
+        msg.fields.iter().find_map(|field| match field {
+            Field::SubEnum(e) if e.name == name => Some(e),
+            _ => None,
+        })
+    });
+
+    nested.or_else(|| {
+        protos.into_iter().find_map(|proto| {
+            proto.elems.iter().find_map(|elem| match elem {
+                Elem::Enum(e) if qualify(proto.package().unwrap_or(""), &e.name) == full_name => {
+                    Some(e)
+                }
+                _ => None,
+            })
+        })
+    })
+}
+
+/// Field of a message definition, with oneof members flattened. The
+/// generators look at the field types only.
+#[cfg(any(feature = "zod", feature = "payloads"))]
+#[cfg_attr(not(feature = "payloads"), allow(dead_code))]
+pub(crate) struct Declared<'a> {
+    pub name: &'a str,
+    pub field_type: &'a str,
+    pub flag: &'a crate::parser::Flag,
+    /// key type of map fields
+    pub key_type: Option<&'a str>,
+    pub oneof: Option<&'a str>,
+    pub options: &'a [crate::parser::Option],
+    /// custom JSON name of singular fields (see `Field::Single`)
+    pub json_name: Option<&'a str>,
+}
+
+#[cfg(any(feature = "zod", feature = "payloads"))]
+pub(crate) fn declared<'a>(
+    fields: &'a [Field],
+    oneof: Option<&'a str>,
+    into: &mut Vec<Declared<'a>>,
+) {
+    for field in fields {
+        match field {
+            Field::Single {
+                name,
+                field_type,
+                flag,
+                options,
+                json_name,
+                ..
+            } => into.push(Declared {
+                name,
+                field_type,
+                flag,
+                key_type: None,
+                oneof,
+                options,
+                json_name: json_name.as_deref(),
+            }),
+            Field::Map {
+                name,
+                key_type,
+                value_type,
+                options,
+                ..
+            } => into.push(Declared {
+                name,
+                field_type: value_type,
+                flag: &crate::parser::Flag::Repeated,
+                key_type: Some(key_type),
+                oneof,
+                options,
+                json_name: None,
+            }),
+            Field::OneOf { name, fields, .. } => declared(fields, Some(name), into),
+            _ => (),
+        }
+    }
+}
+
+const SCALARS: [&str; 15] = [
+    "double", "float", "int32", "int64", "uint32", "uint64", "sint32", "sint64", "fixed32",
+    "fixed64", "sfixed32", "sfixed64", "bool", "string", "bytes",
+];
+
+/// Whether the given type name is one of the protobuf scalar value types.
+pub fn is_scalar(name: &str) -> bool {
+    SCALARS.contains(&name)
+}
+
+/// Levenshtein distance of the two strings, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + (ca != *cb) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Last part of a dotted protobuf name, e.g. `User` of `foo.v1.User`.
+fn short_name(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// Join a scope and a name into a dotted protobuf name.
+pub fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{Elem, Enum, Field, ImportModifier, Msg, Proto, Span};
+
+    use super::Registry;
+
+    fn proto(file: &str, package: &str, elems: Vec<Elem>) -> Proto {
+        let mut all = vec![Elem::Package {
+            name: package.to_string(),
+            span: Span::default(),
+        }];
+        all.extend(elems);
+
+        Proto {
+            syntax: "proto3".to_string(),
+            edition: None,
+            file: file.to_string(),
+            elems: all,
+            span: Span::default(),
+        }
+    }
+
+    fn import(name: &str, modifier: ImportModifier) -> Elem {
+        Elem::Import {
+            name: name.to_string(),
+            modifier,
+            span: Span::default(),
+        }
+    }
+
+    fn msg(name: &str, fields: Vec<Field>) -> Msg {
+        Msg {
+            name: name.to_string(),
+            fields,
+            deprecated: false,
+            doc: None,
+            span: Span::default(),
+        }
+    }
+
+    #[test]
+    fn resolve_innermost_scope_first() {
+        let p = proto(
+            "a.proto",
+            "foo.v1",
+            vec![
+                Elem::Message(msg(
+                    "Outer",
+                    vec![Field::SubEnum(Enum {
+                        name: "Kind".to_string(),
+                        values: vec![],
+                        options: vec![],
+                        deprecated: false,
+                        span: Span::default(),
+                    })],
+                )),
+                Elem::Enum(Enum {
+                    name: "Kind".to_string(),
+                    values: vec![],
+                    options: vec![],
+                    deprecated: false,
+                    span: Span::default(),
+                }),
+            ],
+        );
+        let registry = Registry::new([&p]);
+
+        let inner = registry.resolve("Kind", "foo.v1.Outer").unwrap();
+        assert_eq!(inner.full_name, "foo.v1.Outer.Kind");
+        assert_eq!(inner.ts_name, "Outer_Kind");
+
+        let outer = registry.resolve("Kind", "foo.v1").unwrap();
+        assert_eq!(outer.full_name, "foo.v1.Kind");
+    }
+
+    #[test]
+    fn resolve_across_packages() {
+        let a = proto(
+            "a.proto",
+            "foo.v1",
+            vec![Elem::Message(msg("User", vec![]))],
+        );
+        let b = proto(
+            "b.proto",
+            "bar.v1",
+            vec![
+                import("a.proto", ImportModifier::None),
+                Elem::Message(msg("Order", vec![])),
+            ],
+        );
+        let registry = Registry::new([&a, &b]);
+
+        let user = registry.resolve("foo.v1.User", "bar.v1.Order").unwrap();
+        assert_eq!(user.file, "a.proto");
+
+        // partially qualified from a sibling package
+        assert!(registry.resolve("v1.User", "foo.v2").is_some());
+        assert!(registry.resolve("v1.User", "bar.v1").is_none());
+        assert!(registry.resolve("v1.User", "foo.v1.User").is_some());
+        assert!(registry.resolve("User", "bar.v1").is_none());
+    }
+
+    #[test]
+    fn resolve_fully_qualified() {
+        let a = proto("a.proto", "foo", vec![Elem::Message(msg("User", vec![]))]);
+        let b = proto(
+            "b.proto",
+            "bar.foo",
+            vec![Elem::Message(msg("User", vec![]))],
+        );
+        let registry = Registry::new([&a, &b]);
+
+        // without the leading dot the innermost scope wins
+        assert_eq!(
+            registry.resolve("foo.User", "bar.baz").unwrap().file,
+            "b.proto"
+        );
+        assert_eq!(
+            registry.resolve(".foo.User", "bar.baz").unwrap().file,
+            "a.proto"
+        );
+        assert!(registry.resolve(".User", "foo").is_none());
+    }
+
+    #[test]
+    fn resolve_visible_imports_only() {
+        let a = proto("a.proto", "a", vec![Elem::Message(msg("A", vec![]))]);
+        let b = proto("b.proto", "b", vec![Elem::Message(msg("B", vec![]))]);
+        let c = proto(
+            "c.proto",
+            "c",
+            vec![
+                import("a.proto", ImportModifier::None),
+                import("b.proto", ImportModifier::Public),
+            ],
+        );
+        let d = proto(
+            "d.proto",
+            "d",
+            vec![
+                import("c.proto", ImportModifier::None),
+                Elem::Message(msg("D", vec![])),
+            ],
+        );
+        let registry = Registry::new([&a, &b, &c, &d]);
+
+        // re-exported by the direct import
+        assert_eq!(registry.resolve("b.B", "d.D").unwrap().file, "b.proto");
+        assert!(registry.resolve(".b.B", "d").is_some());
+        // imported by the direct import without re-exporting it
+        assert!(registry.resolve("a.A", "d.D").is_none());
+        assert!(registry.resolve(".a.A", "d").is_none());
+        assert!(registry.resolve("a.A", "c").is_some());
+        // from outside of any file
+        assert!(registry.resolve("a.A", "").is_some());
+    }
+
+    #[test]
+    fn suggest_close_matches() {
+        let a = proto(
+            "a.proto",
+            "foo.v1",
+            vec![
+                Elem::Message(msg("UserProfile", vec![])),
+                Elem::Message(msg("Address", vec![])),
+            ],
+        );
+        let b = proto(
+            "b.proto",
+            "bar.v1",
+            vec![Elem::Message(msg("Order", vec![]))],
+        );
+        let registry = Registry::new([&a, &b]);
+
+        let suggest = |name: &str, scope: &str| registry.suggest(name, scope);
+        assert_eq!(
+            suggest("UserProfil", "foo.v1").as_deref(),
+            Some("UserProfile")
+        );
+        assert_eq!(
+            suggest("Adress", "foo.v1.UserProfile").as_deref(),
+            Some("Address")
+        );
+        assert_eq!(suggest("Ordr", "foo.v1").as_deref(), Some("bar.v1.Order"));
+        assert_eq!(
+            suggest("foo.v2.Address", "bar.v1").as_deref(),
+            Some("foo.v1.Address")
+        );
+        assert_eq!(suggest("strng", "foo.v1").as_deref(), Some("string"));
+        assert_eq!(suggest("Payment", "foo.v1"), None);
+
+        assert_eq!(
+            registry.unresolved("UserProfil", "foo.v1").to_string(),
+            "could not find type named: UserProfil, did you mean `UserProfile`?"
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Include paths referring to remote sources, that are fetched on demand and
+//! cached locally:
+//!
+//! - `https://example.com/protos` (plain files below the given URL)
+//! - `git+https://github.com/org/contracts.git#v1.2.0` (repository pinned to a
+//!   branch, tag or commit), optionally followed by a subdirectory of the
+//!   repository: `git+https://github.com/org/contracts.git#v1.2.0:proto`
+//!
+//! Sources are fetched over https only, unless plain http is allowed
+//! explicitly (see `--allow-http`). All other schemes (e.g. `git+ssh://` or
+//! `git+file://`) are rejected.
+
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use crate::errors::PtError;
+use crate::Opts;
+
+#[derive(Debug, PartialEq)]
+pub enum Remote<'a> {
+    Http(&'a str),
+    Git {
+        url: &'a str,
+        reference: &'a str,
+        dir: &'a str,
+    },
+}
+
+impl<'a> Remote<'a> {
+    /// Parse the given include path, returning `None` for local directories.
+    pub fn parse(opts: &Opts, include: &'a str) -> Result<Option<Remote<'a>>, PtError> {
+        let invalid = |message: &str| {
+            Err(PtError::InvalidArgument(format!(
+                "{}: {}",
+                message, include
+            )))
+        };
+
+        let (url, remote) = match include.strip_prefix("git+") {
+            Some(repo) => {
+                let (url, reference) = repo.split_once('#').ok_or_else(|| {
+                    PtError::InvalidArgument(format!("git include path without ref: {}", include))
+                })?;
+                let (reference, dir) = reference.split_once(':').unwrap_or((reference, ""));
+
+                // neither may be taken for an option of git
+                if url.starts_with('-') || reference.starts_with('-') {
+                    return invalid("invalid git include path");
+                }
+                if escapes(dir) {
+                    return invalid("subdirectory outside of git repository");
+                }
+                (
+                    url,
+                    Remote::Git {
+                        url,
+                        reference,
+                        dir,
+                    },
+                )
+            }
+            None if include.contains("://") => {
+                (include, Remote::Http(include.trim_end_matches('/')))
+            }
+            None => return Ok(None),
+        };
+
+        if url.starts_with("http://") && !opts.allow_http {
+            return invalid("remote source over plain http, see --allow-http");
+        }
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return invalid("remote source over unsupported scheme, https is required");
+        }
+        Ok(Some(remote))
+    }
+
+    /// Local path of the given import, fetching it if not cached already.
+    pub fn find(&self, opts: &Opts, import: &str) -> Result<Option<PathBuf>, PtError> {
+        // imports must stay within the source, neither the cache nor the
+        // URL may be escaped
+        if escapes(import) {
+            return Err(PtError::InvalidArgument(format!(
+                "import outside of remote source: {}",
+                import
+            )));
+        }
+
+        let path = match self {
+            Remote::Http(url) => {
+                let path = cache_dir(opts).join("http").join(cache_name(url));
+                let path = path.join(import);
+                if !path.is_file() {
+                    download(opts, &format!("{}/{}", url, import), &path)?;
+                }
+                path
+            }
+            Remote::Git {
+                url,
+                reference,
+                dir,
+            } => {
+                let repo = cache_dir(opts)
+                    .join("git")
+                    .join(cache_name(&format!("{}#{}", url, reference)));
+                if !repo.is_dir() {
+                    checkout(opts, url, reference, &repo)?;
+                }
+                repo.join(dir).join(import)
+            }
+        };
+
+        Ok(Some(path).filter(|path| path.is_file()))
+    }
+}
+
+/// Whether the given relative path leaves the directory it is relative to.
+fn escapes(path: &str) -> bool {
+    Path::new(path).components().any(|component| {
+        matches!(
+            component,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    })
+}
+
+/// Directory remote sources are cached in: `--cache-dir`, otherwise the user's
+/// cache directory.
+fn cache_dir(opts: &Opts) -> PathBuf {
+    if let Some(dir) = &opts.cache_dir {
+        return PathBuf::from(dir);
+    }
+
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("protots")
+}
+
+/// Name of the cache directory of the given source, distinct for every
+/// source: all characters but alphanumerics, `.` and `-` are escaped as `_`
+/// followed by their hex code.
+fn cache_name(source: &str) -> String {
+    let name = source.split_once("://").map_or(source, |(_, rest)| rest);
+    let mut escaped = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'.' || byte == b'-' {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("_{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+fn download(opts: &Opts, url: &str, path: &Path) -> Result<(), PtError> {
+    std::fs::create_dir_all(path.parent().unwrap_or(path))?;
+
+    if opts.verbose > 0 {
+        opts.messages.push(format!("fetching {}", url));
+    }
+
+    // download into a temporary file first so no partial files are cached
+    let partial = path.with_extension("partial");
+    let status = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--output",
+        ])
+        .arg(&partial)
+        .arg(url)
+        .status()?;
+
+    match status.code() {
+        Some(0) => Ok(std::fs::rename(partial, path)?),
+        // HTTP errors (e.g. 404) are treated like files missing locally
+        Some(22) => {
+            let _ = std::fs::remove_file(partial);
+            Ok(())
+        }
+        _ => Err(PtError::RemoteFetch(url.to_string())),
+    }
+}
+
+fn checkout(opts: &Opts, url: &str, reference: &str, repo: &Path) -> Result<(), PtError> {
+    if opts.verbose > 0 {
+        opts.messages
+            .push(format!("fetching {}#{}", url, reference));
+    }
+
+    // checkout into a temporary directory first so no partial checkouts are
+    // cached
+    let partial = repo.with_extension("partial");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+    std::fs::create_dir_all(&partial)?;
+
+    let git = |args: &[&str]| -> Result<(), PtError> {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&partial)
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(PtError::RemoteFetch(format!("{}#{}", url, reference)))
+        }
+    };
+
+    // fetching a single ref works for branches, tags and commits alike
+    git(&["init", "--quiet"])?;
+    git(&[
+        "fetch",
+        "--quiet",
+        "--depth",
+        "1",
+        "--end-of-options",
+        url,
+        reference,
+    ])?;
+    git(&["checkout", "--quiet", "FETCH_HEAD"])?;
+
+    std::fs::rename(partial, repo)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::{cache_name, Remote};
+    use crate::Opts;
+
+    #[test]
+    fn parse_remote_include_paths() {
+        let opts = Opts::default();
+        assert_eq!(Remote::parse(&opts, "protos").unwrap(), None);
+        assert_eq!(
+            Remote::parse(&opts, "https://example.com/protos/").unwrap(),
+            Some(Remote::Http("https://example.com/protos"))
+        );
+        assert_eq!(
+            Remote::parse(
+                &opts,
+                "git+https://github.com/org/contracts.git#v1.2.0:proto"
+            )
+            .unwrap(),
+            Some(Remote::Git {
+                url: "https://github.com/org/contracts.git",
+                reference: "v1.2.0",
+                dir: "proto",
+            })
+        );
+        assert!(Remote::parse(&opts, "git+https://github.com/org/contracts.git").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_unsafe_include_paths() {
+        let opts = Opts::default();
+        let error = |include: &str| match Remote::parse(&opts, include) {
+            Err(err) => err.to_string(),
+            Ok(remote) => panic!("expected {} to be rejected, got {:?}", include, remote),
+        };
+
+        assert_eq!(
+            error("http://example.com/protos"),
+            "invalid argument: remote source over plain http, see --allow-http: http://example.com/protos"
+        );
+        for include in [
+            "git+git://example.com/contracts.git#v1",
+            "git+ssh://git@example.com/contracts.git#v1",
+            "git+file:///srv/contracts.git#v1",
+            "ftp://example.com/protos",
+        ] {
+            assert_eq!(
+                error(include),
+                format!(
+                    "invalid argument: remote source over unsupported scheme, https is required: {}",
+                    include
+                )
+            );
+        }
+        for include in [
+            "git+--upload-pack=touch /tmp/pwned#proto",
+            "git+https://example.com/contracts.git#--upload-pack=id",
+        ] {
+            assert!(error(include).starts_with("invalid argument: invalid git include path"));
+        }
+        for include in [
+            "git+https://example.com/contracts.git#v1:../../..",
+            "git+https://example.com/contracts.git#v1:/etc",
+        ] {
+            assert!(error(include)
+                .starts_with("invalid argument: subdirectory outside of git repository"));
+        }
+
+        let opts = Opts {
+            allow_http: true,
+            ..Opts::default()
+        };
+        assert_eq!(
+            Remote::parse(&opts, "http://example.com/protos").unwrap(),
+            Some(Remote::Http("http://example.com/protos"))
+        );
+    }
+
+    #[test]
+    fn cache_names_are_distinct() {
+        assert_ne!(cache_name("https://h/a_b"), cache_name("https://h/a/b"));
+        assert_eq!(cache_name("https://h/a_b#v1"), "h_2fa_5fb_23v1");
+    }
+
+    #[test]
+    fn find_in_git_repository() {
+        let dir = std::env::temp_dir().join(format!("protots-remote-{}", std::process::id()));
+        let repo = dir.join("repo");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(repo.join("proto/common")).unwrap();
+        std::fs::write(
+            repo.join("proto/common/address.proto"),
+            "syntax = \"proto3\";",
+        )
+        .unwrap();
+
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success());
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "protos"]);
+        git(&["tag", "v1"]);
+
+        let opts = Opts {
+            cache_dir: Some(dir.join("cache").to_string_lossy().to_string()),
+            verbose: 1,
+            ..Opts::default()
+        };
+        // file URLs are rejected as include paths, so the source is given
+        // directly
+        let url = format!("file://{}", repo.display());
+        let remote = Remote::Git {
+            url: &url,
+            reference: "v1",
+            dir: "proto",
+        };
+
+        let found = remote.find(&opts, "common/address.proto").unwrap().unwrap();
+        assert!(found.starts_with(dir.join("cache")));
+        assert_eq!(
+            opts.messages.take(),
+            [format!("fetching file://{}#v1", repo.display())]
+        );
+        assert!(remote
+            .find(&opts, "common/missing.proto")
+            .unwrap()
+            .is_none());
+        // the repository is checked out once only
+        assert!(opts.messages.take().is_empty());
+
+        for import in ["../repo/proto/common/address.proto", "/etc/passwd"] {
+            assert!(remote.find(&opts, import).is_err());
+        }
+    }
+}
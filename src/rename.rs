@@ -0,0 +1,529 @@
+//! Rename a message, enum or field across all proto files of a project.
+//!
+//! Files are rewritten on the token level, so that only the affected
+//! identifiers change and the formatting and comments of the files are
+//! preserved.
+
+use std::path::{Path, PathBuf};
+
+use crate::errors::PtError;
+use crate::imports;
+use crate::parser::{self, Field};
+use crate::registry::{self, Registry};
+use crate::typescript::snake_to_camel;
+use crate::Opts;
+
+/// Result of a rename: the rewritten files and the generated typescript
+/// identifiers that change accordingly.
+pub struct Rename {
+    /// rewritten files (path, new content, number of replaced identifiers)
+    pub files: Vec<(PathBuf, String, usize)>,
+    /// affected typescript identifiers (old, new)
+    pub identifiers: Vec<(String, String)>,
+}
+
+enum Target<'a> {
+    Type {
+        full_name: &'a str,
+        new_name: &'a str,
+    },
+    Field {
+        message: &'a str,
+        name: &'a str,
+        new_name: &'a str,
+    },
+}
+
+/// Rename the fully-qualified type or field `from` to `to` in all proto files
+/// found below the given project roots. `to` is either the new simple name or
+/// a fully-qualified name in the same scope as `from`.
+pub fn rename(opts: &Opts, roots: &[String], from: &str, to: &str) -> Result<Rename, PtError> {
+    let mut sources = Vec::new();
+    for root in roots {
+        let mut paths = Vec::new();
+        imports::proto_files(opts, Path::new(root), &mut paths)?;
+
+        for path in paths {
+            // files are named the way they are imported, i.e. relative to
+            // their root, so the imports resolve among them
+            let name = path.strip_prefix(root).unwrap_or(&path);
+            let content = opts.files.read_to_string(&path)?;
+            let name = name.to_string_lossy().replace('\\', "/");
+            let proto = parser::parse(opts, &name, &content)?;
+            sources.push((path, content, proto));
+        }
+    }
+
+    let protos = sources.iter().map(|(_, _, proto)| proto);
+    let registry = Registry::new(protos.clone());
+
+    let (scope, old_name) = split_name(from);
+    let new_name = match to.rsplit_once('.') {
+        Some((new_scope, name)) if new_scope == scope => name,
+        Some(_) => {
+            return Err(PtError::InvalidArgument(format!(
+                "cannot move {} into another scope: {}",
+                from, to
+            )))
+        }
+        None => to,
+    };
+    let valid = new_name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && new_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(PtError::InvalidArgument(format!("invalid name: {}", to)));
+    }
+
+    let target = if registry.get(from).is_some() {
+        if registry.get(&registry::qualify(scope, new_name)).is_some() {
+            return Err(PtError::InvalidArgument(format!(
+                "type already exists: {}",
+                registry::qualify(scope, new_name)
+            )));
+        }
+        Target::Type {
+            full_name: from,
+            new_name,
+        }
+    } else {
+        match registry::find_message(protos.clone(), scope) {
+            Some(msg) if has_field(&msg.fields, old_name) => {
+                if has_field(&msg.fields, new_name) {
+                    return Err(PtError::InvalidArgument(format!(
+                        "field already exists: {}",
+                        registry::qualify(scope, new_name)
+                    )));
+                }
+                Target::Field {
+                    message: scope,
+                    name: old_name,
+                    new_name,
+                }
+            }
+            _ => return Err(PtError::ProtobufTypeNotFound(from.to_string())),
+        }
+    };
+
+    let mut files = Vec::new();
+    for (path, content, _) in &sources {
+        let (rewritten, count) = rewrite(&registry, &target, content);
+        if count > 0 {
+            files.push((path.clone(), rewritten, count));
+        }
+    }
+
+    Ok(Rename {
+        files,
+        identifiers: identifiers(&registry, &target),
+    })
+}
+
+fn split_name(name: &str) -> (&str, &str) {
+    name.rsplit_once('.').unwrap_or(("", name))
+}
+
+fn has_field(fields: &[Field], field_name: &str) -> bool {
+    fields.iter().any(|field| match field {
+        Field::Single { name, .. } | Field::Map { name, .. } => name == field_name,
+        Field::OneOf { fields, .. } => has_field(fields, field_name),
+        _ => false,
+    })
+}
+
+fn identifiers(registry: &Registry, target: &Target) -> Vec<(String, String)> {
+    match target {
+        Target::Type {
+            full_name,
+            new_name,
+        } => {
+            let ptype = registry.get(full_name).unwrap();
+            let old_ts = &ptype.ts_name;
+            let new_ts = match old_ts.rsplit_once('_') {
+                Some((parent, _)) => format!("{}_{}", parent, new_name),
+                None => new_name.to_string(),
+            };
+
+            // nested types are prefixed with the name of their parents
+            let mut nested = registry
+                .types()
+                .filter(|nested| nested.full_name.starts_with(&format!("{}.", full_name)))
+                .map(|nested| nested.ts_name.clone())
+                .collect::<Vec<_>>();
+            nested.sort();
+
+            [old_ts.clone()]
+                .into_iter()
+                .chain(nested)
+                .flat_map(|ts_name| {
+                    let renamed = format!("{}{}", new_ts, &ts_name[old_ts.len()..]);
+                    [
+                        (ts_name.clone(), renamed.clone()),
+                        (format!("{}Schema", ts_name), format!("{}Schema", renamed)),
+                    ]
+                })
+                .collect()
+        }
+        Target::Field {
+            message,
+            name,
+            new_name,
+        } => {
+            let ts_name = registry
+                .get(message)
+                .map(|ptype| ptype.ts_name.clone())
+                .unwrap_or_default();
+            vec![(
+                format!("{}.{}", ts_name, snake_to_camel(name)),
+                format!("{}.{}", ts_name, snake_to_camel(new_name)),
+            )]
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Ident,
+    Str,
+    Number,
+    Punct(char),
+}
+
+/// Split the input into tokens (kind and byte range), skipping whitespace
+/// and comments.
+fn tokenize(input: &str) -> Vec<(Token, usize, usize)> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+
+    let ident_char = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'.';
+
+    while idx < bytes.len() {
+        let start = idx;
+        match bytes[idx] {
+            b if b.is_ascii_whitespace() => idx += 1,
+            b'/' if bytes.get(idx + 1) == Some(&b'/') => {
+                while idx < bytes.len() && bytes[idx] != b'\n' {
+                    idx += 1;
+                }
+            }
+            b'/' if bytes.get(idx + 1) == Some(&b'*') => {
+                idx += 2;
+                while idx < bytes.len() && !input[idx..].starts_with("*/") {
+                    idx += 1;
+                }
+                idx = (idx + 2).min(bytes.len());
+            }
+            quote @ (b'"' | b'\'') => {
+                idx += 1;
+                while idx < bytes.len() && bytes[idx] != quote {
+                    idx += if bytes[idx] == b'\\' { 2 } else { 1 };
+                }
+                idx = (idx + 1).min(bytes.len());
+                tokens.push((Token::Str, start, idx));
+            }
+            b if b.is_ascii_digit()
+                || (b == b'-' && bytes.get(idx + 1).is_some_and(u8::is_ascii_digit)) =>
+            {
+                idx += 1;
+                while idx < bytes.len() && ident_char(bytes[idx]) {
+                    idx += 1;
+                }
+                tokens.push((Token::Number, start, idx));
+            }
+            b if ident_char(b) => {
+                while idx < bytes.len() && ident_char(bytes[idx]) {
+                    idx += 1;
+                }
+                tokens.push((Token::Ident, start, idx));
+            }
+            _ => {
+                // multi-byte characters outside of strings and comments are
+                // not valid protobuf anyway
+                let c = input[idx..].chars().next().unwrap_or_default();
+                idx += c.len_utf8();
+                tokens.push((Token::Punct(c), start, idx));
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Keywords that are followed by the name of a declaration.
+const DECLARATIONS: [&str; 5] = ["message", "enum", "service", "rpc", "oneof"];
+
+fn rewrite(registry: &Registry, target: &Target, content: &str) -> (String, usize) {
+    let tokens = tokenize(content);
+    let text = |idx: usize| {
+        tokens
+            .get(idx)
+            .map(|(_, start, end)| &content[*start..*end])
+            .unwrap_or("")
+    };
+
+    let mut package = "";
+    // names of the enclosing blocks (if they declare a scope)
+    let mut blocks: Vec<Option<&str>> = Vec::new();
+    let mut pending_block = None;
+    // depth of option statements and field option lists, which only refer to
+    // options and not to types
+    let mut option_depth = None;
+    let mut brackets = 0;
+    let mut edits = Vec::new();
+
+    for (idx, (token, start, end)) in tokens.iter().enumerate() {
+        let scope = || {
+            [package]
+                .into_iter()
+                .chain(blocks.iter().flatten().copied())
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(".")
+        };
+
+        match token {
+            Token::Punct('{') => blocks.push(pending_block.take()),
+            Token::Punct('}') => {
+                blocks.pop();
+            }
+            Token::Punct('[') => brackets += 1,
+            Token::Punct(']') => brackets -= 1,
+            Token::Punct(';') if option_depth == Some(blocks.len()) => option_depth = None,
+            Token::Ident => {
+                let word = &content[*start..*end];
+                let prev = if idx > 0 { text(idx - 1) } else { "" };
+                let next = text(idx + 1);
+
+                if prev == "package" {
+                    package = word;
+                } else if word == "option" && option_depth.is_none() {
+                    option_depth = Some(blocks.len());
+                } else if DECLARATIONS.contains(&prev) {
+                    if prev == "message" || prev == "enum" {
+                        pending_block = Some(word);
+                    }
+                    if let Target::Type {
+                        full_name,
+                        new_name,
+                    } = target
+                    {
+                        if (prev == "message" || prev == "enum")
+                            && registry::qualify(&scope(), word) == *full_name
+                        {
+                            edits.push((*start, *end, new_name.to_string()));
+                        }
+                    }
+                } else if next == "=" {
+                    if let Target::Field {
+                        message,
+                        name,
+                        new_name,
+                    } = target
+                    {
+                        if option_depth.is_none()
+                            && brackets == 0
+                            && word == *name
+                            && scope() == *message
+                        {
+                            edits.push((*start, *end, new_name.to_string()));
+                        }
+                    }
+                } else if option_depth.is_none() && brackets == 0 {
+                    if let Target::Type {
+                        full_name,
+                        new_name,
+                    } = target
+                    {
+                        if let Some(edit) =
+                            rename_reference(registry, word, &scope(), full_name, new_name)
+                        {
+                            edits.push((*start, *end, edit));
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let count = edits.len();
+    let mut rewritten = content.to_string();
+    for (start, end, replacement) in edits.into_iter().rev() {
+        rewritten.replace_range(start..end, &replacement);
+    }
+    (rewritten, count)
+}
+
+/// New spelling of the type reference `word` in case it refers to the renamed
+/// type or one of its nested types.
+fn rename_reference(
+    registry: &Registry,
+    word: &str,
+    scope: &str,
+    full_name: &str,
+    new_name: &str,
+) -> Option<String> {
+    let absolute = word.starts_with('.');
+    let name = word.trim_start_matches('.');
+    let resolved = if absolute {
+        registry.get(name)?
+    } else {
+        registry.resolve(name, scope)?
+    };
+
+    if resolved.full_name != full_name
+        && !resolved.full_name.starts_with(&format!("{}.", full_name))
+    {
+        return None;
+    }
+
+    // the written reference corresponds to the last segments of the resolved
+    // name, only rename in case the renamed segment is part of it
+    let written = name.split('.').collect::<Vec<_>>();
+    let resolved_len = resolved.full_name.split('.').count();
+    let renamed_idx =
+        (full_name.split('.').count() + written.len()).checked_sub(resolved_len + 1)?;
+
+    let mut parts = written;
+    parts[renamed_idx] = new_name;
+    Some(format!(
+        "{}{}",
+        if absolute { "." } else { "" },
+        parts.join(".")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::rename;
+    use crate::provider::{Files, Memory};
+    use crate::Opts;
+
+    #[test]
+    fn rename_rejects_invalid_names() {
+        let opts = Opts {
+            files: Files(Rc::new(Memory::new([(
+                "foo/user.proto",
+                "syntax = \"proto3\";\npackage foo;\nmessage User { string name = 1; }\n",
+            )]))),
+            ..Opts::default()
+        };
+        let roots = vec!["foo".to_string()];
+
+        for to in ["", "foo.", "1Foo", "foo.User.2name", "Us-er"] {
+            let from = if to.starts_with("foo.User.") {
+                "foo.User.name"
+            } else {
+                "foo.User"
+            };
+            match rename(&opts, &roots, from, to) {
+                Err(err) => assert!(err.to_string().contains("invalid name"), "{}", err),
+                Ok(_) => panic!("{} was accepted", to),
+            }
+        }
+        assert!(rename(&opts, &roots, "foo.User", "_Account1").is_ok());
+    }
+
+    #[test]
+    fn rename_type_and_field() {
+        let dir = std::env::temp_dir().join(format!("protots-rename-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("foo")).unwrap();
+        std::fs::write(
+            dir.join("foo/user.proto"),
+            r#"syntax = "proto3";
+package foo;
+
+// a User
+message User {
+  string user_name = 1 [json_name = "userName"];
+  Role role = 2;
+  enum Role {
+    NONE = 0;
+  }
+}
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("foo/order.proto"),
+            r#"syntax = "proto3";
+package foo;
+import "foo/user.proto";
+
+message Order {
+  foo.User   owner = 1;
+  map<string, foo.User> users = 2;
+  User.Role role = 3;
+  string User = 4;
+}
+
+service Orders {
+  rpc Get(User) returns (Order) {
+    option (google.api.http) = { get: "/User" };
+  }
+}
+"#,
+        )
+        .unwrap();
+
+        let opts = Opts::default();
+        let roots = vec![dir.to_string_lossy().to_string()];
+
+        let result = rename(&opts, &roots, "foo.User", "Account").unwrap();
+        assert_eq!(result.files.len(), 2);
+        assert_eq!(
+            result.files[0].1,
+            r#"syntax = "proto3";
+package foo;
+import "foo/user.proto";
+
+message Order {
+  foo.Account   owner = 1;
+  map<string, foo.Account> users = 2;
+  Account.Role role = 3;
+  string User = 4;
+}
+
+service Orders {
+  rpc Get(Account) returns (Order) {
+    option (google.api.http) = { get: "/User" };
+  }
+}
+"#
+        );
+        assert!(result.files[1].1.contains("// a User\nmessage Account {"));
+        assert_eq!(
+            result.identifiers,
+            vec![
+                ("User".to_string(), "Account".to_string()),
+                ("UserSchema".to_string(), "AccountSchema".to_string()),
+                ("User_Role".to_string(), "Account_Role".to_string()),
+                (
+                    "User_RoleSchema".to_string(),
+                    "Account_RoleSchema".to_string()
+                ),
+            ]
+        );
+
+        let result = rename(&opts, &roots, "foo.User.user_name", "login").unwrap();
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.files[0].2, 1);
+        assert!(result.files[0]
+            .1
+            .contains("string login = 1 [json_name = \"userName\"];"));
+        assert_eq!(
+            result.identifiers,
+            vec![("User.userName".to_string(), "User.login".to_string())]
+        );
+
+        assert!(rename(&opts, &roots, "foo.User", "Order").is_err());
+        assert!(rename(&opts, &roots, "foo.Missing", "Other").is_err());
+    }
+}
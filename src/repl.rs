@@ -0,0 +1,268 @@
+//! Interactive playground reading proto snippets from stdin and printing the
+//! generated output of each snippet right away.
+//!
+//! Definitions of previous snippets stay known, so later snippets may refer
+//! to them.
+
+use std::io::{BufRead, BufReader, Write};
+
+use crate::errors::PtError;
+use crate::parser::{self, Proto};
+use crate::{typescript, Io, Opts, Target};
+
+const FILE: &str = "repl.proto";
+
+const HELP: &str = "\
+enter proto definitions (a message, enum or service at a time) or commands:
+  :target [NAME]   show or switch the output target (zod, interfaces,
+                   effect, typebox, yup)
+  :preset [NAME]   show or switch the option preset (default, clients,
+                   validation-hook)
+  :ast             print the AST of the last snippet
+  :help            show this help
+  :quit            exit
+";
+
+const PRESETS: [&str; 3] = ["default", "clients", "validation-hook"];
+
+/// Options of the given preset, based on the options the playground was
+/// started with.
+fn preset(name: &str, base: &Opts) -> Opts {
+    let mut opts = Opts {
+        target: base.target,
+        runtime_module: base.runtime_module.clone(),
+        method_options: base.method_options.clone(),
+        ..Opts::default()
+    };
+    match name {
+        "clients" => {
+            opts.clients = true;
+            opts.deprecation_warnings = true;
+        }
+        "validation-hook" => opts.validation_hook = Some("./telemetry".to_string()),
+        _ => (),
+    }
+    opts
+}
+
+struct Session {
+    opts: Opts,
+    preset: String,
+    /// definitions of all previous snippets
+    known: Vec<Proto>,
+    last: Option<Proto>,
+}
+
+impl Session {
+    fn command(&mut self, command: &str, out: &mut dyn Write) -> Result<bool, PtError> {
+        let (command, arg) = command.split_once(' ').unwrap_or((command, ""));
+        let arg = arg.trim();
+
+        match command {
+            ":quit" | ":q" => return Ok(false),
+            ":help" => write!(out, "{}", HELP)?,
+            ":ast" => match &self.last {
+                Some(proto) => writeln!(out, "{:#?}", proto.elems)?,
+                None => writeln!(out, "no snippet yet")?,
+            },
+            ":target" if arg.is_empty() => writeln!(out, "{}", self.opts.target.name())?,
+            ":target" => match Target::parse(arg) {
+                Some(target) => self.opts.target = target,
+                None => {
+                    let names = Target::ALL
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<_>>();
+                    writeln!(
+                        out,
+                        "unknown target {} (available: {})",
+                        arg,
+                        names.join(", ")
+                    )?
+                }
+            },
+            ":preset" if arg.is_empty() => writeln!(out, "{}", self.preset)?,
+            ":preset" if PRESETS.contains(&arg) => {
+                self.opts = preset(arg, &self.opts);
+                self.preset = arg.to_string();
+            }
+            ":preset" => writeln!(
+                out,
+                "unknown preset {} (available: {})",
+                arg,
+                PRESETS.join(", ")
+            )?,
+            other => writeln!(out, "unknown command {} (see :help)", other)?,
+        }
+
+        Ok(true)
+    }
+
+    fn snippet(&mut self, snippet: &str, out: &mut dyn Write) -> Result<(), PtError> {
+        let input = if declares_syntax(snippet) {
+            snippet.to_string()
+        } else {
+            format!("syntax = \"proto3\";\n{}", snippet)
+        };
+
+        let proto = parser::parse(&self.opts, FILE, &input)?;
+        let output = typescript::to_schema(&self.opts, &proto, &self.known)?;
+
+        // the file header and imports are the same for every snippet
+        let body = output
+            .lines()
+            .skip_while(|line| {
+                line.is_empty() || line.starts_with("//") || line.starts_with("import ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        writeln!(out, "{}", body.trim())?;
+
+        self.known.push(proto.clone());
+        self.last = Some(proto);
+        Ok(())
+    }
+}
+
+/// Whether the snippet starts with a syntax or edition statement, so it is
+/// taken as is instead of being declared proto3.
+fn declares_syntax(snippet: &str) -> bool {
+    let keyword = snippet
+        .trim_start()
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or("");
+    keyword == "syntax" || keyword == "edition"
+}
+
+/// Whether the snippet is complete, i.e. all braces are closed and the last
+/// statement is terminated.
+fn complete(snippet: &str) -> bool {
+    let depth = snippet.chars().fold(0i32, |depth, c| match c {
+        '{' => depth + 1,
+        '}' => depth - 1,
+        _ => depth,
+    });
+    let trimmed = snippet.trim_end();
+    depth <= 0 && (trimmed.ends_with('}') || trimmed.ends_with(';'))
+}
+
+pub fn run(opts: &Opts, io: &mut Io) -> Result<(), PtError> {
+    let mut session = Session {
+        opts: preset("default", opts),
+        preset: "default".to_string(),
+        known: Vec::new(),
+        last: None,
+    };
+    let mut snippet = String::new();
+    let out = &mut *io.stdout;
+
+    write!(out, "{}", HELP)?;
+    write!(out, "> ")?;
+    out.flush()?;
+
+    let mut lines = BufReader::new(&mut *io.stdin).lines();
+    while let Some(line) = lines.next().transpose()? {
+        if snippet.is_empty() && line.trim().starts_with(':') {
+            if !session.command(line.trim(), out)? {
+                return Ok(());
+            }
+        } else if !line.trim().is_empty() || !snippet.is_empty() {
+            snippet.push_str(&line);
+            snippet.push('\n');
+
+            if complete(&snippet) {
+                if let Err(err) = session.snippet(&snippet, out) {
+                    writeln!(out, "error: {}", err)?;
+                }
+                for message in session.opts.messages.take() {
+                    writeln!(io.stderr, "{}", message)?;
+                }
+                snippet.clear();
+            }
+        }
+
+        write!(out, "{}", if snippet.is_empty() { "> " } else { ". " })?;
+        out.flush()?;
+    }
+
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use crate::{Io, Opts};
+
+    fn run_repl(input: &str) -> String {
+        let mut stdin = input.as_bytes();
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut io = Io {
+            stdin: &mut stdin,
+            stdout: &mut stdout,
+            stderr: &mut stderr,
+            color: false,
+            files: Default::default(),
+            #[cfg(feature = "cli")]
+            lint_rules: Default::default(),
+        };
+
+        run(&Opts::default(), &mut io).unwrap();
+        String::from_utf8(stdout).unwrap()
+    }
+
+    #[test]
+    fn repl_snippets_and_commands() {
+        let output = run_repl(
+            r#"message Address {
+  string city = 1;
+}
+message User { Address address = 1; }
+:preset clients
+:preset
+service Users {
+  rpc Get(User) returns (User);
+}
+:target plain
+:ast
+message Broken {
+:quit
+"#,
+        );
+
+        let output = output.split_once(":quit            exit\n").unwrap().1;
+        assert!(output.starts_with(
+            "> . . export const AddressSchema = z.object({\n  city: z.string(),\n});"
+        ));
+        assert!(
+            output.contains("export const UserSchema = z.object({\n  address: AddressSchema,\n});")
+        );
+        assert!(output.contains("> clients\n"));
+        assert!(output.contains("export class UsersClient {"));
+        assert!(output.contains("unknown target plain (available: zod"));
+        assert!(output.contains("Service {\n        name: \"Users\","));
+        // incomplete snippets are discarded on exit
+        assert!(output.ends_with(". \n"));
+    }
+
+    #[test]
+    #[cfg(feature = "interfaces")]
+    fn repl_targets_and_syntax() {
+        let output = run_repl(
+            r#":target interfaces
+:target
+message Settings { string syntax_version = 1; }
+syntax = "proto2"; message Legacy { required string name = 1; }
+:quit
+"#,
+        );
+
+        let output = output.split_once(":quit            exit\n").unwrap().1;
+        assert!(output.contains("> interfaces\n"));
+        assert!(output.contains("export interface Settings {\n  syntaxVersion: string;\n}"));
+        assert!(output.contains("export interface Legacy {\n  name: string;\n}"));
+        assert!(!output.contains("error"));
+    }
+}
@@ -0,0 +1,409 @@
+//! Random JSON payloads of message types, e.g. for load tests or fuzzing
+//! consumers of an API.
+//!
+//! The payloads follow the proto3 JSON mapping (see `payload`) and pass its
+//! validation. Generation is deterministic for a given seed.
+
+use crate::errors::PtError;
+use crate::json::{self, Json};
+use crate::parser::{EnumValue, Flag, Proto};
+use crate::payload::{self, Target};
+use crate::registry::{self, declared, Declared, Registry};
+
+/// Nesting depth from which optional message fields are left out, so
+/// recursive types terminate.
+const MAX_DEPTH: usize = 4;
+
+const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Knobs {
+    /// maximum number of elements of repeated and map fields
+    pub max_items: usize,
+    /// maximum length of strings and bytes
+    pub max_len: usize,
+}
+
+impl Default for Knobs {
+    fn default() -> Knobs {
+        Knobs {
+            max_items: 3,
+            max_len: 12,
+        }
+    }
+}
+
+/// `count` random payloads of the fully-qualified message type.
+pub fn sample(
+    protos: &[Proto],
+    message: &str,
+    count: usize,
+    seed: u64,
+    knobs: Knobs,
+) -> Result<Vec<Json>, PtError> {
+    let registry = Registry::new(protos);
+    let target = payload::target(&registry, message, "")
+        .filter(|target| matches!(target, Target::Message(_) | Target::WellKnown(_)))
+        .ok_or_else(|| registry.unresolved(message, ""))?;
+
+    let mut sampler = Sampler {
+        protos,
+        registry,
+        knobs,
+        rng: Rng(seed),
+    };
+    Ok((0..count).map(|_| sampler.target(&target, "", 0)).collect())
+}
+
+/// Inputs of a `sample` run, recorded along with the payloads so they can be
+/// regenerated byte-for-byte.
+pub struct Envelope<'a> {
+    pub proto: &'a str,
+    pub include_paths: &'a [String],
+    pub message: &'a str,
+    pub count: usize,
+    pub seed: u64,
+    pub knobs: Knobs,
+    /// file the envelope is written to, if not stdout
+    pub out: Option<&'a str>,
+}
+
+impl Envelope<'_> {
+    /// Single JSON document of the payloads, headed by the protots version,
+    /// the inputs and the command line regenerating the very same payloads.
+    pub fn wrap(&self, payloads: Vec<Json>) -> Json {
+        let mut command = format!(
+            "protots sample --proto {} --message {} --count {} --seed {} --max-items {} --max-len {}",
+            self.proto,
+            self.message,
+            self.count,
+            self.seed,
+            self.knobs.max_items,
+            self.knobs.max_len
+        );
+        command.push_str(" --envelope");
+        if let Some(out) = self.out {
+            command.push_str(" --out ");
+            command.push_str(out);
+        }
+        for include in self.include_paths {
+            command.push_str(" -I ");
+            command.push_str(include);
+        }
+
+        // the seed as string, as it may exceed the precision of JSON numbers
+        let generator = Json::object([
+            ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+            ("proto", Json::str(self.proto)),
+            ("message", Json::str(self.message)),
+            ("count", Json::Num(self.count.to_string())),
+            ("seed", Json::Str(self.seed.to_string())),
+            ("maxItems", Json::Num(self.knobs.max_items.to_string())),
+            ("maxLen", Json::Num(self.knobs.max_len.to_string())),
+            ("command", Json::Str(command)),
+        ]);
+        Json::object([
+            ("generator", generator),
+            ("payloads", Json::Array(payloads)),
+        ])
+    }
+}
+
+/// splitmix64
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = self.0;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^ (value >> 31)
+    }
+
+    /// Uniformly distributed value of `0..=max`.
+    fn below(&mut self, max: u64) -> u64 {
+        match max.checked_add(1) {
+            Some(bound) => self.next() % bound,
+            None => self.next(),
+        }
+    }
+
+    fn range(&mut self, min: i128, max: i128) -> i128 {
+        min + self.below((max - min) as u64) as i128
+    }
+
+    fn chance(&mut self) -> bool {
+        self.next() & 1 == 1
+    }
+}
+
+struct Sampler<'a> {
+    protos: &'a [Proto],
+    registry: Registry,
+    knobs: Knobs,
+    rng: Rng,
+}
+
+impl Sampler<'_> {
+    fn message(&mut self, full_name: &str, depth: usize) -> Json {
+        let Some(msg) = registry::find_message(self.protos, full_name) else {
+            return Json::Object(Vec::new());
+        };
+        let mut decls = Vec::new();
+        declared(&msg.fields, None, &mut decls);
+
+        // at most a single member of every oneof is set
+        let mut oneofs: Vec<(&str, Option<&str>)> = Vec::new();
+        for decl in &decls {
+            if let Some(oneof) = decl.oneof {
+                if !oneofs.iter().any(|(name, _)| *name == oneof) {
+                    let members = decls.iter().filter(|d| d.oneof == Some(oneof)).count();
+                    // choosing one past the last member leaves the oneof unset
+                    let chosen = self.rng.below(members as u64) as usize;
+                    let member = decls
+                        .iter()
+                        .filter(|d| d.oneof == Some(oneof))
+                        .nth(chosen)
+                        .filter(|_| depth < MAX_DEPTH)
+                        .map(|d| d.name);
+                    oneofs.push((oneof, member));
+                }
+            }
+        }
+
+        let mut fields = Vec::new();
+        for decl in &decls {
+            let included = match decl.oneof {
+                Some(oneof) => oneofs.contains(&(oneof, Some(decl.name))),
+                None => self.included(decl, full_name, depth),
+            };
+            if included {
+                let value = self.field(decl, full_name, depth);
+                fields.push((payload::json_name(decl), value));
+            }
+        }
+
+        Json::Object(fields)
+    }
+
+    /// Whether a field outside of oneofs is part of the payload: required and
+    /// implicit presence fields always are, others by chance.
+    fn included(&mut self, decl: &Declared, scope: &str, depth: usize) -> bool {
+        let message = matches!(
+            payload::target(&self.registry, decl.field_type, scope),
+            Some(Target::Message(_) | Target::WellKnown(_))
+        );
+        match decl.flag {
+            Flag::Required => true,
+            _ if message && depth >= MAX_DEPTH => false,
+            Flag::None if !message => true,
+            _ => self.rng.chance(),
+        }
+    }
+
+    fn field(&mut self, decl: &Declared, scope: &str, depth: usize) -> Json {
+        let count = self.rng.below(self.knobs.max_items as u64) as usize;
+
+        match decl.key_type {
+            Some(key_type) => {
+                let mut entries: Vec<(String, Json)> = Vec::new();
+                for _ in 0..count {
+                    let key = match self.value(key_type, scope, depth + 1) {
+                        Json::Str(key) | Json::Num(key) => key,
+                        Json::Bool(key) => key.to_string(),
+                        _ => continue,
+                    };
+                    if !entries.iter().any(|(other, _)| *other == key) {
+                        let value = self.value(decl.field_type, scope, depth + 1);
+                        entries.push((key, value));
+                    }
+                }
+                Json::Object(entries)
+            }
+            None if matches!(decl.flag, Flag::Repeated) => Json::Array(
+                (0..count)
+                    .map(|_| self.value(decl.field_type, scope, depth + 1))
+                    .collect(),
+            ),
+            None => self.value(decl.field_type, scope, depth + 1),
+        }
+    }
+
+    fn value(&mut self, field_type: &str, scope: &str, depth: usize) -> Json {
+        match payload::target(&self.registry, field_type, scope) {
+            Some(target) => self.target(&target, field_type, depth),
+            None => Json::Null,
+        }
+    }
+
+    fn target(&mut self, target: &Target, field_type: &str, depth: usize) -> Json {
+        match target {
+            Target::Scalar => self.scalar(field_type),
+            Target::Message(full_name) => self.message(full_name, depth),
+            Target::Enum(full_name) => {
+                let names = registry::find_enum(self.protos, full_name)
+                    .map(|e| {
+                        e.values
+                            .iter()
+                            .filter_map(|value| match value {
+                                EnumValue::Single { name, .. } => Some(name.as_str()),
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                match names.len() {
+                    0 => Json::Num("0".to_string()),
+                    len => Json::str(names[self.rng.below(len as u64 - 1) as usize]),
+                }
+            }
+            Target::WellKnown(full_name) => self.well_known(full_name),
+        }
+    }
+
+    fn scalar(&mut self, field_type: &str) -> Json {
+        let len = self.rng.below(self.knobs.max_len as u64) as usize;
+
+        match field_type {
+            "string" => Json::Str(
+                (0..len)
+                    .map(|_| CHARS[self.rng.below(CHARS.len() as u64 - 1) as usize] as char)
+                    .collect(),
+            ),
+            "bytes" => {
+                let bytes = (0..len).map(|_| self.rng.next() as u8).collect::<Vec<_>>();
+                Json::Str(payload::encode_base64(&bytes))
+            }
+            "bool" => Json::Bool(self.rng.chance()),
+            "double" | "float" => {
+                // three decimal places keep the values readable
+                let value = self.rng.range(-1_000_000, 1_000_000) as f64 / 1000.0;
+                Json::Num(payload::float(value, field_type == "float"))
+            }
+            "int32" | "sint32" | "sfixed32" => {
+                Json::Num(self.rng.range(i32::MIN.into(), i32::MAX.into()).to_string())
+            }
+            "uint32" | "fixed32" => Json::Num(self.rng.range(0, u32::MAX.into()).to_string()),
+            "int64" | "sint64" | "sfixed64" => {
+                Json::Str(self.rng.range(i64::MIN.into(), i64::MAX.into()).to_string())
+            }
+            _ => Json::Str(self.rng.below(u64::MAX).to_string()),
+        }
+    }
+
+    fn well_known(&mut self, full_name: &str) -> Json {
+        if let Some(scalar) = json::wrapped(full_name) {
+            return self.scalar(scalar);
+        }
+
+        match full_name.trim_start_matches("google.protobuf.") {
+            "Timestamp" => {
+                // between 2000 and 2040
+                let seconds = self.rng.range(946_684_800, 2_208_988_800) as i64;
+                let nanos = self.rng.below(999) as u32 * 1_000_000;
+                Json::Str(json::format_timestamp(seconds, nanos))
+            }
+            "Duration" => Json::Str(format!(
+                "{}{}s",
+                self.rng.below(86_400),
+                json::fraction(self.rng.below(999) as u32 * 1_000_000)
+            )),
+            "Value" => self.scalar("string"),
+            "ListValue" => Json::Array(Vec::new()),
+            "NullValue" => Json::Null,
+            "FieldMask" => Json::str(""),
+            "Any" => Json::object([(
+                "@type",
+                Json::str("type.googleapis.com/google.protobuf.Empty"),
+            )]),
+            // Struct and Empty
+            _ => Json::Object(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sample, Envelope, Knobs};
+    use crate::json::Json;
+    use crate::parser::parse;
+    use crate::{payload, Opts};
+
+    #[test]
+    fn sample_valid_payloads() {
+        let proto = parse(
+            &Opts::default(),
+            "tree.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+import "google/protobuf/timestamp.proto";
+enum Color { COLOR_UNSPECIFIED = 0; COLOR_RED = 1; }
+message Node {
+  string name = 1;
+  repeated Node children = 2;
+  map<uint64, Color> colors = 3;
+  optional bytes data = 4;
+  google.protobuf.Timestamp created = 5;
+  oneof weight {
+    float light = 6;
+    sfixed64 heavy = 7;
+  }
+}"#,
+        )
+        .unwrap();
+        let protos = [proto];
+
+        let payloads = sample(&protos, "foo.v1.Node", 20, 7, Knobs::default()).unwrap();
+        assert_eq!(payloads.len(), 20);
+        for json in &payloads {
+            assert_eq!(
+                payload::validate(&protos, "foo.v1.Node", json).unwrap(),
+                vec![]
+            );
+        }
+        let nested = payloads.iter().any(|json| {
+            matches!(json, Json::Object(fields) if fields.iter().any(|(name, value)|
+                name == "children" && matches!(value, Json::Array(values) if !values.is_empty())))
+        });
+        assert!(nested);
+
+        // deterministic for the same seed
+        let again = sample(&protos, "foo.v1.Node", 20, 7, Knobs::default()).unwrap();
+        assert_eq!(payloads, again);
+
+        let empty = Knobs {
+            max_items: 0,
+            max_len: 0,
+        };
+        let payloads = sample(&protos, "foo.v1.Node", 1, 1, empty).unwrap();
+        assert!(matches!(&payloads[0], Json::Object(fields)
+            if fields.iter().any(|(name, value)| name == "name" && *value == Json::str(""))));
+        assert!(sample(&protos, "foo.v1.Color", 1, 1, empty).is_err());
+    }
+
+    #[test]
+    fn sample_envelope() {
+        let envelope = Envelope {
+            proto: "protos/user.proto",
+            include_paths: &["protos".to_string()],
+            message: "foo.v1.User",
+            count: 1,
+            seed: u64::MAX,
+            knobs: Knobs::default(),
+            out: Some("tests/users.json"),
+        };
+        let json = envelope.wrap(vec![Json::object([])]).to_string();
+        assert_eq!(
+            json,
+            format!(
+                "{{\"generator\":{{\"version\":\"{}\",\"proto\":\"protos/user.proto\",\
+                 \"message\":\"foo.v1.User\",\"count\":1,\"seed\":\"18446744073709551615\",\
+                 \"maxItems\":3,\"maxLen\":12,\"command\":\"protots sample --proto protos/user.proto \
+                 --message foo.v1.User --count 1 --seed 18446744073709551615 --max-items 3 \
+                 --max-len 12 --envelope --out tests/users.json -I protos\"}},\"payloads\":[{{}}]}}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+}
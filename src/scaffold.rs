@@ -0,0 +1,186 @@
+//! Scaffolding of new proto files following the project conventions given in
+//! the `.prototsrc` configuration:
+//!
+//! - `syntax`: syntax of new files (default: `proto3`)
+//! - `header`: comment line(s) at the top of new files
+//! - `option`: file option(s) of new files, `{package}` and `{package_path}`
+//!   (package with slashes) are replaced accordingly, e.g.
+//!   `option = go_package = "github.com/org/api/{package_path}"`
+
+use crate::config::Config;
+use crate::errors::PtError;
+
+/// Kind of the top-level element a new file is created with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Kind {
+    Message,
+    Enum,
+    Service,
+}
+
+impl Kind {
+    pub fn parse(kind: &str) -> Result<Kind, PtError> {
+        match kind {
+            "message" => Ok(Kind::Message),
+            "enum" => Ok(Kind::Enum),
+            "service" => Ok(Kind::Service),
+            other => Err(PtError::InvalidArgument(format!(
+                "unknown element kind: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Content of a new proto `file`. The package defaults to the directory of
+/// the file (`foo/v1/user.proto` is in package `foo.v1`), the name of the
+/// element to the file name (`User`).
+pub fn scaffold(
+    config: &Config,
+    kind: &Kind,
+    file: &str,
+    package: Option<&str>,
+    name: Option<&str>,
+) -> Result<String, PtError> {
+    let file = file.trim_start_matches("./");
+    let (dir, file_name) = file.rsplit_once('/').unwrap_or(("", file));
+    let package = package
+        .map(str::to_string)
+        .unwrap_or_else(|| dir.replace('/', "."));
+    let name = name.map(str::to_string).unwrap_or_else(|| {
+        file_name
+            .trim_end_matches(".proto")
+            .split('_')
+            .map(upper_first)
+            .collect()
+    });
+
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(PtError::InvalidArgument(format!("invalid name: {}", name)));
+    }
+
+    let mut str = String::new();
+
+    for header in config.get_all("header") {
+        if header.is_empty() {
+            str.push_str("//\n");
+        } else {
+            str.push_str(&format!("// {}\n", header));
+        }
+    }
+    if config.get("header").is_some() {
+        str.push('\n');
+    }
+
+    str.push_str(&format!(
+        "syntax = \"{}\";\n",
+        config.get("syntax").unwrap_or("proto3")
+    ));
+
+    if !package.is_empty() {
+        str.push_str(&format!("\npackage {};\n", package));
+    }
+
+    let options = config
+        .get_all("option")
+        .map(|option| {
+            option
+                .replace("{package}", &package)
+                .replace("{package_path}", &package.replace('.', "/"))
+        })
+        .collect::<Vec<_>>();
+    if !options.is_empty() {
+        str.push('\n');
+        for option in options {
+            str.push_str(&format!("option {};\n", option));
+        }
+    }
+
+    str.push('\n');
+    match kind {
+        Kind::Message => str.push_str(&format!("message {} {{\n}}\n", name)),
+        Kind::Enum => str.push_str(&format!(
+            "enum {} {{\n  {}_UNSPECIFIED = 0;\n}}\n",
+            name,
+            screaming_snake(&name)
+        )),
+        Kind::Service => str.push_str(&format!("service {} {{\n}}\n", name)),
+    }
+
+    Ok(str)
+}
+
+fn upper_first(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+fn screaming_snake(name: &str) -> String {
+    let mut str = String::new();
+    for (idx, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && idx > 0 && !str.ends_with('_') {
+            str.push('_');
+        }
+        str.push(c.to_ascii_uppercase());
+    }
+    str
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scaffold, Kind};
+    use crate::config::Config;
+
+    #[test]
+    fn scaffold_with_config() {
+        let config = Config::parse(
+            "header = Code owned by the API team.\noption = go_package = \"github.com/org/api/{package_path}\"\noption = java_package = \"com.org.{package}\"",
+        )
+        .unwrap();
+
+        let content = scaffold(
+            &config,
+            &Kind::Message,
+            "foo/v1/user.proto",
+            Some("foo.v1"),
+            Some("User"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            content,
+            r#"// Code owned by the API team.
+
+syntax = "proto3";
+
+package foo.v1;
+
+option go_package = "github.com/org/api/foo/v1";
+option java_package = "com.org.foo.v1";
+
+message User {
+}
+"#
+        );
+    }
+
+    #[test]
+    fn scaffold_defaults() {
+        let content = scaffold(
+            &Config::default(),
+            &Kind::Enum,
+            "./common/order_status.proto",
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content,
+            "syntax = \"proto3\";\n\npackage common;\n\nenum OrderStatus {\n  ORDER_STATUS_UNSPECIFIED = 0;\n}\n"
+        );
+    }
+}
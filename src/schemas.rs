@@ -0,0 +1,1165 @@
+//! Schemas of validation libraries other than zod (see `--target`). The
+//! libraries differ in their constructors only, so they are described by a
+//! `Library` each and generated the same way: a schema of every message and
+//! enum along with the type inferred from it, services are omitted.
+//!
+//! The schemas are declared before they are referred to: enums first, then
+//! the messages ordered by their dependencies (see `order`). Only references
+//! within cycles of messages end up in front of the declaration of the
+//! schema they refer to, they are deferred (see `Library::suspend`).
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::errors::{Location, PtError};
+use crate::fingerprint::{enum_fingerprint, fingerprint};
+use crate::imports;
+use crate::parser::{Elem, Enum, EnumValue, Field, Flag, Msg, Proto};
+use crate::registry::{self, Kind, ProtoType, Registry};
+use crate::typescript::{
+    field_location, format_fingerprint, format_proto_name, header, jsdoc, property_key,
+    public_exports, snake_to_camel,
+};
+use crate::verify::{Property, Shape, Shapes};
+use crate::{Int64, Opts};
+
+/// Constructors of the schemas of a validation library.
+pub struct Library {
+    /// import of the library's constructors
+    pub import: &'static str,
+    pub string: &'static str,
+    pub number: &'static str,
+    /// 64 bit integers, given as string or number in JSON
+    pub bigint: &'static str,
+    pub boolean: &'static str,
+    /// `google.protobuf.Timestamp`, given as RFC 3339 string in JSON
+    pub date: &'static str,
+    /// opening and closing of the object schema of the given properties
+    pub object: (&'static str, &'static str),
+    pub optional: fn(&str) -> String,
+    pub array: fn(&str) -> String,
+    pub record: fn(&str, &str) -> String,
+    pub union: fn(&[String]) -> String,
+    /// schema of the enum, given the names of its values
+    pub literals: fn(&[String]) -> String,
+    /// type inferred from the given schema
+    pub infer: fn(&str) -> String,
+    /// reference to the schema of a message declared further down the file,
+    /// or the message being declared itself, which is not initialized yet
+    pub suspend: fn(&ProtoType) -> String,
+    /// the schema declared under the given id, for libraries referencing
+    /// schemas declared further down by id (see `suspend`)
+    pub identified: Option<fn(&str, &str) -> String>,
+    /// name a message refers to itself by within its schema, for libraries
+    /// with recursive schemas of their own (see `recursive`)
+    pub this: Option<&'static str>,
+    /// schema of a message referring to itself by `this`
+    pub recursive: fn(&str) -> String,
+    /// types declared as interfaces of the messages referred to by `suspend`,
+    /// which cannot be inferred from their schemas referring to themselves
+    pub interfaces: &'static [Types],
+}
+
+/// TypeScript types of the schemas of a validation library.
+pub struct Types {
+    /// suffix of the name of the interface, e.g. of the encoded type
+    pub suffix: &'static str,
+    pub string: &'static str,
+    pub number: &'static str,
+    pub bigint: &'static str,
+    pub boolean: &'static str,
+    pub date: &'static str,
+    /// whether the properties are readonly
+    pub readonly: bool,
+    pub array: fn(&str) -> String,
+    pub record: fn(&str) -> String,
+    pub union: fn(&[String]) -> String,
+    /// whether the property of a oneof is optional
+    pub optional_oneofs: bool,
+    /// type inferred from the given schema of a type without an interface
+    pub inferred: fn(&str) -> String,
+}
+
+/// [Effect Schema](https://effect.website/docs/schema/introduction), whose
+/// `Schema.BigInt` and `Schema.Date` decode the JSON strings of 64 bit
+/// integers and timestamps.
+pub const EFFECT: Library = Library {
+    import: "import { Schema } from \"effect\";",
+    string: "Schema.String",
+    number: "Schema.Number",
+    bigint: "Schema.BigInt",
+    boolean: "Schema.Boolean",
+    date: "Schema.Date",
+    object: ("Schema.Struct({", "})"),
+    optional: |schema| format!("Schema.optional({})", schema),
+    array: |schema| format!("Schema.Array({})", schema),
+    record: |key, value| format!("Schema.Record({{ key: {}, value: {} }})", key, value),
+    union: |cases| format!("Schema.Union({})", cases.join(", ")),
+    literals: |names| format!("Schema.Literal({})", quoted(names).join(", ")),
+    infer: |schema| format!("typeof {}.Type", schema),
+    suspend: |ptype| {
+        format!(
+            "Schema.suspend((): Schema.Schema<{0}, {0}Encoded> => {1})",
+            ptype.ts_name, ptype.schema
+        )
+    },
+    identified: None,
+    this: None,
+    recursive: |schema| schema.to_string(),
+    interfaces: &[
+        Types {
+            suffix: "",
+            string: "string",
+            number: "number",
+            bigint: "bigint",
+            boolean: "boolean",
+            date: "Date",
+            readonly: true,
+            array: |value| format!("ReadonlyArray<{}>", value),
+            record: |value| format!("{{ readonly [key: string]: {} }}", value),
+            union: |cases| cases.join(" | "),
+            optional_oneofs: false,
+            inferred: |schema| format!("typeof {}.Type", schema),
+        },
+        Types {
+            suffix: "Encoded",
+            string: "string",
+            number: "number",
+            bigint: "string",
+            boolean: "boolean",
+            date: "string",
+            readonly: true,
+            array: |value| format!("ReadonlyArray<{}>", value),
+            record: |value| format!("{{ readonly [key: string]: {} }}", value),
+            union: |cases| cases.join(" | "),
+            optional_oneofs: false,
+            inferred: |schema| format!("typeof {}.Encoded", schema),
+        },
+    ],
+};
+
+/// [TypeBox](https://github.com/sinclairzx81/typebox), whose schemas are JSON
+/// schemas validating the JSON representation as is, e.g. by AJV in Fastify.
+pub const TYPEBOX: Library = Library {
+    import: "import { Type, type Static } from \"@sinclair/typebox\";",
+    string: "Type.String()",
+    number: "Type.Number()",
+    bigint: "Type.Union([Type.String(), Type.Integer()])",
+    boolean: "Type.Boolean()",
+    date: "Type.String({ format: \"date-time\" })",
+    object: ("Type.Object({", "})"),
+    optional: |schema| format!("Type.Optional({})", schema),
+    array: |schema| format!("Type.Array({})", schema),
+    record: |key, value| format!("Type.Record({}, {})", key, value),
+    union: |cases| format!("Type.Union([{}])", cases.join(", ")),
+    literals: |names| {
+        let literals = quoted(names)
+            .iter()
+            .map(|name| format!("Type.Literal({})", name))
+            .collect::<Vec<_>>();
+        format!("Type.Union([{}])", literals.join(", "))
+    },
+    infer: |schema| format!("Static<typeof {}>", schema),
+    suspend: |ptype| format!("Type.Ref(\"{}\")", ptype.full_name),
+    identified: Some(|schema, id| {
+        let schema = schema.strip_suffix(')').unwrap_or(schema);
+        format!("{}, {{ $id: \"{}\" }})", schema, id)
+    }),
+    this: Some("This"),
+    recursive: |schema| format!("Type.Recursive((This) => {})", schema),
+    interfaces: &[],
+};
+
+/// [yup](https://github.com/jquense/yup), lacking unions, records and bigints:
+/// oneofs are tested against their cases, maps validated by the schema of
+/// their values per key and 64 bit integers are kept as decimal strings.
+pub const YUP: Library = Library {
+    import: "import * as yup from \"yup\";",
+    string: "yup.string().defined()",
+    number: "yup.number().defined()",
+    bigint: "yup.string().matches(/^-?[0-9]+$/).defined()",
+    boolean: "yup.boolean().defined()",
+    date: "yup.date().defined()",
+    object: ("yup.object().shape({", "})"),
+    // fields are optional by default, which `defined()` switched off
+    optional: |schema| {
+        let schema = schema.strip_suffix(".defined()").unwrap_or(schema);
+        format!("{}.optional()", schema)
+    },
+    array: |schema| format!("yup.array({}).defined()", schema),
+    record: |_, value| {
+        format!(
+            "yup.lazy((map?: Record<string, unknown>) => yup.object(Object.keys(map || {{}})\
+             .reduce<Record<string, yup.AnySchema>>((shape, key) => Object.assign(shape, {{ [key]: {} }}), {{}})))",
+            value
+        )
+    },
+    union: |cases| {
+        format!(
+            "yup.mixed().test(\"oneof\", \"${{path}} matches none of the cases\", (value) => \
+             value === undefined || [{}].some((schema) => schema.isValidSync(value)))",
+            cases.join(", ")
+        )
+    },
+    literals: |names| {
+        let union = match names.is_empty() {
+            true => "never".to_string(),
+            false => quoted(names).join(" | "),
+        };
+        format!(
+            "yup.mixed<{}>().oneOf([{}]).defined()",
+            union,
+            quoted(names).join(", ")
+        )
+    },
+    infer: |schema| format!("yup.InferType<typeof {}>", schema),
+    suspend: |ptype| {
+        format!(
+            "yup.lazy((): yup.Schema<{}> => {})",
+            ptype.ts_name, ptype.schema
+        )
+    },
+    identified: None,
+    this: None,
+    recursive: |schema| schema.to_string(),
+    interfaces: &[Types {
+        suffix: "",
+        string: "string",
+        number: "number",
+        bigint: "string",
+        boolean: "boolean",
+        date: "Date",
+        readonly: false,
+        array: |value| format!("{}[]", value),
+        record: |value| format!("Record<string, {}>", value),
+        // the cases are tested only, the value is not typed any further
+        union: |_| "{} | undefined".to_string(),
+        optional_oneofs: true,
+        inferred: |schema| format!("yup.InferType<typeof {}>", schema),
+    }],
+};
+
+fn quoted(names: &[String]) -> Vec<String> {
+    names.iter().map(|name| format!("\"{}\"", name)).collect()
+}
+
+/// Generate the schemas of the given proto file for the library, skipping the
+/// types and fields that failed to generate (see `typescript::generate`).
+pub fn generate(
+    library: &Library,
+    opts: &Opts,
+    proto: &Proto,
+    deps: &[Proto],
+) -> (String, Vec<PtError>) {
+    let (schemas, errors, _) = generate_shapes(library, opts, proto, deps);
+    (schemas, errors)
+}
+
+/// Generate the schemas (see `generate`) along with the shapes of their types.
+pub(crate) fn generate_shapes(
+    library: &Library,
+    opts: &Opts,
+    proto: &Proto,
+    deps: &[Proto],
+) -> (String, Vec<PtError>, Shapes) {
+    let registry = Registry::new([proto].into_iter().chain(deps));
+    let mut messages = Vec::new();
+    let mut enums = Vec::new();
+    collect(
+        &proto.elems,
+        proto.package().unwrap_or(""),
+        &mut messages,
+        &mut enums,
+    );
+    let (messages, cyclic) = order(&registry, &proto.file, messages);
+
+    let ctx = Context {
+        library,
+        opts,
+        file: &proto.file,
+        registry: &registry,
+        imports: RefCell::new(BTreeMap::new()),
+        errors: RefCell::new(Vec::new()),
+        declared: RefCell::new(BTreeSet::new()),
+        suspended: RefCell::new(BTreeSet::new()),
+        current: RefCell::new(None),
+        recursive: RefCell::new(BTreeSet::new()),
+        cyclic,
+        shapes: RefCell::new(Shapes::new()),
+    };
+    let mut body = String::with_capacity(4096);
+
+    for (scope, e) in enums {
+        let location = Location {
+            line: e.span.line,
+            ..Location::new("enum", registry::qualify(&scope, &e.name))
+        };
+        if let Some(value) = ctx.recover(location, format_enum(&ctx, e, &scope)) {
+            body.push_str(&value);
+        }
+    }
+    for (scope, msg) in messages {
+        let location = Location {
+            line: msg.span.line,
+            ..Location::new("message", registry::qualify(&scope, &msg.name))
+        };
+        if let Some(value) = ctx.recover(location, format_msg(&ctx, msg, &scope)) {
+            body.push_str(&value);
+        }
+    }
+
+    let mut str = String::with_capacity(body.len() + 512);
+
+    str.push_str(&header(opts, proto));
+    str.push_str(library.import);
+    str.push('\n');
+    for (module, names) in ctx.imports.borrow().iter() {
+        str.push_str(
+            format!(
+                "import {{ {} }} from \"{}\";\n",
+                names.iter().cloned().collect::<Vec<_>>().join(", "),
+                module
+            )
+            .as_str(),
+        );
+    }
+    str.push_str(&public_exports(proto));
+    str.push('\n');
+
+    str.push_str(&body);
+
+    (str, ctx.errors.take(), ctx.shapes.take())
+}
+
+/// The messages and enums declared in the given elements, including nested
+/// ones, along with the scope they are declared in.
+fn collect<'a>(
+    elems: &'a [Elem],
+    scope: &str,
+    messages: &mut Vec<(String, &'a Msg)>,
+    enums: &mut Vec<(String, &'a Enum)>,
+) {
+    fn nested<'a>(
+        msg: &'a Msg,
+        scope: &str,
+        messages: &mut Vec<(String, &'a Msg)>,
+        enums: &mut Vec<(String, &'a Enum)>,
+    ) {
+        messages.push((scope.to_string(), msg));
+        let scope = registry::qualify(scope, &msg.name);
+        for field in &msg.fields {
+            match field {
+                Field::SubMessage(sub_msg) => nested(sub_msg, &scope, messages, enums),
+                Field::SubEnum(e) => enums.push((scope.clone(), e)),
+                _ => (),
+            }
+        }
+    }
+
+    for elem in elems {
+        match elem {
+            Elem::Message(msg) => nested(msg, scope, messages, enums),
+            Elem::Enum(e) => enums.push((scope.to_string(), e)),
+            _ => (),
+        }
+    }
+}
+
+/// The given messages ordered by their dependencies: every message follows
+/// the messages of the file it refers to, unless they refer to it in turn.
+/// Messages without dependencies among each other keep their order. Along
+/// with the messages referred to before their declaration, within cycles.
+fn order<'a>(
+    registry: &Registry,
+    file: &str,
+    messages: Vec<(String, &'a Msg)>,
+) -> (Vec<(String, &'a Msg)>, BTreeSet<String>) {
+    struct Visit<'a, 'b> {
+        names: &'b [String],
+        dependencies: &'b [Vec<usize>],
+        messages: &'b [(String, &'a Msg)],
+        visited: Vec<bool>,
+        /// whether the message is ordered already, i.e. declared before the
+        /// ones visited from now on
+        declared: Vec<bool>,
+        ordered: Vec<(String, &'a Msg)>,
+        cyclic: BTreeSet<String>,
+    }
+
+    fn visit(index: usize, state: &mut Visit) {
+        if state.visited[index] {
+            return;
+        }
+        // messages are marked before their dependencies are visited, so
+        // references back to them (cycles) are not followed
+        state.visited[index] = true;
+        for dependency in &state.dependencies[index] {
+            let dependency = *dependency;
+            if state.visited[dependency] && !state.declared[dependency] {
+                state.cyclic.insert(state.names[dependency].clone());
+            }
+            visit(dependency, state);
+        }
+        state.declared[index] = true;
+        state.ordered.push(state.messages[index].clone());
+    }
+
+    let names = messages
+        .iter()
+        .map(|(scope, msg)| registry::qualify(scope, &msg.name))
+        .collect::<Vec<_>>();
+    let dependencies = messages
+        .iter()
+        .zip(&names)
+        .map(|((_, msg), full_name)| {
+            let mut fields = Vec::new();
+            registry::declared(&msg.fields, None, &mut fields);
+            fields
+                .iter()
+                .filter_map(|field| registry.resolve(field.field_type, full_name))
+                .filter(|ptype| ptype.kind == Kind::Message && ptype.file == file)
+                .filter_map(|ptype| names.iter().position(|name| *name == ptype.full_name))
+                .collect()
+        })
+        .collect::<Vec<_>>();
+
+    let mut state = Visit {
+        names: &names,
+        dependencies: &dependencies,
+        messages: &messages,
+        visited: vec![false; messages.len()],
+        declared: vec![false; messages.len()],
+        ordered: Vec::with_capacity(messages.len()),
+        cyclic: BTreeSet::new(),
+    };
+    for index in 0..messages.len() {
+        visit(index, &mut state);
+    }
+    (state.ordered, state.cyclic)
+}
+
+fn format_msg(ctx: &Context, msg: &Msg, scope: &str) -> Result<String, PtError> {
+    let ptype = ctx.declared(&msg.name, scope)?;
+    let mut properties = Vec::new();
+    let mut shape = Vec::new();
+
+    ctx.current.replace(Some(ptype.full_name.clone()));
+    for field in &msg.fields {
+        let location = field_location(&ptype.full_name, "", field);
+        let value = format_field(ctx, field, &ptype.full_name);
+        if let Some((property, value)) = ctx.recover(location, value).flatten() {
+            shape.push(property);
+            properties.push(value);
+        }
+    }
+    ctx.current.replace(None);
+    ctx.shapes
+        .borrow_mut()
+        .insert(ptype.ts_name.clone(), Shape::message(shape));
+
+    let mut str = String::new();
+
+    let deprecated = if msg.deprecated {
+        "/** @deprecated */\n"
+    } else {
+        ""
+    };
+    // the types of schemas referring to themselves cannot be inferred, they
+    // are declared along with the suspended references instead
+    let interfaces = match ctx.cyclic.contains(&ptype.full_name) {
+        true => ctx.library.interfaces,
+        false => &[],
+    };
+    for types in interfaces {
+        str.push_str(deprecated);
+        str.push_str(&format_interface(ctx, types, msg, ptype));
+    }
+    let (open, close) = ctx.library.object;
+    let mut schema = format!("{}\n", open);
+    for property in properties {
+        schema.push_str("  ");
+        schema.push_str(&property);
+        schema.push_str(",\n");
+    }
+    schema.push_str(close);
+    if ctx.recursive.borrow_mut().remove(&ptype.full_name) {
+        schema = (ctx.library.recursive)(&schema);
+    }
+    str.push_str(deprecated);
+    str.push_str(&format!(
+        "export const {} = {};\n\n",
+        ptype.schema,
+        ctx.identify(ptype, &schema)
+    ));
+    ctx.declared.borrow_mut().insert(ptype.full_name.clone());
+
+    if interfaces.is_empty() {
+        str.push_str(deprecated);
+        str.push_str(&format_type(ctx, ptype));
+    }
+
+    if ctx.opts.proto_names {
+        str.push_str(&format_proto_name(
+            "export ",
+            &ptype.ts_name,
+            &ptype.full_name,
+        ));
+    }
+    if ctx.opts.fingerprints {
+        str.push_str(&format_fingerprint(
+            "export ",
+            &ptype.ts_name,
+            fingerprint(msg),
+        ));
+    }
+
+    Ok(str)
+}
+
+/// Property of the given field, keyed like in the JSON representation.
+fn format_field(
+    ctx: &Context,
+    field: &Field,
+    scope: &str,
+) -> Result<Option<(Property, String)>, PtError> {
+    let library = ctx.library;
+    match field {
+        Field::Single {
+            name,
+            field_type,
+            flag,
+            deprecated,
+            json_name,
+            ..
+        } => {
+            let key = match json_name {
+                Some(json_name) => property_key(json_name),
+                None => snake_to_camel(name),
+            };
+            let schema = type_name(ctx, field_type, scope)?;
+            let schema = match flag {
+                Flag::Optional => (library.optional)(&schema),
+                Flag::Repeated => (library.array)(&schema),
+                Flag::None | Flag::Required => schema,
+            };
+            let value = format!("{}{}: {}", jsdoc(*deprecated), key, schema);
+            Ok(Some((Property::new(key, *flag == Flag::Optional), value)))
+        }
+        Field::Map {
+            name,
+            value_type,
+            deprecated,
+            ..
+        } => {
+            let key = snake_to_camel(name);
+            let value = format!(
+                "{}{}: {}",
+                jsdoc(*deprecated),
+                key,
+                // keys of JSON objects are strings
+                (library.record)(library.string, &type_name(ctx, value_type, scope)?)
+            );
+            Ok(Some((Property::new(key, false), value)))
+        }
+        Field::OneOf { name, fields, .. } => {
+            let (open, close) = library.object;
+            let mut cases = Vec::new();
+            for case in fields {
+                let value = format_field(ctx, case, scope);
+                let prefix = format!("{}.", name);
+                if let Some(value) = ctx.recover(field_location(scope, &prefix, case), value) {
+                    cases.extend(value.map(|(_, value)| format!("{} {} {}", open, value, close)));
+                }
+            }
+            let schema = match cases.len() {
+                0 => return Ok(None),
+                1 => cases.remove(0),
+                _ => (library.union)(&cases),
+            };
+            let key = snake_to_camel(name);
+            let value = format!("{}: {}", key, schema);
+            Ok(Some((Property::new(key, false), value)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Interface of the type of the given message, matching the one inferred
+/// from its schema. The fields failing to generate are omitted, their errors
+/// are reported along with the schema already.
+fn format_interface(ctx: &Context, types: &Types, msg: &Msg, ptype: &ProtoType) -> String {
+    let mut str = format!("export interface {}{} {{\n", ptype.ts_name, types.suffix);
+    for field in &msg.fields {
+        if let Ok(Some(property)) = interface_property(ctx, types, field, &ptype.full_name) {
+            str.push_str("  ");
+            str.push_str(&property);
+            str.push_str(";\n");
+        }
+    }
+    str.push_str("}\n\n");
+    str
+}
+
+/// Property of the given field in the interface (see `format_field`).
+fn interface_property(
+    ctx: &Context,
+    types: &Types,
+    field: &Field,
+    scope: &str,
+) -> Result<Option<String>, PtError> {
+    let readonly = if types.readonly { "readonly " } else { "" };
+    match field {
+        Field::Single {
+            name,
+            field_type,
+            flag,
+            json_name,
+            ..
+        } => {
+            let key = match json_name {
+                Some(json_name) => property_key(json_name),
+                None => snake_to_camel(name),
+            };
+            let value = interface_type(ctx, types, field_type, scope)?;
+            Ok(Some(match flag {
+                Flag::Optional => format!("{}{}?: {} | undefined", readonly, key, value),
+                Flag::Repeated => format!("{}{}: {}", readonly, key, (types.array)(&value)),
+                Flag::None | Flag::Required => format!("{}{}: {}", readonly, key, value),
+            }))
+        }
+        Field::Map {
+            name, value_type, ..
+        } => {
+            let value = interface_type(ctx, types, value_type, scope)?;
+            Ok(Some(format!(
+                "{}{}: {}",
+                readonly,
+                snake_to_camel(name),
+                (types.record)(&value)
+            )))
+        }
+        Field::OneOf { name, fields, .. } => {
+            let mut cases = Vec::new();
+            for case in fields {
+                if let Ok(Some(value)) = interface_property(ctx, types, case, scope) {
+                    cases.push(format!("{{ {} }}", value));
+                }
+            }
+            if cases.is_empty() {
+                return Ok(None);
+            }
+            let value = (types.union)(&cases);
+            Ok(Some(match types.optional_oneofs {
+                true => format!("{}{}?: {}", readonly, snake_to_camel(name), value),
+                false => format!("{}{}: {}", readonly, snake_to_camel(name), value),
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Type of the given field type in the interface (see `type_name`).
+fn interface_type(
+    ctx: &Context,
+    types: &Types,
+    type_name: &str,
+    scope: &str,
+) -> Result<String, PtError> {
+    let native = match type_name {
+        "string" | "bytes" => types.string,
+        "int32" | "double" | "float" | "uint32" | "sint32" | "fixed32" | "sfixed32" => types.number,
+        "int64" | "uint64" | "fixed64" | "sfixed64" | "sint64" => match ctx.opts.int64() {
+            Int64::Bigint => types.bigint,
+            Int64::String => types.string,
+            Int64::Number => types.number,
+        },
+        "bool" => types.boolean,
+        "google.protobuf.Timestamp" | ".google.protobuf.Timestamp" => types.date,
+        _ => {
+            let ptype = ctx
+                .resolve(type_name, scope)
+                .ok_or_else(|| ctx.registry.unresolved(type_name, scope))?;
+            if ptype.file == ctx.file && ctx.cyclic.contains(&ptype.full_name) {
+                return Ok(format!("{}{}", ptype.ts_name, types.suffix));
+            }
+            return Ok((types.inferred)(&ptype.schema));
+        }
+    };
+    Ok(native.to_string())
+}
+
+fn format_enum(ctx: &Context, value: &Enum, scope: &str) -> Result<String, PtError> {
+    let ptype = ctx.declared(&value.name, scope)?;
+    let mut str = String::with_capacity(256);
+
+    let names = value
+        .values
+        .iter()
+        .filter_map(|value| match value {
+            EnumValue::Single { name, .. } => Some(name.clone()),
+            EnumValue::Reserved { .. } => None,
+        })
+        .collect::<Vec<_>>();
+
+    let deprecated = if value.deprecated {
+        "/** @deprecated */\n"
+    } else {
+        ""
+    };
+    str.push_str(deprecated);
+    str.push_str(
+        format!(
+            "export const {} = {};\n\n",
+            ptype.schema,
+            ctx.identify(ptype, &(ctx.library.literals)(&names))
+        )
+        .as_str(),
+    );
+    ctx.declared.borrow_mut().insert(ptype.full_name.clone());
+    ctx.shapes
+        .borrow_mut()
+        .insert(ptype.ts_name.clone(), Shape::enumeration(names));
+    str.push_str(deprecated);
+    str.push_str(&format_type(ctx, ptype));
+
+    if ctx.opts.proto_names {
+        str.push_str(&format_proto_name(
+            "export ",
+            &ptype.ts_name,
+            &ptype.full_name,
+        ));
+    }
+    if ctx.opts.fingerprints {
+        str.push_str(&format_fingerprint(
+            "export ",
+            &ptype.ts_name,
+            enum_fingerprint(value),
+        ));
+    }
+
+    Ok(str)
+}
+
+fn format_type(ctx: &Context, ptype: &ProtoType) -> String {
+    format!(
+        "export type {} = {};\n\n",
+        ptype.ts_name,
+        (ctx.library.infer)(&ptype.schema)
+    )
+}
+
+fn type_name(ctx: &Context, type_name: &str, scope: &str) -> Result<String, PtError> {
+    let library = ctx.library;
+    let native = match type_name {
+        "string" | "bytes" => library.string,
+        "int32" | "double" | "float" | "uint32" | "sint32" | "fixed32" | "sfixed32" => {
+            library.number
+        }
+        "int64" | "uint64" | "fixed64" | "sfixed64" | "sint64" => match ctx.opts.int64() {
+            Int64::Bigint => library.bigint,
+            Int64::String => library.string,
+            Int64::Number => library.number,
+        },
+        "bool" => library.boolean,
+        "google.protobuf.Timestamp" | ".google.protobuf.Timestamp" => library.date,
+        _ => {
+            let ptype = ctx
+                .resolve(type_name, scope)
+                .ok_or_else(|| ctx.registry.unresolved(type_name, scope))?;
+            return Ok(ctx.reference(ptype));
+        }
+    };
+    Ok(native.to_string())
+}
+
+struct Context<'a> {
+    library: &'a Library,
+    opts: &'a Opts,
+    file: &'a str,
+    registry: &'a Registry,
+    /// schemas referenced from other modules, grouped by module
+    imports: RefCell<BTreeMap<String, BTreeSet<String>>>,
+    /// errors of the generation phase, see `PtError::Located`
+    errors: RefCell<Vec<PtError>>,
+    /// types of the file whose schemas are declared already
+    declared: RefCell<BTreeSet<String>>,
+    /// types of the file referenced before their schemas are declared
+    suspended: RefCell<BTreeSet<String>>,
+    /// message whose schema is being declared
+    current: RefCell<Option<String>>,
+    /// messages referring to themselves (see `Library::recursive`)
+    recursive: RefCell<BTreeSet<String>>,
+    /// messages referred to before their declaration, within cycles
+    cyclic: BTreeSet<String>,
+    /// shapes of the types declared (see `verify`)
+    shapes: RefCell<Shapes>,
+}
+
+impl<'a> Context<'a> {
+    fn recover<T>(&self, location: Location, result: Result<T, PtError>) -> Option<T> {
+        result
+            .map_err(|error| self.errors.borrow_mut().push(error.at(location)))
+            .ok()
+    }
+
+    fn declared(&self, name: &str, scope: &str) -> Result<&'a ProtoType, PtError> {
+        let full_name = registry::qualify(scope, name);
+        self.registry
+            .get(&full_name)
+            .ok_or(PtError::ProtobufTypeNotFound(full_name))
+    }
+
+    /// Reference to the schema of the given type, suspended if it is a type of
+    /// the file that is not declared yet, as its schema is not initialized.
+    fn reference(&self, ptype: &ProtoType) -> String {
+        if ptype.file != self.file || self.declared.borrow().contains(&ptype.full_name) {
+            return ptype.schema.clone();
+        }
+        if let Some(this) = self.library.this {
+            if self.current.borrow().as_ref() == Some(&ptype.full_name) {
+                self.recursive.borrow_mut().insert(ptype.full_name.clone());
+                return this.to_string();
+            }
+        }
+        self.suspended.borrow_mut().insert(ptype.full_name.clone());
+        (self.library.suspend)(ptype)
+    }
+
+    /// The schema of the given type, with the id it was referenced by before
+    /// its declaration (see `Library::identified`).
+    fn identify(&self, ptype: &ProtoType, schema: &str) -> String {
+        match self.library.identified {
+            Some(identified) if self.suspended.borrow().contains(&ptype.full_name) => {
+                identified(schema, &ptype.full_name)
+            }
+            _ => schema.to_string(),
+        }
+    }
+
+    /// Resolve a type reference and keep track of the schemas to be imported.
+    fn resolve(&self, name: &str, scope: &str) -> Option<&'a ProtoType> {
+        let ptype = self.registry.resolve(name, scope)?;
+
+        if ptype.file != self.file {
+            self.imports
+                .borrow_mut()
+                .entry(imports::module_path(self.file, &ptype.file))
+                .or_default()
+                .insert(ptype.schema.clone());
+        }
+
+        Some(ptype)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, EFFECT, TYPEBOX, YUP};
+    use crate::parser::parse;
+    use crate::Opts;
+
+    #[test]
+    fn generate_effect_schemas() {
+        let opts = Opts::default();
+        let common = parse(
+            &opts,
+            "foo/common.proto",
+            "syntax = \"proto3\";\npackage foo;\nmessage Money { int64 units = 1; }",
+        )
+        .unwrap();
+        let p = parse(
+            &opts,
+            "foo/user.proto",
+            r#"syntax = "proto3";
+package foo;
+import "foo/common.proto";
+import "google/protobuf/timestamp.proto";
+message User {
+  string name = 1;
+  optional int32 age = 2;
+  repeated Money balances = 3;
+  map<string, int64> counts = 4;
+  oneof contact { string email = 5; string phone = 6; }
+  Kind kind = 7;
+  google.protobuf.Timestamp created_at = 8;
+  enum Kind { KIND_UNSPECIFIED = 0; KIND_ADMIN = 1; }
+}"#,
+        )
+        .unwrap();
+
+        let (schemas, errors) = generate(&EFFECT, &opts, &p, &[common]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(schemas.contains(
+            "import { Schema } from \"effect\";\nimport { MoneySchema } from \"./common\";\n"
+        ));
+        assert!(schemas.ends_with(
+            r#"export const User_KindSchema = Schema.Literal("KIND_UNSPECIFIED", "KIND_ADMIN");
+
+export type User_Kind = typeof User_KindSchema.Type;
+
+export const UserSchema = Schema.Struct({
+  name: Schema.String,
+  age: Schema.optional(Schema.Number),
+  balances: Schema.Array(MoneySchema),
+  counts: Schema.Record({ key: Schema.String, value: Schema.BigInt }),
+  contact: Schema.Union(Schema.Struct({ email: Schema.String }), Schema.Struct({ phone: Schema.String })),
+  kind: User_KindSchema,
+  createdAt: Schema.Date,
+});
+
+export type User = typeof UserSchema.Type;
+
+"#
+        ));
+    }
+
+    #[test]
+    fn generate_typebox_schemas() {
+        let opts = Opts::default();
+        let p = parse(
+            &opts,
+            "user.proto",
+            r#"syntax = "proto3";
+import "google/protobuf/timestamp.proto";
+message User {
+  optional string name = 1;
+  repeated int64 ids = 2;
+  map<string, Kind> kinds = 3;
+  oneof contact { string email = 4; string phone = 5; }
+  google.protobuf.Timestamp created_at = 6;
+}
+enum Kind { KIND_UNSPECIFIED = 0; KIND_ADMIN = 1; }"#,
+        )
+        .unwrap();
+
+        let (schemas, errors) = generate(&TYPEBOX, &opts, &p, &[]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(schemas.contains("import { Type, type Static } from \"@sinclair/typebox\";\n"));
+        assert!(schemas.ends_with(
+            r#"export const KindSchema = Type.Union([Type.Literal("KIND_UNSPECIFIED"), Type.Literal("KIND_ADMIN")]);
+
+export type Kind = Static<typeof KindSchema>;
+
+export const UserSchema = Type.Object({
+  name: Type.Optional(Type.String()),
+  ids: Type.Array(Type.Union([Type.String(), Type.Integer()])),
+  kinds: Type.Record(Type.String(), KindSchema),
+  contact: Type.Union([Type.Object({ email: Type.String() }), Type.Object({ phone: Type.String() })]),
+  createdAt: Type.String({ format: "date-time" }),
+});
+
+export type User = Static<typeof UserSchema>;
+
+"#
+        ));
+    }
+
+    #[test]
+    fn generate_yup_schemas() {
+        let opts = Opts::default();
+        let p = parse(
+            &opts,
+            "user.proto",
+            r#"syntax = "proto3";
+message User {
+  optional string name = 1;
+  repeated int64 ids = 2;
+  map<string, int32> counts = 3;
+  oneof contact { string email = 4; string phone = 5; }
+  Kind kind = 6;
+  enum Kind { KIND_UNSPECIFIED = 0; KIND_ADMIN = 1; }
+}"#,
+        )
+        .unwrap();
+
+        let (schemas, errors) = generate(&YUP, &opts, &p, &[]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(schemas.contains("import * as yup from \"yup\";\n"));
+        assert!(schemas.contains(
+            r#"export const User_KindSchema = yup.mixed<"KIND_UNSPECIFIED" | "KIND_ADMIN">().oneOf(["KIND_UNSPECIFIED", "KIND_ADMIN"]).defined();"#
+        ));
+        assert!(schemas.contains(
+            r#"export const UserSchema = yup.object().shape({
+  name: yup.string().optional(),
+  ids: yup.array(yup.string().matches(/^-?[0-9]+$/).defined()).defined(),
+  counts: yup.lazy((map?: Record<string, unknown>) => yup.object(Object.keys(map || {}).reduce<Record<string, yup.AnySchema>>((shape, key) => Object.assign(shape, { [key]: yup.number().defined() }), {}))),
+  contact: yup.mixed().test("oneof", "${path} matches none of the cases", (value) => value === undefined || [yup.object().shape({ email: yup.string().defined() }), yup.object().shape({ phone: yup.string().defined() })].some((schema) => schema.isValidSync(value))),
+  kind: User_KindSchema,
+});
+
+export type User = yup.InferType<typeof UserSchema>;
+"#
+        ));
+    }
+
+    #[test]
+    fn generate_suspended_references() {
+        let opts = Opts::default();
+        let p = parse(
+            &opts,
+            "tree.proto",
+            r#"syntax = "proto3";
+message Tree {
+  Node root = 1;
+  Kind kind = 2;
+  Tree parent = 3;
+}
+message Node { repeated Node children = 1; Tree tree = 2; }
+enum Kind { KIND_UNSPECIFIED = 0; }"#,
+        )
+        .unwrap();
+
+        let (schemas, errors) = generate(&EFFECT, &opts, &p, &[]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(schemas.contains(
+            r#"export const NodeSchema = Schema.Struct({
+  children: Schema.Array(Schema.suspend((): Schema.Schema<Node, NodeEncoded> => NodeSchema)),
+  tree: Schema.suspend((): Schema.Schema<Tree, TreeEncoded> => TreeSchema),
+});"#
+        ));
+        assert!(schemas.contains(
+            r#"export const TreeSchema = Schema.Struct({
+  root: NodeSchema,
+  kind: KindSchema,
+  parent: Schema.suspend((): Schema.Schema<Tree, TreeEncoded> => TreeSchema),
+});"#
+        ));
+        assert!(schemas.contains(
+            r#"export interface Tree {
+  readonly root: Node;
+  readonly kind: typeof KindSchema.Type;
+  readonly parent: Tree;
+}
+
+export interface TreeEncoded {
+  readonly root: NodeEncoded;
+  readonly kind: typeof KindSchema.Encoded;
+  readonly parent: TreeEncoded;
+}
+"#
+        ));
+        assert!(!schemas.contains("export type Tree ="));
+        let kind = schemas.find("export const KindSchema").unwrap();
+        assert!(kind < schemas.find("export const NodeSchema").unwrap());
+
+        let (schemas, errors) = generate(&TYPEBOX, &opts, &p, &[]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(schemas.contains(
+            r#"export const NodeSchema = Type.Recursive((This) => Type.Object({
+  children: Type.Array(This),
+  tree: Type.Ref("Tree"),
+}));"#
+        ));
+        assert!(schemas.contains(
+            r#"export const TreeSchema = Type.Recursive((This) => Type.Object({
+  root: NodeSchema,
+  kind: KindSchema,
+  parent: This,
+}), { $id: "Tree" });"#
+        ));
+        assert!(schemas.contains(
+            r#"export const KindSchema = Type.Union([Type.Literal("KIND_UNSPECIFIED")]);"#
+        ));
+
+        let (schemas, errors) = generate(&YUP, &opts, &p, &[]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(schemas.contains(
+            r#"export const TreeSchema = yup.object().shape({
+  root: NodeSchema,
+  kind: KindSchema,
+  parent: yup.lazy((): yup.Schema<Tree> => TreeSchema),
+});"#
+        ));
+    }
+
+    #[test]
+    fn generate_interfaces_of_cyclic_types() {
+        let opts = Opts::default();
+        let p = parse(
+            &opts,
+            "rec.proto",
+            r#"syntax = "proto3";
+message Node {
+  repeated Node children = 1;
+  Leaf leaf = 2;
+  optional int64 size = 3;
+}
+message Leaf { Node parent = 1; }"#,
+        )
+        .unwrap();
+
+        let (schemas, errors) = generate(&EFFECT, &opts, &p, &[]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(schemas.ends_with(
+            r#"export const LeafSchema = Schema.Struct({
+  parent: Schema.suspend((): Schema.Schema<Node, NodeEncoded> => NodeSchema),
+});
+
+export type Leaf = typeof LeafSchema.Type;
+
+export interface Node {
+  readonly children: ReadonlyArray<Node>;
+  readonly leaf: typeof LeafSchema.Type;
+  readonly size?: bigint | undefined;
+}
+
+export interface NodeEncoded {
+  readonly children: ReadonlyArray<NodeEncoded>;
+  readonly leaf: typeof LeafSchema.Encoded;
+  readonly size?: string | undefined;
+}
+
+export const NodeSchema = Schema.Struct({
+  children: Schema.Array(Schema.suspend((): Schema.Schema<Node, NodeEncoded> => NodeSchema)),
+  leaf: LeafSchema,
+  size: Schema.optional(Schema.BigInt),
+});
+
+"#
+        ));
+
+        let (schemas, errors) = generate(&TYPEBOX, &opts, &p, &[]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(schemas.ends_with(
+            r#"export const LeafSchema = Type.Object({
+  parent: Type.Ref("Node"),
+});
+
+export type Leaf = Static<typeof LeafSchema>;
+
+export const NodeSchema = Type.Recursive((This) => Type.Object({
+  children: Type.Array(This),
+  leaf: LeafSchema,
+  size: Type.Optional(Type.Union([Type.String(), Type.Integer()])),
+}), { $id: "Node" });
+
+export type Node = Static<typeof NodeSchema>;
+
+"#
+        ));
+
+        let (schemas, errors) = generate(&YUP, &opts, &p, &[]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(schemas.ends_with(
+            r#"export const LeafSchema = yup.object().shape({
+  parent: yup.lazy((): yup.Schema<Node> => NodeSchema),
+});
+
+export type Leaf = yup.InferType<typeof LeafSchema>;
+
+export interface Node {
+  children: Node[];
+  leaf: yup.InferType<typeof LeafSchema>;
+  size?: string | undefined;
+}
+
+export const NodeSchema = yup.object().shape({
+  children: yup.array(yup.lazy((): yup.Schema<Node> => NodeSchema)).defined(),
+  leaf: LeafSchema,
+  size: yup.string().matches(/^-?[0-9]+$/).optional(),
+});
+
+"#
+        ));
+    }
+}
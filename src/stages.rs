@@ -0,0 +1,190 @@
+//! Intermediate stages of the generation of a file (see `--debug-stages`),
+//! written to a directory so they can be attached to bug reports and the
+//! stage that misbehaves can be told apart:
+//!
+//! 1. `01-tokens.txt`: token stream of the lexer, one token per line
+//! 2. `02-ast.json`: syntax tree of the parser (see `ast`)
+//! 3. `03-model.json`: files loaded and types resolved across them
+//! 4. `04-output.ts` and `04-descriptor.desc`: output of the generators,
+//!    the typescript of the targeted flavor and the descriptor set
+//!
+//! The stages are written up to the first one that fails, whose error is
+//! written to `<stage>.error.txt` instead.
+
+use std::path::{Path, PathBuf};
+
+use crate::errors::PtError;
+use crate::json::Json;
+use crate::lexer::{self, Kind};
+use crate::registry::{self, Registry};
+use crate::{ast, descriptor, imports, parser, typescript, Opts};
+
+/// Write the stages of the given input file to the directory, returning the
+/// paths of the files written.
+pub fn dump(opts: &Opts, dir: &Path, file: &str, input: &str) -> Result<Vec<PathBuf>, PtError> {
+    let mut written = Vec::new();
+    let mut write = |name: &str, content: &[u8]| -> Result<(), PtError> {
+        let path = dir.join(name);
+        opts.files.write(&path, content)?;
+        written.push(path);
+        Ok(())
+    };
+
+    write("01-tokens.txt", tokens(input).as_bytes())?;
+
+    let stages = (|| {
+        let proto = parser::parse(opts, file, input).map_err(|err| ("02-ast", err))?;
+        let version = opts.ast_version.unwrap_or(ast::VERSION);
+        let ast = format!("{:#}\n", ast::to_json_version(&proto, version));
+
+        let deps = imports::resolve(opts, &proto).map_err(|err| ("03-model", err))?;
+        let model = format!("{:#}\n", model(&proto, &deps));
+
+        let output =
+            typescript::to_schema(opts, &proto, &deps).map_err(|err| ("04-output", err))?;
+        let descriptor =
+            descriptor::to_descriptor_set(&proto, &deps).map_err(|err| ("04-descriptor", err))?;
+        Ok((ast, model, output, descriptor))
+    })();
+
+    match stages {
+        Ok((ast, model, output, descriptor)) => {
+            write("02-ast.json", ast.as_bytes())?;
+            write("03-model.json", model.as_bytes())?;
+            write("04-output.ts", format!("{}\n", output).as_bytes())?;
+            write("04-descriptor.desc", &descriptor)?;
+            Ok(written)
+        }
+        Err((stage, err)) => {
+            write(
+                &format!("{}.error.txt", stage),
+                format!("{}\n", err).as_bytes(),
+            )?;
+            Err(err)
+        }
+    }
+}
+
+/// Tokens along with their position and kind, e.g. `1:1 ident syntax`,
+/// followed by the errors of the lexer.
+fn tokens(input: &str) -> String {
+    let lines = std::iter::once(0)
+        .chain(input.match_indices('\n').map(|(idx, _)| idx + 1))
+        .collect::<Vec<_>>();
+    let position = |offset: usize| {
+        let line = lines.partition_point(|start| *start <= offset);
+        (line, offset - lines[line - 1] + 1)
+    };
+
+    let (tokens, errors) = lexer::tokenize(input);
+    let mut str = String::with_capacity(tokens.len() * 16);
+    for token in &tokens {
+        let (line, column) = position(token.start);
+        let kind = match token.kind {
+            Kind::Ident => "ident",
+            Kind::Number => "number",
+            Kind::Str => "string",
+            Kind::Punct => "punct",
+            Kind::Eof => "eof",
+        };
+        str.push_str(&format!("{}:{} {} {}\n", line, column, kind, token.text));
+    }
+    for error in &errors {
+        let (line, column) = position(error.offset);
+        str.push_str(&format!("{}:{} error {}\n", line, column, error.message));
+    }
+    str
+}
+
+/// Files loaded, the input file first, and the types resolved across them.
+fn model(proto: &parser::Proto, deps: &[parser::Proto]) -> Json {
+    let registry = Registry::new([proto].into_iter().chain(deps));
+    let mut types = registry.types().collect::<Vec<_>>();
+    types.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+
+    let files = [proto].into_iter().chain(deps);
+    Json::object([
+        (
+            "files",
+            Json::Array(files.map(|file| Json::str(&file.file)).collect()),
+        ),
+        (
+            "types",
+            Json::Array(
+                types
+                    .iter()
+                    .map(|ptype| {
+                        let kind = match ptype.kind {
+                            registry::Kind::Message => "message",
+                            registry::Kind::Enum => "enum",
+                        };
+                        Json::object([
+                            ("name", Json::str(&ptype.full_name)),
+                            ("kind", Json::str(kind)),
+                            ("file", Json::str(&ptype.file)),
+                            ("typescript", Json::str(&ptype.ts_name)),
+                            ("schema", Json::str(&ptype.schema)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::rc::Rc;
+
+    use super::dump;
+    use crate::provider::{FileProvider, Files, Memory};
+    use crate::Opts;
+
+    #[test]
+    fn dump_stages() {
+        let files = Rc::new(Memory::new([(
+            "protos/common/date.proto",
+            "syntax = \"proto3\";\npackage common;\nmessage Date {}",
+        )]));
+        let opts = Opts {
+            include_paths: vec!["protos".to_string()],
+            files: Files(files.clone()),
+            ..Opts::default()
+        };
+        let input = "syntax = \"proto3\";\nimport \"common/date.proto\";\n\
+                     message User { common.Date birthday = 1; }";
+
+        let written = dump(&opts, Path::new("stages"), "user.proto", input).unwrap();
+        assert_eq!(written.len(), 5);
+
+        let tokens = files
+            .read_to_string(Path::new("stages/01-tokens.txt"))
+            .unwrap();
+        assert!(tokens.starts_with("1:1 ident syntax\n1:8 punct =\n1:10 string \"proto3\"\n"));
+        let model = files
+            .read_to_string(Path::new("stages/03-model.json"))
+            .unwrap();
+        assert!(model.contains("\"common/date.proto\""));
+        assert!(model.contains("\"name\": \"common.Date\""));
+        let output = files
+            .read_to_string(Path::new("stages/04-output.ts"))
+            .unwrap();
+        assert!(output.contains("birthday: DateSchema"));
+
+        let err = dump(
+            &opts,
+            Path::new("broken"),
+            "user.proto",
+            "syntax = \"proto3\";\nmessage {",
+        )
+        .unwrap_err();
+        let error = files
+            .read_to_string(Path::new("broken/02-ast.error.txt"))
+            .unwrap();
+        assert_eq!(error, format!("{}\n", err));
+        assert!(files
+            .read_to_string(Path::new("broken/01-tokens.txt"))
+            .is_ok());
+    }
+}
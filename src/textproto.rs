@@ -0,0 +1,566 @@
+//! Parser of the protobuf [text format] (`.textproto` files) and validation
+//! of parsed messages against their message definitions.
+//!
+//! [text format]: https://protobuf.dev/reference/protobuf/textformat-spec/
+
+use nom::branch::alt;
+use nom::bytes::complete::{escaped, is_not, tag, take_while, take_while1};
+use nom::character::complete::{alpha1, alphanumeric1, anychar, char, multispace1};
+use nom::combinator::{map, opt, recognize};
+use nom::error::VerboseError;
+use nom::multi::{many0, many1, separated_list0};
+use nom::sequence::{delimited, pair, preceded};
+use nom::IResult;
+
+use crate::errors::PtError;
+use crate::parser::{unescape, Flag, Proto};
+use crate::registry::{self, declared, Declared, Kind, Registry};
+
+type ParserResult<'a, O> = IResult<&'a str, O, VerboseError<&'a str>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// string or bytes literal, adjacent literals are concatenated
+    Str(String),
+    /// number literal as written, e.g. `-12`, `0x1f` or `1.5e3f`
+    Number(String),
+    /// identifier like an enum value, `true` or `inf`, possibly negated
+    Ident(String),
+    List(Vec<Value>),
+    Msg(Vec<FieldValue>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldValue {
+    /// field name, extension names and `Any` type URLs are kept in brackets
+    pub name: String,
+    pub value: Value,
+    /// line the field starts at (1-based)
+    pub line: usize,
+}
+
+/// Parse the fields of a text format message.
+pub fn parse(file: &str, input: &str) -> Result<Vec<FieldValue>, PtError> {
+    let error = |rest: &str| {
+        PtError::ParsingError(format!(
+            "{}:{}: invalid text format",
+            file,
+            line_of(input, rest)
+        ))
+    };
+
+    match message(input, input) {
+        Ok(("", fields)) => Ok(fields),
+        Ok((rest, _)) => Err(error(rest)),
+        Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
+            Err(error(err.errors.first().map_or(input, |(rest, _)| rest)))
+        }
+        Err(nom::Err::Incomplete(_)) => Err(PtError::IncompleteParsing),
+    }
+}
+
+fn line_of(full: &str, rest: &str) -> usize {
+    full[..full.len() - rest.len()].matches('\n').count() + 1
+}
+
+/// Whitespace and `#` comments.
+fn ws(input: &str) -> ParserResult<'_, &str> {
+    recognize(many0(alt((
+        multispace1,
+        preceded(char('#'), take_while(|chr| chr != '\n')),
+    ))))(input)
+}
+
+fn identifier(input: &str) -> ParserResult<'_, &str> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0(alt((alphanumeric1, tag("_")))),
+    ))(input)
+}
+
+fn message<'a>(full: &'a str, input: &'a str) -> ParserResult<'a, Vec<FieldValue>> {
+    let mut fields = Vec::new();
+    let (mut input, _) = ws(input)?;
+
+    loop {
+        match field(full, input) {
+            Ok((rest, field)) => {
+                fields.push(field);
+                let (rest, _) = ws(rest)?;
+                let (rest, _) = opt(alt((char(','), char(';'))))(rest)?;
+                let (rest, _) = ws(rest)?;
+                input = rest;
+            }
+            Err(nom::Err::Error(_)) => return Ok((input, fields)),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn field<'a>(full: &'a str, input: &'a str) -> ParserResult<'a, FieldValue> {
+    let line = line_of(full, input);
+    let (input, name) = alt((
+        recognize(delimited(char('['), is_not("]"), char(']'))),
+        identifier,
+    ))(input)?;
+    let (input, _) = ws(input)?;
+    let (input, colon) = opt(char(':'))(input)?;
+    let (input, _) = ws(input)?;
+
+    // the colon may only be omitted for message values
+    let (input, value) = match colon {
+        Some(_) => value(full, input)?,
+        None => alt((|i| message_value(full, i), |i| list(full, i)))(input)?,
+    };
+
+    let name = name.split_whitespace().collect();
+    Ok((input, FieldValue { name, value, line }))
+}
+
+fn value<'a>(full: &'a str, input: &'a str) -> ParserResult<'a, Value> {
+    alt((
+        string,
+        |i| message_value(full, i),
+        |i| list(full, i),
+        scalar,
+    ))(input)
+}
+
+fn message_value<'a>(full: &'a str, input: &'a str) -> ParserResult<'a, Value> {
+    let (input, close) = alt((map(char('{'), |_| '}'), map(char('<'), |_| '>')))(input)?;
+    let (input, fields) = message(full, input)?;
+    let (input, _) = char(close)(input)?;
+    Ok((input, Value::Msg(fields)))
+}
+
+fn list<'a>(full: &'a str, input: &'a str) -> ParserResult<'a, Value> {
+    let (input, _) = char('[')(input)?;
+    let (input, _) = ws(input)?;
+    let (input, values) = separated_list0(char(','), delimited(ws, |i| value(full, i), ws))(input)?;
+    let (input, _) = char(']')(input)?;
+    Ok((input, Value::List(values)))
+}
+
+/// Number or identifier, both possibly negated.
+fn scalar(input: &str) -> ParserResult<'_, Value> {
+    let (input, minus) = opt(char('-'))(input)?;
+    let (input, _) = ws(input)?;
+    let sign = if minus.is_some() { "-" } else { "" };
+
+    alt((
+        map(number, move |num| Value::Number(format!("{}{}", sign, num))),
+        map(identifier, move |ident| {
+            Value::Ident(format!("{}{}", sign, ident))
+        }),
+    ))(input)
+}
+
+fn number(input: &str) -> ParserResult<'_, &str> {
+    let digits = |i| take_while1(|chr: char| chr.is_ascii_digit())(i);
+    let (rest, _) = alt((digits, recognize(pair(char('.'), digits))))(input)?;
+
+    // remaining digits, fraction, exponent (including its sign), suffix
+    let mut end = input.len() - rest.len();
+    let bytes = input.as_bytes();
+    while end < bytes.len() {
+        let chr = bytes[end] as char;
+        let exponent_sign = (chr == '-' || chr == '+')
+            && matches!(bytes[end - 1], b'e' | b'E')
+            && !input[..end].starts_with("0x");
+        if chr.is_ascii_alphanumeric() || chr == '.' || exponent_sign {
+            end += 1;
+        } else {
+            break;
+        }
+    }
+    Ok((&input[end..], &input[..end]))
+}
+
+fn string(input: &str) -> ParserResult<'_, Value> {
+    let literal = |quote: char, not: &'static str| {
+        delimited(
+            char(quote),
+            opt(escaped(is_not(not), '\\', anychar)),
+            char(quote),
+        )
+    };
+    let (input, parts) = many1(delimited(
+        ws,
+        alt((literal('"', "\\\"\n"), literal('\'', "\\'\n"))),
+        ws,
+    ))(input)?;
+
+    let value = parts
+        .into_iter()
+        .map(|part| unescape(part.unwrap_or("")))
+        .collect();
+    Ok((input, Value::Str(value)))
+}
+
+/// Problem of a text format message found by `validate`.
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Check the parsed fields against the definition of the fully-qualified
+/// message type: unknown fields, values not matching the field types, fields
+/// given more than once and missing required fields. Extension fields and
+/// expanded `Any` values are not checked.
+pub fn validate(
+    protos: &[Proto],
+    message: &str,
+    fields: &[FieldValue],
+) -> Result<Vec<Issue>, PtError> {
+    let registry = Registry::new(protos);
+    if registry::find_message(protos, message).is_none() {
+        return Err(registry.unresolved(message, ""));
+    }
+
+    let mut validator = Validator {
+        protos,
+        registry,
+        issues: Vec::new(),
+    };
+    validator.message(message, fields, 1);
+    Ok(validator.issues)
+}
+
+struct Validator<'a> {
+    protos: &'a [Proto],
+    registry: Registry,
+    issues: Vec<Issue>,
+}
+
+impl Validator<'_> {
+    fn issue(&mut self, line: usize, message: String) {
+        self.issues.push(Issue { line, message });
+    }
+
+    fn message(&mut self, full_name: &str, fields: &[FieldValue], line: usize) {
+        let Some(msg) = registry::find_message(self.protos, full_name) else {
+            self.issue(line, format!("unknown message type {}", full_name));
+            return;
+        };
+        let mut decls = Vec::new();
+        declared(&msg.fields, None, &mut decls);
+
+        let mut seen: Vec<&Declared> = Vec::new();
+        for field in fields.iter().filter(|field| !field.name.starts_with('[')) {
+            let Some(decl) = decls.iter().find(|decl| decl.name == field.name) else {
+                self.issue(
+                    field.line,
+                    format!("unknown field {} of {}", field.name, full_name),
+                );
+                continue;
+            };
+            let repeated = matches!(decl.flag, Flag::Repeated);
+
+            if !repeated && seen.iter().any(|other| other.name == decl.name) {
+                self.issue(
+                    field.line,
+                    format!("field {} is given more than once", field.name),
+                );
+            } else if let Some(other) = seen.iter().find(|other| {
+                other.name != decl.name && decl.oneof.is_some() && other.oneof == decl.oneof
+            }) {
+                self.issue(
+                    field.line,
+                    format!(
+                        "fields {} and {} of oneof {} are both set",
+                        other.name,
+                        decl.name,
+                        decl.oneof.unwrap_or_default()
+                    ),
+                );
+            }
+            seen.push(decl);
+
+            let values = match &field.value {
+                Value::List(values) if repeated => values.iter().collect(),
+                Value::List(_) => {
+                    self.issue(field.line, format!("field {} is not repeated", field.name));
+                    continue;
+                }
+                value => vec![value],
+            };
+            for value in values {
+                match decl.key_type {
+                    Some(key_type) => self.map_entry(decl, key_type, value, full_name, field.line),
+                    None => self.value(decl.name, decl.field_type, value, full_name, field.line),
+                }
+            }
+        }
+
+        for decl in &decls {
+            if matches!(decl.flag, Flag::Required)
+                && !seen.iter().any(|seen| seen.name == decl.name)
+            {
+                self.issue(
+                    line,
+                    format!("missing required field {} of {}", decl.name, full_name),
+                );
+            }
+        }
+    }
+
+    fn map_entry(
+        &mut self,
+        decl: &Declared,
+        key_type: &str,
+        value: &Value,
+        scope: &str,
+        line: usize,
+    ) {
+        let Value::Msg(fields) = value else {
+            self.issue(line, format!("expected map entry for {}", decl.name));
+            return;
+        };
+        for field in fields {
+            match field.name.as_str() {
+                "key" => self.value("key", key_type, &field.value, scope, field.line),
+                "value" => self.value("value", decl.field_type, &field.value, scope, field.line),
+                other => self.issue(
+                    field.line,
+                    format!("unknown field {} of map entry {}", other, decl.name),
+                ),
+            }
+        }
+    }
+
+    fn value(&mut self, name: &str, field_type: &str, value: &Value, scope: &str, line: usize) {
+        if registry::is_scalar(field_type) {
+            if !valid_scalar(field_type, value) {
+                self.issue(
+                    line,
+                    format!(
+                        "invalid {} value {} for {}",
+                        field_type,
+                        describe(value),
+                        name
+                    ),
+                );
+            }
+            return;
+        }
+
+        let Some(ptype) = self.registry.resolve(field_type, scope) else {
+            self.issue(line, format!("unknown type {} of {}", field_type, name));
+            return;
+        };
+        let full_name = ptype.full_name.clone();
+
+        match (ptype.kind, value) {
+            (Kind::Message, Value::Msg(fields)) => self.message(&full_name, fields, line),
+            (Kind::Enum, Value::Ident(ident)) => {
+                let known = registry::find_enum(self.protos, &full_name).is_some_and(|e| {
+                    e.values.iter().any(|value| {
+                        matches!(value, crate::parser::EnumValue::Single { name, .. } if name == ident)
+                    })
+                });
+                if !known {
+                    self.issue(
+                        line,
+                        format!("unknown value {} of enum {} for {}", ident, full_name, name),
+                    );
+                }
+            }
+            (Kind::Enum, Value::Number(num)) if integer(num).is_some() => (),
+            (kind, value) => self.issue(
+                line,
+                format!(
+                    "invalid value {} for {} of {} {}",
+                    describe(value),
+                    name,
+                    match kind {
+                        Kind::Message => "message",
+                        Kind::Enum => "enum",
+                    },
+                    full_name
+                ),
+            ),
+        }
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Str(str) => format!("{:?}", str),
+        Value::Number(value) | Value::Ident(value) => value.clone(),
+        Value::List(_) => "[...]".to_string(),
+        Value::Msg(_) => "{...}".to_string(),
+    }
+}
+
+/// Value of a decimal, hex or octal integer literal.
+fn integer(num: &str) -> std::option::Option<i128> {
+    let (negative, digits) = match num.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, num),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i128::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i128::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn valid_scalar(field_type: &str, value: &Value) -> bool {
+    let range = |min: i128, max: i128| match value {
+        Value::Number(num) => integer(num).is_some_and(|value| (min..=max).contains(&value)),
+        _ => false,
+    };
+
+    match field_type {
+        "string" | "bytes" => matches!(value, Value::Str(_)),
+        "bool" => match value {
+            Value::Ident(ident) => matches!(
+                ident.as_str(),
+                "true" | "false" | "True" | "False" | "t" | "f"
+            ),
+            Value::Number(num) => num == "0" || num == "1",
+            _ => false,
+        },
+        "double" | "float" => match value {
+            Value::Number(num) => {
+                integer(num).is_some() || num.trim_end_matches(['f', 'F']).parse::<f64>().is_ok()
+            }
+            Value::Ident(ident) => matches!(
+                ident.trim_start_matches('-').to_ascii_lowercase().as_str(),
+                "inf" | "infinity" | "nan"
+            ),
+            _ => false,
+        },
+        "int32" | "sint32" | "sfixed32" => range(i32::MIN.into(), i32::MAX.into()),
+        "uint32" | "fixed32" => range(0, u32::MAX.into()),
+        "int64" | "sint64" | "sfixed64" => range(i64::MIN.into(), i64::MAX.into()),
+        "uint64" | "fixed64" => range(0, u64::MAX.into()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, validate, FieldValue, Issue, Value};
+    use crate::parser;
+    use crate::Opts;
+
+    #[test]
+    fn parse_text_format() {
+        let fields = parse(
+            "config.textproto",
+            r#"# service config
+name: "api" ' server'
+port: 0x1f90
+ratio: -1.5e-3f
+mode: FAST
+tags: ["a", "b\x41\101"]
+limits { max: 10 }
+[foo.v1.ext] < enabled: true >;
+"#,
+        )
+        .unwrap();
+
+        let field = |name: &str, value: Value, line: usize| FieldValue {
+            name: name.to_string(),
+            value,
+            line,
+        };
+        assert_eq!(
+            fields,
+            vec![
+                field("name", Value::Str("api server".to_string()), 2),
+                field("port", Value::Number("0x1f90".to_string()), 3),
+                field("ratio", Value::Number("-1.5e-3f".to_string()), 4),
+                field("mode", Value::Ident("FAST".to_string()), 5),
+                field(
+                    "tags",
+                    Value::List(vec![
+                        Value::Str("a".to_string()),
+                        Value::Str("bAA".to_string())
+                    ]),
+                    6
+                ),
+                field(
+                    "limits",
+                    Value::Msg(vec![field("max", Value::Number("10".to_string()), 7)]),
+                    7
+                ),
+                field(
+                    "[foo.v1.ext]",
+                    Value::Msg(vec![field("enabled", Value::Ident("true".to_string()), 8)]),
+                    8
+                ),
+            ]
+        );
+
+        assert_eq!(
+            parse("config.textproto", "name: \"api\"\nport: }")
+                .unwrap_err()
+                .to_string(),
+            "proto parsing failed: config.textproto:2: invalid text format"
+        );
+    }
+
+    #[test]
+    fn validate_text_format() {
+        let proto = parser::parse(
+            &Opts::default(),
+            "config.proto",
+            r#"syntax = "proto2";
+package foo.v1;
+message Config {
+  required string name = 1;
+  optional uint32 port = 2;
+  repeated Mode modes = 3;
+  map<string, Limit> limits = 4;
+  oneof backend {
+    string host = 5;
+    string socket = 6;
+  }
+  enum Mode {
+    FAST = 0;
+    SAFE = 1;
+  }
+  message Limit {
+    optional int32 max = 1;
+  }
+}"#,
+        )
+        .unwrap();
+
+        let fields = parse(
+            "config.textproto",
+            r#"port: -1
+port: 80
+modes: [FAST, SLOW]
+limits { key: "cpu" value { max: "high" } }
+host: "localhost"
+socket: "/tmp/socket"
+unknown: 1
+"#,
+        )
+        .unwrap();
+
+        let issue = |line: usize, message: &str| Issue {
+            line,
+            message: message.to_string(),
+        };
+        assert_eq!(
+            validate(&[proto], "foo.v1.Config", &fields).unwrap(),
+            vec![
+                issue(1, "invalid uint32 value -1 for port"),
+                issue(2, "field port is given more than once"),
+                issue(3, "unknown value SLOW of enum foo.v1.Config.Mode for modes"),
+                issue(4, "invalid int32 value \"high\" for max"),
+                issue(6, "fields host and socket of oneof backend are both set"),
+                issue(7, "unknown field unknown of foo.v1.Config"),
+                issue(1, "missing required field name of foo.v1.Config"),
+            ]
+        );
+    }
+}
@@ -1,45 +1,621 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
 
-use crate::errors::PtError;
-use crate::parser::{Elem, Enum, EnumValue, Field, Flag, Msg, Proto};
+use crate::errors::{Location, PtError};
+use crate::fingerprint::{enum_fingerprint, fingerprint};
+use crate::imports;
+#[cfg(feature = "interfaces")]
+use crate::interfaces;
+use crate::json;
+use crate::parser::{
+    self, Elem, Enum, EnumValue, Field, Flag, ImportModifier, Msg, Number, OptionValue, Proto,
+    ServiceNode,
+};
+use crate::registry::{self, ProtoType, Registry};
+#[cfg(any(feature = "effect", feature = "typebox", feature = "yup"))]
+use crate::schemas;
+use crate::verify::{Property, Shape, Shapes};
+use crate::{Bytes, EnumStyle, Int64, MethodCase, Opts, Target, TsLibTarget, ZodVersion};
 
 const DEFAULT_CAPACITY: usize = 10 * 1024;
 
-pub fn to_schema(proto: &Proto) -> Result<String, PtError> {
-    let ctx = Context::new(proto);
-    let mut str = String::with_capacity(DEFAULT_CAPACITY);
+/// Maximum number of types used of an imported file that are generated into
+/// the importing module instead of being imported (see `--inline-deps`).
+const INLINE_LIMIT: usize = 2;
 
-    str.push_str("//\n");
-    str.push_str("// Code generated by protots - DO NOT EDIT\n");
-    str.push_str(format!("// Source: {}\n", proto.file).as_str());
-    str.push_str("//\n");
-    str.push_str("\n");
-    str.push_str("import { z } from \"zod\";");
-    str.push_str("\n");
-    str.push_str("\n");
+/// Local helper wrapping a schema's parsing so that failures are reported to
+/// the hook configured via `--validation-hook`.
+const VALIDATION_HOOK: &str = r#"function withValidationHook<T extends z.ZodTypeAny>(typeName: string, schema: T): T {
+  const safeParse = schema.safeParse.bind(schema);
+  schema.safeParse = ((data: unknown, params?: Partial<z.ParseParams>) => {
+    const result = safeParse(data, params);
+    if (!result.success) {
+      onValidationFailure(typeName, result.error.issues);
+    }
+    return result;
+  }) as T["safeParse"];
+  return schema;
+}
+
+"#;
+
+/// Variant of `VALIDATION_HOOK` for zod 4, whose `parse` does not call
+/// `safeParse` anymore and which dropped `z.ParseParams`.
+const VALIDATION_HOOK_V4: &str = r#"function withValidationHook<T extends z.ZodType>(typeName: string, schema: T): T {
+  const safeParse = schema.safeParse.bind(schema);
+  schema.safeParse = ((data: unknown, params?: Parameters<T["safeParse"]>[1]) => {
+    const result = safeParse(data, params);
+    if (!result.success) {
+      onValidationFailure(typeName, result.error.issues);
+    }
+    return result;
+  }) as T["safeParse"];
+  schema.parse = ((data: unknown, params?: Parameters<T["parse"]>[1]) => {
+    const result = schema.safeParse(data, params);
+    if (!result.success) {
+      throw result.error;
+    }
+    return result.data;
+  }) as T["parse"];
+  return schema;
+}
+
+"#;
+
+/// Runtime helper module the generated service clients are built upon.
+const RUNTIME: &str = r#"//
+// Code generated by protots - DO NOT EDIT
+//
+
+export interface MethodInfo {
+  /** fully-qualified name of the service */
+  service: string;
+  method: string;
+  /** path of the method, e.g. "/foo.v1.Users/GetUser" */
+  path: string;
+  /** method metadata like idempotency level */
+  options?: Record<string, unknown>;
+}
+
+export type Request = (method: MethodInfo, request: unknown) => Promise<unknown>;
+
+export type Interceptor = (next: Request) => Request;
+
+export interface Transport {
+  request: Request;
+  /** applied in order, the first interceptor sees a request first */
+  interceptors?: Interceptor[];
+}
+
+export function call<Res>(
+  transport: Transport,
+  method: MethodInfo,
+  request: unknown,
+  parse: (value: unknown) => Res,
+): Promise<Res> {
+  const send = (transport.interceptors ?? []).reduceRight(
+    (next: Request, interceptor: Interceptor) => interceptor(next),
+    transport.request,
+  );
+  return send(method, request).then(parse);
+}
+
+export function warnDeprecated(method: string): void {
+  const env = (globalThis as { process?: { env?: Record<string, string | undefined> } })
+    .process?.env;
+  if (env?.NODE_ENV !== "production") {
+    console.warn(`${method} is deprecated`);
+  }
+}
+
+/** JSON representation of a `google.protobuf.Any` */
+export interface AnyMessage {
+  "@type": string;
+  [field: string]: unknown;
+}
+
+export interface MessageSchema {
+  parse(value: unknown): unknown;
+}
+
+const schemas: Record<string, MessageSchema> = {};
+
+/** register the message schemas of a generated module by their fully-qualified names */
+export function registerSchemas(entries: Record<string, MessageSchema>): void {
+  Object.keys(entries).forEach((name) => {
+    schemas[name] = entries[name];
+  });
+}
+
+/** fully-qualified name of a type URL, e.g. "foo.v1.User" of "type.googleapis.com/foo.v1.User" */
+export function typeName(typeUrl: string): string {
+  return typeUrl.substring(typeUrl.lastIndexOf("/") + 1);
+}
+
+function registered(name: string): MessageSchema {
+  const schema = schemas[name];
+  if (!schema) {
+    throw new Error(`unknown message type ${name}`);
+  }
+  return schema;
+}
+
+/** wrap the message of the given type, validated against its schema */
+export function packAny(
+  message: object,
+  name: string,
+  prefix: string = "type.googleapis.com/",
+): AnyMessage {
+  registered(name).parse(message);
+  return Object.assign({ "@type": prefix + name }, message);
+}
+
+/** unwrap the message of a registered type, validated against its schema */
+export function unpackAny(any: AnyMessage): { name: string; message: unknown } {
+  const name = typeName(any["@type"]);
+  const fields: Record<string, unknown> = {};
+  Object.keys(any).forEach((key) => {
+    if (key !== "@type") {
+      fields[key] = any[key];
+    }
+  });
+  return { name, message: registered(name).parse(fields) };
+}
+"#;
+
+/// JSON helpers of the runtime module for 64 bit integers generated as
+/// `bigint`, which `JSON.stringify` throws on and `JSON.parse` rounds.
+const RUNTIME_BIGINT_JSON: &str = r#"
+/** `JSON.stringify` writing bigints as decimal strings like the proto3 JSON mapping */
+export function jsonStringify(value: unknown, space?: string | number): string {
+  return JSON.stringify(
+    value,
+    (_key: string, value: unknown) => (typeof value === "bigint" ? value.toString() : value),
+    space,
+  );
+}
+
+/**
+ * `JSON.parse` of a message validated by its schema. Integers beyond the safe
+ * range are passed to the schema as exact decimal strings where the runtime
+ * exposes the source text to revivers.
+ */
+export function jsonParse<T>(text: string, schema: { parse(value: unknown): T }): T {
+  return schema.parse(
+    JSON.parse(text, (_key: string, value: unknown, context?: { source?: string }) =>
+      typeof value === "number" &&
+      !Number.isSafeInteger(value) &&
+      context?.source !== undefined &&
+      /^-?[0-9]+$/.test(context.source)
+        ? context.source
+        : value,
+    ),
+  );
+}
+"#;
+
+/// Runtime helper module (see `RUNTIME`) for the language level targeted
+/// (see `--ts-lib-target`): ES2017 lacks optional chaining, nullish
+/// coalescing and `globalThis`.
+pub fn runtime(opts: &Opts) -> String {
+    match opts.ts_lib_target {
+        TsLibTarget::Es2020 => format!("{}{}", RUNTIME, RUNTIME_BIGINT_JSON),
+        TsLibTarget::Es2017 => RUNTIME
+            .replace("(transport.interceptors ?? [])", "(transport.interceptors || [])")
+            .replace(
+                r#"  const env = (globalThis as { process?: { env?: Record<string, string | undefined> } })
+    .process?.env;
+  if (env?.NODE_ENV !== "production") {"#,
+                r#"  const env = typeof process === "undefined" ? undefined : process.env;
+  if (!env || env.NODE_ENV !== "production") {"#,
+            )
+            .replace(
+                "export function warnDeprecated",
+                "declare const process: { env?: Record<string, string | undefined> } | undefined;\n\nexport function warnDeprecated",
+            ),
+    }
+}
+
+/// Generate the typescript module of the given proto file in the targeted
+/// flavor (see `--target`). All errors of the generation phase are reported
+/// together, in lenient mode (see `--lenient`) as warnings (see
+/// `Opts::messages`) along with the code of the types that could be
+/// generated.
+pub fn to_schema(opts: &Opts, proto: &Proto, deps: &[Proto]) -> Result<String, PtError> {
+    emit(opts, proto, deps).map(|(schema, _)| schema)
+}
+
+/// Shapes of the types the module of the target (see `Opts::target`) declares
+/// for the given proto file, as reported by the backend (see `verify`).
+pub(crate) fn to_shapes(opts: &Opts, proto: &Proto, deps: &[Proto]) -> Result<Shapes, PtError> {
+    emit(opts, proto, deps).map(|(_, shapes)| shapes)
+}
+
+fn emit(opts: &Opts, proto: &Proto, deps: &[Proto]) -> Result<(String, Shapes), PtError> {
+    let (schema, errors, shapes) = match opts.target {
+        Target::Zod => generate_shapes(opts, proto, deps),
+        #[cfg(feature = "interfaces")]
+        Target::Interfaces => interfaces::generate_shapes(opts, proto, deps),
+        #[cfg(feature = "effect")]
+        Target::Effect => schemas::generate_shapes(&schemas::EFFECT, opts, proto, deps),
+        #[cfg(feature = "typebox")]
+        Target::TypeBox => schemas::generate_shapes(&schemas::TYPEBOX, opts, proto, deps),
+        #[cfg(feature = "yup")]
+        Target::Yup => schemas::generate_shapes(&schemas::YUP, opts, proto, deps),
+        #[allow(unreachable_patterns)]
+        target => {
+            return Err(PtError::InvalidArgument(format!(
+                "target {} is not enabled in this build",
+                target.name()
+            )))
+        }
+    };
+    if errors.is_empty() {
+        return Ok((schema, shapes));
+    }
+    if !opts.lenient {
+        return Err(PtError::Generation(errors));
+    }
+    for error in errors {
+        opts.messages.push(format!("warning: {}", error));
+    }
+    Ok((schema, shapes))
+}
+
+/// Generate the zod schemas of the given proto file, skipping the
+/// types, fields and methods that failed to generate. The errors returned
+/// carry the location they occurred at (see `PtError::Located`).
+pub fn generate(opts: &Opts, proto: &Proto, deps: &[Proto]) -> (String, Vec<PtError>) {
+    let (output, errors, _) = generate_shapes(opts, proto, deps);
+    (output, errors)
+}
+
+/// Generate the zod schemas (see `generate`) along with the shapes of the
+/// types exported.
+pub(crate) fn generate_shapes(
+    opts: &Opts,
+    proto: &Proto,
+    deps: &[Proto],
+) -> (String, Vec<PtError>, Shapes) {
+    let registry = Registry::new([proto].into_iter().chain(deps));
+    let mut pass = generate_pass(opts, proto, deps, &registry, &BTreeSet::new());
+
+    // the files that contribute few enough types are inlined, which in turn
+    // may reference further types of them, so files are dropped until the
+    // remaining ones stay within the limit; each pass only removes files
+    if opts.inline_deps {
+        let mut inlined = BTreeSet::new();
+        let mut first = true;
+        loop {
+            let candidates = pass
+                .used
+                .iter()
+                .filter(|(file, _)| first || inlined.contains(*file))
+                .filter(|(_, types)| types.len() <= INLINE_LIMIT)
+                .filter(|(_, types)| !types.iter().any(|ptype| clashes(&registry, proto, ptype)))
+                .map(|(file, _)| file.clone())
+                .collect::<BTreeSet<_>>();
+            if candidates == inlined {
+                break;
+            }
+            inlined = candidates;
+            first = false;
+            pass = generate_pass(opts, proto, deps, &registry, &inlined);
+        }
+    }
 
+    // required fields are generated just like singular ones, which may
+    // surprise proto2 users expecting stricter schemas
+    if let Some(first) = pass.required.first().filter(|_| !opts.strict_required) {
+        opts.messages.push(format!(
+            "warning: {} `required` field(s) generated as non-optional fields of objects \
+             accepting unknown keys, first {}; see --strict-required",
+            pass.required.len(),
+            first
+        ));
+    }
+
+    (pass.output, pass.errors, pass.shapes)
+}
+
+/// Whether the inlined type would be declared under the same name as a type
+/// of the importing file.
+fn clashes(registry: &Registry, proto: &Proto, full_name: &str) -> bool {
+    let Some(inlined) = registry.get(full_name) else {
+        return true;
+    };
+    registry
+        .types()
+        .any(|ptype| ptype.file == proto.file && ptype.ts_name == inlined.ts_name)
+}
+
+/// Output of a single generation of a file, inlining the types used of the
+/// given files.
+struct Pass {
+    output: String,
+    errors: Vec<PtError>,
+    required: Vec<Location>,
+    /// types referenced of other files, grouped by file, not counting the
+    /// nested types of referenced messages
+    used: BTreeMap<String, BTreeSet<String>>,
+    shapes: Shapes,
+}
+
+fn generate_pass(
+    opts: &Opts,
+    proto: &Proto,
+    deps: &[Proto],
+    registry: &Registry,
+    inlined: &BTreeSet<String>,
+) -> Pass {
+    let ctx = Context::new(opts, proto, registry, inlined);
+    let scope = proto.package().unwrap_or("");
+    let mut body = String::with_capacity(DEFAULT_CAPACITY);
+
+    if let Some(hook) = &opts.validation_hook {
+        ctx.import(hook, "onValidationFailure");
+    }
+
+    // the route tables reference the schemas right away, so they follow all
+    // messages
+    let mut routes = String::new();
     for elem in &proto.elems {
         match elem {
-            Elem::Message(msg) => str.push_str(format_msg(&ctx, msg, None)?.as_str()),
-            Elem::Enum(e) => str.push_str(format_enum(&ctx, e, None)?.as_str()),
+            Elem::Message(msg) => {
+                let location = Location {
+                    line: msg.span.line,
+                    ..Location::new("message", registry::qualify(scope, &msg.name))
+                };
+                if let Some(value) = ctx.recover(location, format_msg(&ctx, msg, scope)) {
+                    body.push_str(&value);
+                }
+            }
+            Elem::Enum(e) => {
+                let location = Location {
+                    line: e.span.line,
+                    ..Location::new("enum", registry::qualify(scope, &e.name))
+                };
+                if let Some(value) = ctx.recover(location, format_enum(&ctx, e, scope)) {
+                    body.push_str(&value);
+                }
+            }
+            Elem::Service { name, nodes, .. } => {
+                let location = Location::new("service", registry::qualify(scope, name));
+                let metadata = ctx
+                    .recover(location, format_service_metadata(&ctx, name, nodes, scope))
+                    .unwrap_or_default();
+                body.push_str(metadata.as_str());
+                if ctx.opts.method_paths {
+                    body.push_str(&format_paths(name, nodes, scope));
+                }
+                if ctx.opts.routes {
+                    routes.push_str(&format_routes(&ctx, name, nodes, scope));
+                }
+                if ctx.opts.clients {
+                    body.push_str(&format_client(
+                        &ctx,
+                        name,
+                        nodes,
+                        scope,
+                        !metadata.is_empty(),
+                    ));
+                }
+            }
             _ => (),
         }
     }
 
-    Ok(str)
+    body.push_str(&routes);
+    if opts.any_helpers {
+        body.push_str(&format_registration(&ctx));
+    }
+
+    // inlined types are declared before the types of the file, the ones
+    // referenced by other inlined types before those
+    let mut declarations = Vec::new();
+    let mut generated = BTreeSet::new();
+    loop {
+        let next = ctx
+            .inline
+            .borrow()
+            .iter()
+            .find(|full_name| !generated.contains(*full_name))
+            .cloned();
+        let Some(full_name) = next else {
+            break;
+        };
+        generated.insert(full_name.clone());
+
+        let (scope, _) = full_name.rsplit_once('.').unwrap_or(("", &full_name));
+        let kind = match registry.get(&full_name).map(|ptype| &ptype.kind) {
+            Some(registry::Kind::Enum) => "enum",
+            _ => "message",
+        };
+        let result = match registry::find_message(deps, &full_name) {
+            Some(msg) => format_msg(&ctx, msg, scope),
+            None => registry::find_enum(deps, &full_name)
+                .ok_or_else(|| PtError::ProtobufTypeNotFound(full_name.clone()))
+                .and_then(|e| format_enum(&ctx, e, scope)),
+        };
+        if let Some(value) = ctx.recover(Location::new(kind, full_name.clone()), result) {
+            declarations.push((full_name, value));
+        }
+    }
+    // nested types are declared along with their parents already
+    let used = ctx.used.take();
+    let used = used
+        .into_iter()
+        .map(|(file, types)| {
+            let outermost = types
+                .iter()
+                .filter(|full_name| !has_ancestor(&types, full_name))
+                .cloned()
+                .collect::<BTreeSet<_>>();
+            (file, outermost)
+        })
+        .collect::<BTreeMap<_, _>>();
+    let declared = declarations
+        .iter()
+        .map(|(full_name, _)| full_name.clone())
+        .collect::<BTreeSet<_>>();
+    let body = declarations
+        .iter()
+        .rev()
+        .filter(|(full_name, _)| !has_ancestor(&declared, full_name))
+        .map(|(_, value)| value.as_str())
+        .chain([body.as_str()])
+        .collect::<String>();
+
+    let mut str = String::with_capacity(body.len() + 512);
+
+    str.push_str(&header(opts, proto));
+    str.push_str("import { z } from \"zod\";");
+    str.push('\n');
+
+    for (module, names) in ctx.imports.borrow().iter() {
+        str.push_str(
+            format!(
+                "import {{ {} }} from \"{}\";\n",
+                names.iter().cloned().collect::<Vec<_>>().join(", "),
+                module
+            )
+            .as_str(),
+        );
+    }
+
+    str.push_str(&public_exports(proto));
+    str.push('\n');
+
+    if opts.validation_hook.is_some() {
+        str.push_str(match opts.zod_version {
+            ZodVersion::V3 => VALIDATION_HOOK,
+            ZodVersion::V4 => VALIDATION_HOOK_V4,
+        });
+    }
+
+    str.push_str(&body);
+
+    Pass {
+        output: str,
+        errors: ctx.errors.take(),
+        required: ctx.required.take(),
+        used,
+        shapes: ctx.shapes.take(),
+    }
+}
+
+/// Whether a parent message of the given type is among the types.
+fn has_ancestor(types: &BTreeSet<String>, full_name: &str) -> bool {
+    full_name
+        .match_indices('.')
+        .any(|(idx, _)| types.contains(&full_name[..idx]))
+}
+
+/// Comment atop generated modules: the banner (see `--banner`) followed by
+/// the notice that the module is generated and the file it is generated of.
+pub(crate) fn header(opts: &Opts, proto: &Proto) -> String {
+    let mut str = String::with_capacity(256);
+
+    let compat = &opts.compat;
+    let source = if compat.source_import_name || opts.file.is_empty() {
+        &proto.file
+    } else {
+        &opts.file
+    };
+
+    if let Some(template) = &opts.banner {
+        str.push_str(&banner(template, source));
+    }
+    str.push_str("//\n");
+    if compat.version_stamp {
+        str.push_str(
+            format!(
+                "// Code generated by protots v{} - DO NOT EDIT\n",
+                compat.version
+            )
+            .as_str(),
+        );
+    } else {
+        str.push_str("// Code generated by protots - DO NOT EDIT\n");
+    }
+    str.push_str(format!("// Source: {}\n", source).as_str());
+    str.push_str("//\n");
+    str.push('\n');
+    str
+}
+
+/// Re-exports of the modules of the public imports, whose types are part of
+/// the file's API just like in protobuf.
+pub(crate) fn public_exports(proto: &Proto) -> String {
+    let mut str = String::new();
+    for elem in &proto.elems {
+        if let Elem::Import {
+            name,
+            modifier: ImportModifier::Public,
+            ..
+        } = elem
+        {
+            str.push_str(
+                format!(
+                    "export * from \"{}\";\n",
+                    imports::module_path(&proto.file, name)
+                )
+                .as_str(),
+            );
+        }
+    }
+    str
+}
+
+/// License header or banner put atop generated files (see `--banner`), with
+/// `{year}` replaced by the current year and `{file}` by the source file.
+/// Templates not written as comments already are commented line by line.
+pub fn banner(template: &str, file: &str) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() as i64);
+    let timestamp = json::format_timestamp(now, 0);
+    let year = timestamp.split('-').next().unwrap_or_default();
+
+    let text = template
+        .trim_end()
+        .replace("{year}", year)
+        .replace("{file}", file);
+    let commented = text.trim_start().starts_with(['/', '*']);
+
+    let mut str = String::with_capacity(text.len() + 64);
+    for line in text.lines() {
+        match (commented, line.trim_end()) {
+            (true, line) => str.push_str(line),
+            (false, "") => str.push_str("//"),
+            (false, line) => {
+                str.push_str("// ");
+                str.push_str(line);
+            }
+        }
+        str.push('\n');
+    }
+    str.push('\n');
+    str
 }
 
-fn format_msg(ctx: &Context, msg: &Msg, parent: Option<&ProtoType>) -> Result<String, PtError> {
+fn format_msg(ctx: &Context, msg: &Msg, scope: &str) -> Result<String, PtError> {
     let mut sub_messages = Vec::new();
     let mut fields = Vec::new();
+    let mut properties = Vec::new();
 
-    let ptype = ctx
-        .get(&msg.name, parent)
-        .ok_or(PtError::ProtobufTypeNotFound(msg.name.clone()))?;
+    let ptype = ctx.declared(&msg.name, scope)?;
     let message_name = &ptype.ts_name;
 
+    let strict = ctx.opts.strict_required && has_required(&msg.fields);
     for field in &msg.fields {
-        if let Some(value) = format_field(ctx, field, Some(ptype), &mut sub_messages)? {
+        let location = field_location(&ptype.full_name, "", field);
+        if has_required(std::slice::from_ref(field)) {
+            ctx.required.borrow_mut().push(location.clone());
+        }
+        let value = format_field(ctx, field, &ptype.full_name, &mut sub_messages);
+        if let Some((property, value)) = ctx.recover(location, value).flatten() {
+            properties.push(property);
             fields.push(value);
         }
     }
@@ -50,305 +626,1334 @@ fn format_msg(ctx: &Context, msg: &Msg, parent: Option<&ProtoType>) -> Result<St
         str.push_str(&sub_msg);
     }
 
-    str.push_str(format!("export const {} = z.object({{\n", ptype.schema).as_str());
+    let export = ctx.export(&ptype.full_name);
+    if !export.is_empty() {
+        ctx.shapes
+            .borrow_mut()
+            .insert(ptype.ts_name.clone(), Shape::message(properties));
+    }
+    let deprecated = if msg.deprecated && ctx.opts.compat.deprecated_jsdoc {
+        "/** @deprecated */\n"
+    } else {
+        ""
+    };
+
+    // the first base schema matching is merged in (see `--base-schema`)
+    let base = ctx
+        .opts
+        .base_schemas
+        .iter()
+        .find(|base| base.matches(&msg.name, &ptype.full_name));
+    if let Some(base) = base {
+        ctx.import(&base.module, &base.schema);
+    }
+    // zod 4 deprecates `.merge()` and `.strict()` in favor of spreading the
+    // shape of the base schema and `z.strictObject`
+    let (open, close) = match (ctx.opts.zod_version, base) {
+        (ZodVersion::V3, Some(base)) => (format!("{}.merge(z.object({{", base.schema), "}))"),
+        (ZodVersion::V3, None) => ("z.object({".to_string(), "})"),
+        (ZodVersion::V4, _) if strict => ("z.strictObject({".to_string(), "})"),
+        (ZodVersion::V4, _) => ("z.object({".to_string(), "})"),
+    };
+    if let (ZodVersion::V4, Some(base)) = (ctx.opts.zod_version, base) {
+        fields.insert(0, format!("...{}.shape", base.schema));
+    }
+    let close = match (ctx.opts.zod_version, strict) {
+        (ZodVersion::V3, true) => close.replacen("})", "}).strict()", 1),
+        _ => close.to_string(),
+    };
+
+    str.push_str(deprecated);
+    if ctx.opts.validation_hook.is_some() {
+        str.push_str(
+            format!(
+                "{}const {} = withValidationHook(\"{}\", {}\n",
+                export, ptype.schema, ptype.full_name, open
+            )
+            .as_str(),
+        );
+    } else {
+        str.push_str(format!("{}const {} = {}\n", export, ptype.schema, open).as_str());
+    }
     for field in fields {
         str.push_str("  ");
         str.push_str(field.as_str());
         str.push_str(",\n");
     }
-    str.push_str("});\n\n");
+    if ctx.opts.validation_hook.is_some() {
+        str.push_str(&format!("{});\n\n", close));
+    } else {
+        str.push_str(&format!("{};\n\n", close));
+    }
 
+    str.push_str(deprecated);
     str.push_str(
         format!(
-            "export type {} = z.infer<typeof {}>;\n\n",
-            message_name, ptype.schema
+            "{}type {} = z.infer<typeof {}>;\n\n",
+            export, message_name, ptype.schema
         )
         .as_str(),
     );
 
+    if ctx.opts.ts_proto_compat {
+        str.push_str(&format_ts_proto_message(
+            ctx,
+            export,
+            message_name,
+            &ptype.schema,
+        ));
+    }
+    if ctx.opts.proto_names {
+        str.push_str(&format_proto_name(export, message_name, &ptype.full_name));
+    }
+    if ctx.opts.fingerprints {
+        str.push_str(&format_fingerprint(export, message_name, fingerprint(msg)));
+    }
+
+    ctx.messages.borrow_mut().push(ptype);
     Ok(str)
 }
 
+/// Property of the given field along with its schema.
 fn format_field(
     ctx: &Context,
     field: &Field,
-    parent: Option<&ProtoType>,
+    scope: &str,
     elements: &mut Vec<String>,
-) -> Result<Option<String>, PtError> {
+) -> Result<Option<(Property, String)>, PtError> {
     match field {
         Field::Single {
             name,
             field_type,
-            idx: _,
             flag,
-        } => Ok(Some(format!(
-            "{}: {}",
-            snake_to_camel(name),
-            flagged_field(type_name(ctx, &field_type, parent)?, flag)
-        ))),
+            options,
+            deprecated,
+            json_name,
+            ..
+        } => {
+            let rules = SizeRules::new(options);
+            let mut ts_type = type_name(ctx, field_type, scope)?;
+            if field_type == "string" {
+                ts_type.push_str(&rules.string);
+            }
+            let mut ts_type = flagged_field(&ts_type, flag);
+            if matches!(flag, Flag::Repeated) {
+                ts_type.push_str(&rules.items);
+            }
+
+            // the key of the JSON representation, which the schemas parse
+            let key = match json_name
+                .as_deref()
+                .filter(|_| ctx.opts.compat.json_name_keys)
+            {
+                Some(json_name) => property_key(json_name),
+                None => snake_to_camel(name),
+            };
+            let deprecated = *deprecated && ctx.opts.compat.deprecated_jsdoc;
+            let value = format!("{}{}: {}", jsdoc(deprecated), key, ts_type);
+            Ok(Some((Property::new(key, *flag == Flag::Optional), value)))
+        }
         Field::Map {
             name,
             key_type,
             value_type,
-            idx: _,
-        } => Ok(Some(format!(
-            "{}: z.record({}, {})",
-            snake_to_camel(name),
-            type_name(ctx, key_type, parent)?,
-            type_name(ctx, value_type, parent)?
-        ))),
-        Field::OneOf { name, fields } => Ok(Some(format!(
-            "{}: {}",
-            snake_to_camel(name),
-            format_oneof(ctx, fields, parent, elements)?
-        ))),
+            deprecated,
+            ..
+        } => {
+            let key = snake_to_camel(name);
+            let value = format!(
+                "{}{}: z.record({}, {})",
+                jsdoc(*deprecated && ctx.opts.compat.deprecated_jsdoc),
+                key,
+                map_key(ctx, key_type, scope)?,
+                type_name(ctx, value_type, scope)?
+            );
+            Ok(Some((Property::new(key, false), value)))
+        }
+        Field::OneOf { name, fields, .. } => {
+            let key = snake_to_camel(name);
+            Ok(format_oneof(ctx, name, fields, scope, elements)
+                .map(|oneof| (Property::new(&key, false), format!("{}: {}", key, oneof))))
+        }
         Field::SubMessage(msg) => {
-            elements.push(format_msg(ctx, msg, parent)?);
+            elements.push(format_msg(ctx, msg, scope)?);
             Ok(None)
         }
         Field::SubEnum(e) => {
-            elements.push(format_enum(ctx, e, parent)?);
+            elements.push(format_enum(ctx, e, scope)?);
             Ok(None)
         }
         _ => Ok(None),
     }
 }
 
+/// Deprecation notice put before the properties of deprecated fields.
+pub(crate) fn jsdoc(deprecated: bool) -> &'static str {
+    if deprecated {
+        "/** @deprecated */\n  "
+    } else {
+        ""
+    }
+}
+
+/// Schema of the oneof, if at least one of its cases could be generated.
 fn format_oneof(
     ctx: &Context,
-    oneof: &Vec<Field>,
-    parent: Option<&ProtoType>,
+    name: &str,
+    oneof: &[Field],
+    scope: &str,
     elements: &mut Vec<String>,
-) -> Result<String, PtError> {
-    let cases: Vec<_> = oneof
-        .iter()
-        .map(|case| format_field(ctx, case, parent, elements))
-        .collect::<Result<Vec<_>, _>>()?
-        .into_iter()
-        .flatten()
-        .map(|value| format!("z.object({{ {} }})", value))
-        .collect();
+) -> Option<String> {
+    let mut failed = false;
+    let mut cases = Vec::new();
+    for case in oneof {
+        match format_field(ctx, case, scope, elements) {
+            Ok(value) => cases.extend(value.map(|(_, value)| format!("z.object({{ {} }})", value))),
+            Err(error) => {
+                let prefix = format!("{}.", name);
+                ctx.fail(field_location(scope, &prefix, case), error);
+                failed = true;
+            }
+        }
+    }
+
+    if failed && cases.is_empty() {
+        return None;
+    }
 
     // z.union does not support single element lists
     if cases.len() == 1 {
         let single_field = &cases[0];
-        return Ok(single_field.to_string());
+        return Some(single_field.to_string());
     }
 
-    Ok(format!("z.union([{}])", cases.join(", ")))
+    Some(format!("z.union([{}])", cases.join(", ")))
+}
+
+/// Value unknown values of the enum fall back to, which is its zero value
+/// or, in the absence of one, its first value (see `Compat::catch_first_value`).
+fn enum_default<'a>(opts: &Opts, value: &'a Enum) -> Option<&'a str> {
+    value.default_value().filter(|name| {
+        opts.compat.catch_first_value
+            || value.values.iter().any(|value| {
+                matches!(value, EnumValue::Single { name: zero, idx: 0, .. } if zero == name)
+            })
+    })
 }
 
-fn format_enum(ctx: &Context, value: &Enum, parent: Option<&ProtoType>) -> Result<String, PtError> {
+fn format_enum(ctx: &Context, value: &Enum, scope: &str) -> Result<String, PtError> {
     let mut str = String::with_capacity(512);
-    let ptype = ctx
-        .get(&value.name, parent)
-        .ok_or(PtError::ProtobufTypeNotFound(value.name.clone()))?;
+    let ptype = ctx.declared(&value.name, scope)?;
     let enum_name = &ptype.ts_name;
 
-    str.push_str(format!("export enum {} {{\n", enum_name).as_str());
+    let deprecated = if value.deprecated && ctx.opts.compat.deprecated_jsdoc {
+        "/** @deprecated */\n"
+    } else {
+        ""
+    };
 
-    for value in &value.values {
-        match value {
-            EnumValue::Single { name, idx: _ } => {
-                str.push_str(format!("  {} = \"{}\",\n", name, name).as_str())
-            }
-            EnumValue::Reserved { idx: _ } => (),
-        }
-    }
+    let names = value
+        .values
+        .iter()
+        .filter_map(|value| match value {
+            EnumValue::Single {
+                name,
+                idx,
+                deprecated,
+                ..
+            } => Some((name, idx, *deprecated)),
+            EnumValue::Reserved { .. } => None,
+        })
+        .collect::<Vec<_>>();
 
-    str.push_str("}\n\n");
+    let prefix = ctx
+        .opts
+        .strip_enum_prefix
+        .then(|| enum_prefix(value))
+        .flatten();
 
-    let default_case = value.values.iter().find_map(|value| match value {
-        EnumValue::Single { name, idx } => {
-            if *idx == 0 {
-                Some(name)
-            } else {
-                None
+    if ctx.opts.enum_style == EnumStyle::StringUnion {
+        let values = names
+            .iter()
+            .map(|(name, _, _)| format!("\"{}\"", name))
+            .collect::<Vec<_>>();
+        let schema = if values.is_empty() {
+            "z.never()".to_string()
+        } else {
+            let catch = enum_default(ctx.opts, value)
+                .map(|def_case| format!(".catch(\"{}\")", def_case))
+                .unwrap_or_default();
+            format!("z.enum([{}]){}", values.join(", "), catch)
+        };
+        str.push_str(deprecated);
+        str.push_str(format!("export const {} = {};\n\n", ptype.schema, schema).as_str());
+        str.push_str(deprecated);
+        str.push_str(
+            format!(
+                "export type {} = z.infer<typeof {}>;\n\n",
+                enum_name, ptype.schema
+            )
+            .as_str(),
+        );
+    } else {
+        str.push_str(deprecated);
+        if ctx.opts.enum_style == EnumStyle::ConstObject {
+            str.push_str(format!("export const {} = {{\n", enum_name).as_str());
+        } else {
+            str.push_str(format!("export enum {} {{\n", enum_name).as_str());
+        }
+
+        for (name, idx, deprecated) in &names {
+            if *deprecated && ctx.opts.compat.deprecated_jsdoc {
+                str.push_str("  /** @deprecated */\n");
             }
+            let member = enum_member(prefix.as_deref(), name);
+            let line = match ctx.opts.enum_style {
+                EnumStyle::ConstObject => format!("  {}: \"{}\",\n", member, name),
+                EnumStyle::Numeric => format!("  {} = {},\n", member, idx),
+                _ => format!("  {} = \"{}\",\n", member, name),
+            };
+            str.push_str(&line);
         }
-        EnumValue::Reserved { idx: _ } => None,
-    });
 
-    let catch = default_case
-        .map(|def_case| format!(".catch({}.{})", enum_name, def_case))
-        .unwrap_or_else(|| String::new());
+        if ctx.opts.enum_style == EnumStyle::ConstObject {
+            str.push_str("} as const;\n\n");
+            str.push_str(deprecated);
+            str.push_str(
+                format!(
+                    "export type {} = (typeof {})[keyof typeof {}];\n\n",
+                    enum_name, enum_name, enum_name
+                )
+                .as_str(),
+            );
+        } else {
+            str.push_str("}\n\n");
+        }
 
-    str.push_str(
-        format!(
-            "export const {} = z.nativeEnum({}){};\n\n",
-            ptype.schema, enum_name, catch
-        )
-        .as_str(),
+        let catch = enum_default(ctx.opts, value)
+            .map(|def_case| {
+                let member = enum_member(prefix.as_deref(), def_case);
+                format!(".catch({}.{})", enum_name, member)
+            })
+            .unwrap_or_default();
+
+        // zod 4 accepts enums in `z.enum` and deprecates `z.nativeEnum`
+        let schema = match ctx.opts.zod_version {
+            ZodVersion::V3 => "z.nativeEnum",
+            ZodVersion::V4 => "z.enum",
+        };
+        str.push_str(deprecated);
+        str.push_str(
+            format!(
+                "export const {} = {}({}){};\n\n",
+                ptype.schema, schema, enum_name, catch
+            )
+            .as_str(),
+        );
+    }
+    ctx.enums.borrow_mut().insert(ptype.full_name.clone());
+    ctx.shapes.borrow_mut().insert(
+        ptype.ts_name.clone(),
+        Shape::enumeration(names.iter().map(|(name, _, _)| name.to_string())),
     );
 
+    if ctx.opts.ts_proto_compat {
+        str.push_str(&format_ts_proto_enum(ctx, enum_name, &ptype.schema));
+    }
+    if ctx.opts.proto_names {
+        str.push_str(&format_proto_name("export ", enum_name, &ptype.full_name));
+    }
+    if ctx.opts.fingerprints {
+        str.push_str(&format_fingerprint(
+            "export ",
+            enum_name,
+            enum_fingerprint(value),
+        ));
+    }
+
     Ok(str)
 }
 
-fn type_name<'a>(
-    ctx: &'a Context,
-    type_name: &'a str,
-    parent: Option<&ProtoType>,
-) -> Result<&'a str, PtError> {
-    match type_name {
-        // native types
-
-        // strings
-        "string" | "bytes" => Ok("z.string()"),
-        // numbers
-        "int32" | "double" | "float" | "uint32" | "sint32" | "fixed32" | "sfixed32" => {
-            Ok("z.number()")
-        }
-        // bigint numbers
-        "int64" | "uint64" | "fixed64" | "sfixed64" | "sint64" => Ok("z.coerce.bigint()"),
-
-        // boolean
-        "bool" => Ok("z.boolean()"),
-
-        // external types
-        "google.protobuf.Timestamp" => Ok("z.coerce.date()"),
-
-        // try to lookup other types
-        _ => ctx
-            .get(type_name, parent)
-            .map(|ptype| ptype.schema.as_str())
-            .ok_or(PtError::ProtobufTypeNotFound(type_name.to_string())),
+/// Registration of the schemas of the file's messages in the runtime module
+/// (see `--any-helpers`), which `unpackAny` looks them up in by type URL.
+fn format_registration(ctx: &Context) -> String {
+    let messages = ctx.messages.borrow();
+    if messages.is_empty() {
+        return String::new();
     }
-}
+    ctx.import(&ctx.opts.runtime_module, "registerSchemas");
 
-fn flagged_field(field: &str, flag: &Flag) -> String {
-    match flag {
-        Flag::Optional => format!("z.optional({})", field),
-        Flag::Repeated => format!("z.array({})", field),
-        Flag::None => field.to_string(),
-        Flag::Required => field.to_string(),
+    let mut str = String::from("registerSchemas({\n");
+    for ptype in messages.iter() {
+        str.push_str(format!("  \"{}\": {},\n", ptype.full_name, ptype.schema).as_str());
     }
+    str.push_str("});\n\n");
+    str
 }
 
-fn to_camel(word: &str) -> String {
-    let first_char = word.chars().nth(0);
-    first_char
-        .map(|first| {
-            let mut new_word = Vec::with_capacity(word.len());
-            new_word.push(first.to_ascii_uppercase());
-            new_word.extend(word.chars().skip(1));
-            new_word.into_iter().collect()
-        })
-        .unwrap_or_else(|| word.to_string())
+/// Prefix of the enum name in upper snake case that all values share, like
+/// `COLOR_` of `COLOR_RED` of the enum `Color` as the proto style guide
+/// prescribes, unless a member name would not be a valid identifier
+/// without it (see `--strip-enum-prefix`).
+fn enum_prefix(value: &Enum) -> Option<String> {
+    let prefix = format!("{}_", pascal_to_snake(&value.name).to_ascii_uppercase());
+    let strippable = value.values.iter().all(|value| match value {
+        EnumValue::Single { name, .. } => name
+            .strip_prefix(&prefix)
+            .and_then(|member| member.chars().next())
+            .is_some_and(|chr| chr.is_ascii_alphabetic() || chr == '_'),
+        EnumValue::Reserved { .. } => true,
+    });
+    strippable.then_some(prefix)
 }
 
-fn snake_to_camel(input: &str) -> String {
-    input
-        .split('_')
-        .filter(|part| !part.is_empty())
-        .enumerate()
-        .map(|(idx, part)| {
-            if idx > 0 {
-                to_camel(part)
-            } else {
-                part.to_owned()
-            }
-        })
-        .collect::<Vec<_>>()
-        .concat()
+/// Name of the member of an enum value, with the shared prefix stripped.
+fn enum_member<'a>(prefix: Option<&str>, name: &'a str) -> &'a str {
+    prefix
+        .and_then(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
 }
 
-struct ProtoType {
-    full_name: String,
-    ts_name: String,
-    schema: String,
+/// Adapter of a message named like the message object of ts-proto (see
+/// `--ts-proto-compat`), so its callers can migrate to the schemas gradually.
+/// `toJSON` writes bigints as decimal strings like the proto3 JSON mapping.
+fn format_ts_proto_message(ctx: &Context, export: &str, name: &str, schema: &str) -> String {
+    let replacer = match ctx.opts.int64() {
+        Int64::Bigint => {
+            ", (_key: string, value: unknown) =>\n      typeof value === \"bigint\" ? value.toString() : value,\n    "
+        }
+        Int64::String | Int64::Number => "",
+    };
+    format!(
+        r#"{export}const {name} = {{
+  fromJSON(object: unknown): {name} {{
+    return {schema}.parse(object);
+  }},
+  toJSON(message: {name}): unknown {{
+    return JSON.parse(JSON.stringify(message{replacer}));
+  }},
+  create(base?: Partial<{name}>): {name} {{
+    return {schema}.parse(base ?? {{}});
+  }},
+  fromPartial(object: Partial<{name}>): {name} {{
+    return {schema}.parse(object);
+  }},
+}};
+
+"#
+    )
 }
 
-impl ProtoType {
-    fn new(name: &str, parents: Vec<String>) -> ProtoType {
-        let parts = parents
-            .into_iter()
-            .chain([name.to_string()])
-            .collect::<Vec<_>>();
-        let full_name = parts.join(".");
-        let ts_name = parts.join("_");
-        let schema = format!("{}Schema", ts_name);
+/// JSON conversion functions of an enum named like the ones of ts-proto (see
+/// `--ts-proto-compat`).
+fn format_ts_proto_enum(ctx: &Context, name: &str, schema: &str) -> String {
+    let mut chars = name.chars();
+    let function = chars
+        .next()
+        .map(|first| first.to_lowercase().chain(chars).collect::<String>())
+        .unwrap_or_default();
+    // numeric enums map their numbers back to the names
+    let to_json = match ctx.opts.enum_style {
+        EnumStyle::Numeric => format!("{}[object]", name),
+        _ => "object".to_string(),
+    };
+    format!(
+        r#"export function {function}FromJSON(object: unknown): {name} {{
+  return {schema}.parse(object);
+}}
 
-        ProtoType {
-            full_name,
-            ts_name,
-            schema,
-        }
-    }
-}
+export function {function}ToJSON(object: {name}): string {{
+  return {to_json};
+}}
 
-struct Context {
-    types: HashMap<String, ProtoType>,
+"#
+    )
 }
 
-impl Context {
-    fn new(proto: &Proto) -> Context {
-        let mut map = HashMap::new();
+/// Fully-qualified protobuf name of a type (see `--proto-names`), e.g. for the
+/// type URLs of `google.protobuf.Any`.
+pub(crate) fn format_proto_name(export: &str, ts_name: &str, full_name: &str) -> String {
+    format!(
+        "{}const {}ProtoName = \"{}\";\n\n",
+        export, ts_name, full_name
+    )
+}
 
-        for elem in &proto.elems {
-            match elem {
-                Elem::Message(msg) => {
-                    map.insert(msg.name.clone(), ProtoType::new(&msg.name, Vec::new()));
+/// Structural fingerprint of a type (see `fingerprint`), as string since
+/// numbers cannot hold 64 bits.
+pub(crate) fn format_fingerprint(export: &str, ts_name: &str, fingerprint: u64) -> String {
+    format!(
+        "{}const {}Fingerprint = \"{:016x}\";\n\n",
+        export, ts_name, fingerprint
+    )
+}
 
-                    for ptype in msg
-                        .fields
-                        .iter()
-                        .flat_map(|fld| Self::collect(fld, vec![msg.name.clone()]))
-                    {
-                        map.insert(ptype.full_name.clone(), ptype);
-                    }
-                }
-                Elem::Enum(e) => {
-                    map.insert(e.name.clone(), ProtoType::new(&e.name, Vec::new()));
-                }
-                _ => (),
+/// Metadata of the service's methods, like idempotency level or custom
+/// options (see `--method-option`), that transports can act on. The object is
+/// omitted if none of the methods has any metadata at all. Options are keyed
+/// by the camelCased last part of their name, which has to be unique.
+fn format_service_metadata(
+    ctx: &Context,
+    name: &str,
+    nodes: &[ServiceNode],
+    scope: &str,
+) -> Result<String, PtError> {
+    let mut methods = Vec::new();
+    for node in nodes {
+        let ServiceNode::Rpc(rpc) = node else {
+            continue;
+        };
+        let mut keys: Vec<(String, String)> = Vec::new();
+        let mut entries = Vec::new();
+        for opt in rpc.options.iter().filter(|opt| {
+            opt.name == "idempotency_level" || ctx.opts.method_options.contains(&opt.name.path())
+        }) {
+            let path = opt.name.path();
+            let key = snake_to_camel(path.rsplit('.').next().unwrap_or(&path));
+            let value = option_literal(&opt.value);
+            if let Some((_, other)) = keys.iter().find(|(other_key, _)| *other_key == key) {
+                let location = Location::new("service", registry::qualify(scope, name)).member(
+                    "method",
+                    &rpc.name,
+                    rpc.span.line,
+                );
+                return Err(PtError::InvalidArgument(format!(
+                    "options {} and {} map to the same metadata key `{}`",
+                    other, path, key
+                ))
+                .at(location));
             }
+            entries.push(format!("{}: {}", key, value));
+            keys.push((key, path));
         }
-
-        Context { types: map }
+        methods.push((&rpc.name, entries));
     }
 
-    fn get(&self, name: &str, parent: Option<&ProtoType>) -> Option<&ProtoType> {
-        // first try the name as-is
-        self.types
-            .get(name)
-            // then try with the parent's name prepended
-            .or_else(|| {
-                parent.and_then(|p| self.types.get(format!("{}.{}", p.full_name, name).as_str()))
-            })
+    if methods.iter().all(|(_, entries)| entries.is_empty()) {
+        return Ok(String::new());
     }
 
-    fn collect(field: &Field, mut parent: Vec<String>) -> Vec<ProtoType> {
-        let mut types = Vec::new();
-        match field {
-            Field::SubMessage(msg) => {
-                let ptype = ProtoType::new(&msg.name, parent.clone());
-                types.push(ptype);
+    let mut str = String::with_capacity(512);
 
-                parent.push(msg.name.clone());
-                types.extend(
-                    msg.fields
-                        .iter()
-                        .flat_map(|fld| Self::collect(fld, parent.clone())),
-                );
-            }
-            Field::SubEnum(e) => types.push(ProtoType::new(&e.name, parent)),
-            _ => (),
+    str.push_str(format!("export const {}Metadata = {{\n", name).as_str());
+    for (method, entries) in methods {
+        if entries.is_empty() {
+            str.push_str(format!("  {}: {{}},\n", method).as_str());
+        } else {
+            str.push_str(format!("  {}: {{ {} }},\n", method, entries.join(", ")).as_str());
+        }
+    }
+    str.push_str("} as const;\n\n");
+
+    Ok(str)
+}
+
+/// Client of a service that sends its requests through a `Transport` of the
+/// runtime helper module (see `--emit runtime`). Deprecated methods, or all
+/// methods of a deprecated service, are marked in jsdoc and warn on their
+/// calls (see `--deprecation-warnings`).
+fn format_client(
+    ctx: &Context,
+    name: &str,
+    nodes: &[ServiceNode],
+    scope: &str,
+    has_metadata: bool,
+) -> String {
+    let service = registry::qualify(scope, name);
+    let mut str = String::with_capacity(1024);
+
+    ctx.import(&ctx.opts.runtime_module, "Transport");
+    ctx.import(&ctx.opts.runtime_module, "call");
+
+    let options = nodes
+        .iter()
+        .filter_map(|node| match node {
+            ServiceNode::Option(option) => Some(option.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let service_deprecated = parser::is_deprecated(&options);
+
+    if service_deprecated {
+        str.push_str("/** @deprecated */\n");
+    }
+    str.push_str(format!("export class {}Client {{\n", name).as_str());
+    str.push_str("  constructor(private readonly transport: Transport) {}\n");
+
+    for node in nodes {
+        let rpc = match node {
+            ServiceNode::Rpc(rpc) => rpc,
+            _ => continue,
+        };
+
+        str.push('\n');
+
+        if rpc.stream_request || rpc.stream_response {
+            str.push_str(
+                format!("  // streaming method {} is not supported yet\n", rpc.name).as_str(),
+            );
+            continue;
+        }
+
+        let location =
+            Location::new("service", &service).member("method", &rpc.name, rpc.span.line);
+        let request = ctx
+            .resolve_type(&rpc.request, scope)
+            .ok_or_else(|| ctx.registry.unresolved(&rpc.request, scope));
+        let response = ctx
+            .resolve_type(&rpc.response, scope)
+            .ok_or_else(|| ctx.registry.unresolved(&rpc.response, scope));
+        let (Some(request), Some(response)) = (
+            ctx.recover(location.clone(), request),
+            ctx.recover(location, response),
+        ) else {
+            continue;
+        };
+
+        let deprecated = rpc.deprecated || service_deprecated;
+        if deprecated {
+            str.push_str("  /** @deprecated */\n");
+        }
+        str.push_str(
+            format!(
+                "  {}(request: {}): Promise<{}> {{\n",
+                method_name(ctx.opts, &rpc.name),
+                request.ts_name,
+                response.ts_name
+            )
+            .as_str(),
+        );
+        if deprecated && ctx.opts.deprecation_warnings {
+            ctx.import(&ctx.opts.runtime_module, "warnDeprecated");
+            str.push_str(
+                format!(
+                    "    warnDeprecated(\"{}\");\n",
+                    registry::qualify(&service, &rpc.name)
+                )
+                .as_str(),
+            );
+        }
+        str.push_str("    return call(\n");
+        str.push_str("      this.transport,\n");
+        str.push_str(
+            format!(
+                "      {{ service: \"{}\", method: \"{}\", path: \"/{}/{}\"{} }},\n",
+                service,
+                rpc.name,
+                service,
+                rpc.name,
+                if has_metadata {
+                    format!(", options: {}Metadata.{}", name, rpc.name)
+                } else {
+                    String::new()
+                }
+            )
+            .as_str(),
+        );
+        str.push_str("      request,\n");
+        str.push_str(format!("      (value) => {}.parse(value),\n", response.schema).as_str());
+        str.push_str("    );\n");
+        str.push_str("  }\n");
+    }
+
+    str.push_str("}\n\n");
+
+    str
+}
+
+/// Full paths of the service's methods by rpc name (see `--method-paths`),
+/// e.g. for transports routing by path.
+fn format_paths(name: &str, nodes: &[ServiceNode], scope: &str) -> String {
+    let service = registry::qualify(scope, name);
+    let mut str = format!("export const {}Paths = {{\n", name);
+    for node in nodes {
+        if let ServiceNode::Rpc(rpc) = node {
+            str.push_str(format!("  {}: \"/{}/{}\",\n", rpc.name, service, rpc.name).as_str());
+        }
+    }
+    str.push_str("} as const;\n\n");
+    str
+}
+
+/// Route table of the service (see `--routes`): the path, schemas and
+/// streaming flags of every method by rpc name, for generic transports, mock
+/// servers or gateways.
+fn format_routes(ctx: &Context, name: &str, nodes: &[ServiceNode], scope: &str) -> String {
+    let service = registry::qualify(scope, name);
+    let mut str = format!("export const {}Routes = {{\n", name);
+
+    for node in nodes {
+        let ServiceNode::Rpc(rpc) = node else {
+            continue;
+        };
+        let location =
+            Location::new("service", &service).member("method", &rpc.name, rpc.span.line);
+        let (Some(request), Some(response)) = (
+            ctx.recover(location.clone(), type_name(ctx, &rpc.request, scope)),
+            ctx.recover(location, type_name(ctx, &rpc.response, scope)),
+        ) else {
+            continue;
+        };
+
+        str.push_str(format!("  {}: {{\n", rpc.name).as_str());
+        str.push_str(format!("    path: \"/{}/{}\",\n", service, rpc.name).as_str());
+        str.push_str(format!("    requestSchema: {},\n", request).as_str());
+        str.push_str(format!("    responseSchema: {},\n", response).as_str());
+        str.push_str(format!("    requestStream: {},\n", rpc.stream_request).as_str());
+        str.push_str(format!("    responseStream: {},\n", rpc.stream_response).as_str());
+        str.push_str("  },\n");
+    }
+
+    str.push_str("} as const;\n\n");
+    str
+}
+
+/// Name of the client method of the rpc (see `--method-case`,
+/// `--method-prefix` and `--method-suffix`). Following a prefix, camelCase
+/// names continue with an uppercase letter, e.g. `apiGetUser`.
+fn method_name(opts: &Opts, rpc_name: &str) -> String {
+    let name = match opts.method_case {
+        MethodCase::Camel if opts.method_prefix.is_empty() => lower_first(rpc_name),
+        MethodCase::Camel | MethodCase::Pascal => rpc_name.to_string(),
+        MethodCase::Snake => pascal_to_snake(rpc_name),
+    };
+    format!("{}{}{}", opts.method_prefix, name, opts.method_suffix)
+}
+
+/// snake_case of a PascalCase name, acronyms are kept together, e.g.
+/// `get_http_user` of `GetHTTPUser`.
+fn pascal_to_snake(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (idx, chr) in chars.iter().enumerate() {
+        if chr.is_ascii_uppercase() && idx > 0 {
+            let prev = chars[idx - 1];
+            let next_lower = chars.get(idx + 1).is_some_and(char::is_ascii_lowercase);
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                snake.push('_');
+            }
+        }
+        snake.push(chr.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// Typescript literal of an option value. Aggregate values are object
+/// literals keyed by the camelCased field names, repeated fields as arrays.
+fn option_literal(value: &OptionValue) -> String {
+    match value {
+        OptionValue::Str { value } => format!("{:?}", value),
+        OptionValue::Constant { value } => format!("\"{}\"", value),
+        OptionValue::Num {
+            value: Number::Float(value),
+        } if value.is_nan() => "NaN".to_string(),
+        OptionValue::Num {
+            value: Number::Float(value),
+        } if value.is_infinite() => if *value < 0.0 {
+            "-Infinity"
+        } else {
+            "Infinity"
+        }
+        .to_string(),
+        OptionValue::Num { value } => value.to_string(),
+        OptionValue::Bool { value } => value.to_string(),
+        OptionValue::Array(values) => format!(
+            "[{}]",
+            values
+                .iter()
+                .map(option_literal)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        OptionValue::Msg(fields) => {
+            let mut names = Vec::new();
+            for (name, _) in fields {
+                if !names.contains(&name.as_str()) {
+                    names.push(name.as_str());
+                }
+            }
+            let entries = names
+                .into_iter()
+                .map(|name| {
+                    let occurrences = fields
+                        .iter()
+                        .filter(|(field, _)| field == name)
+                        .collect::<Vec<_>>();
+                    let literal = match occurrences.as_slice() {
+                        [(_, single)] => option_literal(single),
+                        _ => {
+                            let values = value.get_all(name).map(option_literal);
+                            format!("[{}]", values.collect::<Vec<_>>().join(", "))
+                        }
+                    };
+                    // extension fields keep their bracketed name
+                    let key = match name.starts_with('[') {
+                        true => format!("{:?}", name),
+                        false => property_key(&snake_to_camel(name)),
+                    };
+                    format!("{}: {}", key, literal)
+                })
+                .collect::<Vec<_>>();
+            match entries.is_empty() {
+                true => "{}".to_string(),
+                false => format!("{{ {} }}", entries.join(", ")),
+            }
         }
-        types
+    }
+}
+
+/// Option prefixes of the validation rules of `protoc-gen-validate` and
+/// `protovalidate` respectively.
+const RULE_PREFIXES: [&str; 2] = ["validate.rules.", "buf.validate.field."];
+
+/// Size bounds of strings and repeated fields given via validation rules,
+/// e.g. `[(validate.rules).string.max_len = 64]`, as zod method calls.
+#[derive(Default)]
+struct SizeRules {
+    string: String,
+    items: String,
+}
+
+impl SizeRules {
+    fn new(options: &[parser::Option]) -> SizeRules {
+        let mut rules = SizeRules::default();
+        let mut flattened = Vec::new();
+        for option in options {
+            flatten_option(&option.name.path(), &option.value, &mut flattened);
+        }
+
+        for (name, value) in flattened {
+            let (
+                Some(rule),
+                OptionValue::Num {
+                    value: Number::Int(value),
+                },
+            ) = (
+                RULE_PREFIXES
+                    .iter()
+                    .find_map(|prefix| name.strip_prefix(prefix)),
+                value,
+            )
+            else {
+                continue;
+            };
+
+            let (bounds, method) = match rule {
+                "string.min_len" => (&mut rules.string, "min"),
+                "string.max_len" => (&mut rules.string, "max"),
+                "string.len" => (&mut rules.string, "length"),
+                "repeated.min_items" => (&mut rules.items, "min"),
+                "repeated.max_items" => (&mut rules.items, "max"),
+                _ => continue,
+            };
+            bounds.push_str(&format!(".{}({})", method, value));
+        }
+
+        rules
+    }
+}
+
+/// Flatten message literal option values into their dotted field paths, so
+/// `(validate.rules).string = {max_len: 64}` and
+/// `(validate.rules).string.max_len = 64` are treated the same.
+fn flatten_option<'a>(
+    name: &str,
+    value: &'a OptionValue,
+    flattened: &mut Vec<(String, &'a OptionValue)>,
+) {
+    match value {
+        OptionValue::Msg(fields) => {
+            for (field, value) in fields {
+                flatten_option(&format!("{}.{}", name, field), value, flattened);
+            }
+        }
+        _ => flattened.push((name.to_string(), value)),
+    }
+}
+
+/// Standard or URL-safe base64, with or without padding, as proto3 JSON
+/// parsers accept it.
+const BASE64: &str = "z.string().regex(/^[A-Za-z0-9+\\/_-]*={0,2}$/)";
+
+/// Schema of bytes fields (see `--bytes`).
+fn bytes(opts: &Opts) -> String {
+    match opts.bytes {
+        Bytes::Base64 if !opts.compat.bytes_base64 => "z.string()".to_string(),
+        Bytes::Base64 => BASE64.to_string(),
+        Bytes::Uint8Array => format!(
+            "z.union([z.instanceof(Uint8Array), {}.transform((value) => \
+             Uint8Array.from(atob(value.replace(/-/g, \"+\").replace(/_/g, \"/\")), \
+             (chr) => chr.charCodeAt(0)))])",
+            BASE64
+        ),
+    }
+}
+
+/// Schema of the keys of a map, which are strings in JSON (and in javascript
+/// objects): integral keys are decimal strings and booleans are given as
+/// `"true"` or `"false"`. Older releases used the schema of the scalar type
+/// instead (see `Compat::string_map_keys`).
+fn map_key(ctx: &Context, key_type: &str, scope: &str) -> Result<String, PtError> {
+    if !ctx.opts.compat.string_map_keys {
+        return type_name(ctx, key_type, scope);
+    }
+    let schema = match key_type {
+        "int32" | "sint32" | "sfixed32" | "int64" | "sint64" | "sfixed64" => {
+            "z.string().regex(/^-?[0-9]+$/)"
+        }
+        "uint32" | "fixed32" | "uint64" | "fixed64" => "z.string().regex(/^[0-9]+$/)",
+        "bool" => "z.enum([\"true\", \"false\"])",
+        // the parser accepts string keys only otherwise
+        _ => "z.string()",
+    };
+    Ok(schema.to_string())
+}
+
+fn type_name(ctx: &Context, type_name: &str, scope: &str) -> Result<String, PtError> {
+    let native = match type_name {
+        // native types
+
+        // strings
+        "string" => "z.string()",
+        "bytes" => return Ok(bytes(ctx.opts)),
+        // numbers
+        "int32" | "double" | "float" | "uint32" | "sint32" | "fixed32" | "sfixed32" => "z.number()",
+        // bigint numbers
+        "int64" | "uint64" | "fixed64" | "sfixed64" | "sint64" => match ctx.opts.int64() {
+            Int64::Bigint => "z.coerce.bigint()",
+            // decimal strings like in the proto3 JSON mapping
+            Int64::String => "z.union([z.string(), z.number()]).transform(String)",
+            Int64::Number => "z.coerce.number()",
+        },
+
+        // boolean
+        "bool" => "z.boolean()",
+
+        // try to lookup other types
+        _ => {
+            // well-known types with a special JSON mapping need not be
+            // imported
+            let full_name = ctx
+                .registry
+                .resolve(type_name, scope)
+                .map_or(type_name.trim_start_matches('.'), |ptype| {
+                    ptype.full_name.as_str()
+                });
+            if let Some(schema) = well_known(ctx, full_name, scope)? {
+                return Ok(schema);
+            }
+
+            let ptype = ctx
+                .resolve(type_name, scope)
+                .ok_or_else(|| ctx.registry.unresolved(type_name, scope))?;
+            // enums of this file declared further down, e.g. nested in a
+            // later message, are not initialized yet
+            if ctx.opts.compat.lazy_enum_refs
+                && ptype.kind == registry::Kind::Enum
+                && ptype.file == ctx.file
+                && !ctx.enums.borrow().contains(&ptype.full_name)
+            {
+                return Ok(format!("z.lazy(() => {})", ptype.schema));
+            }
+            // the same goes for messages declared further down, or the
+            // message being declared itself; its nested messages are
+            // declared before it
+            if ctx.opts.compat.lazy_message_refs
+                && ptype.kind == registry::Kind::Message
+                && registry::find_message([ctx.proto], &ptype.full_name).is_some()
+                && !ptype.full_name.starts_with(&format!("{}.", scope))
+                && !ctx
+                    .messages
+                    .borrow()
+                    .iter()
+                    .any(|declared| declared.full_name == ptype.full_name)
+            {
+                return Ok(format!("z.lazy(() => {})", ptype.schema));
+            }
+            return Ok(ptype.schema.clone());
+        }
+    };
+    Ok(native.to_string())
+}
+
+/// Schema of a well-known type following its JSON mapping, unless replaced
+/// via `--wkt-schema`. Wrapper types are their scalar or `null`.
+fn well_known(ctx: &Context, full_name: &str, scope: &str) -> Result<Option<String>, PtError> {
+    if let Some(custom) = ctx
+        .opts
+        .wkt_schemas
+        .iter()
+        .find(|wkt| wkt.name == full_name)
+    {
+        if let Some(module) = &custom.module {
+            ctx.import(module, &custom.schema);
+        }
+        return Ok(Some(custom.schema.clone()));
+    }
+    if let Some(scalar) = json::wrapped(full_name) {
+        return Ok(Some(format!(
+            "{}.nullable()",
+            type_name(ctx, scalar, scope)?
+        )));
+    }
+
+    let schema = match full_name {
+        "google.protobuf.Timestamp" => "z.coerce.date()",
+        "google.protobuf.Duration" => "z.string().regex(/^-?[0-9]+(\\.[0-9]{1,9})?s$/)",
+        "google.protobuf.FieldMask" => "z.string()",
+        // the fields of the packed message are next to the type URL
+        "google.protobuf.Any" => match ctx.opts.zod_version {
+            ZodVersion::V3 => "z.object({ \"@type\": z.string() }).passthrough()",
+            ZodVersion::V4 => "z.looseObject({ \"@type\": z.string() })",
+        },
+        "google.protobuf.Struct" => "z.record(z.string(), z.unknown())",
+        "google.protobuf.ListValue" => "z.array(z.unknown())",
+        "google.protobuf.Value" => "z.unknown()",
+        "google.protobuf.NullValue" => "z.null()",
+        "google.protobuf.Empty" => "z.object({})",
+        _ => return Ok(None),
+    };
+    Ok(Some(schema.to_string()))
+}
+
+/// Location of the field or nested type declared in the given message, the
+/// prefix is put before the field name, e.g. the name of its oneof.
+pub(crate) fn field_location(message: &str, prefix: &str, field: &Field) -> Location {
+    let (kind, name, span) = match field {
+        Field::Single { name, span, .. } | Field::Map { name, span, .. } => ("field", name, span),
+        Field::OneOf { name, span, .. } => ("oneof", name, span),
+        Field::SubMessage(msg) => ("message", &msg.name, &msg.span),
+        Field::SubEnum(e) => ("enum", &e.name, &e.span),
+        _ => return Location::new("message", message),
+    };
+    Location::new("message", message).member(kind, format!("{}{}", prefix, name), span.line)
+}
+
+/// Whether any of the given fields is a proto2 `required` field.
+fn has_required(fields: &[Field]) -> bool {
+    fields.iter().any(|field| {
+        matches!(
+            field,
+            Field::Single {
+                flag: Flag::Required,
+                ..
+            }
+        )
+    })
+}
+
+fn flagged_field(field: &str, flag: &Flag) -> String {
+    match flag {
+        Flag::Optional => format!("z.optional({})", field),
+        Flag::Repeated => format!("z.array({})", field),
+        Flag::None => field.to_string(),
+        Flag::Required => field.to_string(),
+    }
+}
+
+fn to_camel(word: &str) -> String {
+    let first_char = word.chars().next();
+    first_char
+        .map(|first| {
+            let mut new_word = Vec::with_capacity(word.len());
+            new_word.push(first.to_ascii_uppercase());
+            new_word.extend(word.chars().skip(1));
+            new_word.into_iter().collect()
+        })
+        .unwrap_or_else(|| word.to_string())
+}
+
+fn lower_first(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_ascii_lowercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+/// Object property key of the given name, quoted unless it is a valid
+/// identifier, e.g. a custom JSON name `"user-id"`.
+pub(crate) fn property_key(name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == '$')
+        && chars.all(|chr| chr.is_ascii_alphanumeric() || chr == '_' || chr == '$');
+    match identifier {
+        true => name.to_string(),
+        false => format!("{:?}", name),
+    }
+}
+
+pub(crate) fn snake_to_camel(input: &str) -> String {
+    input
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .enumerate()
+        .map(|(idx, part)| {
+            if idx > 0 {
+                to_camel(part)
+            } else {
+                part.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+/// Nested messages used solely as field types within their parent message.
+///
+/// Only references of the given file are taken into account, types of other
+/// files referencing nested messages are not known.
+fn private_nested(proto: &Proto, registry: &Registry) -> BTreeSet<String> {
+    let scope = proto.package().unwrap_or("");
+    // referenced types along with the scope referencing them, empty for
+    // references outside of messages
+    let mut references = Vec::new();
+    let mut nested = Vec::new();
+
+    for elem in &proto.elems {
+        match elem {
+            Elem::Message(msg) => {
+                message_references(registry, msg, scope, None, &mut references, &mut nested)
+            }
+            Elem::Extend { fields, .. } => {
+                let mut decls = Vec::new();
+                registry::declared(fields, None, &mut decls);
+                for decl in decls {
+                    if let Some(ptype) = registry.resolve(decl.field_type, scope) {
+                        references.push((ptype.full_name.clone(), String::new()));
+                    }
+                }
+            }
+            Elem::Service { nodes, .. } => {
+                for node in nodes {
+                    if let ServiceNode::Rpc(rpc) = node {
+                        for name in [&rpc.request, &rpc.response] {
+                            if let Some(ptype) = registry.resolve(name, scope) {
+                                references.push((ptype.full_name.clone(), String::new()));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    nested
+        .into_iter()
+        .filter(|(full_name, parent)| {
+            let mut referrers = references
+                .iter()
+                .filter(|(target, _)| target == full_name)
+                .map(|(_, referrer)| referrer)
+                .peekable();
+            let inner = format!("{}.", parent);
+            referrers.peek().is_some()
+                && referrers.all(|referrer| referrer == parent || referrer.starts_with(&inner))
+        })
+        .map(|(full_name, _)| full_name)
+        .collect()
+}
+
+/// Collect the types referenced by the fields of the message and its nested
+/// messages, along with the nested messages and their parents.
+fn message_references(
+    registry: &Registry,
+    msg: &Msg,
+    scope: &str,
+    parent: Option<&str>,
+    references: &mut Vec<(String, String)>,
+    nested: &mut Vec<(String, String)>,
+) {
+    let full_name = registry::qualify(scope, &msg.name);
+    if let Some(parent) = parent {
+        nested.push((full_name.clone(), parent.to_string()));
+    }
+
+    let mut decls = Vec::new();
+    registry::declared(&msg.fields, None, &mut decls);
+    for decl in decls {
+        if let Some(ptype) = registry.resolve(decl.field_type, &full_name) {
+            references.push((ptype.full_name.clone(), full_name.clone()));
+        }
+    }
+    for field in &msg.fields {
+        match field {
+            Field::SubMessage(sub_msg) => message_references(
+                registry,
+                sub_msg,
+                &full_name,
+                Some(&full_name),
+                references,
+                nested,
+            ),
+            // extensions are used outside of the message declaring them
+            Field::Extend { fields, .. } => {
+                let mut decls = Vec::new();
+                registry::declared(fields, None, &mut decls);
+                for decl in decls {
+                    if let Some(ptype) = registry.resolve(decl.field_type, &full_name) {
+                        references.push((ptype.full_name.clone(), String::new()));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+struct Context<'a> {
+    opts: &'a Opts,
+    file: &'a str,
+    /// file being generated, its types are declared in order
+    proto: &'a Proto,
+    registry: &'a Registry,
+    /// names referenced from other modules, grouped by module
+    imports: RefCell<BTreeMap<String, BTreeSet<String>>>,
+    /// nested messages that are not exported (see `--private-nested`)
+    private: BTreeSet<String>,
+    /// errors of the generation phase, see `PtError::Located`
+    errors: RefCell<Vec<PtError>>,
+    /// locations of the proto2 `required` fields generated
+    required: RefCell<Vec<Location>>,
+    /// files whose types are generated into this module instead of being
+    /// imported (see `--inline-deps`)
+    inlined: &'a BTreeSet<String>,
+    /// types of the inlined files referenced
+    inline: RefCell<BTreeSet<String>>,
+    /// types of other files referenced, grouped by file
+    used: RefCell<BTreeMap<String, BTreeSet<String>>>,
+    /// messages whose schemas are generated, i.e. declared already
+    messages: RefCell<Vec<&'a ProtoType>>,
+    /// enums whose schemas are declared already
+    enums: RefCell<BTreeSet<String>>,
+    /// shapes of the types exported (see `verify`)
+    shapes: RefCell<Shapes>,
+}
+
+impl<'a> Context<'a> {
+    fn new(
+        opts: &'a Opts,
+        proto: &'a Proto,
+        registry: &'a Registry,
+        inlined: &'a BTreeSet<String>,
+    ) -> Context<'a> {
+        Context {
+            opts,
+            file: &proto.file,
+            proto,
+            registry,
+            imports: RefCell::new(BTreeMap::new()),
+            private: match opts.private_nested {
+                true => private_nested(proto, registry),
+                false => BTreeSet::new(),
+            },
+            errors: RefCell::new(Vec::new()),
+            required: RefCell::new(Vec::new()),
+            inlined,
+            inline: RefCell::new(BTreeSet::new()),
+            used: RefCell::new(BTreeMap::new()),
+            messages: RefCell::new(Vec::new()),
+            enums: RefCell::new(BTreeSet::new()),
+            shapes: RefCell::new(Shapes::new()),
+        }
+    }
+
+    /// Record an error of the generation phase that occurred at the given
+    /// location, unless it carries a more specific one already.
+    fn fail(&self, location: Location, error: PtError) {
+        self.errors.borrow_mut().push(error.at(location));
+    }
+
+    /// Value of the given result, recording its error otherwise so that the
+    /// generation continues with the next type or field.
+    fn recover<T>(&self, location: Location, result: Result<T, PtError>) -> Option<T> {
+        result.map_err(|error| self.fail(location, error)).ok()
+    }
+
+    /// Export keyword of the declaration of the given type, if exported.
+    fn export(&self, full_name: &str) -> &'static str {
+        match self.private.contains(full_name) {
+            true => "",
+            false => "export ",
+        }
+    }
+
+    /// Lookup a type that is declared in the given scope.
+    fn declared(&self, name: &str, scope: &str) -> Result<&'a ProtoType, PtError> {
+        let full_name = registry::qualify(scope, name);
+        self.registry
+            .get(&full_name)
+            .ok_or(PtError::ProtobufTypeNotFound(full_name))
+    }
+
+    /// Resolve a type reference and keep track of the files that have to be imported.
+    fn resolve(&self, name: &str, scope: &str) -> Option<&'a ProtoType> {
+        let ptype = self.registry.resolve(name, scope)?;
+
+        if ptype.file != self.file {
+            self.used
+                .borrow_mut()
+                .entry(ptype.file.clone())
+                .or_default()
+                .insert(ptype.full_name.clone());
+            if self.inlined.contains(&ptype.file) {
+                self.inline.borrow_mut().insert(ptype.full_name.clone());
+            } else {
+                self.import(&imports::module_path(self.file, &ptype.file), &ptype.schema);
+            }
+        }
+
+        Some(ptype)
+    }
+
+    /// Resolve a type reference whose typescript type is used as well.
+    fn resolve_type(&self, name: &str, scope: &str) -> Option<&'a ProtoType> {
+        let ptype = self.resolve(name, scope)?;
+
+        if ptype.file != self.file && !self.inlined.contains(&ptype.file) {
+            self.import(
+                &imports::module_path(self.file, &ptype.file),
+                &ptype.ts_name,
+            );
+        }
+
+        Some(ptype)
+    }
+
+    fn import(&self, module: &str, name: &str) {
+        self.imports
+            .borrow_mut()
+            .entry(module.to_string())
+            .or_default()
+            .insert(name.to_string());
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::{Elem, Field, Msg, Proto};
+    use crate::compat::Compat;
+    use crate::errors::PtError;
+    use crate::parser::{Elem, Enum, Field, ImportModifier, Msg, Proto, Span};
+    use crate::{
+        BaseSchema, Bytes, EnumStyle, Int64, MethodCase, Opts, Target, TsLibTarget, WktSchema,
+        ZodVersion,
+    };
 
-    use super::to_schema;
+    use super::{method_name, runtime, to_schema};
 
     fn proto(elem: Elem) -> Proto {
         Proto {
             syntax: "proto3".to_string(),
+            edition: None,
             file: "file.proto".to_string(),
             elems: vec![elem],
+            span: Span::default(),
         }
     }
 
@@ -363,16 +1968,26 @@ mod tests {
                     field_type: "string".to_string(),
                     idx: 1,
                     flag: crate::parser::Flag::None,
+                    options: vec![],
+                    json_name: None,
+                    deprecated: false,
+                    doc: None,
+                    span: Span::default(),
                 }],
+                options: vec![],
+                span: Span::default(),
             }],
+            deprecated: false,
+            doc: None,
+            span: Span::default(),
         }));
 
-        let schema = to_schema(&p);
-        assert_eq!(schema.is_ok(), true);
+        let schema = to_schema(&Opts::default(), &p, &[]);
+        assert!(schema.is_ok());
         assert_eq!(
             schema.unwrap(),
             r#"//
-// Code generated by protots - DO NOT EDIT
+// Code generated by protots v0.2 - DO NOT EDIT
 // Source: file.proto
 //
 
@@ -400,23 +2015,38 @@ export type Test = z.infer<typeof TestSchema>;
                         field_type: "string".to_string(),
                         idx: 1,
                         flag: crate::parser::Flag::None,
+                        options: vec![],
+                        json_name: None,
+                        deprecated: false,
+                        doc: None,
+                        span: Span::default(),
                     },
                     Field::Single {
                         name: "two".to_string(),
                         field_type: "int32".to_string(),
                         idx: 2,
                         flag: crate::parser::Flag::None,
+                        options: vec![],
+                        json_name: None,
+                        deprecated: false,
+                        doc: None,
+                        span: Span::default(),
                     },
                 ],
+                options: vec![],
+                span: Span::default(),
             }],
+            deprecated: false,
+            doc: None,
+            span: Span::default(),
         }));
 
-        let schema = to_schema(&p);
-        assert_eq!(schema.is_ok(), true);
+        let schema = to_schema(&Opts::default(), &p, &[]);
+        assert!(schema.is_ok());
         assert_eq!(
             schema.unwrap(),
             r#"//
-// Code generated by protots - DO NOT EDIT
+// Code generated by protots v0.2 - DO NOT EDIT
 // Source: file.proto
 //
 
@@ -431,4 +2061,1096 @@ export type Test = z.infer<typeof TestSchema>;
 "#
         );
     }
+
+    #[test]
+    fn to_schema_imported_package_type() {
+        let p = Proto {
+            syntax: "proto3".to_string(),
+            edition: None,
+            file: "foo/v1/user.proto".to_string(),
+            elems: vec![
+                Elem::Package {
+                    name: "foo.v1".to_string(),
+                    span: Span::default(),
+                },
+                Elem::Import {
+                    name: "common/address.proto".to_string(),
+                    modifier: ImportModifier::None,
+                    span: Span::default(),
+                },
+                Elem::Message(Msg {
+                    name: "User".to_string(),
+                    fields: vec![Field::Single {
+                        name: "address".to_string(),
+                        field_type: "common.Address".to_string(),
+                        idx: 1,
+                        flag: crate::parser::Flag::None,
+                        options: vec![],
+                        json_name: None,
+                        deprecated: false,
+                        doc: None,
+                        span: Span::default(),
+                    }],
+                    deprecated: false,
+                    doc: None,
+                    span: Span::default(),
+                }),
+            ],
+            span: Span::default(),
+        };
+        let dep = Proto {
+            syntax: "proto3".to_string(),
+            edition: None,
+            file: "common/address.proto".to_string(),
+            elems: vec![
+                Elem::Package {
+                    name: "common".to_string(),
+                    span: Span::default(),
+                },
+                Elem::Message(Msg {
+                    name: "Address".to_string(),
+                    fields: vec![],
+                    deprecated: false,
+                    doc: None,
+                    span: Span::default(),
+                }),
+            ],
+            span: Span::default(),
+        };
+
+        let schema = to_schema(&Opts::default(), &p, &[dep]);
+        assert!(schema.is_ok());
+        assert_eq!(
+            schema.unwrap(),
+            r#"//
+// Code generated by protots v0.2 - DO NOT EDIT
+// Source: foo/v1/user.proto
+//
+
+import { z } from "zod";
+import { AddressSchema } from "../../common/address";
+
+export const UserSchema = z.object({
+  address: AddressSchema,
+});
+
+export type User = z.infer<typeof UserSchema>;
+
+"#
+        );
+    }
+
+    #[test]
+    fn to_schema_public_import() {
+        let p = crate::parser::parse(
+            &Opts::default(),
+            "foo/v1/user.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+import public "common/address.proto";
+import weak "foo/v1/legacy.proto";
+message User {}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&Opts::default(), &p, &[]).unwrap();
+        assert!(schema
+            .contains("import { z } from \"zod\";\nexport * from \"../../common/address\";\n\n"));
+        assert!(!schema.contains("legacy"));
+    }
+
+    #[test]
+    fn to_schema_private_nested() {
+        let opts = Opts {
+            private_nested: true,
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message Outer {
+  message Inner {
+    message Leaf {}
+    Leaf leaf = 1;
+  }
+  message Shared {}
+  message Unused {}
+  oneof kind { Inner inner = 1; }
+  Shared shared = 2;
+}
+message Other { Outer.Shared shared = 1; }
+service Outers { rpc Get(Outer.Unused) returns (Outer); }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains("\nconst Outer_Inner_LeafSchema = "));
+        assert!(schema.contains("\ntype Outer_Inner = z.infer<typeof Outer_InnerSchema>;"));
+        assert!(schema.contains("\nexport const Outer_SharedSchema = "));
+        assert!(schema.contains("\nexport const Outer_UnusedSchema = "));
+        assert!(schema.contains("\nexport const OuterSchema = "));
+
+        let schema = to_schema(&Opts::default(), &p, &[]).unwrap();
+        assert!(schema.contains("\nexport const Outer_Inner_LeafSchema = "));
+    }
+
+    #[test]
+    fn to_schema_banner() {
+        let opts = Opts {
+            banner: Some("Copyright (c) {year} ACME Inc.\n\nSource: {file}\n".to_string()),
+            ..Opts::default()
+        };
+        let p = proto(Elem::Message(Msg {
+            name: "Test".to_string(),
+            fields: vec![],
+            deprecated: false,
+            doc: None,
+            span: Span::default(),
+        }));
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        let (banner, rest) = schema.split_once("\n\n//\n// Code generated").unwrap();
+        assert!(banner.starts_with("// Copyright (c) 2"));
+        assert!(banner.ends_with(" ACME Inc.\n//\n// Source: file.proto"));
+        assert!(rest.starts_with(" by protots"));
+
+        // templates written as comments are kept as they are
+        assert_eq!(
+            super::banner("/*\n * {file}\n */\n", "a.proto"),
+            "/*\n * a.proto\n */\n\n"
+        );
+    }
+
+    #[test]
+    fn to_schema_deprecated_message() {
+        let p = crate::parser::parse(
+            &Opts::default(),
+            "file.proto",
+            r#"syntax = "proto3";
+message Old {
+  option deprecated = true;
+  option (my.ext).label = "old";
+  string name = 1;
+}"#,
+        )
+        .unwrap();
+        let Elem::Message(msg) = &p.elems[0] else {
+            panic!("expected message");
+        };
+        assert_eq!(msg.options().count(), 2);
+
+        let schema = to_schema(&Opts::default(), &p, &[]).unwrap();
+        assert!(schema.contains("\n/** @deprecated */\nexport const OldSchema = z.object({\n"));
+        assert!(schema.contains("\n/** @deprecated */\nexport type Old = "));
+    }
+
+    #[test]
+    fn to_schema_deprecated_fields_and_enums() {
+        let p = crate::parser::parse(
+            &Opts::default(),
+            "file.proto",
+            r#"syntax = "proto3";
+message User {
+  string name = 1 [deprecated = true];
+  map<string, int32> labels = 2 [deprecated = true];
+  int32 age = 3;
+}
+enum Status {
+  option deprecated = true;
+  ACTIVE = 0;
+}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&Opts::default(), &p, &[]).unwrap();
+        assert!(schema.contains(
+            "  /** @deprecated */\n  name: z.string(),\n  /** @deprecated */\n  labels: z.record(z.string(), z.number()),\n  age: z.number(),\n"
+        ));
+        assert!(schema.contains("\n/** @deprecated */\nexport enum Status {\n"));
+        assert!(schema.contains("\n/** @deprecated */\nexport const StatusSchema = "));
+    }
+
+    #[test]
+    fn to_schema_collects_errors() {
+        let p = crate::parser::parse(
+            &Opts::default(),
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message User {
+  string name = 1;
+  Unknown address = 2;
+  oneof contact {
+    string email = 3;
+    Phone phone = 4;
+  }
+}
+message Group { map<string, Missing> users = 1; }"#,
+        )
+        .unwrap();
+
+        let (schema, errors) = super::generate(&Opts::default(), &p, &[]);
+        let paths = errors
+            .iter()
+            .map(|error| match error {
+                PtError::Located { location, .. } => location.path(),
+                _ => panic!("expected location of {}", error),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "foo.v1.User.address",
+                "foo.v1.User.contact.phone",
+                "foo.v1.Group.users"
+            ]
+        );
+        assert!(schema
+            .contains("  name: z.string(),\n  contact: z.object({ email: z.string() }),\n});"));
+        assert!(schema.contains("export const GroupSchema = z.object({\n});"));
+
+        let error = to_schema(&Opts::default(), &p, &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "code generation failed with 3 error(s):\n  \
+             could not find type named: Unknown in message foo.v1.User, field address (line 5)\n  \
+             could not find type named: Phone in message foo.v1.User, field contact.phone (line 8)\n  \
+             could not find type named: Missing in message foo.v1.Group, field users (line 11)"
+        );
+
+        let opts = Opts {
+            lenient: true,
+            ..Default::default()
+        };
+        assert_eq!(to_schema(&opts, &p, &[]).unwrap(), schema);
+    }
+
+    #[test]
+    fn to_schema_strict_required() {
+        let p = crate::parser::parse(
+            &Opts::default(),
+            "file.proto",
+            r#"syntax = "proto2";
+message User { required string name = 1; optional int32 age = 2; }
+message Group { optional string name = 1; }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&Opts::default(), &p, &[]).unwrap();
+        assert!(schema.contains("  name: z.string(),\n  age: z.optional(z.number()),\n});"));
+
+        let opts = Opts {
+            strict_required: true,
+            ..Opts::default()
+        };
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains("  age: z.optional(z.number()),\n}).strict();"));
+        assert!(schema.contains("  name: z.optional(z.string()),\n});"));
+    }
+
+    #[test]
+    fn to_schema_negative_enum_values() {
+        let p = crate::parser::parse(
+            &Opts::default(),
+            "file.proto",
+            r#"syntax = "proto2";
+enum Status { UNKNOWN = -1; ACTIVE = 1; }
+enum Level { LOW = -2; NONE = 0; }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&Opts::default(), &p, &[]).unwrap();
+        assert!(schema.contains("export enum Status {\n  UNKNOWN = \"UNKNOWN\",\n"));
+        assert!(schema.contains("z.nativeEnum(Status).catch(Status.UNKNOWN);"));
+        assert!(schema.contains("z.nativeEnum(Level).catch(Level.NONE);"));
+    }
+
+    #[test]
+    fn to_schema_enum_styles() {
+        let source = r#"syntax = "proto3";
+enum Status { UNKNOWN = 0; ACTIVE = 2; }"#;
+        let schema = |enum_style| {
+            let opts = Opts {
+                enum_style,
+                ..Opts::default()
+            };
+            let p = crate::parser::parse(&opts, "file.proto", source).unwrap();
+            to_schema(&opts, &p, &[]).unwrap()
+        };
+
+        let native = schema(EnumStyle::NativeEnum);
+        assert!(native.contains("export enum Status {\n  UNKNOWN = \"UNKNOWN\",\n"));
+
+        let object = schema(EnumStyle::ConstObject);
+        assert!(object.contains("export const Status = {\n  UNKNOWN: \"UNKNOWN\",\n"));
+        assert!(object
+            .contains("} as const;\n\nexport type Status = (typeof Status)[keyof typeof Status];"));
+        assert!(object.contains("z.nativeEnum(Status).catch(Status.UNKNOWN);"));
+
+        let union = schema(EnumStyle::StringUnion);
+        assert!(union.contains(
+            "export const StatusSchema = z.enum([\"UNKNOWN\", \"ACTIVE\"]).catch(\"UNKNOWN\");"
+        ));
+        assert!(union.contains("export type Status = z.infer<typeof StatusSchema>;"));
+        assert!(!union.contains("export enum"));
+
+        let numeric = schema(EnumStyle::Numeric);
+        assert!(numeric.contains("export enum Status {\n  UNKNOWN = 0,\n  ACTIVE = 2,\n}"));
+        assert!(numeric.contains("z.nativeEnum(Status).catch(Status.UNKNOWN);"));
+    }
+
+    #[test]
+    fn to_schema_ts_proto_compat() {
+        let opts = Opts {
+            ts_proto_compat: true,
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+enum Color { COLOR_UNSPECIFIED = 0; COLOR_RED = 1; }
+message User { int64 id = 1; Color color = 2; }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            "export function colorFromJSON(object: unknown): Color {\n  return ColorSchema.parse(object);\n}"
+        ));
+        assert!(schema
+            .contains("export function colorToJSON(object: Color): string {\n  return object;\n}"));
+        assert!(schema.contains(
+            "export const User = {\n  fromJSON(object: unknown): User {\n    return UserSchema.parse(object);\n  },"
+        ));
+        assert!(schema.contains("typeof value === \"bigint\" ? value.toString() : value,\n    ));"));
+        assert!(schema.contains("  fromPartial(object: Partial<User>): User {\n"));
+
+        let opts = Opts {
+            enum_style: EnumStyle::Numeric,
+            ..opts
+        };
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains("  return Color[object];\n"));
+    }
+
+    #[test]
+    fn to_schema_strip_enum_prefix() {
+        let opts = Opts {
+            strip_enum_prefix: true,
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+enum HttpMethod { HTTP_METHOD_UNSPECIFIED = 0; HTTP_METHOD_GET = 1; }
+enum Level { LEVEL_UNSPECIFIED = 0; LEVEL_1 = 1; }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            "export enum HttpMethod {\n  UNSPECIFIED = \"HTTP_METHOD_UNSPECIFIED\",\n  GET = \"HTTP_METHOD_GET\",\n}"
+        ));
+        assert!(schema.contains("z.nativeEnum(HttpMethod).catch(HttpMethod.UNSPECIFIED);"));
+        // `1` is no valid member name
+        assert!(schema.contains("  LEVEL_1 = \"LEVEL_1\",\n"));
+        assert!(schema.contains("z.nativeEnum(Level).catch(Level.LEVEL_UNSPECIFIED);"));
+    }
+
+    #[test]
+    fn to_schema_json_name() {
+        let p = crate::parser::parse(
+            &Opts::default(),
+            "file.proto",
+            r#"syntax = "proto3";
+message User {
+  string user_name = 1 [json_name = "login"];
+  string user_id = 2 [json_name = "user-id"];
+  int32 age = 3;
+}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&Opts::default(), &p, &[]).unwrap();
+        assert!(schema
+            .contains("  login: z.string(),\n  \"user-id\": z.string(),\n  age: z.number(),\n"));
+    }
+
+    #[test]
+    fn to_schema_fingerprints() {
+        let opts = Opts {
+            fingerprints: true,
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+message User { string name = 1; }
+enum Kind { KIND_UNSPECIFIED = 0; }"#,
+        )
+        .unwrap();
+        let Elem::Message(msg) = &p.elems[0] else {
+            panic!("expected message");
+        };
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(&format!(
+            "export type User = z.infer<typeof UserSchema>;\n\n\
+             export const UserFingerprint = \"{:016x}\";\n",
+            crate::fingerprint::fingerprint(msg)
+        )));
+        assert!(schema.contains("export const KindFingerprint = \""));
+    }
+
+    #[test]
+    fn to_schema_any_helpers() {
+        let opts = Opts {
+            any_helpers: true,
+            runtime_module: "./protots-runtime".to_string(),
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message User { message Address {} }
+enum Kind { KIND_UNSPECIFIED = 0; }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains("import { registerSchemas } from \"./protots-runtime\";\n"));
+        assert!(schema.ends_with(
+            r#"registerSchemas({
+  "foo.v1.User.Address": User_AddressSchema,
+  "foo.v1.User": UserSchema,
+});
+
+"#
+        ));
+        assert!(runtime(&opts).contains("export function unpackAny(any: AnyMessage)"));
+    }
+
+    #[test]
+    fn runtime_bigint_json() {
+        let opts = Opts::default();
+        assert!(runtime(&opts).contains("export function jsonStringify("));
+        assert!(runtime(&opts).contains("export function jsonParse<T>("));
+
+        // 64 bit integers are strings already
+        let opts = Opts {
+            ts_lib_target: TsLibTarget::Es2017,
+            ..Opts::default()
+        };
+        assert!(!runtime(&opts).contains("jsonStringify"));
+    }
+
+    #[test]
+    fn to_schema_int64() {
+        let input = "syntax = \"proto3\";\nmessage Counter { int64 value = 1; }";
+        let schema = |int64: Option<Int64>, target: Target| {
+            let opts = Opts {
+                int64,
+                target,
+                ..Opts::default()
+            };
+            let p = crate::parser::parse(&opts, "file.proto", input).unwrap();
+            to_schema(&opts, &p, &[]).unwrap()
+        };
+
+        assert!(schema(None, Target::Zod).contains("  value: z.coerce.bigint(),\n"));
+        assert!(schema(Some(Int64::Number), Target::Zod).contains("  value: z.coerce.number(),\n"));
+        assert!(schema(Some(Int64::String), Target::Zod)
+            .contains("  value: z.union([z.string(), z.number()]).transform(String),\n"));
+        #[cfg(feature = "interfaces")]
+        assert!(schema(Some(Int64::String), Target::Interfaces).contains("  value: string;\n"));
+    }
+
+    #[test]
+    fn to_schema_bytes() {
+        let input = "syntax = \"proto3\";\nmessage Blob { bytes data = 1; }";
+        let schema = |bytes: Bytes, target: Target| {
+            let opts = Opts {
+                bytes,
+                target,
+                ..Opts::default()
+            };
+            let p = crate::parser::parse(&opts, "file.proto", input).unwrap();
+            to_schema(&opts, &p, &[]).unwrap()
+        };
+
+        assert!(schema(Bytes::Base64, Target::Zod)
+            .contains("  data: z.string().regex(/^[A-Za-z0-9+\\/_-]*={0,2}$/),\n"));
+        assert!(schema(Bytes::Uint8Array, Target::Zod).contains(
+            "  data: z.union([z.instanceof(Uint8Array), z.string().regex(/^[A-Za-z0-9+\\/_-]*={0,2}$/)\
+             .transform((value) => Uint8Array.from(atob(value.replace(/-/g, \"+\").replace(/_/g, \"/\")), \
+             (chr) => chr.charCodeAt(0)))]),\n"
+        ));
+        #[cfg(feature = "interfaces")]
+        assert!(schema(Bytes::Uint8Array, Target::Interfaces).contains("  data: Uint8Array;\n"));
+    }
+
+    #[test]
+    fn to_schema_proto_names() {
+        let opts = Opts {
+            proto_names: true,
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message User { enum Kind { KIND_UNSPECIFIED = 0; } }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            "export type User = z.infer<typeof UserSchema>;\n\n\
+             export const UserProtoName = \"foo.v1.User\";\n"
+        ));
+        assert!(schema.contains("export const User_KindProtoName = \"foo.v1.User.Kind\";\n"));
+    }
+
+    #[test]
+    fn to_schema_well_known_types() {
+        let opts = Opts {
+            wkt_schemas: vec![
+                WktSchema::parse("Duration=@/time#DurationSchema").unwrap(),
+                WktSchema::parse("FieldMask=z.string().min(1)").unwrap(),
+            ],
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message Event {
+  google.protobuf.Timestamp at = 1;
+  google.protobuf.Duration took = 2;
+  .google.protobuf.FieldMask mask = 3;
+  repeated google.protobuf.Any details = 4;
+  google.protobuf.Struct labels = 5;
+  google.protobuf.Value value = 6;
+  google.protobuf.ListValue values = 7;
+  google.protobuf.Empty nothing = 8;
+  google.protobuf.Int64Value count = 9;
+  optional google.protobuf.StringValue name = 10;
+}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains("import { DurationSchema } from \"@/time\";\n"));
+        assert!(
+            schema.contains(
+                "  at: z.coerce.date(),\n\
+             \x20 took: DurationSchema,\n\
+             \x20 mask: z.string().min(1),\n\
+             \x20 details: z.array(z.object({ \"@type\": z.string() }).passthrough()),\n\
+             \x20 labels: z.record(z.string(), z.unknown()),\n\
+             \x20 value: z.unknown(),\n\
+             \x20 values: z.array(z.unknown()),\n\
+             \x20 nothing: z.object({}),\n\
+             \x20 count: z.coerce.bigint().nullable(),\n\
+             \x20 name: z.optional(z.string().nullable()),\n"
+            ),
+            "{}",
+            schema
+        );
+    }
+
+    #[test]
+    fn to_schema_map_fields() {
+        let opts = Opts::default();
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+message A { map<string, B.Kind> kinds = 1; map<uint64, Top> tops = 2; map<bool, string> flags = 3; }
+message B { enum Kind { KIND_UNSPECIFIED = 0; } map<string, Kind> own = 1; }
+enum Top { TOP_UNSPECIFIED = 0; }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            "  kinds: z.record(z.string(), z.lazy(() => B_KindSchema)),\n\
+             \x20 tops: z.record(z.string().regex(/^[0-9]+$/), z.lazy(() => TopSchema)),\n\
+             \x20 flags: z.record(z.enum([\"true\", \"false\"]), z.string()),\n"
+        ));
+        // declared before the message already
+        assert!(schema.contains("  own: z.record(z.string(), B_KindSchema),\n"));
+    }
+
+    #[test]
+    fn to_schema_forward_message_references() {
+        let source = r#"syntax = "proto3";
+message Node { repeated Node children = 1; Leaf leaf = 2; Inner inner = 3; message Inner {} }
+message Leaf { Node parent = 1; }"#;
+        let opts = Opts::default();
+        let p = crate::parser::parse(&opts, "rec.proto", source).unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            "export const NodeSchema = z.object({\n\
+             \x20 children: z.array(z.lazy(() => NodeSchema)),\n\
+             \x20 leaf: z.lazy(() => LeafSchema),\n\
+             \x20 inner: Node_InnerSchema,\n\
+             });\n"
+        ));
+        // declared before the message already
+        assert!(schema.contains("  parent: NodeSchema,\n"));
+
+        let opts = Opts {
+            compat: crate::compat::Compat::parse("0.1").unwrap(),
+            ..Opts::default()
+        };
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains("  leaf: LeafSchema,\n"));
+    }
+
+    #[test]
+    fn to_schema_service_metadata() {
+        let opts = Opts {
+            method_options: vec!["my.api.timeout_ms".to_string(), "my.retry".to_string()],
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+message Req {}
+service Users {
+  rpc Get(Req) returns (Req) {
+    option idempotency_level = NO_SIDE_EFFECTS;
+    option (my.api.timeout_ms) = 500;
+    option (other.ignored) = true;
+    option (my.retry) = { max_attempts: 3 codes: "UNAVAILABLE" codes: "ABORTED" backoff { initial_ms: 10 } };
+  }
+  rpc Update(Req) returns (Req);
+}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.ends_with(
+            r#"export const UsersMetadata = {
+  Get: { idempotencyLevel: "NO_SIDE_EFFECTS", timeoutMs: 500, retry: { maxAttempts: 3, codes: ["UNAVAILABLE", "ABORTED"], backoff: { initialMs: 10 } } },
+  Update: {},
+} as const;
+
+"#
+        ));
+    }
+
+    #[test]
+    fn to_schema_service_metadata_duplicate_keys() {
+        let opts = Opts {
+            method_options: vec!["my.retry".to_string(), "other.retry".to_string()],
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+message Req {}
+service Users {
+  rpc Get(Req) returns (Req) {
+    option (my.retry) = 1;
+    option (other.retry) = 2;
+  }
+}"#,
+        )
+        .unwrap();
+
+        let error = to_schema(&opts, &p, &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "code generation failed with 1 error(s):\n  invalid argument: options my.retry and \
+             other.retry map to the same metadata key `retry` in service Users, method Get (line 4)"
+        );
+    }
+
+    #[test]
+    fn to_schema_service_client() {
+        let opts = Opts {
+            clients: true,
+            deprecation_warnings: true,
+            runtime_module: "./protots-runtime".to_string(),
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message Req {}
+service Users {
+  rpc Get(Req) returns (Req) {
+    option deprecated = true;
+  }
+  rpc Watch(Req) returns (stream Req);
+}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema
+            .contains(r#"import { Transport, call, warnDeprecated } from "./protots-runtime";"#));
+        assert!(schema.ends_with(
+            r#"export class UsersClient {
+  constructor(private readonly transport: Transport) {}
+
+  /** @deprecated */
+  get(request: Req): Promise<Req> {
+    warnDeprecated("foo.v1.Users.Get");
+    return call(
+      this.transport,
+      { service: "foo.v1.Users", method: "Get", path: "/foo.v1.Users/Get" },
+      request,
+      (value) => ReqSchema.parse(value),
+    );
+  }
+
+  // streaming method Watch is not supported yet
+}
+
+"#
+        ));
+    }
+
+    #[test]
+    fn to_schema_deprecated_clients() {
+        let opts = Opts {
+            clients: true,
+            runtime_module: "./protots-runtime".to_string(),
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+message Req {}
+service Legacy {
+  option deprecated = true;
+  rpc Get(Req) returns (Req);
+}
+service Users {
+  rpc Get(Req) returns (Req);
+  rpc Old(Req) returns (Req) { option deprecated = true; }
+}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            "/** @deprecated */\nexport class LegacyClient {\n  constructor(private readonly transport: Transport) {}\n\n  /** @deprecated */\n  get(request: Req)"
+        ));
+        assert!(schema.contains(
+            "\nexport class UsersClient {\n  constructor(private readonly transport: Transport) {}\n\n  get(request: Req)"
+        ));
+        assert!(schema.contains(
+            "  /** @deprecated */\n  old(request: Req): Promise<Req> {\n    return call(\n"
+        ));
+        // warnings on calls are opt-in
+        assert!(!schema.contains("warnDeprecated"));
+    }
+
+    #[test]
+    fn to_schema_client_method_names() {
+        let opts = Opts {
+            clients: true,
+            method_case: MethodCase::Snake,
+            method_suffix: "_async".to_string(),
+            method_paths: true,
+            runtime_module: "./protots-runtime".to_string(),
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message Req {}
+service Users { rpc GetHTTPUser(Req) returns (Req); rpc ListUsers2(Req) returns (Req); }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            "export const UsersPaths = {\n\
+             \x20 GetHTTPUser: \"/foo.v1.Users/GetHTTPUser\",\n\
+             \x20 ListUsers2: \"/foo.v1.Users/ListUsers2\",\n\
+             } as const;\n"
+        ));
+        assert!(schema.contains("  get_http_user_async(request: Req): Promise<Req> {\n"));
+        assert!(schema.contains("  list_users2_async(request: Req): Promise<Req> {\n"));
+
+        let opts = Opts {
+            method_case: MethodCase::Camel,
+            method_prefix: "api".to_string(),
+            ..opts
+        };
+        assert_eq!(method_name(&opts, "GetUser"), "apiGetUser_async");
+    }
+
+    #[test]
+    fn to_schema_routes() {
+        let opts = Opts {
+            routes: true,
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+service Users {
+  rpc Get(Req) returns (Res);
+  rpc Watch(Req) returns (stream google.protobuf.Empty);
+}
+message Req {}
+message Res {}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(
+            schema.ends_with(
+                r#"export type Res = z.infer<typeof ResSchema>;
+
+export const UsersRoutes = {
+  Get: {
+    path: "/foo.v1.Users/Get",
+    requestSchema: ReqSchema,
+    responseSchema: ResSchema,
+    requestStream: false,
+    responseStream: false,
+  },
+  Watch: {
+    path: "/foo.v1.Users/Watch",
+    requestSchema: ReqSchema,
+    responseSchema: z.object({}),
+    requestStream: false,
+    responseStream: true,
+  },
+} as const;
+
+"#
+            ),
+            "{}",
+            schema
+        );
+    }
+
+    #[test]
+    fn to_schema_validation_hook() {
+        let opts = Opts {
+            validation_hook: Some("@/telemetry".to_string()),
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            "syntax = \"proto3\";\npackage foo.v1;\nmessage User { string name = 1; }",
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains("import { onValidationFailure } from \"@/telemetry\";\n"));
+        assert!(schema.contains("function withValidationHook<T extends z.ZodTypeAny>"));
+        assert!(schema.contains(
+            r#"export const UserSchema = withValidationHook("foo.v1.User", z.object({
+  name: z.string(),
+}));"#
+        ));
+    }
+
+    #[test]
+    fn to_schema_size_rules() {
+        let opts = Opts::default();
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+message User {
+  string name = 1 [(validate.rules).string.max_len = 64];
+  repeated string tags = 2 [(validate.rules).repeated = {min_items: 1, max_items: 5}];
+  optional string code = 3 [(buf.validate.field).string = {len: 4}];
+  int32 age = 4 [(validate.rules).int32.lte = 150];
+}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            r#"export const UserSchema = z.object({
+  name: z.string().max(64),
+  tags: z.array(z.string()).min(1).max(5),
+  code: z.optional(z.string().length(4)),
+  age: z.number(),
+});"#
+        ));
+    }
+
+    #[test]
+    fn to_schema_compat_header() {
+        let p = proto(Elem::Enum(Enum {
+            name: "Kind".to_string(),
+            values: vec![],
+            options: vec![],
+            deprecated: false,
+            span: Span::default(),
+        }));
+        let opts = Opts {
+            file: "./protos/file.proto".to_string(),
+            compat: Compat::parse("0.1").unwrap(),
+            ..Opts::default()
+        };
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.starts_with(
+            "//\n// Code generated by protots - DO NOT EDIT\n// Source: ./protos/file.proto\n//\n"
+        ));
+    }
+
+    #[test]
+    fn to_schema_es2017_target() {
+        let opts = Opts {
+            ts_lib_target: TsLibTarget::Es2017,
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+message Counter {
+  int64 total = 1;
+  repeated fixed64 ids = 2;
+}"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            r#"export const CounterSchema = z.object({
+  total: z.union([z.string(), z.number()]).transform(String),
+  ids: z.array(z.union([z.string(), z.number()]).transform(String)),
+});"#
+        ));
+
+        let runtime = runtime(&opts);
+        assert!(runtime.contains("(transport.interceptors || [])"));
+        for construct in ["??", "?.", "globalThis"] {
+            assert!(!runtime.contains(construct), "{}", construct);
+        }
+    }
+
+    #[test]
+    fn to_schema_base_schema() {
+        let opts = Opts {
+            base_schemas: vec![
+                BaseSchema::parse("*Event=@/events#BaseEventSchema").unwrap(),
+                BaseSchema::parse("foo.v1.*=./base#BaseSchema").unwrap(),
+            ],
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto3";
+package foo.v1;
+message UserCreatedEvent { string name = 1; }
+message User { string name = 1; }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains("import { BaseEventSchema } from \"@/events\";\n"));
+        assert!(schema.contains("import { BaseSchema } from \"./base\";\n"));
+        assert!(schema.contains(
+            r#"export const UserCreatedEventSchema = BaseEventSchema.merge(z.object({
+  name: z.string(),
+}));"#
+        ));
+        assert!(schema.contains("export const UserSchema = BaseSchema.merge(z.object({\n"));
+    }
+
+    #[test]
+    fn to_schema_inline_deps() {
+        let opts = Opts {
+            inline_deps: true,
+            ..Opts::default()
+        };
+        let parse = |file: &str, input: &str| crate::parser::parse(&opts, file, input).unwrap();
+        let common = parse(
+            "common.proto",
+            r#"syntax = "proto3";
+message Money { Currency currency = 1; message Cents { int32 value = 1; } Cents cents = 2; }
+enum Currency { CURRENCY_UNSPECIFIED = 0; }
+message Unused {}"#,
+        );
+        let many = parse(
+            "many.proto",
+            "syntax = \"proto3\";\nmessage A {}\nmessage B {}\nmessage C {}",
+        );
+        let p = parse(
+            "order.proto",
+            r#"syntax = "proto3";
+import "common.proto";
+import "many.proto";
+message Order { Money total = 1; A a = 2; B b = 3; C c = 4; }"#,
+        );
+
+        let schema = to_schema(&opts, &p, &[common, many]).unwrap();
+        assert!(schema.contains("import { ASchema, BSchema, CSchema } from \"./many\";\n"));
+        assert!(!schema.contains("./common"));
+        assert!(!schema.contains("UnusedSchema"));
+        let currency = schema.find("export const CurrencySchema").unwrap();
+        let money = schema.find("export const MoneySchema").unwrap();
+        let order = schema.find("export const OrderSchema").unwrap();
+        assert!(currency < money && money < order, "{}", schema);
+        assert_eq!(schema.matches("export const Money_CentsSchema").count(), 1);
+    }
+
+    #[test]
+    fn to_schema_zod_v4() {
+        let opts = Opts {
+            zod_version: ZodVersion::V4,
+            strict_required: true,
+            validation_hook: Some("@/telemetry".to_string()),
+            base_schemas: vec![BaseSchema::parse("*Event=@/events#BaseEventSchema").unwrap()],
+            ..Opts::default()
+        };
+        let p = crate::parser::parse(
+            &opts,
+            "file.proto",
+            r#"syntax = "proto2";
+message CreatedEvent { required string name = 1; }
+enum Kind { KIND_UNSPECIFIED = 0; }"#,
+        )
+        .unwrap();
+
+        let schema = to_schema(&opts, &p, &[]).unwrap();
+        assert!(schema.contains(
+            r#"export const CreatedEventSchema = withValidationHook("CreatedEvent", z.strictObject({
+  ...BaseEventSchema.shape,
+  name: z.string(),
+}));"#
+        ));
+        assert!(
+            schema.contains("export const KindSchema = z.enum(Kind).catch(Kind.KIND_UNSPECIFIED);")
+        );
+        assert!(schema.contains("<T extends z.ZodType>"));
+        assert!(!schema.contains("z.ParseParams"));
+    }
 }
@@ -0,0 +1,267 @@
+//! Consistency check of the generation targets (see `--verify-targets`): the
+//! typescript modules of two targets, e.g. zod and interfaces, are generated
+//! from the same file and have to agree in the shape of every type, i.e. the
+//! names of messages and enums, the fields of messages and their optionality
+//! and the values of enums. This guards against a backend drifting when new
+//! proto features are added.
+//!
+//! The shapes are reported by the backends themselves, recording the types
+//! they declare (and export) along with the keys of their properties as they
+//! generate them (see `typescript::to_shapes`).
+
+use std::collections::BTreeMap;
+
+use crate::errors::PtError;
+use crate::parser::Proto;
+use crate::{typescript, Opts, Target};
+
+/// Shape of a generated message or enum.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Shape {
+    /// fields of a message by key, whether they are optional
+    fields: BTreeMap<String, bool>,
+    /// values of an enum, in order
+    values: Vec<String>,
+}
+
+impl Shape {
+    pub(crate) fn message(properties: impl IntoIterator<Item = Property>) -> Shape {
+        Shape {
+            fields: properties
+                .into_iter()
+                .map(|property| (property.key, property.optional))
+                .collect(),
+            values: Vec::new(),
+        }
+    }
+
+    pub(crate) fn enumeration(values: impl IntoIterator<Item = String>) -> Shape {
+        Shape {
+            fields: BTreeMap::new(),
+            values: values.into_iter().collect(),
+        }
+    }
+}
+
+/// Property of a generated message, keyed like in the JSON representation.
+#[derive(Debug)]
+pub(crate) struct Property {
+    pub key: String,
+    pub optional: bool,
+}
+
+impl Property {
+    pub(crate) fn new(key: impl Into<String>, optional: bool) -> Property {
+        Property {
+            key: key.into(),
+            optional,
+        }
+    }
+}
+
+/// Shapes of the declarations of a generated module, by type name.
+pub(crate) type Shapes = BTreeMap<String, Shape>;
+
+/// Difference in the shape of a type between the generation targets.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    pub type_name: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.type_name, self.message)
+    }
+}
+
+/// Generate the typescript modules of the given proto for both targets and
+/// return where their type shapes disagree, along with the number of types
+/// compared.
+pub fn verify_targets(
+    opts: &Opts,
+    targets: (Target, Target),
+    proto: &Proto,
+    deps: &[Proto],
+) -> Result<(usize, Vec<Mismatch>), PtError> {
+    let generate = |target| {
+        let opts = Opts {
+            target,
+            ..opts.clone()
+        };
+        typescript::to_shapes(&opts, proto, deps)
+    };
+    let (first, second) = (generate(targets.0)?, generate(targets.1)?);
+
+    let names = (targets.0.name(), targets.1.name());
+    Ok((first.len(), compare(names, &first, &second)))
+}
+
+fn compare(targets: (&str, &str), first: &Shapes, second: &Shapes) -> Vec<Mismatch> {
+    let mismatch = |type_name: &str, message: String| Mismatch {
+        type_name: type_name.to_string(),
+        message,
+    };
+    let optionality = |optional: bool| if optional { "optional" } else { "required" };
+    let mut mismatches = Vec::new();
+
+    for (name, shape) in first {
+        let Some(other) = second.get(name) else {
+            mismatches.push(mismatch(name, format!("missing in {}", targets.1)));
+            continue;
+        };
+        for (field, optional) in &shape.fields {
+            match other.fields.get(field) {
+                None => mismatches.push(mismatch(
+                    name,
+                    format!("field {} missing in {}", field, targets.1),
+                )),
+                Some(other) if other != optional => mismatches.push(mismatch(
+                    name,
+                    format!(
+                        "field {} is {} in {} but {} in {}",
+                        field,
+                        optionality(*optional),
+                        targets.0,
+                        optionality(*other),
+                        targets.1
+                    ),
+                )),
+                Some(_) => (),
+            }
+        }
+        for field in other.fields.keys() {
+            if !shape.fields.contains_key(field) {
+                mismatches.push(mismatch(
+                    name,
+                    format!("field {} missing in {}", field, targets.0),
+                ));
+            }
+        }
+        if shape.values != other.values {
+            mismatches.push(mismatch(
+                name,
+                format!(
+                    "values are {} in {} but {} in {}",
+                    shape.values.join(", "),
+                    targets.0,
+                    other.values.join(", "),
+                    targets.1
+                ),
+            ));
+        }
+    }
+
+    for name in second.keys().filter(|name| !first.contains_key(*name)) {
+        mismatches.push(mismatch(name, format!("missing in {}", targets.0)));
+    }
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, verify_targets, Mismatch, Property, Shape, Shapes};
+    use crate::{Opts, Target};
+
+    const INPUT: &str = r#"syntax = "proto3";
+package foo.v1;
+// user of the system
+message User {
+  optional string name = 1;
+  map<string, int32> labels = 2;
+  oneof contact {
+    string email = 3;
+    string phone = 4;
+  }
+  repeated Kind kinds = 5;
+  Address address = 6;
+  enum Kind {
+    KIND_UNSPECIFIED = 0;
+    ADMIN = 1;
+  }
+}
+message Address { string street = 1; }"#;
+
+    #[test]
+    fn verify_targets_agree() {
+        let opts = Opts::default();
+        let proto = crate::parser::parse(&opts, "foo/v1/user.proto", INPUT).unwrap();
+
+        // all other targets enabled in this build
+        for (_, other) in &Target::ALL[1..] {
+            let (types, mismatches) =
+                verify_targets(&opts, (Target::Zod, *other), &proto, &[]).unwrap();
+            assert_eq!(mismatches, vec![], "{}", other.name());
+            assert_eq!(types, 3);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "interfaces")]
+    fn verify_targets_diverge() {
+        // nested messages used by their parent only are private to the zod
+        // module, the interfaces export them regardless
+        let opts = Opts {
+            private_nested: true,
+            ..Opts::default()
+        };
+        let input = "syntax = \"proto3\";\nmessage User {\n  Tag tag = 1;\n  \
+                     message Tag { string label = 1; }\n}";
+        let proto = crate::parser::parse(&opts, "user.proto", input).unwrap();
+
+        let (types, mismatches) =
+            verify_targets(&opts, (Target::Zod, Target::Interfaces), &proto, &[]).unwrap();
+        assert_eq!(types, 1);
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(Mismatch::to_string)
+                .collect::<Vec<_>>(),
+            ["User_Tag: missing in zod"]
+        );
+    }
+
+    #[test]
+    fn compare_shapes() {
+        let zod = Shapes::from([
+            (
+                "User".to_string(),
+                Shape::message([Property::new("name", true), Property::new("age", false)]),
+            ),
+            (
+                "Kind".to_string(),
+                Shape::enumeration(["ADMIN".to_string()]),
+            ),
+            (
+                "Role".to_string(),
+                Shape::enumeration(["OWNER".to_string()]),
+            ),
+        ]);
+        let interfaces = Shapes::from([
+            (
+                "User".to_string(),
+                Shape::message([Property::new("name", false), Property::new("email", true)]),
+            ),
+            (
+                "Role".to_string(),
+                Shape::enumeration(["OWNER".to_string(), "GUEST".to_string()]),
+            ),
+        ]);
+
+        let mismatches = compare(("zod", "interfaces"), &zod, &interfaces)
+            .iter()
+            .map(Mismatch::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            mismatches,
+            [
+                "Kind: missing in interfaces",
+                "Role: values are OWNER in zod but OWNER, GUEST in interfaces",
+                "User: field age missing in interfaces",
+                "User: field name is optional in zod but required in interfaces",
+                "User: field email missing in zod",
+            ]
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Watch mode (see the `watch` command): the proto files below a directory are
+//! polled for changes and the outputs affected regenerated in batches, i.e.
+//! the typescript of the files changed and of the files importing them.
+//!
+//! Saves in quick succession (e.g. by editors writing several files or
+//! formatters running on save) are debounced into a single batch. With
+//! `--error-overlay` the current errors are written to a typescript module
+//! (see `OVERLAY_FILE`) as well, so the frontend's dev server surfaces them
+//! instead of a terminal that is easily missed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::errors::PtError;
+use crate::parser::{self, Elem};
+use crate::{imports, typescript, Io, Opts};
+
+/// Module of the output directory listing the current errors.
+pub const OVERLAY_FILE: &str = "__protots_errors.ts";
+
+/// Interval the proto files are checked for changes at.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct Watcher<'a> {
+    opts: &'a Opts,
+    dir: PathBuf,
+    out_dir: PathBuf,
+    error_overlay: bool,
+    /// contents of the proto files as of the last scan
+    contents: BTreeMap<PathBuf, String>,
+    /// files imported by each proto file, as import names
+    imports: BTreeMap<PathBuf, Vec<String>>,
+    /// errors of the last generation of each file
+    errors: BTreeMap<PathBuf, String>,
+}
+
+/// Outcome of the regeneration of a batch of changes.
+#[derive(Debug, Default, PartialEq)]
+pub struct Batch {
+    /// outputs whose content changed
+    pub written: Vec<PathBuf>,
+    /// files that failed to generate, along with their error
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl<'a> Watcher<'a> {
+    pub fn new(opts: &'a Opts, dir: &Path, out_dir: &Path, error_overlay: bool) -> Watcher<'a> {
+        Watcher {
+            opts,
+            dir: dir.to_path_buf(),
+            out_dir: out_dir.to_path_buf(),
+            error_overlay,
+            contents: BTreeMap::new(),
+            imports: BTreeMap::new(),
+            errors: BTreeMap::new(),
+        }
+    }
+
+    /// Regenerate whenever files change until the process is stopped, starting
+    /// with all files.
+    pub fn run(&mut self, io: &mut Io, debounce: Duration) -> Result<(), PtError> {
+        writeln!(io.stdout, "watching {}", self.dir.display())?;
+        loop {
+            let mut changed = self.scan()?;
+            if changed.is_empty() {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            // wait for the files to settle
+            loop {
+                std::thread::sleep(debounce);
+                let more = self.scan()?;
+                if more.is_empty() {
+                    break;
+                }
+                changed.extend(more);
+            }
+
+            let batch = self.regenerate(&changed)?;
+            for path in &batch.written {
+                writeln!(io.stdout, "wrote {}", path.display())?;
+            }
+            for (path, error) in &batch.failed {
+                writeln!(io.stderr, "{}: {}", path.display(), error)?;
+            }
+            for message in self.opts.messages.take() {
+                writeln!(io.stderr, "{}", message)?;
+            }
+            writeln!(
+                io.stdout,
+                "{} file(s) changed, {} output(s) written, {} error(s)",
+                changed.len(),
+                batch.written.len(),
+                self.errors.len()
+            )?;
+        }
+    }
+
+    /// Proto files added, changed and removed since the last scan.
+    pub fn scan(&mut self) -> Result<BTreeSet<PathBuf>, PtError> {
+        let mut paths = Vec::new();
+        imports::proto_files(self.opts, &self.dir, &mut paths)?;
+
+        let mut changed = BTreeSet::new();
+        let mut contents = BTreeMap::new();
+        for path in paths {
+            // files may be removed between listing and reading them
+            let Ok(content) = self.opts.files.read_to_string(&path) else {
+                continue;
+            };
+            if self.contents.get(&path) != Some(&content) {
+                let imports = parser::parse_partial(self.opts, "", &content)
+                    .map(|partial| {
+                        partial
+                            .proto
+                            .elems
+                            .iter()
+                            .filter_map(|elem| match elem {
+                                Elem::Import { name, .. } => Some(name.clone()),
+                                _ => None,
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                self.imports.insert(path.clone(), imports);
+                changed.insert(path.clone());
+            }
+            contents.insert(path, content);
+        }
+
+        for path in self.contents.keys() {
+            if !contents.contains_key(path) {
+                self.imports.remove(path);
+                self.errors.remove(path);
+                changed.insert(path.clone());
+            }
+        }
+        self.contents = contents;
+
+        Ok(changed)
+    }
+
+    /// Regenerate the outputs of the changed files and of all files importing
+    /// them, directly or transitively.
+    pub fn regenerate(&mut self, changed: &BTreeSet<PathBuf>) -> Result<Batch, PtError> {
+        let mut batch = Batch::default();
+
+        for path in self.affected(changed) {
+            match self.generate(&path) {
+                Ok(output) => {
+                    self.errors.remove(&path);
+                    let target = self.output_path(&path);
+                    if self.write(&target, output.as_bytes())? {
+                        batch.written.push(target);
+                    }
+                }
+                Err(err) => {
+                    let error = err.to_string();
+                    self.errors.insert(path.clone(), error.clone());
+                    batch.failed.push((path, error));
+                }
+            }
+        }
+
+        if self.error_overlay {
+            let target = self.out_dir.join(OVERLAY_FILE);
+            if self.write(&target, self.overlay().as_bytes())? {
+                batch.written.push(target);
+            }
+        }
+
+        Ok(batch)
+    }
+
+    /// Existing files that are changed or import a changed file.
+    fn affected(&self, changed: &BTreeSet<PathBuf>) -> BTreeSet<PathBuf> {
+        let mut affected = changed
+            .iter()
+            .filter(|path| self.contents.contains_key(*path))
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let mut pending = changed.iter().cloned().collect::<Vec<_>>();
+        while let Some(changed) = pending.pop() {
+            let name = self.import_name(&changed);
+            for (path, imports) in &self.imports {
+                if imports.contains(&name) && affected.insert(path.clone()) {
+                    pending.push(path.clone());
+                }
+            }
+        }
+        affected
+    }
+
+    fn generate(&self, path: &Path) -> Result<String, PtError> {
+        // the watched directory is an include path so that the files can
+        // import each other
+        let mut opts = self.opts.clone();
+        opts.file = path.to_string_lossy().to_string();
+        let dir = self.dir.to_string_lossy().to_string();
+        if !opts.include_paths.contains(&dir) {
+            opts.include_paths.push(dir);
+        }
+
+        let input = opts.files.read_to_string(path)?;
+        let proto = parser::parse(&opts, &imports::import_name(&opts), &input)?;
+        let deps = imports::resolve(&opts, &proto)?;
+        Ok(format!(
+            "{}\n",
+            typescript::to_schema(&opts, &proto, &deps)?
+        ))
+    }
+
+    /// Write the file unless its content is unchanged, which would trigger
+    /// the dev servers watching the outputs needlessly.
+    fn write(&self, path: &Path, content: &[u8]) -> Result<bool, PtError> {
+        if self
+            .opts
+            .files
+            .read(path)
+            .is_ok_and(|current| current == content)
+        {
+            return Ok(false);
+        }
+        self.opts.files.write(path, content)?;
+        Ok(true)
+    }
+
+    fn import_name(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.dir).unwrap_or(path);
+        relative.to_string_lossy().replace('\\', "/")
+    }
+
+    fn output_path(&self, path: &Path) -> PathBuf {
+        let name = self.import_name(path);
+        self.out_dir
+            .join(format!("{}.ts", name.trim_end_matches(".proto")))
+    }
+
+    /// Module exporting the current errors, to be imported by the frontend
+    /// in development to put them in front of the developer.
+    fn overlay(&self) -> String {
+        let mut str = String::from(
+            "//\n// Code generated by protots - DO NOT EDIT\n//\n\n\
+             export interface ProtoError {\n  file: string;\n  message: string;\n}\n\n",
+        );
+        if self.errors.is_empty() {
+            str.push_str("export const errors: ProtoError[] = [];\n");
+            return str;
+        }
+        str.push_str("export const errors: ProtoError[] = [\n");
+        for (path, error) in &self.errors {
+            str.push_str(&format!(
+                "  {{ file: {:?}, message: {:?} }},\n",
+                self.import_name(path),
+                error
+            ));
+        }
+        str.push_str("];\n");
+        str
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    use super::{Watcher, OVERLAY_FILE};
+    use crate::provider::{FileProvider, Files, Memory};
+    use crate::Opts;
+
+    #[test]
+    fn watch_regenerates_affected_outputs() {
+        let files = Rc::new(Memory::new([
+            (
+                "protos/common/date.proto",
+                "syntax = \"proto3\";\npackage common;\nmessage Date {}",
+            ),
+            (
+                "protos/user.proto",
+                "syntax = \"proto3\";\nimport \"common/date.proto\";\n\
+                 message User { common.Date birthday = 1; }",
+            ),
+            (
+                "protos/other.proto",
+                "syntax = \"proto3\";\nmessage Other {}",
+            ),
+        ]));
+        let opts = Opts {
+            files: Files(files.clone()),
+            ..Opts::default()
+        };
+        let mut watcher = Watcher::new(&opts, Path::new("protos"), Path::new("out"), true);
+
+        let changed = watcher.scan().unwrap();
+        assert_eq!(changed.len(), 3);
+        let batch = watcher.regenerate(&changed).unwrap();
+        assert!(batch.failed.is_empty(), "{:?}", batch.failed);
+        assert_eq!(batch.written.len(), 4);
+        assert!(files
+            .read_to_string(Path::new("out/user.ts"))
+            .unwrap()
+            .contains("import { DateSchema } from \"./common/date\";"));
+        assert_eq!(watcher.scan().unwrap(), BTreeSet::new());
+
+        // a broken dependency fails its importers as well
+        files
+            .write(
+                Path::new("protos/common/date.proto"),
+                b"syntax = \"proto3\";\npackage common;\nmessage Day {}",
+            )
+            .unwrap();
+        let changed = watcher.scan().unwrap();
+        assert_eq!(
+            changed,
+            BTreeSet::from([PathBuf::from("protos/common/date.proto")])
+        );
+        let batch = watcher.regenerate(&changed).unwrap();
+        assert_eq!(batch.failed.len(), 1);
+        assert_eq!(batch.failed[0].0, Path::new("protos/user.proto"));
+        assert_eq!(
+            batch.written,
+            [
+                Path::new("out/common/date.ts"),
+                &Path::new("out").join(OVERLAY_FILE)
+            ]
+        );
+        let overlay = files
+            .read_to_string(&Path::new("out").join(OVERLAY_FILE))
+            .unwrap();
+        assert!(overlay.contains("{ file: \"user.proto\", message: "));
+    }
+}
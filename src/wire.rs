@@ -0,0 +1,199 @@
+//! Minimal protobuf wire format encoding and decoding, just enough to read and
+//! write descriptors and plugin requests.
+
+use crate::errors::PtError;
+
+#[derive(Default)]
+pub struct Encoder {
+    pub buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn raw_varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.buf.push(byte);
+                return;
+            }
+            self.buf.push(byte | 0x80);
+        }
+    }
+
+    fn key(&mut self, number: u32, wire_type: u8) {
+        self.raw_varint(((number as u64) << 3) | wire_type as u64);
+    }
+
+    pub fn int32(&mut self, number: u32, value: i32) {
+        self.key(number, 0);
+        // negative values are sign extended to 64 bits
+        self.raw_varint(value as i64 as u64);
+    }
+
+    pub fn bool(&mut self, number: u32, value: bool) {
+        self.key(number, 0);
+        self.raw_varint(value as u64);
+    }
+
+    pub fn bytes(&mut self, number: u32, value: &[u8]) {
+        self.key(number, 2);
+        self.raw_varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn string(&mut self, number: u32, value: &str) {
+        self.bytes(number, value.as_bytes());
+    }
+
+    pub fn message<F>(&mut self, number: u32, encode: F) -> Result<(), PtError>
+    where
+        F: FnOnce(&mut Encoder) -> Result<(), PtError>,
+    {
+        let mut inner = Encoder::default();
+        encode(&mut inner)?;
+        self.bytes(number, &inner.buf);
+        Ok(())
+    }
+}
+
+pub enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    /// Fixed width values are not used by any descriptor field we read.
+    Fixed,
+}
+
+impl<'a> Value<'a> {
+    pub fn bytes(&self) -> Result<&'a [u8], PtError> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => Err(invalid("expected length-delimited value")),
+        }
+    }
+
+    pub fn string(&self) -> Result<String, PtError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| invalid("invalid utf-8 string"))
+    }
+
+    pub fn int32(&self) -> Result<i32, PtError> {
+        match self {
+            Value::Varint(value) => Ok(*value as i32),
+            _ => Err(invalid("expected varint value")),
+        }
+    }
+
+    pub fn bool(&self) -> Result<bool, PtError> {
+        Ok(self.int32()? != 0)
+    }
+}
+
+/// Iterator over the (field number, value) pairs of an encoded message.
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Decoder<'a> {
+        Decoder { buf }
+    }
+
+    fn varint(&mut self) -> Result<u64, PtError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = self
+                .buf
+                .split_first()
+                .ok_or_else(|| invalid("truncated varint"))?;
+            self.buf = rest;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("varint too long"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PtError> {
+        if self.buf.len() < len {
+            return Err(invalid("truncated message"));
+        }
+        let (value, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(value)
+    }
+
+    fn field(&mut self) -> Result<(u32, Value<'a>), PtError> {
+        let key = self.varint()?;
+        let number = (key >> 3) as u32;
+        let value = match key & 0x7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            wire_type => return Err(invalid(&format!("unsupported wire type {}", wire_type))),
+        };
+        Ok((number, value))
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = Result<(u32, Value<'a>), PtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+
+        let field = self.field();
+        if field.is_err() {
+            // do not continue after malformed input
+            self.buf = &[];
+        }
+        Some(field)
+    }
+}
+
+fn invalid(message: &str) -> PtError {
+    PtError::InvalidDescriptor(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoder, Encoder, Value};
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let mut enc = Encoder::default();
+        enc.int32(1, 300);
+        enc.int32(2, -1);
+        enc.string(3, "foo");
+
+        let fields = Decoder::new(&enc.buf)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(fields.len(), 3);
+        assert!(matches!(fields[0], (1, Value::Varint(300))));
+        assert_eq!(fields[1].1.int32().unwrap(), -1);
+        assert_eq!(fields[2].1.string().unwrap(), "foo");
+    }
+
+    #[test]
+    fn decode_truncated() {
+        let mut enc = Encoder::default();
+        enc.string(1, "foo");
+
+        let truncated = &enc.buf[..enc.buf.len() - 1];
+        assert!(Decoder::new(truncated).any(|field| field.is_err()));
+    }
+}
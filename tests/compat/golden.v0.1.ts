@@ -0,0 +1,65 @@
+//
+// Code generated by protots - DO NOT EDIT
+// Source: golden.proto
+//
+
+import { z } from "zod";
+
+export enum User_Kind {
+  KIND_ADMIN = "KIND_ADMIN",
+  KIND_GUEST = "KIND_GUEST",
+}
+
+export const User_KindSchema = z.nativeEnum(User_Kind);
+
+export const User_TagSchema = z.object({
+  label: z.string(),
+  big: z.coerce.bigint(),
+  ratio: z.number(),
+});
+
+export type User_Tag = z.infer<typeof User_TagSchema>;
+
+export const UserSchema = z.object({
+  userName: z.string(),
+  avatar: z.string(),
+  id: z.coerce.bigint(),
+  byNumber: z.record(z.number(), z.string()),
+  statuses: z.record(z.string(), StatusSchema),
+  history: z.array(StatusSchema),
+  display: z.string(),
+  contact: z.union([z.object({ email: z.string() }), z.object({ address: AddressSchema })]),
+  home: AddressSchema,
+  kind: User_KindSchema,
+  active: z.boolean(),
+  score: z.number(),
+  tags: z.array(User_TagSchema),
+  flags: z.record(z.boolean(), z.coerce.bigint()),
+  thumbnail: z.optional(z.string()),
+});
+
+export type User = z.infer<typeof UserSchema>;
+
+export const AddressSchema = z.object({
+  street: z.string(),
+  blobs: z.record(z.coerce.bigint(), z.string()),
+  levels: z.record(z.number(), LevelSchema),
+});
+
+export type Address = z.infer<typeof AddressSchema>;
+
+export enum Level {
+  LEVEL_MINUS = "LEVEL_MINUS",
+  LEVEL_ZERO = "LEVEL_ZERO",
+}
+
+export const LevelSchema = z.nativeEnum(Level).catch(Level.LEVEL_ZERO);
+
+export enum Status {
+  STATUS_UNKNOWN = "STATUS_UNKNOWN",
+  STATUS_OK = "STATUS_OK",
+}
+
+export const StatusSchema = z.nativeEnum(Status).catch(Status.STATUS_UNKNOWN);
+
+
@@ -0,0 +1,66 @@
+//
+// Code generated by protots v0.2 - DO NOT EDIT
+// Source: golden.proto
+//
+
+import { z } from "zod";
+
+export enum User_Kind {
+  KIND_ADMIN = "KIND_ADMIN",
+  KIND_GUEST = "KIND_GUEST",
+}
+
+export const User_KindSchema = z.nativeEnum(User_Kind).catch(User_Kind.KIND_ADMIN);
+
+export const User_TagSchema = z.object({
+  label: z.string(),
+  big: z.coerce.bigint(),
+  ratio: z.number(),
+});
+
+export type User_Tag = z.infer<typeof User_TagSchema>;
+
+export const UserSchema = z.object({
+  userName: z.string(),
+  avatar: z.string().regex(/^[A-Za-z0-9+\/_-]*={0,2}$/),
+  id: z.coerce.bigint(),
+  byNumber: z.record(z.string().regex(/^-?[0-9]+$/), z.string()),
+  statuses: z.record(z.string(), z.lazy(() => StatusSchema)),
+  history: z.array(z.lazy(() => StatusSchema)),
+  shownName: z.string(),
+  contact: z.union([z.object({ email: z.string() }), z.object({ address: z.lazy(() => AddressSchema) })]),
+  home: z.lazy(() => AddressSchema),
+  kind: z.lazy(() => User_KindSchema),
+  active: z.boolean(),
+  score: z.number(),
+  /** @deprecated */
+  tags: z.array(User_TagSchema),
+  flags: z.record(z.enum(["true", "false"]), z.coerce.bigint()),
+  thumbnail: z.optional(z.string().regex(/^[A-Za-z0-9+\/_-]*={0,2}$/)),
+});
+
+export type User = z.infer<typeof UserSchema>;
+
+export const AddressSchema = z.object({
+  street: z.string(),
+  blobs: z.record(z.string().regex(/^[0-9]+$/), z.string().regex(/^[A-Za-z0-9+\/_-]*={0,2}$/)),
+  levels: z.record(z.string().regex(/^-?[0-9]+$/), z.lazy(() => LevelSchema)),
+});
+
+export type Address = z.infer<typeof AddressSchema>;
+
+export enum Level {
+  LEVEL_MINUS = "LEVEL_MINUS",
+  LEVEL_ZERO = "LEVEL_ZERO",
+}
+
+export const LevelSchema = z.nativeEnum(Level).catch(Level.LEVEL_ZERO);
+
+export enum Status {
+  STATUS_UNKNOWN = "STATUS_UNKNOWN",
+  STATUS_OK = "STATUS_OK",
+}
+
+export const StatusSchema = z.nativeEnum(Status).catch(Status.STATUS_UNKNOWN);
+
+